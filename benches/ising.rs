@@ -0,0 +1,152 @@
+//! Throughput of the sweeps of the Ising model on the GPU and of the random number generators on the CPU, run with `cargo bench`.
+//!
+//! The throughput of the sweeps is reported in spin updates per second (`elem/s`), the number of cells of the lattice times the number of sweeps, which can be compared across lattice sizes and machines.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    sync::{Arc, atomic::Ordering},
+    time::{Duration, Instant},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+#[cfg(not(target_arch = "wasm32"))]
+use gpu_random::{GPURng, philox::Philox4x32};
+#[cfg(not(target_arch = "wasm32"))]
+use phase::{
+    error::WGPUError,
+    gpu::{
+        adapter::{create_shader_module, request_adapter, request_device},
+        physics::{
+            Precision,
+            ising::{IsingParameters, IsingPipeline},
+        },
+    },
+    seed::Seed,
+};
+
+/// Side of the square lattices whose sweeps are measured.
+#[cfg(not(target_arch = "wasm32"))]
+const SIZES: [u32; 3] = [256, 1024, 4096];
+
+/// Number of spin updates of each submission, whose sweeps are recorded in a single command buffer: large enough for the GPU to stay busy between the submissions, which are queued back to back and only waited for at the end of a measurement.
+#[cfg(not(target_arch = "wasm32"))]
+const UPDATES_PER_SUBMISSION: u64 = 1 << 24;
+
+/// Variant of the step of the Ising model. The variants of the kernels are added to [VARIANTS] as they land, with the fields selecting them, so that they are measured on the same lattices as the current ones.
+#[cfg(not(target_arch = "wasm32"))]
+struct Variant {
+    name: &'static str,
+    /// Whether the spins of a tile and of its neighbors are loaded into workgroup memory, see [IsingParameters::tiled].
+    tiled: bool,
+    /// Storage of the spins, packed by pairs of f16 in a word with [Precision::F16].
+    precision: Precision,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+const VARIANTS: [Variant; 4] = [
+    Variant {
+        name: "naive",
+        tiled: false,
+        precision: Precision::F32,
+    },
+    Variant {
+        name: "tiled",
+        tiled: true,
+        precision: Precision::F32,
+    },
+    Variant {
+        name: "naive packed",
+        tiled: false,
+        precision: Precision::F16,
+    },
+    Variant {
+        name: "tiled packed",
+        tiled: true,
+        precision: Precision::F16,
+    },
+];
+
+/// Sweeps per second of each [Variant] on each of the [SIZES], with the Metropolis rule at the critical temperature. The benchmarks are skipped without an adapter, and the lattices which do not fit in the device are skipped with a message.
+#[cfg(not(target_arch = "wasm32"))]
+fn sweeps(c: &mut Criterion) {
+    let instance = wgpu::Instance::default();
+    let adapter = match pollster::block_on(request_adapter(
+        &instance,
+        wgpu::PowerPreference::HighPerformance,
+    )) {
+        Err(WGPUError::NoAdapter) => {
+            eprintln!("No adapter available, skipping the benchmarks of the sweeps.");
+            return;
+        }
+        adapter => adapter.unwrap(),
+    };
+    let (device, queue) = pollster::block_on(request_device(&adapter)).unwrap();
+    let shader_module = create_shader_module(&device);
+    let mut group = c.benchmark_group("sweeps");
+    group.sample_size(10).warm_up_time(Duration::from_secs(1));
+    for size in SIZES {
+        let cells = size as u64 * size as u64;
+        let batch = (UPDATES_PER_SUBMISSION / cells).max(1);
+        group.throughput(Throughput::Elements(cells * batch));
+        for variant in &VARIANTS {
+            let parameters = Arc::new(IsingParameters::default());
+            parameters
+                .rule
+                .store(kernel::RULE_METROPOLIS, Ordering::Relaxed);
+            parameters.tiled.store(variant.tiled, Ordering::Relaxed);
+            let mut pipeline = match IsingPipeline::new(
+                &device,
+                &queue,
+                &shader_module,
+                Seed::from_u64(1).0,
+                size,
+                size,
+                parameters,
+                variant.precision,
+                1,
+            ) {
+                Ok(pipeline) => pipeline,
+                Err(err) => {
+                    eprintln!("Skipping {} on {size}×{size}: {err}", variant.name);
+                    continue;
+                }
+            };
+            // The first sweeps wait for the creation of the pipelines and the initialization.
+            pipeline.thermalize(&device, &queue, 1);
+            group.bench_function(
+                BenchmarkId::new(variant.name, format!("{size}x{size}")),
+                |b| {
+                    b.iter_custom(|iterations| {
+                        let start = Instant::now();
+                        for _ in 0..iterations {
+                            pipeline.submit_sweeps(&device, &queue, batch as usize);
+                        }
+                        let _ = device.poll(wgpu::MaintainBase::Wait);
+                        start.elapsed()
+                    })
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+/// Numbers drawn per second from a [Philox4x32] on the CPU, as the reference and the initializations of the host do.
+#[cfg(not(target_arch = "wasm32"))]
+fn philox(c: &mut Criterion) {
+    let mut group = c.benchmark_group("philox4x32");
+    group.throughput(Throughput::Elements(1));
+    let mut rng = Philox4x32::new(Seed::from_u64(1).0, 0);
+    group.bench_function("next_u32", |b| b.iter(|| rng.next_u32()));
+    group.bench_function("next_normal", |b| b.iter(|| rng.next_normal(0.0, 1.0)));
+    group.finish();
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+criterion_group!(benches, philox, sweeps);
+#[cfg(not(target_arch = "wasm32"))]
+criterion_main!(benches);
+
+#[cfg(target_arch = "wasm32")]
+fn main() {}
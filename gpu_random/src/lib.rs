@@ -0,0 +1,235 @@
+#![no_std]
+
+//! Random number generation compatible with [RustGPU](https://rust-gpu.github.io) and WASM.
+//!
+//! This crate was originally created as part of a small project aimed to use [RustGPU](https://rust-gpu.github.io) alongside [wgpu](https://github.com/gfx-rs/wgpu) to create a simple physics simulation that runs on the web with GPU acceleration. The project is available on [GitHub](https://github.com/xayon40-12/phase), with a [web live demo](https://xayon40-12.github.io/phase).
+//!
+//! ## Available random number generators
+//!
+//! The available random number generators are Philox4x32 and Philox2x32, which are the 32 bits variants of the Philox algorithm coming from [Random123](https://random123.com). Philox2x32 has a smaller state, which is useful when a generator is stored for each of many threads. The Xoshiro128++ and PCG32 generators, which are not counter based, are also available to compare with generators of different families.
+//!
+//! ## Usage example
+//! ```rust
+//! // Use random numbers to approximate the value of pi.
+//!
+//! // Import The `GPURng` Trait and the Philox4x32 rng.
+//! use gpu_random::{GPURng, philox::Philox4x32};
+//!
+//! // Create independent random number generators.
+//! let seed = 42;
+//! let mut rands = [Philox4x32::new(seed, 0), Philox4x32::new(seed, 1)];
+//!
+//! // Sample many points in the unit square, which is the top right square of a unit circle
+//! let n = 10000;
+//! let mut inside_circle = 0;
+//! for _ in 0..n {
+//!     // Sample 2 coordinates. This can be done in parallel if needed.
+//!     let x = rands[0].next_uniform();
+//!     let y = rands[1].next_uniform();
+//!
+//!     // Increment the count if the sampled coordinates are inside the unit circle.
+//!     if x * x + y * y <= 1.0 {
+//!         inside_circle += 1;
+//!     }
+//! }
+//! // Compute the area inside the top right part of the unit circle by dividing the number of points found inside with the total number of points sampled.
+//! let area = inside_circle as f32 / n as f32;
+//!
+//! // The total area of the unit circle is pi, so the top right area is pi/4.
+//! let pi = area * 4.0;
+//!
+//! // Verify that the approximate value is close to the exact value
+//! use core::f32::consts::PI;
+//! assert!((pi - PI).abs() / PI < (n as f32).sqrt(), "{pi}")
+//! ```
+
+#[doc = include_str!("../README.md")]
+use core::f32::consts::PI;
+#[allow(unused_imports)]
+use num::Float;
+
+pub mod pcg;
+pub mod philox;
+pub mod widening_mul;
+pub mod xoshiro;
+
+use widening_mul::widening_mul_u32;
+
+/// Mean above which [GPURng::next_poisson] and [GPURng::next_binomial] use a normal approximation instead of an exact inversion.
+pub const NORMAL_APPROXIMATION_THRESHOLD: f32 = 30.0;
+
+/// Convert a random u32 into a uniform random f32 in [0,1) by using its 23 lower bits as mantissa.
+#[inline(always)]
+pub fn uniform_from_u32(val: u32) -> f32 {
+    let exp = 0x3f800000;
+    let mask = 0x007fffff;
+    f32::from_bits(exp | (val & mask)) - 1.0
+}
+
+/// Convert a random u32 into a random sign ±1.0 with equal probability by using its highest bit as sign bit.
+#[inline(always)]
+pub fn sign_from_u32(val: u32) -> f32 {
+    f32::from_bits(0x3f800000 | (val & 0x80000000))
+}
+
+/// Step of the SplitMix64 generator, whose output is a bijective function of the incremented state `x`.
+///
+/// NOTE: This function cannot be called in a WebGPU as u64 is not available.
+#[cfg(not(target_arch = "spirv"))]
+pub fn splitmix64(x: &mut u64) -> u64 {
+    *x = x.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *x;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+/// Key of the stream `index` of the generators sharing a `seed`, for instance to give one [philox::Philox4x32] to each cell of a lattice. The index is mixed with the seed by [splitmix64] so that adjacent indices give keys differing in about half of their bits. As each step of SplitMix64 is a bijection, different indices always give different keys for a same seed.
+///
+/// NOTE: This function cannot be called in a WebGPU as u64 and u128 are not available. The keys are meant to be computed on the host and uploaded with the generators.
+#[cfg(not(target_arch = "spirv"))]
+pub fn stream_key(seed: u128, index: u64) -> [u32; 2] {
+    let mut x =
+        (seed as u64 ^ (seed >> 64) as u64).wrapping_add(index.wrapping_mul(0x9E3779B97F4A7C15));
+    let key = splitmix64(&mut x);
+    [key as u32, (key >> 32) as u32]
+}
+
+/// Box–Muller transform of two uniform random numbers in [0,1) into a pair of independent random numbers with centered reduced normal distribution.
+#[inline(always)]
+pub fn box_muller(u1: f32, u2: f32) -> [f32; 2] {
+    let sqrtln2u1 = (-2.0 * u1.ln()).sqrt();
+    let pi2u2 = 2.0 * PI * u2;
+    let n1 = sqrtln2u1 * pi2u2.cos();
+    let n2 = sqrtln2u1 * pi2u2.sin();
+    [n1, n2]
+}
+
+/// The [GPURng] trait represent random number generator which should  be compatible with [RustGPU](https://rust-gpu.github.io) when targeting for WebGPU with the WASM32 target architecture.
+///
+/// NOTE: Structs implementing this trait are supposed to only use 32 bits primitives. Other primitives such as u64 are prohibited as they are not compatible with WebGPU.
+pub trait GPURng {
+    /// Compute next random u32
+    fn next_u32(&mut self) -> u32;
+    /// Compute next random boolean which is `true` with probability `p`.
+    fn next_bool(&mut self, p: f32) -> bool {
+        self.next_uniform() < p
+    }
+    /// Compute next random sign ±1.0 with equal probability, see [sign_from_u32].
+    fn next_sign(&mut self) -> f32 {
+        sign_from_u32(self.next_u32())
+    }
+    /// Compute next random number with exponential distribution of rate `lambda`, so of mean `1/lambda`, by inversion of the cumulative distribution.
+    fn next_exp(&mut self, lambda: f32) -> f32 {
+        // 1-u is in (0,1] which avoids the logarithm of 0.
+        -(1.0 - self.next_uniform()).ln() / lambda
+    }
+    /// Compute next random integer uniformly distributed in [0,n) without bias, with the method from Daniel Lemire. 2019. Fast Random Integer Generation in an Interval. ACM Trans. Model. Comput. Simul. 29, 1, Article 3. <https://doi.org/10.1145/3230636>
+    ///
+    /// The result is 0 if `n` is 0.
+    fn next_range(&mut self, n: u32) -> u32 {
+        if n == 0 {
+            return 0;
+        }
+        let (mut lo, mut hi) = widening_mul_u32(self.next_u32(), n);
+        if lo < n {
+            // Reject the values in the first 2^32 mod n, which would otherwise be over represented.
+            let threshold = n.wrapping_neg() % n;
+            while lo < threshold {
+                (lo, hi) = widening_mul_u32(self.next_u32(), n);
+            }
+        }
+        hi
+    }
+    /// Compute next random integer with Poisson distribution of mean `lambda`.
+    ///
+    /// For `lambda` below [NORMAL_APPROXIMATION_THRESHOLD], the cumulative distribution is inverted by sequential search, which is exact up to the f32 precision and costs about `lambda` iterations. Above, a normal distribution of mean and variance `lambda` rounded to the nearest integer is used: the mean and variance are correct (up to 1/12 added to the variance by the rounding), but the skewness `1/√lambda` of the Poisson distribution is neglected, so that the probabilities in the tails are wrong by a relative amount of order `1/√lambda` (about 20% at 3 standard deviations for `lambda = 30`). The result is 0 if `lambda` is not positive.
+    fn next_poisson(&mut self, lambda: f32) -> u32 {
+        if lambda <= 0.0 {
+            0
+        } else if lambda < NORMAL_APPROXIMATION_THRESHOLD {
+            // Upper bound of the search, in case the cumulative sum does not reach u because of rounding errors.
+            let max = (lambda + 12.0 * lambda.sqrt() + 12.0) as u32;
+            let u = self.next_uniform();
+            let mut k = 0;
+            let mut pk = (-lambda).exp();
+            let mut cumulative = pk;
+            while u >= cumulative && k < max {
+                k += 1;
+                pk *= lambda / k as f32;
+                cumulative += pk;
+            }
+            k
+        } else {
+            self.next_normal(lambda, lambda.sqrt()).round().max(0.0) as u32
+        }
+    }
+    /// Compute next random integer with binomial distribution, the number of successes among `n` trials of probability `p`.
+    ///
+    /// When the mean number of successes or failures is below [NORMAL_APPROXIMATION_THRESHOLD], the cumulative distribution is inverted by sequential search, which is exact up to the f32 precision. Otherwise, a normal distribution of mean `n p` and variance `n p (1-p)` rounded to the nearest integer and clamped to [0,n] is used, which neglects the skewness `(1-2p)/√(n p (1-p))` of the binomial distribution, see [GPURng::next_poisson] for the consequences.
+    fn next_binomial(&mut self, n: u32, p: f32) -> u32 {
+        if p <= 0.0 || n == 0 {
+            return 0;
+        } else if p >= 1.0 {
+            return n;
+        }
+        // Count the least probable outcome so that the sequential search is as short as possible.
+        let flip = p > 0.5;
+        let q = if flip { 1.0 - p } else { p };
+        let mean = n as f32 * q;
+        let k = if mean < NORMAL_APPROXIMATION_THRESHOLD {
+            let ratio = q / (1.0 - q);
+            let u = self.next_uniform();
+            let mut k = 0;
+            let mut pk = (n as f32 * (1.0 - q).ln()).exp();
+            let mut cumulative = pk;
+            while u >= cumulative && k < n {
+                pk *= ratio * (n - k) as f32 / (k + 1) as f32;
+                k += 1;
+                cumulative += pk;
+            }
+            k
+        } else {
+            let sigma = (mean * (1.0 - q)).sqrt();
+            (self.next_normal(mean, sigma).round().max(0.0) as u32).min(n)
+        };
+        if flip { n - k } else { k }
+    }
+    /// Compute next random u64 from two consecutive calls to [GPURng::next_u32], the first one providing the lower 32 bits. Exactly two u32 are consumed so that the position in the stream stays predictable.
+    ///
+    /// NOTE: This method is only available on the host as u64 is not available in WebGPU.
+    #[cfg(not(target_arch = "spirv"))]
+    fn next_u64(&mut self) -> u64 {
+        let lo = self.next_u32() as u64;
+        let hi = self.next_u32() as u64;
+        lo | (hi << 32)
+    }
+    /// Compute next uniform random f64 in [0,1) with the full 53 bits resolution of the mantissa, using the 53 upper bits of [GPURng::next_u64].
+    ///
+    /// NOTE: This method is only available on the host as f64 is not available in WebGPU.
+    #[cfg(not(target_arch = "spirv"))]
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+    /// Compute next uniform random f32 in [0,1)
+    fn next_uniform(&mut self) -> f32 {
+        uniform_from_u32(self.next_u32())
+    }
+    /// Compute next uniform random f32 in range [min,max)
+    fn next_uniform_range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_uniform()
+    }
+    /// Compute next pair of random numbers with centered reduced normal distribution.
+    /// Normally distributed numbers are generated by pair with this method, using the Box–Muller transform. Only 32 bits primitives and [Float] methods are used so that it can be called from SPIR-V kernels, for instance to inject Gaussian noise in Langevin dynamics.
+    fn next_normal_pair(&mut self) -> [f32; 2] {
+        let u1 = self.next_uniform();
+        let u2 = self.next_uniform();
+        box_muller(u1, u2)
+    }
+    /// Compute next random number with normal distribution of mean `mu` and standard deviation `sigma`.
+    ///
+    /// NOTE: This default implementation is provided for convenience but it is suboptimal as it discards one of the two normally distributed numbers computed by [GPURng::next_normal_pair], since the trait has no state to store it. Implementations should override it by storing the pair and serving both numbers alternately, as done by [philox::Philox4x32].
+    fn next_normal(&mut self, mu: f32, sigma: f32) -> f32 {
+        mu + sigma * self.next_normal_pair()[0]
+    }
+}
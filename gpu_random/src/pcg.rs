@@ -0,0 +1,186 @@
+#[allow(unused_imports)]
+use num::Float;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::widening_mul::widening_mul_u32;
+
+use super::GPURng;
+
+/// Multiplier of the 64 bits linear congruential generator of PCG32, as `[lo, hi]` words.
+const MULTIPLIER: [u32; 2] = [0x4C957F2D, 0x5851F42D];
+
+/// PCG32 (XSH RR variant) random number generator from:
+///
+/// Melissa E. O'Neill. 2014. PCG: A Family of Simple Fast Space-Efficient Statistically Good Algorithms for Random Number Generation. Technical Report HMC-CS-2014-0905, Harvey Mudd College. <https://www.pcg-random.org>
+///
+/// The 64 bits state and increment are emulated with pairs of u32 stored as `[lo, hi]`, the multiplication of the linear congruential step being performed with [widening_mul_u32], so that the output is exactly the one of the reference implementation while being usable in WebGPU.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct Pcg32 {
+    state: [u32; 2],
+    increment: [u32; 2],
+    current_normal: u32,
+    normal: f32,
+}
+
+impl Pcg32 {
+    /// Create a Pcg32 with an initial `seed` for the given `stream`. Each stream uses a different odd increment, so that generators with the same seed but different streams (for instance the cells of a lattice) produce different sequences.
+    ///
+    /// NOTE: This method cannot be called in a WebGPU as u64 is not available. Use [Pcg32::new_u32] instead.
+    pub fn new(seed: u64, stream: u64) -> Self {
+        Self::new_u32(
+            [seed as u32, (seed >> 32) as u32],
+            [stream as u32, (stream >> 32) as u32],
+        )
+    }
+    /// Create a Pcg32 with an initial `seed` for the given `stream`, both given as `[lo, hi]` words. See [Pcg32::new].
+    pub fn new_u32(seed: [u32; 2], stream: [u32; 2]) -> Self {
+        let mut rng = Pcg32 {
+            state: [0, 0],
+            increment: [(stream[0] << 1) | 1, (stream[1] << 1) | (stream[0] >> 31)],
+            current_normal: 0,
+            normal: 0.0,
+        };
+        rng.step();
+        rng.state = add_u64(rng.state, seed);
+        rng.step();
+        rng
+    }
+    /// Current state of the linear congruential generator as `[lo, hi]` words.
+    pub fn state(&self) -> [u32; 2] {
+        self.state
+    }
+    /// Advance the linear congruential generator once.
+    #[inline(always)]
+    fn step(&mut self) {
+        self.state = add_u64(mul_u64(self.state, MULTIPLIER), self.increment);
+    }
+}
+
+/// Wrapping addition of two u64 given as `[lo, hi]` words.
+#[inline(always)]
+fn add_u64(a: [u32; 2], b: [u32; 2]) -> [u32; 2] {
+    let lo = a[0].wrapping_add(b[0]);
+    let carry = (lo < a[0]) as u32;
+    [lo, a[1].wrapping_add(b[1]).wrapping_add(carry)]
+}
+
+/// Wrapping multiplication of two u64 given as `[lo, hi]` words.
+#[inline(always)]
+fn mul_u64(a: [u32; 2], b: [u32; 2]) -> [u32; 2] {
+    let (lo, hi) = widening_mul_u32(a[0], b[0]);
+    let hi = hi
+        .wrapping_add(a[0].wrapping_mul(b[1]))
+        .wrapping_add(a[1].wrapping_mul(b[0]));
+    [lo, hi]
+}
+
+impl GPURng for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let [lo, hi] = self.state;
+        self.step();
+        // ((state >> 18) ^ state) >> 27, truncated to 32 bits.
+        let x_lo = lo ^ ((lo >> 18) | (hi << 14));
+        let x_hi = hi ^ (hi >> 18);
+        let xorshifted = (x_lo >> 27) | (x_hi << 5);
+        let rot = hi >> 27;
+        (xorshifted >> rot) | (xorshifted << (rot.wrapping_neg() & 31))
+    }
+    fn next_normal(&mut self, mu: f32, sigma: f32) -> f32 {
+        let n = if self.current_normal != 0 {
+            self.current_normal = 0;
+            self.normal
+        } else {
+            let [n1, n2] = self.next_normal_pair();
+            self.normal = n2;
+            self.current_normal = 1;
+            n1
+        };
+        mu + sigma * n
+    }
+}
+
+/// Verify that [Pcg32] reproduces exactly the output of the reference implementation of PCG32.
+#[test]
+pub fn test_pcg_reference() {
+    // Output of the `pcg32-demo` of the reference implementation.
+    let mut rng = Pcg32::new(42, 54);
+    for e in [
+        0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e,
+    ] {
+        assert_eq!(rng.next_u32(), e);
+    }
+    // Seed and stream using all the bits of both words.
+    let mut rng = Pcg32::new(0xdeadbeefcafebabe, 0x123456789abcdef);
+    for e in [0x1a70ef54, 0x59a840fe, 0xb799c69e, 0xc645d5c4] {
+        assert_eq!(rng.next_u32(), e);
+    }
+}
+
+/// Verify that the emulated 64 bits arithmetic matches the native one.
+#[test]
+pub fn test_pcg_u64_emulation() {
+    let to_u64 = |[lo, hi]: [u32; 2]| lo as u64 | ((hi as u64) << 32);
+    let mut rng = Pcg32::new(1, 2);
+    for _ in 0..1000 {
+        let a = [rng.next_u32(), rng.next_u32()];
+        let b = [rng.next_u32(), rng.next_u32()];
+        assert_eq!(to_u64(mul_u64(a, b)), to_u64(a).wrapping_mul(to_u64(b)));
+        assert_eq!(to_u64(add_u64(a, b)), to_u64(a).wrapping_add(to_u64(b)));
+    }
+}
+
+/// Verify that the random numbers from [Pcg32::next_normal] are normally distributed, and that neighboring streams are not correlated.
+#[test]
+pub fn test_pcg_normal() {
+    let mut rng = Pcg32::new(0, 0);
+    let mu = 17.3;
+    let sigma = 12.1;
+    let samples: [f32; 10000] = core::array::from_fn(|_| rng.next_normal(mu, sigma));
+    let inv_count = (samples.len() as f32).recip();
+    let m1 = samples.iter().sum::<f32>() * inv_count;
+    let m2 = samples.iter().map(|n| n * n).sum::<f32>() * inv_count;
+    let rel =
+        |a: f32, b: f32| (a - b).abs() / (a.abs().max(b.abs()) + f32::EPSILON) < inv_count.sqrt();
+    assert!(rel(mu, m1));
+    assert!(rel(sigma, (m2 - m1 * m1).sqrt()));
+
+    let mut a = Pcg32::new(7, 0);
+    let mut b = Pcg32::new(7, 1);
+    let correlation = (0..samples.len())
+        .map(|_| a.next_normal(0.0, 1.0) * b.next_normal(0.0, 1.0))
+        .sum::<f32>()
+        * inv_count;
+    assert!(correlation.abs() < 4.0 * inv_count.sqrt(), "{correlation}");
+}
+
+/// Verify that the generators of adjacent cells, seeded with their [stream_key](crate::stream_key) as in a lattice, produce uncorrelated uniform numbers, and that their normal numbers have the third and fourth moments of a normal distribution.
+#[test]
+pub fn test_pcg_streams() {
+    let cell = |i: u64| Pcg32::new_u32(crate::stream_key(42, i), [i as u32, 0]);
+    let (mut a, mut b) = (cell(0), cell(1));
+    assert_ne!(a.state(), b.state());
+    let count = 10000;
+    let inv_count = (count as f32).recip();
+    let mut m = [0.0; 2];
+    let mut ab = 0.0;
+    for _ in 0..count {
+        let x = [a.next_uniform(), b.next_uniform()].map(|u| u - 0.5);
+        for (mk, xk) in m.iter_mut().zip(x) {
+            *mk += xk;
+        }
+        ab += x[0] * x[1];
+    }
+    let tol = 4.0 * inv_count.sqrt();
+    for mk in m {
+        assert!((mk * inv_count).abs() < tol / 12f32.sqrt(), "{mk}");
+    }
+    assert!((ab * inv_count).abs() < tol / 12.0, "{ab}");
+
+    let samples: [f32; 10000] = core::array::from_fn(|_| a.next_normal(0.0, 1.0));
+    let m3 = samples.iter().map(|z| z * z * z).sum::<f32>() * inv_count;
+    let m4 = samples.iter().map(|z| z * z * z * z).sum::<f32>() * inv_count;
+    assert!(m3.abs() < 4.0 * (15.0 * inv_count).sqrt(), "{m3}");
+    assert!((m4 - 3.0).abs() < 4.0 * (96.0 * inv_count).sqrt(), "{m4}");
+}
@@ -0,0 +1,765 @@
+#[allow(unused_imports)]
+use num::Float;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::widening_mul::widening_mul_u32;
+
+use super::GPURng;
+
+/// Philox counter based random number generator from the Random123 paper:
+///
+/// John K. Salmon, Mark A. Moraes, Ron O. Dror, and David E. Shaw. 2011. Parallel random numbers: as easy as 1, 2, 3. In Proceedings of 2011 International Conference for High Performance Computing, Networking, Storage and Analysis (SC '11). Association for Computing Machinery, New York, NY, USA, Article 16, 1–12. <https://doi.org/10.1145/2063384.2063405>
+///
+/// The counter is incremented by one for each block of four u32, the block being the result of the rounds applied to the counter, so that the stream can be advanced by any number of blocks with [Philox4x32::advance].
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct Philox4x32 {
+    counter: [u32; 4],
+    output: [u32; 4],
+    normal: [f32; 2],
+    current_u32: u32,
+    current_normal: u32,
+    key: [u32; 2],
+    rounds: u32,
+}
+
+impl Philox4x32 {
+    /// Number of rounds used by default. Random123 recommends 10 rounds (Philox4x32-10) as the safety margin above the 7 rounds which are the minimum passing the BigCrush test suite. The cost of a block is proportional to the number of rounds, so [Philox4x32::with_rounds] can be used to trade this margin for speed.
+    pub const DEFAULT_ROUNDS: u32 = 10;
+    /// Create a Philox4x32 with an initial `seed` and `key`. The `key` allows to have many independent streams of random numbers for a same initial `seed`.
+    ///
+    /// The integers are split into words starting with the least significant one, so that a seed gives the same stream on every target.
+    ///
+    /// NOTE: This method cannot be called in a WebGPU as it u128 and u64 are not available. Use [Philox4x32::new_u32] instead.
+    pub fn new(seed: u128, key: u64) -> Self {
+        Self::new_u32(
+            [
+                seed as u32,
+                (seed >> 32) as u32,
+                (seed >> 64) as u32,
+                (seed >> 96) as u32,
+            ],
+            [key as u32, (key >> 32) as u32],
+        )
+    }
+    /// Create a Philox4x32 with an initial `seed` and `key`. The `key` allows to have many independent streams of random numbers for a same initial `seed`.
+    pub fn new_u32(seed: [u32; 4], key: [u32; 2]) -> Self {
+        Philox4x32 {
+            counter: seed,
+            output: [0; 4],
+            current_u32: u32::MAX,
+            normal: [0.0; 2],
+            key,
+            current_normal: u32::MAX,
+            rounds: Self::DEFAULT_ROUNDS,
+        }
+    }
+    /// Set a different number of rounds used by the Philox algorithm.
+    pub fn with_rounds(mut self, rounds: u32) -> Self {
+        self.rounds = rounds;
+        self
+    }
+    /// Set a fifferent value for the `key`.
+    pub fn set_key(&mut self, key: [u32; 2]) {
+        self.key = key;
+    }
+    /// Counter of the next block of four u32 which will be computed by [GPURng::next_u32].
+    pub fn counter(&self) -> [u32; 4] {
+        self.counter
+    }
+    /// Set the counter of the next block to be computed, discarding the rest of the current block.
+    pub fn set_counter(&mut self, counter: [u32; 4]) {
+        self.counter = counter;
+        self.current_u32 = 4;
+    }
+    /// Index in the current block of the next u32 returned by [GPURng::next_u32], 4 meaning that a new block will be computed first. Together with [Philox4x32::counter] it gives the position in the stream of random numbers.
+    pub fn index(&self) -> u32 {
+        self.current_u32.min(4)
+    }
+    /// Skip `n_blocks` blocks of four u32 without computing them, discarding the rest of the current block.
+    ///
+    /// NOTE: This method cannot be called in a WebGPU as u64 is not available. Use [Philox4x32::advance_u32] instead.
+    pub fn advance(&mut self, n_blocks: u64) {
+        self.advance_u32([n_blocks as u32, (n_blocks >> 32) as u32]);
+    }
+    /// Skip `n_blocks` blocks given as `[lo, hi]` words, see [Philox4x32::advance].
+    pub fn advance_u32(&mut self, n_blocks: [u32; 2]) {
+        let (c0, carry0) = self.counter[0].overflowing_add(n_blocks[0]);
+        let (c1, carry1a) = self.counter[1].overflowing_add(n_blocks[1]);
+        let (c1, carry1b) = c1.overflowing_add(carry0 as u32);
+        let (c2, carry2) = self.counter[2].overflowing_add((carry1a || carry1b) as u32);
+        let c3 = self.counter[3].wrapping_add(carry2 as u32);
+        self.set_counter([c0, c1, c2, c3]);
+    }
+    /// Compute the block of the current counter and increment the counter.
+    fn next(&mut self) {
+        self.output = philox_rounds(self.counter, self.key, self.rounds);
+        self.advance_u32([1, 0]);
+        self.current_u32 = 0;
+    }
+    /// Compute the random u32 associated to `counter` without modifying the state of the generator.
+    ///
+    /// Contrary to [GPURng::next_u32], the result only depends on the `key` and the `counter`, so that the random number for a given cell, step and draw can be computed directly by encoding them in `counter`. This makes the random stream independent of the number of draws or dispatches performed before.
+    pub fn random_at(&self, counter: [u32; 4]) -> u32 {
+        self.block_at(counter)[0]
+    }
+    /// Compute the four random u32 associated to `counter` without modifying the state of the generator. See [Philox4x32::random_at].
+    pub fn block_at(&self, counter: [u32; 4]) -> [u32; 4] {
+        philox_rounds(counter, self.key, self.rounds)
+    }
+}
+
+/// Apply `rounds` rounds of the Philox algorithm to `counter` with the given `key`.
+#[inline(always)]
+fn philox_rounds(mut counter: [u32; 4], mut key: [u32; 2], rounds: u32) -> [u32; 4] {
+    for _ in 0..rounds {
+        let (lo0, hi0) = widening_mul_u32(0xD2511F53u32, counter[0]);
+        let (lo1, hi1) = widening_mul_u32(0xCD9E8D57u32, counter[2]);
+        counter = [
+            hi1 ^ key[0] ^ counter[1],
+            lo1,
+            hi0 ^ key[1] ^ counter[3],
+            lo0,
+        ];
+        key[0] = key[0].wrapping_add(0x9E3779B9);
+        key[1] = key[1].wrapping_add(0xBB67AE85);
+    }
+    counter
+}
+
+impl GPURng for Philox4x32 {
+    fn next_u32(&mut self) -> u32 {
+        if self.current_u32 > 3 {
+            self.next();
+        }
+        let val = self.output[self.current_u32 as usize];
+        self.current_u32 += 1;
+        val
+    }
+    fn next_normal(&mut self, mu: f32, sigma: f32) -> f32 {
+        if self.current_normal > 1 {
+            self.normal = self.next_normal_pair();
+            self.current_normal = 0;
+        }
+        let n = self.normal[self.current_normal as usize];
+        self.current_normal += 1;
+        mu + sigma * n
+    }
+}
+
+/// Philox counter based random number generator with a two words counter and a one word key, from the same paper as [Philox4x32].
+///
+/// Its state is 36 bytes instead of the 60 bytes of [Philox4x32], which matters when one generator is stored per lattice site. Only the second number of each normal pair needs to be stored as the first one is returned directly.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct Philox2x32 {
+    counter: [u32; 2],
+    output: [u32; 2],
+    key: u32,
+    rounds: u32,
+    current_u32: u32,
+    current_normal: u32,
+    normal: f32,
+}
+
+impl Philox2x32 {
+    /// Number of rounds used by default, the ones of the Philox2x32-10 variant recommended by Random123.
+    pub const DEFAULT_ROUNDS: u32 = 10;
+    /// Create a Philox2x32 with an initial `seed` and `key`. The `key` allows to have many independent streams of random numbers for a same initial `seed`.
+    ///
+    /// The seed is split into words starting with the least significant one, like [Philox4x32::new].
+    ///
+    /// NOTE: This method cannot be called in a WebGPU as u64 is not available. Use [Philox2x32::new_u32] instead.
+    pub fn new(seed: u64, key: u32) -> Self {
+        Self::new_u32([seed as u32, (seed >> 32) as u32], key)
+    }
+    /// Create a Philox2x32 with an initial `seed` and `key`. The `key` allows to have many independent streams of random numbers for a same initial `seed`.
+    pub fn new_u32(seed: [u32; 2], key: u32) -> Self {
+        Philox2x32 {
+            counter: seed,
+            output: [0; 2],
+            key,
+            rounds: Self::DEFAULT_ROUNDS,
+            current_u32: u32::MAX,
+            current_normal: 0,
+            normal: 0.0,
+        }
+    }
+    /// Set a different number of rounds used by the Philox algorithm.
+    pub fn with_rounds(mut self, rounds: u32) -> Self {
+        self.rounds = rounds;
+        self
+    }
+    /// Set a different value for the `key`.
+    pub fn set_key(&mut self, key: u32) {
+        self.key = key;
+    }
+    /// Counter of the next block of two u32 which will be computed by [GPURng::next_u32].
+    pub fn counter(&self) -> [u32; 2] {
+        self.counter
+    }
+    /// Set the counter of the next block to be computed, discarding the rest of the current block.
+    pub fn set_counter(&mut self, counter: [u32; 2]) {
+        self.counter = counter;
+        self.current_u32 = 2;
+    }
+    /// Skip `n_blocks` blocks of two u32 given as `[lo, hi]` words without computing them, discarding the rest of the current block.
+    pub fn advance_u32(&mut self, n_blocks: [u32; 2]) {
+        let (c0, carry) = self.counter[0].overflowing_add(n_blocks[0]);
+        let c1 = self.counter[1]
+            .wrapping_add(n_blocks[1])
+            .wrapping_add(carry as u32);
+        self.set_counter([c0, c1]);
+    }
+    /// Index in the current block of the next u32 returned by [GPURng::next_u32], 2 meaning that a new block will be computed first. Together with [Philox2x32::counter] it gives the position in the stream of random numbers.
+    pub fn index(&self) -> u32 {
+        self.current_u32.min(2)
+    }
+    /// Compute the block of the current counter and increment the counter.
+    fn next(&mut self) {
+        self.output = philox2x32_rounds(self.counter, self.key, self.rounds);
+        self.advance_u32([1, 0]);
+        self.current_u32 = 0;
+    }
+    /// Compute the random u32 associated to `counter` without modifying the state of the generator. See [Philox4x32::random_at].
+    pub fn random_at(&self, counter: [u32; 2]) -> u32 {
+        self.block_at(counter)[0]
+    }
+    /// Compute the two random u32 associated to `counter` without modifying the state of the generator. See [Philox4x32::random_at].
+    pub fn block_at(&self, counter: [u32; 2]) -> [u32; 2] {
+        philox2x32_rounds(counter, self.key, self.rounds)
+    }
+}
+
+/// Apply `rounds` rounds of the Philox2x32 algorithm to `counter` with the given `key`.
+#[inline(always)]
+fn philox2x32_rounds(mut counter: [u32; 2], mut key: u32, rounds: u32) -> [u32; 2] {
+    for _ in 0..rounds {
+        let (lo, hi) = widening_mul_u32(0xD256D193u32, counter[0]);
+        counter = [hi ^ key ^ counter[1], lo];
+        key = key.wrapping_add(0x9E3779B9);
+    }
+    counter
+}
+
+impl GPURng for Philox2x32 {
+    fn next_u32(&mut self) -> u32 {
+        if self.current_u32 > 1 {
+            self.next();
+        }
+        let val = self.output[self.current_u32 as usize];
+        self.current_u32 += 1;
+        val
+    }
+    fn next_normal(&mut self, mu: f32, sigma: f32) -> f32 {
+        let n = if self.current_normal != 0 {
+            self.current_normal = 0;
+            self.normal
+        } else {
+            let [n1, n2] = self.next_normal_pair();
+            self.normal = n2;
+            self.current_normal = 1;
+            n1
+        };
+        mu + sigma * n
+    }
+}
+
+/// Simple test to verify that the random number from [Philox4x32::next_normal] are actually normally distributed, including their third and fourth moments, and that the two numbers served from a same pair are not correlated.
+#[test]
+pub fn test_philox_normal() {
+    let mut phi = Philox4x32::new(0, 0);
+    let mut m1 = 0.0;
+    let mut m2 = 0.0;
+    let mu = 17.3;
+    let sigma = 12.1;
+    let samples: [f32; 10000] = core::array::from_fn(|_| phi.next_normal(mu, sigma));
+    let count = samples.len();
+    for n in samples {
+        m1 += n;
+        m2 += n * n;
+    }
+    let inv_count = (count as f32).recip();
+    m1 *= inv_count;
+    m2 *= inv_count;
+
+    let r_mu = m1;
+    let r_sigma = (m2 - m1 * m1).sqrt();
+    // Four standard errors of the mean and of the standard deviation.
+    assert!((mu - r_mu).abs() < 4.0 * sigma * inv_count.sqrt(), "{r_mu}");
+    assert!(
+        (sigma - r_sigma).abs() / sigma < 4.0 * (0.5 * inv_count).sqrt(),
+        "{r_sigma}"
+    );
+
+    // Skewness and kurtosis of the reduced numbers, which are respectively 0 and 3 for a normal distribution.
+    let reduced = samples.map(|n| (n - mu) / sigma);
+    let m3 = reduced.iter().map(|z| z * z * z).sum::<f32>() * inv_count;
+    let m4 = reduced.iter().map(|z| z * z * z * z).sum::<f32>() * inv_count;
+    assert!(m3.abs() < 4.0 * (15.0 * inv_count).sqrt(), "{m3}");
+    assert!((m4 - 3.0).abs() < 4.0 * (96.0 * inv_count).sqrt(), "{m4}");
+
+    // The numbers are served by pairs, so the correlation between the two numbers of each pair must vanish.
+    let pair_correlation =
+        reduced.chunks(2).map(|pair| pair[0] * pair[1]).sum::<f32>() * 2.0 * inv_count;
+    assert!(
+        pair_correlation.abs() < 4.0 * (2.0 * inv_count).sqrt(),
+        "{pair_correlation}"
+    );
+
+    // Both numbers of a pair are used before a new pair is computed.
+    let mut phi = Philox4x32::new(3, 5);
+    let mut copy = phi;
+    let pair = copy.next_normal_pair();
+    assert_eq!(pair, [phi.next_normal(0.0, 1.0), phi.next_normal(0.0, 1.0)]);
+}
+
+/// Verify that both numbers of the pairs from [GPURng::next_normal_pair] have zero mean and unit variance, and that they are not correlated.
+#[test]
+pub fn test_philox_normal_pair() {
+    let mut phi = Philox4x32::new(0, 1);
+    let mut m1 = [0.0; 2];
+    let mut m2 = [0.0; 2];
+    let mut m12 = 0.0;
+    let count = 10000;
+    for _ in 0..count {
+        let [n1, n2] = phi.next_normal_pair();
+        m1[0] += n1;
+        m1[1] += n2;
+        m2[0] += n1 * n1;
+        m2[1] += n2 * n2;
+        m12 += n1 * n2;
+    }
+    let inv_count = (count as f32).recip();
+    let tol = 4.0 * inv_count.sqrt();
+    for k in 0..2 {
+        let mean = m1[k] * inv_count;
+        let variance = m2[k] * inv_count - mean * mean;
+        assert!(mean.abs() < tol, "{mean}");
+        assert!((variance - 1.0).abs() < 2.0 * tol, "{variance}");
+    }
+    let covariance = m12 * inv_count - m1[0] * m1[1] * inv_count * inv_count;
+    assert!(covariance.abs() < tol, "{covariance}");
+}
+
+/// Verify that [Philox4x32::random_at] does not depend on the draws performed before and reproduces the first output of the stateful generator.
+#[test]
+pub fn test_philox_random_at() {
+    let mut phi = Philox4x32::new(7, 3);
+    let counter = phi.counter;
+    let first = phi.random_at(counter);
+    for _ in 0..10 {
+        phi.next_u32();
+    }
+    assert_eq!(first, phi.random_at(counter));
+    assert_eq!(first, Philox4x32::new(7, 3).next_u32());
+    assert_ne!(
+        first,
+        phi.random_at([counter[0] ^ 1, counter[1], counter[2], counter[3]])
+    );
+}
+
+/// Verify that [GPURng::next_u64] consumes exactly two u32, and that [GPURng::next_f64] is uniform in [0,1) with more resolution than a f32.
+#[test]
+pub fn test_philox_u64_f64() {
+    let mut a = Philox4x32::new(11, 2);
+    let mut b = a;
+    for _ in 0..7 {
+        let lo = b.next_u32() as u64;
+        let hi = b.next_u32() as u64;
+        assert_eq!(a.next_u64(), lo | (hi << 32));
+        assert_eq!((a.counter(), a.index()), (b.counter(), b.index()));
+    }
+
+    let count = 10000;
+    let mut m1 = 0.0;
+    let mut m2 = 0.0;
+    let mut fine = false;
+    for _ in 0..count {
+        let x = a.next_f64();
+        assert!((0.0..1.0).contains(&x), "{x}");
+        m1 += x;
+        m2 += x * x;
+        fine |= (x * (1u32 << 24) as f64).fract() != 0.0;
+    }
+    let inv_count = (count as f64).recip();
+    let mean = m1 * inv_count;
+    let variance = m2 * inv_count - mean * mean;
+    let tol = 4.0 * inv_count.sqrt();
+    assert!((mean - 0.5).abs() < tol * (1.0f64 / 12.0).sqrt(), "{mean}");
+    assert!(
+        (variance - 1.0 / 12.0).abs() < tol * (1.0f64 / 180.0).sqrt(),
+        "{variance}"
+    );
+    assert!(fine);
+}
+
+/// Verify the distributions of [GPURng::next_bool], [GPURng::next_sign] and [GPURng::next_exp].
+#[test]
+pub fn test_philox_bool_sign_exp() {
+    let mut phi = Philox4x32::new(5, 8);
+    let count = 10000;
+    let inv_count = (count as f32).recip();
+    let tol = 4.0 * inv_count.sqrt();
+
+    let p = 0.3;
+    let trues = (0..count).filter(|_| phi.next_bool(p)).count() as f32 * inv_count;
+    assert!((trues - p).abs() < tol * (p * (1.0 - p)).sqrt(), "{trues}");
+
+    let signs: [f32; 10000] = core::array::from_fn(|_| phi.next_sign());
+    assert!(signs.iter().all(|&s| s == 1.0 || s == -1.0));
+    let mean = signs.iter().sum::<f32>() * inv_count;
+    assert!(mean.abs() < tol, "{mean}");
+
+    let lambda = 2.5;
+    let samples: [f32; 10000] = core::array::from_fn(|_| phi.next_exp(lambda));
+    assert!(samples.iter().all(|&x| x >= 0.0 && x.is_finite()));
+    let mean = samples.iter().sum::<f32>() * inv_count;
+    let variance = samples.iter().map(|x| x * x).sum::<f32>() * inv_count - mean * mean;
+    assert!((mean * lambda - 1.0).abs() < tol, "{mean}");
+    // The fourth moment of the exponential distribution is 24/lambda^4, so the variance of the estimated variance is about 20/lambda^4/count.
+    assert!(
+        (variance * lambda * lambda - 1.0).abs() < tol * 20f32.sqrt(),
+        "{variance}"
+    );
+}
+
+/// Verify with a chi-square test that [GPURng::next_range] is uniform for a number of values which is not a power of two.
+#[test]
+pub fn test_philox_range() {
+    let mut phi = Philox4x32::new(9, 4);
+    let n = 7;
+    let count = 70000;
+    let mut histogram = [0u32; 7];
+    for _ in 0..count {
+        histogram[phi.next_range(n) as usize] += 1;
+    }
+    let expected = count as f32 / n as f32;
+    let chi2 = histogram
+        .iter()
+        .map(|&h| (h as f32 - expected).powi(2) / expected)
+        .sum::<f32>();
+    // 99.9% quantile of the chi-square distribution with 6 degrees of freedom.
+    assert!(chi2 < 22.46, "{chi2} {histogram:?}");
+    assert_eq!(phi.next_range(1), 0);
+    assert_eq!(phi.next_range(0), 0);
+    assert!((0..1000).all(|_| phi.next_range(u32::MAX) < u32::MAX));
+}
+
+/// Verify the mean and variance of [GPURng::next_poisson] in both the inversion and the normal approximation regimes, and compare the distribution with the exact one for a small mean with a Kolmogorov–Smirnov style distance.
+#[test]
+pub fn test_philox_poisson() {
+    let mut phi = Philox4x32::new(13, 2);
+    let count = 20000;
+    let inv_count = (count as f32).recip();
+    for lambda in [0.1, 3.0, 50.0] {
+        let samples: [f32; 20000] = core::array::from_fn(|_| phi.next_poisson(lambda) as f32);
+        let mean = samples.iter().sum::<f32>() * inv_count;
+        let variance = samples.iter().map(|x| x * x).sum::<f32>() * inv_count - mean * mean;
+        let tol = 4.0 * inv_count.sqrt();
+        assert!(
+            (mean - lambda).abs() < tol * lambda.sqrt(),
+            "{lambda} {mean}"
+        );
+        // The variance of the estimated variance is about (lambda + 2 lambda^2)/count.
+        assert!(
+            (variance - lambda).abs() < tol * (lambda + 2.0 * lambda * lambda).sqrt() + 0.1,
+            "{lambda} {variance}"
+        );
+    }
+
+    let lambda = 3.0f32;
+    let mut histogram = [0u32; 32];
+    for _ in 0..count {
+        histogram[(phi.next_poisson(lambda) as usize).min(31)] += 1;
+    }
+    let mut pk = (-lambda).exp();
+    let mut exact = 0.0;
+    let mut empirical = 0.0;
+    let mut distance = 0.0f32;
+    for (k, h) in histogram.iter().enumerate() {
+        exact += pk;
+        pk *= lambda / (k + 1) as f32;
+        empirical += *h as f32 * inv_count;
+        distance = distance.max((exact - empirical).abs());
+    }
+    // Critical value of the Kolmogorov–Smirnov test at the 1% level, which is conservative for a discrete distribution.
+    assert!(distance < 1.63 * inv_count.sqrt(), "{distance}");
+}
+
+/// Verify the mean and variance of [GPURng::next_binomial] with the inversion, the symmetry for `p > 0.5` and the normal approximation.
+#[test]
+pub fn test_philox_binomial() {
+    let mut phi = Philox4x32::new(17, 3);
+    let count = 20000;
+    let inv_count = (count as f64).recip();
+    for (n, p) in [(20, 0.3), (1000, 0.01), (100, 0.9), (1000, 0.5)] {
+        let samples: [u32; 20000] = core::array::from_fn(|_| phi.next_binomial(n, p));
+        assert!(samples.iter().all(|&k| k <= n));
+        // Accumulate in f64 as the mean can be large compared to the standard deviation.
+        let samples = samples.map(|k| k as f64);
+        let mean = samples.iter().sum::<f64>() * inv_count;
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() * inv_count;
+        let exact_mean = n as f64 * p as f64;
+        let exact_variance = exact_mean * (1.0 - p as f64);
+        let tol = 4.0 * inv_count.sqrt();
+        assert!(
+            (mean - exact_mean).abs() < tol * exact_variance.sqrt(),
+            "{n} {p} {mean}"
+        );
+        assert!(
+            (variance - exact_variance).abs() < tol * exact_variance * 2f64.sqrt() + 0.1,
+            "{n} {p} {variance}"
+        );
+    }
+    assert_eq!(phi.next_binomial(10, 0.0), 0);
+    assert_eq!(phi.next_binomial(10, 1.0), 10);
+}
+
+/// Verify [Philox4x32] against the known answer test vectors of Random123 for philox4x32_7 and philox4x32_10.
+#[test]
+pub fn test_philox_kat() {
+    let pi = [0x243f6a88, 0x85a308d3, 0x13198a2e, 0x03707344];
+    let pi_key = [0xa4093822, 0x299f31d0];
+    for (rounds, expected) in [
+        (
+            7,
+            [
+                [0x5f6fb709, 0x0d893f64, 0x4f121f81, 0x4f730a48],
+                [0x5207ddc2, 0x45165e59, 0x4d8ee751, 0x8c52f662],
+                [0x4dfccaba, 0x190a87f0, 0xc47362ba, 0xb6b5242a],
+            ],
+        ),
+        (
+            10,
+            [
+                [0x6627e8d5, 0xe169c58d, 0xbc57ac4c, 0x9b00dbd8],
+                [0x408f276d, 0x41c83b0e, 0xa20bc7c6, 0x6d5451fd],
+                [0xd16cfe09, 0x94fdcceb, 0x5001e420, 0x24126ea1],
+            ],
+        ),
+    ] {
+        let phi = Philox4x32::new_u32([0; 4], [0; 2]).with_rounds(rounds);
+        assert_eq!(phi.block_at([0; 4]), expected[0]);
+        let phi = Philox4x32::new_u32([0; 4], [u32::MAX; 2]).with_rounds(rounds);
+        assert_eq!(phi.block_at([u32::MAX; 4]), expected[1]);
+        let mut phi = Philox4x32::new_u32(pi, pi_key).with_rounds(rounds);
+        assert_eq!(phi.block_at(pi), expected[2]);
+        // The stream of a generator starts with the block of its initial counter.
+        let block: [u32; 4] = core::array::from_fn(|_| phi.next_u32());
+        assert_eq!(block, expected[2]);
+    }
+    assert_eq!(Philox4x32::DEFAULT_ROUNDS, 10);
+}
+
+/// Verify that [Philox4x32::advance] is equivalent to generating the skipped blocks, including the carries between the words of the counter.
+#[test]
+pub fn test_philox_advance() {
+    for seed in [0, u32::MAX as u128, u64::MAX as u128 - 3, u128::MAX - 5] {
+        let n = 10;
+        let mut a = Philox4x32::new(seed, 6);
+        for _ in 0..4 * n {
+            a.next_u32();
+        }
+        let mut b = Philox4x32::new(seed, 6);
+        b.advance(n);
+        assert_eq!((a.counter(), a.index()), (b.counter(), b.index()));
+        let block_a: [u32; 4] = core::array::from_fn(|_| a.next_u32());
+        let block_b: [u32; 4] = core::array::from_fn(|_| b.next_u32());
+        assert_eq!(block_a, block_b);
+    }
+    let mut phi = Philox4x32::new(0, 0);
+    phi.advance(1 << 40);
+    assert_eq!(phi.counter(), [0, 1 << 8, 0, 0]);
+    phi.set_counter([u32::MAX; 4]);
+    phi.advance(1);
+    assert_eq!(phi.counter(), [0; 4]);
+}
+
+/// Verify that [Philox2x32::advance_u32] is equivalent to generating the skipped blocks.
+#[test]
+pub fn test_philox2x32_advance() {
+    let n = 10;
+    let mut a = Philox2x32::new(u32::MAX as u64 - 4, 6);
+    for _ in 0..2 * n {
+        a.next_u32();
+    }
+    let mut b = Philox2x32::new(u32::MAX as u64 - 4, 6);
+    b.advance_u32([n, 0]);
+    assert_eq!((a.counter(), a.index()), (b.counter(), b.index()));
+    assert_eq!(a.next_u32(), b.next_u32());
+}
+
+/// Verify that the keys given by [stream_key](crate::stream_key) are distinct and that the Philox4x32 streams of adjacent indices are not correlated.
+#[test]
+pub fn test_stream_key() {
+    let seed = 0x0123_4567_89ab_cdef_0011_2233_4455_6677;
+    let mut keys: [u64; 4096] = core::array::from_fn(|i| {
+        let [lo, hi] = crate::stream_key(seed, i as u64);
+        lo as u64 | ((hi as u64) << 32)
+    });
+    assert_eq!(crate::stream_key(seed, 7), crate::stream_key(seed, 7));
+    // Adjacent indices give keys differing in about half of their 64 bits.
+    let flipped = keys
+        .windows(2)
+        .map(|w| (w[0] ^ w[1]).count_ones())
+        .sum::<u32>() as f32
+        / (keys.len() - 1) as f32;
+    assert!((flipped - 32.0).abs() < 1.0, "{flipped}");
+    keys.sort_unstable();
+    assert!(keys.windows(2).all(|w| w[0] != w[1]));
+
+    let count = 10000;
+    let inv_count = (count as f32).recip();
+    for index in [0, 1, 1000] {
+        let [a, b] =
+            [index, index + 1].map(|i| Philox4x32::new_u32([0; 4], crate::stream_key(seed, i)));
+        let (mut a, mut b) = (a, b);
+        let correlation = (0..count)
+            .map(|_| (a.next_uniform() - 0.5) * (b.next_uniform() - 0.5))
+            .sum::<f32>()
+            * inv_count;
+        assert!(
+            correlation.abs() < 4.0 * inv_count.sqrt() / 12.0,
+            "{correlation}"
+        );
+    }
+}
+
+#[test]
+pub fn test_philox_pi() {
+    // Use random numbers to approximate the value of pi.
+
+    // Create independent random number generators.
+    let seed = 42;
+    let mut rands = [Philox4x32::new(seed, 0), Philox4x32::new(seed, 1)];
+
+    // Sample many points in the unit square, which is the to right square of a unit. circle
+    let n = 10000;
+    let mut inside_circle = 0;
+    for _ in 0..n {
+        // Compute 2 coordinates. This can be done in parallel if needed.
+        let x = rands[0].next_uniform();
+        let y = rands[1].next_uniform();
+
+        if x * x + y * y <= 1.0 {
+            // Increment the count if the sampled coordinates are inside the unit circle.
+            inside_circle += 1;
+        }
+    }
+    // Compute the area inside the top right part of the unit circle by dividing the number of point found inside with the total number of point sampled.
+    let area = inside_circle as f32 / n as f32;
+
+    // The total area of the unit circle is pi, so the top right area is pi/4.
+    let pi = area * 4.0;
+
+    use core::f32::consts::PI;
+    assert!((pi - PI).abs() / PI < (n as f32).sqrt(), "{pi}")
+}
+
+/// Mirror of [test_philox_normal] for [Philox2x32].
+#[test]
+pub fn test_philox2x32_normal() {
+    let mut phi = Philox2x32::new(0, 0);
+    let mu = 17.3;
+    let sigma = 12.1;
+    let samples: [f32; 10000] = core::array::from_fn(|_| phi.next_normal(mu, sigma));
+    let inv_count = (samples.len() as f32).recip();
+    let m1 = samples.iter().sum::<f32>() * inv_count;
+    let m2 = samples.iter().map(|n| n * n).sum::<f32>() * inv_count;
+    let rel =
+        |a: f32, b: f32| (a - b).abs() / (a.abs().max(b.abs()) + f32::EPSILON) < inv_count.sqrt();
+    assert!(rel(mu, m1));
+    assert!((sigma - (m2 - m1 * m1).sqrt()).abs() / sigma < 4.0 * (0.5 * inv_count).sqrt());
+
+    let reduced = samples.map(|n| (n - mu) / sigma);
+    let m3 = reduced.iter().map(|z| z * z * z).sum::<f32>() * inv_count;
+    let m4 = reduced.iter().map(|z| z * z * z * z).sum::<f32>() * inv_count;
+    assert!(m3.abs() < 4.0 * (15.0 * inv_count).sqrt(), "{m3}");
+    assert!((m4 - 3.0).abs() < 4.0 * (96.0 * inv_count).sqrt(), "{m4}");
+
+    let mut phi = Philox2x32::new(3, 5);
+    let mut copy = phi;
+    let pair = copy.next_normal_pair();
+    assert_eq!(pair, [phi.next_normal(0.0, 1.0), phi.next_normal(0.0, 1.0)]);
+}
+
+/// Mirror of [test_philox_random_at] for [Philox2x32].
+#[test]
+pub fn test_philox2x32_random_at() {
+    let mut phi = Philox2x32::new(7, 3);
+    let counter = phi.counter();
+    let first = phi.random_at(counter);
+    for _ in 0..10 {
+        phi.next_u32();
+    }
+    assert_eq!(first, phi.random_at(counter));
+    assert_eq!(first, Philox2x32::new(7, 3).next_u32());
+    assert_ne!(first, phi.random_at([counter[0] ^ 1, counter[1]]));
+}
+
+/// Mirror of [test_philox_u64_f64] for [Philox2x32].
+#[test]
+pub fn test_philox2x32_u64_f64() {
+    let mut a = Philox2x32::new(11, 2);
+    let mut b = a;
+    for _ in 0..7 {
+        let lo = b.next_u32() as u64;
+        let hi = b.next_u32() as u64;
+        assert_eq!(a.next_u64(), lo | (hi << 32));
+        assert_eq!((a.counter(), a.index()), (b.counter(), b.index()));
+    }
+    let count = 10000;
+    let inv_count = (count as f64).recip();
+    let mean = (0..count).map(|_| a.next_f64()).sum::<f64>() * inv_count;
+    assert!(
+        (mean - 0.5).abs() < 4.0 * (inv_count / 12.0).sqrt(),
+        "{mean}"
+    );
+}
+
+/// Mirror of [test_philox_pi] for [Philox2x32].
+#[test]
+pub fn test_philox2x32_pi() {
+    let mut rands = [Philox2x32::new(42, 0), Philox2x32::new(42, 1)];
+    let n = 10000;
+    let mut inside_circle = 0;
+    for _ in 0..n {
+        let x = rands[0].next_uniform();
+        let y = rands[1].next_uniform();
+        if x * x + y * y <= 1.0 {
+            inside_circle += 1;
+        }
+    }
+    let pi = inside_circle as f32 / n as f32 * 4.0;
+    use core::f32::consts::PI;
+    assert!((pi - PI).abs() / PI < 4.0 / (n as f32).sqrt(), "{pi}")
+}
+
+/// Verify [Philox2x32] against the known answer test vectors of Random123 for philox2x32_10.
+#[test]
+pub fn test_philox2x32_kat() {
+    let phi = Philox2x32::new_u32([0, 0], 0);
+    assert_eq!(phi.block_at([0, 0]), [0xff1dae59, 0x6cd10df2]);
+    let phi = Philox2x32::new_u32([0, 0], 0xffffffff);
+    assert_eq!(
+        phi.block_at([0xffffffff, 0xffffffff]),
+        [0x2c3f628b, 0xab4fd7ad]
+    );
+    let phi = Philox2x32::new_u32([0, 0], 0x13198a2e);
+    assert_eq!(
+        phi.block_at([0x243f6a88, 0x85a308d3]),
+        [0xdd7ce038, 0xf62a4c12]
+    );
+}
+
+/// Verify that the integer seeds and keys are split into words starting with the least significant one, whatever the byte order of the target.
+#[test]
+pub fn test_philox_seed_words() {
+    let phi = Philox4x32::new(0x00000004_00000003_00000002_00000001, 0x00000006_00000005);
+    assert_eq!(phi.counter, [1, 2, 3, 4]);
+    assert_eq!(phi.key, [5, 6]);
+    let phi = Philox2x32::new(0x00000002_00000001, 3);
+    assert_eq!(phi.counter, [1, 2]);
+}
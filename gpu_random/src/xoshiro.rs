@@ -0,0 +1,178 @@
+#[allow(unused_imports)]
+use num::Float;
+
+use bytemuck::{Pod, Zeroable};
+
+use super::GPURng;
+#[cfg(not(target_arch = "spirv"))]
+use super::splitmix64;
+
+/// Xoshiro128++ random number generator from:
+///
+/// David Blackman and Sebastiano Vigna. 2021. Scrambled Linear Pseudorandom Number Generators. ACM Trans. Math. Softw. 47, 4, Article 36, 1–32. <https://doi.org/10.1145/3460772>
+///
+/// Contrary to [Philox4x32](crate::philox::Philox4x32), this is not a counter based generator: the random numbers depend on the number of draws performed before. It is provided as a generator of a different family, in order to rule out artifacts of the random numbers in the measurements.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct Xoshiro128pp {
+    state: [u32; 4],
+    current_normal: u32,
+    normal: f32,
+}
+
+/// Rotate the bits of `x` by `k` to the left with 32 bits shifts only, to avoid relying on a rotate instruction in SPIR-V.
+#[inline(always)]
+#[allow(clippy::manual_rotate)]
+fn rotl(x: u32, k: u32) -> u32 {
+    (x << k) | (x >> (32 - k))
+}
+
+impl Xoshiro128pp {
+    /// Create a Xoshiro128pp from its raw `state`, which must not be zero everywhere.
+    pub fn from_state(state: [u32; 4]) -> Self {
+        Xoshiro128pp {
+            state,
+            current_normal: 0,
+            normal: 0.0,
+        }
+    }
+    /// Create a Xoshiro128pp for the `stream` of the given `seed`. The seed and the stream are mixed with SplitMix64 so that the states of close streams (for instance consecutive cells of a lattice) are decorrelated.
+    ///
+    /// NOTE: This method cannot be called in a WebGPU as u64 is not available. Use [Xoshiro128pp::from_state] instead.
+    #[cfg(not(target_arch = "spirv"))]
+    pub fn from_seed_stream(seed: u64, stream: u64) -> Self {
+        let mut s = stream;
+        let mut x = seed ^ splitmix64(&mut s);
+        let a = splitmix64(&mut x);
+        let b = splitmix64(&mut x);
+        let state = [a as u32, (a >> 32) as u32, b as u32, (b >> 32) as u32];
+        if state == [0; 4] {
+            Self::from_state([1, 0, 0, 0])
+        } else {
+            Self::from_state(state)
+        }
+    }
+    /// Current raw state of the generator.
+    pub fn state(&self) -> [u32; 4] {
+        self.state
+    }
+    /// Advance the generator by 2^64 draws, which is equivalent to 2^64 calls to [GPURng::next_u32]. This allows to split the period of a generator in 2^64 non overlapping streams.
+    pub fn jump(&mut self) {
+        const JUMP: [u32; 4] = [0x8764000b, 0xf542d2d3, 0x6fa035c3, 0x77f2db5b];
+        let mut s = [0; 4];
+        for j in JUMP {
+            for b in 0..32 {
+                if j & (1 << b) != 0 {
+                    for (sk, xk) in s.iter_mut().zip(self.state) {
+                        *sk ^= xk;
+                    }
+                }
+                self.step();
+            }
+        }
+        self.state = s;
+    }
+    /// Advance the state once.
+    #[inline(always)]
+    fn step(&mut self) {
+        let s = &mut self.state;
+        let t = s[1] << 9;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = rotl(s[3], 11);
+    }
+}
+
+impl GPURng for Xoshiro128pp {
+    fn next_u32(&mut self) -> u32 {
+        let s = self.state;
+        let result = rotl(s[0].wrapping_add(s[3]), 7).wrapping_add(s[0]);
+        self.step();
+        result
+    }
+    fn next_normal(&mut self, mu: f32, sigma: f32) -> f32 {
+        let n = if self.current_normal != 0 {
+            self.current_normal = 0;
+            self.normal
+        } else {
+            let [n1, n2] = self.next_normal_pair();
+            self.normal = n2;
+            self.current_normal = 1;
+            n1
+        };
+        mu + sigma * n
+    }
+}
+
+/// Verify [Xoshiro128pp] and [Xoshiro128pp::jump] against the output of the reference implementation for the state `[1, 2, 3, 4]`.
+#[test]
+pub fn test_xoshiro_reference() {
+    let mut rng = Xoshiro128pp::from_state([1, 2, 3, 4]);
+    let expected = [
+        641, 1573767, 3222811527, 3517856514, 836907274, 4247214768, 3867114732, 1355841295,
+        495546011, 621204420,
+    ];
+    for e in expected {
+        assert_eq!(rng.next_u32(), e);
+    }
+    let mut rng = Xoshiro128pp::from_state([1, 2, 3, 4]);
+    rng.jump();
+    for e in [3129740764, 111290574, 1158071106, 1835317750] {
+        assert_eq!(rng.next_u32(), e);
+    }
+}
+
+/// Verify that consecutive streams and jumped generators produce uncorrelated uniform numbers.
+#[test]
+pub fn test_xoshiro_streams() {
+    let mut a = Xoshiro128pp::from_seed_stream(42, 0);
+    let mut b = Xoshiro128pp::from_seed_stream(42, 1);
+    let mut c = a;
+    c.jump();
+    assert_ne!(a.state(), b.state());
+    assert_ne!(a.state(), c.state());
+    let count = 10000;
+    let inv_count = (count as f32).recip();
+    let mut m = [0.0; 3];
+    let mut ab = 0.0;
+    let mut ac = 0.0;
+    for _ in 0..count {
+        let x = [a.next_uniform(), b.next_uniform(), c.next_uniform()].map(|u| u - 0.5);
+        for (mk, xk) in m.iter_mut().zip(x) {
+            *mk += xk;
+        }
+        ab += x[0] * x[1];
+        ac += x[0] * x[2];
+    }
+    let tol = 4.0 * inv_count.sqrt();
+    for mk in m {
+        assert!((mk * inv_count).abs() < tol / 12f32.sqrt(), "{mk}");
+    }
+    assert!((ab * inv_count).abs() < tol / 12.0, "{ab}");
+    assert!((ac * inv_count).abs() < tol / 12.0, "{ac}");
+}
+
+/// Verify that the random numbers from [Xoshiro128pp::next_normal] are normally distributed.
+#[test]
+pub fn test_xoshiro_normal() {
+    let mut rng = Xoshiro128pp::from_seed_stream(0, 0);
+    let mu = 17.3;
+    let sigma = 12.1;
+    let samples: [f32; 10000] = core::array::from_fn(|_| rng.next_normal(mu, sigma));
+    let inv_count = (samples.len() as f32).recip();
+    let m1 = samples.iter().sum::<f32>() * inv_count;
+    let m2 = samples.iter().map(|n| n * n).sum::<f32>() * inv_count;
+    let rel =
+        |a: f32, b: f32| (a - b).abs() / (a.abs().max(b.abs()) + f32::EPSILON) < inv_count.sqrt();
+    assert!(rel(mu, m1));
+    assert!(rel(sigma, (m2 - m1 * m1).sqrt()));
+
+    let reduced = samples.map(|n| (n - mu) / sigma);
+    let m3 = reduced.iter().map(|z| z * z * z).sum::<f32>() * inv_count;
+    let m4 = reduced.iter().map(|z| z * z * z * z).sum::<f32>() * inv_count;
+    assert!(m3.abs() < 4.0 * (15.0 * inv_count).sqrt(), "{m3}");
+    assert!((m4 - 3.0).abs() < 4.0 * (96.0 * inv_count).sqrt(), "{m4}");
+}
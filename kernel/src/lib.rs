@@ -1,17 +1,51 @@
 #![no_std]
 
+use core::f32::consts::PI;
+
 use bytemuck::{Pod, Zeroable};
 use spirv_std::{
-    glam::{UVec3, Vec2, Vec4, vec4},
+    float::{f16x2_to_vec2, vec2_to_f16x2},
+    glam::{UVec3, Vec2, Vec4, vec2, vec4},
     spirv,
 };
 
-use rand_gpu_wasm::{GPURng, philox::Philox4x32};
+#[cfg(feature = "pcg_rng")]
+use gpu_random::pcg::Pcg32;
+#[cfg(feature = "small_rng")]
+use gpu_random::philox::Philox2x32;
+#[cfg(feature = "xoshiro_rng")]
+use gpu_random::xoshiro::Xoshiro128pp;
+use gpu_random::{GPURng, box_muller, philox::Philox4x32, sign_from_u32, uniform_from_u32};
+
+#[cfg(any(
+    all(feature = "small_rng", feature = "xoshiro_rng"),
+    all(feature = "small_rng", feature = "pcg_rng"),
+    all(feature = "xoshiro_rng", feature = "pcg_rng"),
+))]
+compile_error!(
+    "Only one of the features \"small_rng\", \"xoshiro_rng\" and \"pcg_rng\" can be enabled."
+);
 
 #[allow(unused_imports)]
 use num::Float;
 
 /// Struct which stores the size of the system, the temperature and external field strength.
+///
+/// When `drive_enable` is non zero, the external field oscillates as `h(t) = h0 + A·sin(2π t/P)` where `h0` is `external_field`, `A` is `drive_amplitude`, `P` is `drive_period` and `t` is the number of sweeps `sweep` performed so far.
+///
+/// When `block_size` is non zero, the lattice is coarse-grained by blocks of `block_size`×`block_size` spins, `block_levels` times, in order to show a renormalization group transformation side by side with the original lattice.
+///
+/// The top (last) and bottom (first) rows can be pinned to the values `top_pin` and `bottom_pin`, a value of [NOT_PINNED] meaning that the row evolves freely.
+///
+/// When `half` is non zero, the spins are stored as pairs of f16 packed in each u32 of the buffers instead of one f32 per u32, which halves the memory traffic. The width must then be even, and each invocation of [ising_reset] and [ising_step] updates two consecutive cells of a row.
+///
+/// The candidate of each spin is drawn according to `proposal`, either [PROPOSAL_RESAMPLE] or [PROPOSAL_FLIP].
+///
+/// Several independent replicas of the lattice can be simulated together: their cells are stored one replica after the other in the buffers, and the invocations with `global_invocation_id.z = r` handle the replica `r` (see [IsingCtx::replica_base]). The rendering and the coarse-graining only use the first replica.
+///
+/// When `tempering` is non zero, each replica evolves at its own temperature, given by the `temperatures` buffer of [ising_step] and exchanged by [ising_tempering], instead of `temperature`.
+///
+/// The cells are rendered by [ising_fragment] with the color `up_color` for spin up and `down_color` for spin down, see [IsingCtx::color].
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable)]
 pub struct IsingCtx {
@@ -19,76 +53,1305 @@ pub struct IsingCtx {
     pub height: u32,
     pub temperature: f32,
     pub external_field: f32,
+    pub sweep: u32,
+    pub drive_enable: u32,
+    pub drive_amplitude: f32,
+    pub drive_period: f32,
+    pub top_pin: f32,
+    pub bottom_pin: f32,
+    pub block_size: u32,
+    pub block_levels: u32,
+    pub half: u32,
+    pub proposal: u32,
+    pub tempering: u32,
+    pub _padding: u32,
+    pub up_color: Vec4,
+    pub down_color: Vec4,
+}
+
+/// Sentinel value of [IsingCtx::top_pin] and [IsingCtx::bottom_pin] for rows which are not pinned.
+pub const NOT_PINNED: f32 = 0.0;
+
+/// Acceptance rule of [ising_step] accepting a candidate with probability `1/(1 + exp(ΔE/T))`. This is the default value of the specialization constant with id 0.
+pub const RULE_GLAUBER: u32 = 0;
+/// Acceptance rule of [ising_step] accepting a candidate with probability `min(1, exp(-ΔE/T))`.
+pub const RULE_METROPOLIS: u32 = 1;
+/// Proposal of [ising_step] drawing the candidate as a random sign, so that half of the candidates are the current value of the spin. This is the default value of [IsingCtx::proposal].
+pub const PROPOSAL_RESAMPLE: u32 = 0;
+/// Proposal of [ising_step] taking the flipped spin as candidate. Both proposals sample the same equilibrium since they are symmetric, but the flip proposal changes the spins twice as often, so the dynamics is faster and the acceptance rates differ, especially in a non zero field.
+pub const PROPOSAL_FLIP: u32 = 1;
+
+/// Acceptance rule of [ising_step] ignoring the candidate and drawing the new spin from its equilibrium distribution in the field of its neighbors.
+pub const RULE_HEAT_BATH: u32 = 2;
+
+/// Dynamics flipping single spins with [ising_step], which does not conserve the magnetization. This is the default dynamics.
+pub const DYNAMICS_SPIN_FLIP: u32 = 0;
+/// Dynamics exchanging pairs of neighboring spins with [kawasaki_step], which conserves the magnetization exactly.
+pub const DYNAMICS_KAWASAKI: u32 = 1;
+
+/// Initialization of [ising_reset] drawing each spin as a random sign, which is a state at infinite temperature. This is the default value of the specialization constant with id 0.
+pub const INIT_RANDOM: u32 = 0;
+/// Initialization of [ising_reset] setting every spin up, which is the ground state at zero temperature for `J > 0` (cold start).
+pub const INIT_UP: u32 = 1;
+/// Initialization of [ising_reset] setting every spin down.
+pub const INIT_DOWN: u32 = 2;
+/// Initialization of [ising_reset] setting the spins of the middle half of the rows up and the others down, which gives two flat horizontal domain walls compatible with the periodic boundaries.
+pub const INIT_STRIPE: u32 = 3;
+/// Initialization spreading evenly half of the spins up, placed at the points of a low-discrepancy sequence. It is computed on the host and uploaded rather than being a specialization of [ising_reset], as the sequence is only meant for the initialization while the Monte Carlo updates draw from the pseudo-random generators of the cells.
+pub const INIT_QUASI_RANDOM: u32 = 4;
+
+impl IsingCtx {
+    /// External field at the current sweep, including the sinusoidal drive if enabled.
+    pub fn field(&self) -> f32 {
+        if self.drive_enable != 0 {
+            let phase = 2.0 * PI * self.sweep as f32 / self.drive_period;
+            self.external_field + self.drive_amplitude * phase.sin()
+        } else {
+            self.external_field
+        }
+    }
+    /// Total linear size of a coarse-grained block, `block_size^block_levels`.
+    pub fn block_span(&self) -> u32 {
+        if self.block_levels == 2 {
+            self.block_size * self.block_size
+        } else {
+            self.block_size
+        }
+    }
+    /// Number of cells stored in each u32 of the spin buffers, 2 when [IsingCtx::half] is set and 1 otherwise.
+    pub fn cells_per_word(&self) -> u32 {
+        1 + (self.half != 0) as u32
+    }
+    /// Same context with the size `width`×`height` given by specialization constants, so that the compiler can fold the index arithmetic which depends on it. A size of 0, the default value of the constants, keeps the size of the context.
+    pub fn with_size(&self, width: u32, height: u32) -> IsingCtx {
+        IsingCtx {
+            width: if width != 0 { width } else { self.width },
+            height: if height != 0 { height } else { self.height },
+            ..*self
+        }
+    }
+    /// Index of the first cell of the replica `replica`.
+    pub fn replica_base(&self, replica: u32) -> usize {
+        (replica * self.width * self.height) as usize
+    }
+    /// Color of a cell of value `val`, interpolated linearly from `down_color` at -1 to `up_color` at 1 so that the intermediate values of the coarse-grained or filtered views are shown in between.
+    pub fn color(&self, val: f32) -> Vec4 {
+        let t = (0.5 * (val + 1.0)).clamp(0.0, 1.0);
+        self.down_color * (1.0 - t) + self.up_color * t
+    }
+    /// Value at which the row `iy` is pinned, or [NOT_PINNED].
+    pub fn pin(&self, iy: u32) -> f32 {
+        if iy + 1 == self.height {
+            self.top_pin
+        } else if iy == 0 {
+            self.bottom_pin
+        } else {
+            NOT_PINNED
+        }
+    }
+}
+
+/// Random number generator stored for each cell of the lattice. With the `small_rng` feature, the smaller [Philox2x32] is used in order to reduce the memory needed by large lattices, and with the `xoshiro_rng` or `pcg_rng` features the [Xoshiro128pp] or [Pcg32] generators are used to compare with generators which are not counter based.
+#[cfg(not(any(feature = "small_rng", feature = "xoshiro_rng", feature = "pcg_rng")))]
+pub type CellRng = Philox4x32;
+/// Random number generator stored for each cell of the lattice, see the default [Philox4x32] variant.
+#[cfg(feature = "small_rng")]
+pub type CellRng = Philox2x32;
+/// Random number generator stored for each cell of the lattice, see the default [Philox4x32] variant.
+#[cfg(feature = "xoshiro_rng")]
+pub type CellRng = Xoshiro128pp;
+/// Random number generator stored for each cell of the lattice, see the default [Philox4x32] variant.
+#[cfg(feature = "pcg_rng")]
+pub type CellRng = Pcg32;
+
+/// Draw two random u32 for the draw `draw` of the sweep `sweep`. The cell is identified by the key of its `rng`, so that the result only depends on (cell, sweep, draw) and not on the number of draws performed before. The last word of the counter is used to distinguish the kernels.
+#[cfg(not(any(feature = "small_rng", feature = "xoshiro_rng", feature = "pcg_rng")))]
+fn random_pair_at(rng: &mut CellRng, sweep: u32, draw: u32, kernel: u32) -> [u32; 2] {
+    let [r1, r2, _, _] = rng.block_at([draw, 0, sweep, kernel]);
+    [r1, r2]
+}
+/// Draw two random u32 for the draw `draw` of the sweep `sweep`. The cell is identified by the key of its `rng`, so that the result only depends on (cell, sweep, draw) and not on the number of draws performed before. The upper half of the first word of the counter is used to distinguish the kernels.
+#[cfg(feature = "small_rng")]
+fn random_pair_at(rng: &mut CellRng, sweep: u32, draw: u32, kernel: u32) -> [u32; 2] {
+    rng.block_at([draw | (kernel << 16), sweep])
+}
+/// Draw the next two random u32 of the stream of the cell. Contrary to the counter based generators, the result depends on the number of draws performed before, so `sweep`, `draw` and `kernel` are ignored.
+#[cfg(any(feature = "xoshiro_rng", feature = "pcg_rng"))]
+fn random_pair_at(rng: &mut CellRng, _sweep: u32, _draw: u32, _kernel: u32) -> [u32; 2] {
+    [rng.next_u32(), rng.next_u32()]
+}
+
+/// Draw a uniform random number in [0,1) for the draw `draw` of the sweep `sweep`, see [random_pair_at].
+fn uniform_at(rng: &mut CellRng, sweep: u32, draw: u32, kernel: u32) -> f32 {
+    uniform_from_u32(random_pair_at(rng, sweep, draw, kernel)[0])
+}
+
+/// Draw a random sign ±1 with equal probability for the draw `draw` of the sweep `sweep`, see [random_pair_at].
+fn sign_at(rng: &mut CellRng, sweep: u32, draw: u32, kernel: u32) -> f32 {
+    sign_from_u32(random_pair_at(rng, sweep, draw, kernel)[0])
+}
+
+/// Value of the spin of the cell `i` in `vals`, stored either as the bits of an f32 or as half of a pair of packed f16, see [IsingCtx::half].
+fn load_spin(ising: &IsingCtx, vals: &[u32], i: usize) -> f32 {
+    if ising.half != 0 {
+        let pair: Vec2 = f16x2_to_vec2(vals[i / 2]);
+        if i % 2 == 0 { pair.x } else { pair.y }
+    } else {
+        f32::from_bits(vals[i])
+    }
 }
 
-/// Reset the state by randomizing the value in each cells.
+/// Store in the word `word` of `vals` the spin `a`, and also the spin `b` of the next cell when the spins are packed as pairs of f16, see [IsingCtx::half].
+fn store_spins(ising: &IsingCtx, vals: &mut [u32], word: usize, a: f32, b: f32) {
+    vals[word] = if ising.half != 0 {
+        vec2_to_f16x2(vec2(a, b))
+    } else {
+        a.to_bits()
+    };
+}
+
+/// Initial value of the spin of the cell (`ix`,`iy`) for the initialization `init`, the pinned rows taking their pinned value.
+fn reset_spin(
+    ising: &IsingCtx,
+    init: u32,
+    rngs: &mut [CellRng],
+    base: usize,
+    ix: usize,
+    iy: usize,
+) -> f32 {
+    let pin = ising.pin(iy as u32);
+    if pin != NOT_PINNED {
+        return pin;
+    }
+    match init {
+        INIT_UP => 1.0,
+        INIT_DOWN => -1.0,
+        INIT_STRIPE => {
+            let height = ising.height as usize;
+            if (height / 4..height * 3 / 4).contains(&iy) {
+                1.0
+            } else {
+                -1.0
+            }
+        }
+        _ => sign_at(
+            &mut rngs[base + ix + ising.width as usize * iy],
+            ising.sweep,
+            0,
+            1,
+        ),
+    }
+}
+
+/// Reset the state by setting the value in each cells according to the initialization selected by the specialization constant `init` (id 0) among [INIT_RANDOM], [INIT_UP], [INIT_DOWN] and [INIT_STRIPE]. The random initialization depends on [IsingCtx::sweep], so that resetting again at another sweep gives another state.
 #[spirv(compute(threads(1)))]
 pub fn ising_reset(
     #[spirv(global_invocation_id)] gid: UVec3,
+    #[spirv(spec_constant(id = 0, default = 0))] init: u32,
     #[spirv(uniform, descriptor_set = 0, binding = 0)] ising: &IsingCtx,
-    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] vals: &mut [f32],
-    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] rngs: &mut [Philox4x32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] vals: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] rngs: &mut [CellRng],
 ) {
-    let ix = gid.x as usize;
+    let cells = ising.cells_per_word() as usize;
+    let base = ising.replica_base(gid.z);
+    let ix = gid.x as usize * cells;
     let iy = gid.y as usize;
-    let i = ix + ising.width as usize * iy;
-    vals[i] = 1.0 - 2.0 * rngs[i].next_uniform().round();
+    let a = reset_spin(ising, init, rngs, base, ix, iy);
+    let b = if cells == 2 {
+        reset_spin(ising, init, rngs, base, ix + 1, iy)
+    } else {
+        0.0
+    };
+    store_spins(
+        ising,
+        vals,
+        (base + ix + ising.width as usize * iy) / cells,
+        a,
+        b,
+    );
 }
 
 /// Compute shader for the [Ising model](https://en.wikipedia.org/wiki/Ising_model) which compute a new random candidate in each cells and keep it with a probability depending on the energy of both old and candidate states.
+///
+/// The acceptance probability is selected by the specialization constant `rule` (id 0) among [RULE_GLAUBER], [RULE_METROPOLIS] and [RULE_HEAT_BATH], so that the branch is resolved when the pipeline is created.
+///
+/// The coupling `J` between neighbors is the specialization constant with id 1, given as the bits of an f32 (see [f32::to_bits]) as only integer specialization constants are supported. It defaults to 1.
+///
+/// The width and height of the lattice can be given as the specialization constants with ids 2 and 3, which take precedence over the ones of the context, see [IsingCtx::with_size].
 #[spirv(compute(threads(1)))]
 pub fn ising_step(
     #[spirv(global_invocation_id)] gid: UVec3,
+    #[spirv(spec_constant(id = 0, default = 0))] rule: u32,
+    #[spirv(spec_constant(id = 1, default = 1065353216))] coupling_bits: u32,
+    #[spirv(spec_constant(id = 2, default = 0))] width: u32,
+    #[spirv(spec_constant(id = 3, default = 0))] height: u32,
     #[spirv(uniform, descriptor_set = 0, binding = 0)] ising: &IsingCtx,
-    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] vals: &[f32],
-    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] new_vals: &mut [f32],
-    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] rngs: &mut [Philox4x32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] vals: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] new_vals: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] rngs: &mut [CellRng],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 4)] temperatures: &[f32],
 ) {
-    let ix = gid.x as usize;
+    step_cells(
+        gid,
+        rule,
+        coupling_bits,
+        &ising.with_size(width, height),
+        vals,
+        new_vals,
+        rngs,
+        temperatures,
+    );
+}
+
+/// Values changing at every step, given as push constants to [ising_step_push] so that they are set by the driver at each dispatch instead of being copied into a uniform buffer.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct StepConstants {
+    pub sweep: u32,
+}
+
+/// Same as [ising_step] with the sweep counter taken from the push constants instead of [IsingCtx::sweep].
+#[spirv(compute(threads(1)))]
+pub fn ising_step_push(
+    #[spirv(global_invocation_id)] gid: UVec3,
+    #[spirv(spec_constant(id = 0, default = 0))] rule: u32,
+    #[spirv(spec_constant(id = 1, default = 1065353216))] coupling_bits: u32,
+    #[spirv(spec_constant(id = 2, default = 0))] width: u32,
+    #[spirv(spec_constant(id = 3, default = 0))] height: u32,
+    #[spirv(push_constant)] constants: &StepConstants,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ising: &IsingCtx,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] vals: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] new_vals: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] rngs: &mut [CellRng],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 4)] temperatures: &[f32],
+) {
+    let ising = IsingCtx {
+        sweep: constants.sweep,
+        ..ising.with_size(width, height)
+    };
+    step_cells(
+        gid,
+        rule,
+        coupling_bits,
+        &ising,
+        vals,
+        new_vals,
+        rngs,
+        temperatures,
+    );
+}
+
+/// Update the cells of the invocation `gid` of [ising_step], at the temperature of its replica in `temperatures` if [IsingCtx::tempering] is set.
+#[allow(clippy::too_many_arguments)]
+fn step_cells(
+    gid: UVec3,
+    rule: u32,
+    coupling_bits: u32,
+    ising: &IsingCtx,
+    vals: &[u32],
+    new_vals: &mut [u32],
+    rngs: &mut [CellRng],
+    temperatures: &[f32],
+) {
+    let ising = &IsingCtx {
+        temperature: if ising.tempering != 0 {
+            temperatures[gid.z as usize]
+        } else {
+            ising.temperature
+        },
+        ..*ising
+    };
+    let coupling = f32::from_bits(coupling_bits);
+    let cells = ising.cells_per_word() as usize;
+    let base = ising.replica_base(gid.z);
+    let ix = gid.x as usize * cells;
     let iy = gid.y as usize;
-    let t = ising.temperature;
-    let c = ising.external_field;
+    let a = step_spin(ising, rule, coupling, vals, rngs, base, ix, iy);
+    let b = if cells == 2 {
+        step_spin(ising, rule, coupling, vals, rngs, base, ix + 1, iy)
+    } else {
+        0.0
+    };
+    store_spins(
+        ising,
+        new_vals,
+        (base + ix + ising.width as usize * iy) / cells,
+        a,
+        b,
+    );
+}
+
+/// Side of the square workgroups of [ising_step_tiled], in words of the lattice. It must match the size given to the `compute` attribute of the entry points.
+pub const TILE: u32 = 16;
+/// Number of spins of the tile of [ising_step_tiled] with its halo, for the largest tile which has 2 cells per word.
+const TILE_SPINS: usize = ((2 * TILE + 2) * (TILE + 2)) as usize;
+
+/// Same as [ising_step], with the spins of the `TILE`×`TILE` words of the workgroup and of their halo of neighbors loaded once into workgroup memory, instead of each spin being read from the storage buffer by the invocations of its 4 neighbors too. The lattice is dispatched in workgroups of [TILE]×[TILE] words, the invocations beyond the lattice only helping to load the tile. The random numbers do not depend on the order of the updates, so the result is identical to the one of [ising_step].
+#[spirv(compute(threads(16, 16)))]
+pub fn ising_step_tiled(
+    #[spirv(workgroup_id)] wid: UVec3,
+    #[spirv(local_invocation_id)] lid: UVec3,
+    #[spirv(spec_constant(id = 0, default = 0))] rule: u32,
+    #[spirv(spec_constant(id = 1, default = 1065353216))] coupling_bits: u32,
+    #[spirv(spec_constant(id = 2, default = 0))] width: u32,
+    #[spirv(spec_constant(id = 3, default = 0))] height: u32,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ising: &IsingCtx,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] vals: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] new_vals: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] rngs: &mut [CellRng],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 4)] temperatures: &[f32],
+    #[spirv(workgroup)] tile: &mut [f32; TILE_SPINS],
+) {
+    step_tile(
+        wid,
+        lid,
+        rule,
+        coupling_bits,
+        &ising.with_size(width, height),
+        vals,
+        new_vals,
+        rngs,
+        temperatures,
+        tile,
+    );
+}
+
+/// Same as [ising_step_tiled] with the sweep counter taken from the push constants instead of [IsingCtx::sweep].
+#[spirv(compute(threads(16, 16)))]
+pub fn ising_step_tiled_push(
+    #[spirv(workgroup_id)] wid: UVec3,
+    #[spirv(local_invocation_id)] lid: UVec3,
+    #[spirv(spec_constant(id = 0, default = 0))] rule: u32,
+    #[spirv(spec_constant(id = 1, default = 1065353216))] coupling_bits: u32,
+    #[spirv(spec_constant(id = 2, default = 0))] width: u32,
+    #[spirv(spec_constant(id = 3, default = 0))] height: u32,
+    #[spirv(push_constant)] constants: &StepConstants,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ising: &IsingCtx,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] vals: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] new_vals: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] rngs: &mut [CellRng],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 4)] temperatures: &[f32],
+    #[spirv(workgroup)] tile: &mut [f32; TILE_SPINS],
+) {
+    let ising = IsingCtx {
+        sweep: constants.sweep,
+        ..ising.with_size(width, height)
+    };
+    step_tile(
+        wid,
+        lid,
+        rule,
+        coupling_bits,
+        &ising,
+        vals,
+        new_vals,
+        rngs,
+        temperatures,
+        tile,
+    );
+}
+
+/// Update the cells of the invocation `lid` of the workgroup `wid` of [ising_step_tiled], from the spins loaded into `tile`.
+#[allow(clippy::too_many_arguments)]
+fn step_tile(
+    wid: UVec3,
+    lid: UVec3,
+    rule: u32,
+    coupling_bits: u32,
+    ising: &IsingCtx,
+    vals: &[u32],
+    new_vals: &mut [u32],
+    rngs: &mut [CellRng],
+    temperatures: &[f32],
+    tile: &mut [f32; TILE_SPINS],
+) {
+    let ising = &IsingCtx {
+        temperature: if ising.tempering != 0 {
+            temperatures[wid.z as usize]
+        } else {
+            ising.temperature
+        },
+        ..*ising
+    };
+    let coupling = f32::from_bits(coupling_bits);
+    let cells = ising.cells_per_word();
+    let (w, h) = (ising.width, ising.height);
+    let base = ising.replica_base(wid.z);
+    // The tile covers the cells from (x0-1,y0-1) to (x0+TILE·cells,y0+TILE) included, wrapped around the periodic boundaries. The invocations load its spins in turn, including the halo.
+    let x0 = wid.x * TILE * cells;
+    let y0 = wid.y * TILE;
+    let tile_width = TILE * cells + 2;
+    let mut k = lid.x + TILE * lid.y;
+    while k < tile_width * (TILE + 2) {
+        let x = (x0 + k % tile_width + w - 1) % w;
+        let y = (y0 + k / tile_width + h - 1) % h;
+        tile[k as usize] = load_spin(ising, vals, base + (x + w * y) as usize);
+        k += TILE * TILE;
+    }
+    unsafe { spirv_std::arch::workgroup_memory_barrier_with_group_sync() };
+
+    let ix = x0 + lid.x * cells;
+    let iy = y0 + lid.y;
+    if ix >= w || iy >= h {
+        return;
+    }
+    // Index in the tile of the first cell of the invocation.
+    let t = (lid.x * cells + 1 + tile_width * (lid.y + 1)) as usize;
+    let i = base + (ix + w * iy) as usize;
+    let a = tile_step_spin(
+        ising,
+        rule,
+        coupling,
+        tile,
+        tile_width as usize,
+        t,
+        rngs,
+        i,
+        iy,
+    );
+    let b = if cells == 2 {
+        tile_step_spin(
+            ising,
+            rule,
+            coupling,
+            tile,
+            tile_width as usize,
+            t + 1,
+            rngs,
+            i + 1,
+            iy,
+        )
+    } else {
+        0.0
+    };
+    store_spins(ising, new_vals, i / cells as usize, a, b);
+}
+
+/// New value of the spin at the index `t` of the `tile` of [ising_step_tiled], whose rows are `tile_width` spins long, which is the cell `i` on the row `iy`. The neighbors are summed in the same order as in [step_spin].
+#[allow(clippy::too_many_arguments)]
+fn tile_step_spin(
+    ising: &IsingCtx,
+    rule: u32,
+    coupling: f32,
+    tile: &[f32; TILE_SPINS],
+    tile_width: usize,
+    t: usize,
+    rngs: &mut [CellRng],
+    i: usize,
+    iy: u32,
+) -> f32 {
+    let v = tile[t];
+    if ising.pin(iy) != NOT_PINNED {
+        return v;
+    }
+    let neighbors = tile[t - 1] + tile[t + 1] + tile[t + tile_width] + tile[t - tile_width];
+    update_spin(ising, rule, coupling, v, neighbors, &mut rngs[i])
+}
+
+/// New value of the spin of the cell (`ix`,`iy`) of the replica starting at the cell `base` after a step of [ising_step].
+#[allow(clippy::too_many_arguments)]
+fn step_spin(
+    ising: &IsingCtx,
+    rule: u32,
+    coupling: f32,
+    vals: &[u32],
+    rngs: &mut [CellRng],
+    base: usize,
+    ix: usize,
+    iy: usize,
+) -> f32 {
     let w = ising.width as usize;
     let h = ising.height as usize;
-    let i = ix + w * iy;
-    let il = ((ix + w - 1) % w) + w * iy;
-    let ir = ((ix + 1) % w) + w * iy;
-    let iu = ix + w * ((iy + 1) % h);
-    let id = ix + w * ((iy + h - 1) % h);
+    let i = base + ix + w * iy;
+    let il = base + ((ix + w - 1) % w) + w * iy;
+    let ir = base + ((ix + 1) % w) + w * iy;
+    let iu = base + ix + w * ((iy + 1) % h);
+    let id = base + ix + w * ((iy + h - 1) % h);
 
-    let v = vals[i];
-    let vc = 1.0 - 2.0 * rngs[i].next_uniform().round(); // New candidate
-    let s = -(vals[il] + vals[ir] + vals[iu] + vals[id]);
+    let v = load_spin(ising, vals, i);
+    if ising.pin(iy as u32) != NOT_PINNED {
+        return v;
+    }
+    let neighbors = load_spin(ising, vals, il)
+        + load_spin(ising, vals, ir)
+        + load_spin(ising, vals, iu)
+        + load_spin(ising, vals, id);
+    update_spin(ising, rule, coupling, v, neighbors, &mut rngs[i])
+}
+
+/// New value of the spin `v` whose neighbors sum to `neighbors` after a step of [ising_step], the random numbers being drawn from `rng` for the sweep [IsingCtx::sweep].
+pub fn update_spin(
+    ising: &IsingCtx,
+    rule: u32,
+    coupling: f32,
+    v: f32,
+    neighbors: f32,
+    rng: &mut CellRng,
+) -> f32 {
+    let t = ising.temperature;
+    let c = ising.field();
+    // New candidate
+    let vc = if ising.proposal == PROPOSAL_FLIP {
+        -v
+    } else {
+        sign_at(rng, ising.sweep, 0, 0)
+    };
+    let s = -coupling * neighbors;
 
     let e = v * s - c * v;
     let ec = vc * s - c * vc;
 
-    let r = rngs[i].next_uniform();
+    let r = uniform_at(rng, ising.sweep, 1, 0);
+    if rule == RULE_HEAT_BATH {
+        // Probability of the spin +1, whose energy is `s - c`, at equilibrium with its neighbors.
+        let p = 1.0 / (1.0 + (2.0 * (s - c) / t).exp());
+        return if r < p { 1.0 } else { -1.0 };
+    }
     let q = ((e - ec) / t).exp();
-    let p = q / (1.0 + q);
-    if r < p {
-        new_vals[i] = vc;
+    let p = if rule == RULE_METROPOLIS {
+        q.min(1.0)
+    } else {
+        q / (1.0 + q)
+    };
+    if r < p { vc } else { v }
+}
+
+/// Number of passes of [kawasaki_step] per sweep, each updating a class of bonds which do not touch each other, so that every bond is proposed once per sweep.
+pub const KAWASAKI_PASSES: u32 = 16;
+
+/// Pass of [kawasaki_step] among the [KAWASAKI_PASSES] of a sweep, given in a slot of a uniform buffer selected by a dynamic offset. It is padded to the 16 bytes of the alignment of the uniforms.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct KawasakiPass {
+    pub pass: u32,
+    pub _padding: [u32; 3],
+}
+
+/// Compute shader of the [Kawasaki dynamics](https://en.wikipedia.org/wiki/Ising_model#Kawasaki_dynamics), which proposes to exchange two neighboring spins and accepts with the Metropolis probability `min(1, exp(-ΔE/T))`. As the spins are only exchanged, the magnetization is conserved exactly, which models the phase separation of a mixture at fixed composition. The external field does not change the energy of an exchange.
+///
+/// The exchanges of two bonds which touch each other would not see the same neighbors, so the bonds are split into [KAWASAKI_PASSES] classes updated one after the other in place in `vals`: the pass `pass` handles the horizontal bonds if it is below 8 and the vertical ones otherwise, starting at the cells whose coordinate along the bond is `pass % 4` modulo 4 and whose coordinate across is `pass / 4 % 2` modulo 2, shifted by [IsingCtx::sweep]. The bonds of a class are then at least two cells apart, and each invocation `gid` handles one of them, the lattice being dispatched on (`length along / 4`, `length across / 2`) invocations. When a side is not a multiple of 4 (along) or 2 (across), the last bonds would touch the first ones across the periodic boundaries and are skipped, the shift moving them from one sweep to the next so that every bond is still proposed. The bonds touching a pinned row are skipped as well.
+///
+/// The coupling `J` is the specialization constant with id 1 as for [ising_step].
+#[spirv(compute(threads(1)))]
+pub fn kawasaki_step(
+    #[spirv(global_invocation_id)] gid: UVec3,
+    #[spirv(spec_constant(id = 1, default = 1065353216))] coupling_bits: u32,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ising: &IsingCtx,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] vals: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] rngs: &mut [CellRng],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] temperatures: &[f32],
+    #[spirv(uniform, descriptor_set = 0, binding = 4)] pass: &KawasakiPass,
+) {
+    exchange_pair(
+        gid,
+        coupling_bits,
+        ising,
+        pass.pass,
+        vals,
+        rngs,
+        temperatures,
+    );
+}
+
+/// Same as [kawasaki_step] with the sweep counter taken from the push constants instead of [IsingCtx::sweep].
+#[spirv(compute(threads(1)))]
+pub fn kawasaki_step_push(
+    #[spirv(global_invocation_id)] gid: UVec3,
+    #[spirv(spec_constant(id = 1, default = 1065353216))] coupling_bits: u32,
+    #[spirv(push_constant)] constants: &StepConstants,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ising: &IsingCtx,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] vals: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] rngs: &mut [CellRng],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] temperatures: &[f32],
+    #[spirv(uniform, descriptor_set = 0, binding = 4)] pass: &KawasakiPass,
+) {
+    let ising = IsingCtx {
+        sweep: constants.sweep,
+        ..*ising
+    };
+    exchange_pair(
+        gid,
+        coupling_bits,
+        &ising,
+        pass.pass,
+        vals,
+        rngs,
+        temperatures,
+    );
+}
+
+/// Store the spin `v` in the cell `i` of `vals`, keeping the other spin of its word when the spins are packed as pairs of f16, see [IsingCtx::half].
+fn store_spin(ising: &IsingCtx, vals: &mut [u32], i: usize, v: f32) {
+    if ising.half != 0 {
+        let pair: Vec2 = f16x2_to_vec2(vals[i / 2]);
+        let pair = if i % 2 == 0 {
+            vec2(v, pair.y)
+        } else {
+            vec2(pair.x, v)
+        };
+        vals[i / 2] = vec2_to_f16x2(pair);
     } else {
-        new_vals[i] = v;
+        vals[i] = v.to_bits();
     }
 }
 
-/// Fragment shader for the Ising model which shows spin up as blue and spin down as white.
+/// Sum of the spins of the neighbors of the cell (`x`,`y`) of the replica starting at the cell `base`.
+fn neighbor_sum(ising: &IsingCtx, vals: &[u32], base: usize, x: u32, y: u32) -> f32 {
+    let (w, h) = (ising.width, ising.height);
+    load_spin(ising, vals, base + ((x + w - 1) % w + w * y) as usize)
+        + load_spin(ising, vals, base + ((x + 1) % w + w * y) as usize)
+        + load_spin(ising, vals, base + (x + w * ((y + 1) % h)) as usize)
+        + load_spin(ising, vals, base + (x + w * ((y + h - 1) % h)) as usize)
+}
+
+/// Propose the exchange of the spins of the bond of the invocation `gid` in the pass `pass` of [kawasaki_step].
+fn exchange_pair(
+    gid: UVec3,
+    coupling_bits: u32,
+    ising: &IsingCtx,
+    pass: u32,
+    vals: &mut [u32],
+    rngs: &mut [CellRng],
+    temperatures: &[f32],
+) {
+    let temperature = if ising.tempering != 0 {
+        temperatures[gid.z as usize]
+    } else {
+        ising.temperature
+    };
+    let coupling = f32::from_bits(coupling_bits);
+    let (w, h) = (ising.width, ising.height);
+    let vertical = pass >= 8;
+    let (len_along, len_across) = if vertical { (h, w) } else { (w, h) };
+    if gid.x >= len_along / 4 || gid.y >= len_across / 2 {
+        return;
+    }
+    let along = (4 * gid.x + pass % 4 + ising.sweep % len_along) % len_along;
+    let across = (2 * gid.y + pass / 4 % 2 + ising.sweep % len_across) % len_across;
+    let (x1, y1, x2, y2) = if vertical {
+        (across, along, across, (along + 1) % h)
+    } else {
+        (along, across, (along + 1) % w, across)
+    };
+    if ising.pin(y1) != NOT_PINNED || ising.pin(y2) != NOT_PINNED {
+        return;
+    }
+    let base = ising.replica_base(gid.z);
+    let i1 = base + (x1 + w * y1) as usize;
+    let i2 = base + (x2 + w * y2) as usize;
+    let s1 = load_spin(ising, vals, i1);
+    let s2 = load_spin(ising, vals, i2);
+    if s1 == s2 {
+        return;
+    }
+    // The bond between the two spins is unchanged by the exchange, so it is removed from the sums of their neighbors.
+    let n1 = neighbor_sum(ising, vals, base, x1, y1) - s2;
+    let n2 = neighbor_sum(ising, vals, base, x2, y2) - s1;
+    let delta = coupling * (s1 - s2) * (n1 - n2);
+    if delta <= 0.0
+        || uniform_at(&mut rngs[i1], ising.sweep, pass, 3) < (-delta / temperature).exp()
+    {
+        store_spin(ising, vals, i1, s2);
+        store_spin(ising, vals, i2, s1);
+    }
+}
+
+/// Majority rule over the block of `size`×`size` spins starting at (`x0`,`y0`). Ties are resolved by keeping the value of the first spin of the block.
+fn block_majority(ising: &IsingCtx, vals: &[u32], x0: u32, y0: u32, size: u32) -> f32 {
+    let mut sum = 0.0;
+    for dy in 0..size {
+        for dx in 0..size {
+            sum += load_spin(ising, vals, (x0 + dx + ising.width * (y0 + dy)) as usize);
+        }
+    }
+    if sum == 0.0 {
+        load_spin(ising, vals, (x0 + ising.width * y0) as usize)
+    } else {
+        sum.signum()
+    }
+}
+
+/// Coarse-grain the lattice by the majority rule on blocks of [IsingCtx::block_size]. When [IsingCtx::block_levels] is 2, the blocking is iterated twice: the majority rule is applied on the majorities of the sub-blocks.
+#[spirv(compute(threads(1)))]
+pub fn ising_block(
+    #[spirv(global_invocation_id)] gid: UVec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ising: &IsingCtx,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] vals: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] coarse_vals: &mut [f32],
+) {
+    let b = ising.block_size;
+    let span = ising.block_span();
+    let x0 = gid.x * span;
+    let y0 = gid.y * span;
+    let val = if ising.block_levels == 2 {
+        let mut sum = 0.0;
+        for sy in 0..b {
+            for sx in 0..b {
+                sum += block_majority(ising, vals, x0 + sx * b, y0 + sy * b, b);
+            }
+        }
+        if sum == 0.0 {
+            block_majority(ising, vals, x0, y0, b)
+        } else {
+            sum.signum()
+        }
+    } else {
+        block_majority(ising, vals, x0, y0, b)
+    };
+    let coarse_width = ising.width / span;
+    coarse_vals[(gid.x + coarse_width * gid.y) as usize] = val;
+}
+
+/// Count in each cell the number of disagreeing bonds with its right and upper neighbors, so that the sum over the lattice is the total number of domain wall bonds.
+#[spirv(compute(threads(1)))]
+pub fn ising_bonds(
+    #[spirv(global_invocation_id)] gid: UVec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ising: &IsingCtx,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] vals: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] bonds: &mut [f32],
+) {
+    let base = ising.replica_base(gid.z);
+    let ix = gid.x as usize;
+    let iy = gid.y as usize;
+    let w = ising.width as usize;
+    let h = ising.height as usize;
+    let i = base + ix + w * iy;
+    let ir = base + ((ix + 1) % w) + w * iy;
+    let iu = base + ix + w * ((iy + 1) % h);
+    let v = load_spin(ising, vals, i);
+    bonds[i] =
+        0.5 * ((v - load_spin(ising, vals, ir)).abs() + (v - load_spin(ising, vals, iu)).abs());
+}
+
+/// Context of [ising_tempering]. The partial sums of the spins and of the domain walls of each replica, computed by [reduce_sum], are made of `magnetization_partials` and `walls_partials` values respectively.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct TemperingCtx {
+    pub replicas: u32,
+    /// Number of cells of a replica.
+    pub cells: u32,
+    pub magnetization_partials: u32,
+    pub walls_partials: u32,
+    pub coupling: f32,
+    pub field: f32,
+    /// Sweep at which the exchanges are attempted, which identifies the random numbers of the attempt.
+    pub sweep: u32,
+    pub _padding: u32,
+}
+
+impl TemperingCtx {
+    /// Total energy `-J Σ s_i s_j - h Σ s_i` of the replica `replica` from the partial sums of its spins in `magnetization` and of its domain walls in `walls`: each of the `2·cells` bonds contributes `-J` if its spins are aligned and `+J` if it is a domain wall.
+    pub fn energy(&self, magnetization: &[f32], walls: &[f32], replica: u32) -> f32 {
+        let mut m = 0.0;
+        let start = replica * self.magnetization_partials;
+        for i in start..start + self.magnetization_partials {
+            m += magnetization[i as usize];
+        }
+        let mut w = 0.0;
+        let start = replica * self.walls_partials;
+        for i in start..start + self.walls_partials {
+            w += walls[i as usize];
+        }
+        -self.coupling * (2.0 * self.cells as f32 - 2.0 * w) - self.field * m
+    }
+}
+
+/// Parallel tempering: attempt to exchange the temperatures of the replicas at each pair of adjacent temperatures of the `ladder`, with the Metropolis criterion `min(1, exp((1/T_k - 1/T_k+1)(E_a - E_b)))` where `E_a` and `E_b` are the energies of the replicas at `T_k` and `T_k+1`.
+///
+/// The `state` holds the number of attempts, followed by the replica at each temperature of the ladder and by the number of accepted exchanges of each pair. The temperature of each replica is kept in `temperatures`, read by [ising_step]. A single invocation performs the attempts one pair after the other, drawing its random numbers from `rng`.
+///
+/// The energies are summed in f32, so they are exact as long as the lattice has less than `2^23` cells.
+#[spirv(compute(threads(1)))]
+pub fn ising_tempering(
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] tempering: &TemperingCtx,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] magnetization: &[f32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] walls: &[f32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] ladder: &[f32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 4)] temperatures: &mut [f32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 5)] state: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 6)] rng: &mut [CellRng],
+) {
+    let replicas = tempering.replicas as usize;
+    state[0] += 1;
+    for k in 0..replicas - 1 {
+        let a = state[1 + k];
+        let b = state[2 + k];
+        let delta = (1.0 / ladder[k] - 1.0 / ladder[k + 1])
+            * (tempering.energy(magnetization, walls, a)
+                - tempering.energy(magnetization, walls, b));
+        if uniform_at(&mut rng[0], tempering.sweep, k as u32, 2) < delta.exp() {
+            state[1 + k] = b;
+            state[2 + k] = a;
+            temperatures[a as usize] = ladder[k + 1];
+            temperatures[b as usize] = ladder[k];
+            state[1 + replicas + k] += 1;
+        }
+    }
+}
+
+/// Options of the rendering shared by the fragment shaders, bound at `descriptor_set = 1`.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct RenderCtx {
+    /// Sampling of the fields, [FILTER_NEAREST] or [FILTER_LINEAR].
+    pub filter: u32,
+    /// Whether lines are drawn at the boundaries of the cells, which is only done if the cells are at least [GRID_MIN_CELL_PIXELS] large.
+    pub show_grid: u32,
+    /// Color of the grid lines as RGBA with 8 bits per channel, red in the low bits.
+    pub grid_color: u32,
+    /// Size of the rendered rectangle in pixels.
+    pub viewport_width: f32,
+    pub viewport_height: f32,
+}
+
+/// Minimum size of the cells in pixels for the grid lines to be drawn, so that large lattices are not cluttered.
+pub const GRID_MIN_CELL_PIXELS: f32 = 6.0;
+
+/// Value of [RenderCtx::filter] showing the value of the nearest cell, which is correct for discrete fields such as the Ising spins.
+pub const FILTER_NEAREST: u32 = 0;
+/// Value of [RenderCtx::filter] interpolating bilinearly between the four surrounding cells, which looks much smoother for continuous fields when the lattice is smaller than the window.
+pub const FILTER_LINEAR: u32 = 1;
+
+/// Cell of a `width`×`height` field covering the coordinates `uv`, each cell covering an equal part of the rendered rectangle along each axis.
+pub fn cell_at(width: u32, height: u32, uv: Vec2) -> (usize, usize) {
+    let x = ((uv.x * width as f32) as usize).min(width as usize - 1);
+    let y = ((uv.y * height as f32) as usize).min(height as usize - 1);
+    (x, y)
+}
+
+/// Value at the coordinates `uv` of a `width`×`height` field whose value in the cell (`x`,`y`) is given by `value`, sampled according to [RenderCtx::filter].
+fn sample(
+    render: &RenderCtx,
+    width: u32,
+    height: u32,
+    uv: Vec2,
+    value: impl Fn(usize, usize) -> f32,
+) -> f32 {
+    if render.filter != FILTER_LINEAR {
+        let (x, y) = cell_at(width, height, uv);
+        return value(x, y);
+    }
+    // The values are interpolated between the centers of the cells, and held constant between the centers of the cells of the borders and the edges.
+    let fx = (uv.x * width as f32 - 0.5).clamp(0.0, width as f32 - 1.0);
+    let fy = (uv.y * height as f32 - 0.5).clamp(0.0, height as f32 - 1.0);
+    let x0 = fx as usize;
+    let y0 = fy as usize;
+    let x1 = (x0 + 1).min(width as usize - 1);
+    let y1 = (y0 + 1).min(height as usize - 1);
+    let tx = fx - x0 as f32;
+    let ty = fy - y0 as f32;
+    let bottom = value(x0, y0) * (1.0 - tx) + value(x1, y0) * tx;
+    let top = value(x0, y1) * (1.0 - tx) + value(x1, y1) * tx;
+    bottom * (1.0 - ty) + top * ty
+}
+
+/// Blend the grid lines at the boundaries of the cells of a `width`×`height` field over `color`, if enabled by [RenderCtx::show_grid]. The cells are the ones of [cell_at], and the lines are one pixel thick.
+fn grid(render: &RenderCtx, width: u32, height: u32, uv: Vec2, color: Vec4) -> Vec4 {
+    let cells_x = width as f32;
+    let cells_y = height as f32;
+    // Size of a cell in pixels.
+    let px = render.viewport_width / cells_x;
+    let py = render.viewport_height / cells_y;
+    if render.show_grid == 0 || px < GRID_MIN_CELL_PIXELS || py < GRID_MIN_CELL_PIXELS {
+        return color;
+    }
+    let on_line = (uv.x * cells_x).fract() * px < 1.0 || (uv.y * cells_y).fract() * py < 1.0;
+    if !on_line {
+        return color;
+    }
+    let line = unpack_rgba(render.grid_color);
+    let alpha = line.w;
+    color * (1.0 - alpha) + vec4(line.x, line.y, line.z, 1.0) * alpha
+}
+
+/// Color given as RGBA with 8 bits per channel, red in the low bits, such as [RenderCtx::grid_color].
+pub fn unpack_rgba(color: u32) -> Vec4 {
+    let channel = |shift: u32| ((color >> shift) & 0xff) as f32 / 255.0;
+    vec4(channel(0), channel(8), channel(16), channel(24))
+}
+
+/// Fragment shader for the Ising model which shows spin up with [IsingCtx::up_color] and spin down with [IsingCtx::down_color], blue and white by default.
+///
+/// When the coarse-graining is enabled (see [IsingCtx::block_size]), the left half shows the left half of the lattice and the right half shows the same region of the coarse-grained lattice.
 #[spirv(fragment)]
 pub fn ising_fragment(
     #[spirv(uniform, descriptor_set = 0, binding = 0)] ising: &IsingCtx,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] vals: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] coarse_vals: &[f32],
+    #[spirv(uniform, descriptor_set = 1, binding = 0)] render: &RenderCtx,
+    uv: Vec2,
+    output: &mut Vec4,
+) {
+    let val = if ising.block_size == 0 || uv.x < 0.5 {
+        sample(render, ising.width, ising.height, uv, |x, y| {
+            load_spin(ising, vals, x + ising.width as usize * y)
+        })
+    } else {
+        let (x, y) = cell_at(ising.width, ising.height, vec2(uv.x - 0.5, uv.y));
+        let span = ising.block_span() as usize;
+        let coarse_width = ising.width as usize / span;
+        let coarse_height = ising.height as usize / span;
+        let cx = (x / span).min(coarse_width - 1);
+        let cy = (y / span).min(coarse_height - 1);
+        coarse_vals[cx + coarse_width * cy]
+    };
+
+    let color = ising.color(val);
+    *output = if ising.block_size == 0 || uv.x < 0.5 {
+        grid(render, ising.width, ising.height, uv, color)
+    } else {
+        color
+    };
+}
+
+/// Struct which stores the size of the system and the parameters of the Langevin dynamics of a scalar field φ:
+///
+/// `∂φ/∂t = -Γ(-∇²φ + r φ + u φ³) + √(2Γ) σ η`
+///
+/// where `η` is a Gaussian white noise, `Γ` is `gamma`, `σ` is `noise` and `dt` is the time step of the explicit Euler integration.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct LangevinCtx {
+    pub width: u32,
+    pub height: u32,
+    pub r: f32,
+    pub u: f32,
+    pub gamma: f32,
+    pub dt: f32,
+    pub noise: f32,
+    pub step: u32,
+}
+
+impl LangevinCtx {
+    /// Largest stable time step for the explicit Euler integration of the linear part of the dynamics. The eigenvalues of the discrete Laplacian `-∇²` lie in `[0,8]` in two dimensions, so the linear part is stable if `dt·Γ·(8+|r|) < 2`. The cubic term stiffens the dynamics for large `|φ|`, so a margin is kept by using 1 instead of 2.
+    pub fn max_dt(&self) -> f32 {
+        1.0 / (self.gamma * (8.0 + self.r.abs()))
+    }
+}
+
+/// Reset the field to small random values around zero.
+#[spirv(compute(threads(1)))]
+pub fn langevin_reset(
+    #[spirv(global_invocation_id)] gid: UVec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] langevin: &LangevinCtx,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] vals: &mut [f32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] rngs: &mut [CellRng],
+) {
+    let i = (gid.x + langevin.width * gid.y) as usize;
+    vals[i] = 0.1 * (2.0 * uniform_at(&mut rngs[i], langevin.step, 0, 1) - 1.0);
+}
+
+/// Explicit Euler step of the Langevin dynamics described in [LangevinCtx].
+#[spirv(compute(threads(1)))]
+pub fn langevin_step(
+    #[spirv(global_invocation_id)] gid: UVec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] langevin: &LangevinCtx,
     #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] vals: &[f32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] new_vals: &mut [f32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] rngs: &mut [CellRng],
+) {
+    let ix = gid.x as usize;
+    let iy = gid.y as usize;
+    let w = langevin.width as usize;
+    let h = langevin.height as usize;
+    let i = ix + w * iy;
+    let il = ((ix + w - 1) % w) + w * iy;
+    let ir = ((ix + 1) % w) + w * iy;
+    let iu = ix + w * ((iy + 1) % h);
+    let id = ix + w * ((iy + h - 1) % h);
+
+    let phi = vals[i];
+    let laplacian = vals[il] + vals[ir] + vals[iu] + vals[id] - 4.0 * phi;
+    let force = -langevin.gamma * (-laplacian + langevin.r * phi + langevin.u * phi * phi * phi);
+
+    let [r1, r2] = random_pair_at(&mut rngs[i], langevin.step, 0, 0);
+    // 1-u is in (0,1] which avoids the logarithm of 0 in the Box–Muller transform.
+    let [eta, _] = box_muller(1.0 - uniform_from_u32(r1), uniform_from_u32(r2));
+    let noise = (2.0 * langevin.gamma * langevin.dt).sqrt() * langevin.noise * eta;
+
+    new_vals[i] = phi + langevin.dt * force + noise;
+}
+
+/// Range of the values of a continuous field shown by a colormap, either fixed or the current range of the field computed by [reduce_min_max] and [reduce_range], which are copied over it.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct RangeCtx {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl RangeCtx {
+    /// Position of `value` in the range, clamped to `[0,1]`. A range reduced to a single value, such as the one of a constant field, does not divide by zero.
+    pub fn normalize(&self, value: f32) -> f32 {
+        ((value - self.min) / (self.max - self.min).max(f32::MIN_POSITIVE)).clamp(0.0, 1.0)
+    }
+}
+
+/// Fragment shader for the Langevin dynamics which shows a diverging colormap over the range `range`: blue for `φ ≤ min`, white in the middle of the range and red for `φ ≥ max`. The default range `[-2,2]` shows `φ = 0` in white.
+#[spirv(fragment)]
+pub fn langevin_fragment(
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] langevin: &LangevinCtx,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] vals: &[f32],
+    #[spirv(uniform, descriptor_set = 0, binding = 2)] range: &RangeCtx,
+    #[spirv(uniform, descriptor_set = 1, binding = 0)] render: &RenderCtx,
+    uv: Vec2,
+    output: &mut Vec4,
+) {
+    let phi = sample(render, langevin.width, langevin.height, uv, |x, y| {
+        vals[x + langevin.width as usize * y]
+    });
+    let c = 2.0 * range.normalize(phi) - 1.0;
+
+    *output = if c > 0.0 {
+        vec4(1.0, 1.0 - c, 1.0 - c, 1.0)
+    } else {
+        vec4(1.0 + c, 1.0 + c, 1.0, 1.0)
+    };
+}
+
+/// Context of a reduction pass: the input has `len` words per group and each invocation reduces a chunk of `chunk` consecutive words of the group `global_invocation_id.y`, the groups being stored one after the other. When `half` is non zero, each word holds a pair of packed f16 instead of an f32, see [IsingCtx::half].
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct ReduceCtx {
+    pub len: u32,
+    pub chunk: u32,
+    pub half: u32,
+}
+
+/// Partial sums of `input` over chunks of [ReduceCtx::chunk] values, the partial sums of each group being stored one after the other.
+#[spirv(compute(threads(1)))]
+pub fn reduce_sum(
+    #[spirv(global_invocation_id)] gid: UVec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] reduce: &ReduceCtx,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] input: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] output: &mut [f32],
+) {
+    let partials = reduce.len.div_ceil(reduce.chunk);
+    let group = gid.y * reduce.len;
+    let start = group + gid.x * reduce.chunk;
+    let end = group + ((gid.x + 1) * reduce.chunk).min(reduce.len);
+    let mut sum = 0.0;
+    for i in start..end {
+        let word = input[i as usize];
+        sum += if reduce.half != 0 {
+            let pair: Vec2 = f16x2_to_vec2(word);
+            pair.x + pair.y
+        } else {
+            f32::from_bits(word)
+        };
+    }
+    output[(gid.y * partials + gid.x) as usize] = sum;
+}
+
+/// Partial minima and maxima of `input` over chunks of [ReduceCtx::chunk] values, stored as `(min, max)` pairs one group after the other like the partial sums of [reduce_sum]. The values are f32, [ReduceCtx::half] is ignored. A NaN propagates to the minimum and the maximum (see [min_nan]) so that a blow-up of the values is not hidden.
+#[spirv(compute(threads(1)))]
+pub fn reduce_min_max(
+    #[spirv(global_invocation_id)] gid: UVec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] reduce: &ReduceCtx,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] input: &[f32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] output: &mut [f32],
+) {
+    let partials = reduce.len.div_ceil(reduce.chunk);
+    let group = gid.y * reduce.len;
+    let start = group + gid.x * reduce.chunk;
+    let end = group + ((gid.x + 1) * reduce.chunk).min(reduce.len);
+    let mut min = f32::INFINITY;
+    let mut max = f32::NEG_INFINITY;
+    for i in start..end {
+        let value = input[i as usize];
+        min = min_nan(min, value);
+        max = max_nan(max, value);
+    }
+    let o = 2 * (gid.y * partials + gid.x) as usize;
+    output[o] = min;
+    output[o + 1] = max;
+}
+
+/// Minimum of `a` and `b` which is NaN if either of them is, unlike [f32::min] which ignores a NaN.
+pub fn min_nan(a: f32, b: f32) -> f32 {
+    if a.is_nan() || b.is_nan() {
+        f32::NAN
+    } else {
+        a.min(b)
+    }
+}
+
+/// Maximum of `a` and `b` which is NaN if either of them is, unlike [f32::max] which ignores a NaN.
+pub fn max_nan(a: f32, b: f32) -> f32 {
+    if a.is_nan() || b.is_nan() {
+        f32::NAN
+    } else {
+        a.max(b)
+    }
+}
+
+/// Partial minima, maxima and sums of `input` over chunks of [ReduceCtx::chunk] values in a single pass, stored as `(min, max, sum)` triples one group after the other like the partial sums of [reduce_sum]. A NaN propagates to the minimum and the maximum (see [min_nan]) so that a blow-up of the values is not hidden, and a chunk without values gives `(+∞, -∞, 0)`.
+#[spirv(compute(threads(1)))]
+pub fn reduce_stats(
+    #[spirv(global_invocation_id)] gid: UVec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] reduce: &ReduceCtx,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] input: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] output: &mut [f32],
+) {
+    let partials = reduce.len.div_ceil(reduce.chunk);
+    let group = gid.y * reduce.len;
+    let start = group + gid.x * reduce.chunk;
+    let end = group + ((gid.x + 1) * reduce.chunk).min(reduce.len);
+    let mut min = f32::INFINITY;
+    let mut max = f32::NEG_INFINITY;
+    let mut sum = 0.0;
+    for i in start..end {
+        let word = input[i as usize];
+        if reduce.half != 0 {
+            let pair: Vec2 = f16x2_to_vec2(word);
+            min = min_nan(min, min_nan(pair.x, pair.y));
+            max = max_nan(max, max_nan(pair.x, pair.y));
+            sum += pair.x + pair.y;
+        } else {
+            let value = f32::from_bits(word);
+            min = min_nan(min, value);
+            max = max_nan(max, value);
+            sum += value;
+        }
+    }
+    let o = 3 * (gid.y * partials + gid.x) as usize;
+    output[o] = min;
+    output[o + 1] = max;
+    output[o + 2] = sum;
+}
+
+/// Minimum and maximum of the group `global_invocation_id.x` of [ReduceCtx::len] `(min, max)` pairs computed by [reduce_min_max], stored as a pair in `range`. A NaN of the partials propagates to both, see [min_nan].
+#[spirv(compute(threads(1)))]
+pub fn reduce_range(
+    #[spirv(global_invocation_id)] gid: UVec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] reduce: &ReduceCtx,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] partials: &[f32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] range: &mut [f32],
+) {
+    let group = (gid.x * reduce.len) as usize;
+    let mut min = f32::INFINITY;
+    let mut max = f32::NEG_INFINITY;
+    for i in group..group + reduce.len as usize {
+        min = min_nan(min, partials[2 * i]);
+        max = max_nan(max, partials[2 * i + 1]);
+    }
+    range[2 * gid.x as usize] = min;
+    range[2 * gid.x as usize + 1] = max;
+}
+
+/// Lattice of `width`×`height` vector spins renormalized by [normalize_spins], each spin being `stride` consecutive f32.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct NormalizeCtx {
+    pub width: u32,
+    pub height: u32,
+    pub stride: u32,
+}
+
+/// Rescale the spin of the cell `global_invocation_id.xy` to unit length, which undoes the drift of the rounding errors accumulated by the steps of a model of vector spins such as the XY model (`vec2<f32>`) or the Heisenberg model (`vec4<f32>` with a zero last component, which stays zero). A spin of zero length is left unchanged.
+#[spirv(compute(threads(1)))]
+pub fn normalize_spins(
+    #[spirv(global_invocation_id)] gid: UVec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] normalize: &NormalizeCtx,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] spins: &mut [f32],
+) {
+    let base = ((gid.x + normalize.width * gid.y) * normalize.stride) as usize;
+    let mut length2 = 0.0;
+    for c in 0..normalize.stride {
+        let v = spins[base + c as usize];
+        length2 += v * v;
+    }
+    if length2 > 0.0 {
+        let scale = 1.0 / length2.sqrt();
+        for c in 0..normalize.stride {
+            spins[base + c as usize] *= scale;
+        }
+    }
+}
+
+/// Size of the fields of [two_field_fragment] and their normalization: the field A is shown over `[min_a, max_a]` and the field B over `[min_b, max_b]`. The four bounds are consecutive so that the ranges computed by [reduce_range] can be copied over them for the auto-scaling.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct TwoFieldCtx {
+    pub width: u32,
+    pub height: u32,
+    pub min_a: f32,
+    pub max_a: f32,
+    pub min_b: f32,
+    pub max_b: f32,
+}
+
+impl TwoFieldCtx {
+    /// Position in the colormap of [two_field_fragment] of a cell with the values `a` and `b`: 0 where only A is present, 1 where only B is present and 0.5 where both are equal once normalized.
+    pub fn mix(&self, a: f32, b: f32) -> f32 {
+        let range = |min, max| RangeCtx { min, max };
+        let a = range(self.min_a, self.max_a).normalize(a);
+        let b = range(self.min_b, self.max_b).normalize(b);
+        0.5 + 0.5 * (b - a)
+    }
+}
+
+/// Fragment shader shared by the models of two fields, such as the reaction-diffusion models, which shows a diverging colormap from blue where the field A dominates to white where both fields are balanced and red where the field B dominates, so that the fronts between them stand out. See [TwoFieldCtx::mix].
+#[spirv(fragment)]
+pub fn two_field_fragment(
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] two_field: &TwoFieldCtx,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] field_a: &[f32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] field_b: &[f32],
+    #[spirv(uniform, descriptor_set = 1, binding = 0)] render: &RenderCtx,
     uv: Vec2,
     output: &mut Vec4,
 ) {
-    let w = ising.width as f32;
-    let h = ising.height as f32;
-    let x = (uv.x * (w - 1.0)) as usize;
-    let y = (uv.y * (h - 1.0)) as usize;
-    let id = x + ising.width as usize * y;
-    let val = vals[id];
+    let w = two_field.width;
+    let h = two_field.height;
+    let a = sample(render, w, h, uv, |x, y| field_a[x + w as usize * y]);
+    let b = sample(render, w, h, uv, |x, y| field_b[x + w as usize * y]);
+    let c = 2.0 * two_field.mix(a, b) - 1.0;
+
+    let color = if c > 0.0 {
+        vec4(1.0, 1.0 - c, 1.0 - c, 1.0)
+    } else {
+        vec4(1.0 + c, 1.0 + c, 1.0, 1.0)
+    };
+    *output = grid(render, w, h, uv, color);
+}
+
+/// Context of [rng_self_test], bound as a storage buffer. Each invocation `i` draws `count` consecutive u32 from a [Philox4x32] with the initial counter `seed` and the key `key` whose first word is incremented by `i`.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct RngTestCtx {
+    pub seed: [u32; 4],
+    pub key: [u32; 2],
+    pub count: u32,
+    /// Bound of the values drawn with [GPURng::next_range] in the second half of the outputs of each invocation.
+    pub range: u32,
+}
+
+impl RngTestCtx {
+    /// Generator of the invocation `i`.
+    pub fn rng(&self, i: u32) -> Philox4x32 {
+        Philox4x32::new_u32(self.seed, [self.key[0].wrapping_add(i), self.key[1]])
+    }
+    /// Outputs `count` values of the generator `rng`: the first half are raw u32 and the second half are integers in `[0, range)`, which goes through [widening_mul_u32](gpu_random::widening_mul::widening_mul_u32) a second time.
+    pub fn draw(&self, rng: &mut Philox4x32, j: u32) -> u32 {
+        if j < self.count / 2 {
+            rng.next_u32()
+        } else {
+            rng.next_range(self.range)
+        }
+    }
+}
 
-    *output = vec4(1.0 - val, 1.0 - val, 1.0, 1.0);
+/// Fill `output` with the values of the generators described by [RngTestCtx], so that they can be compared with the same generators on the CPU. This verifies on the actual hardware the emulation of the operations which are not available in WebGPU, such as the 32×32→64 bits multiplication.
+#[spirv(compute(threads(1)))]
+pub fn rng_self_test(
+    #[spirv(global_invocation_id)] gid: UVec3,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] ctx: &RngTestCtx,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] output: &mut [u32],
+) {
+    let mut rng = ctx.rng(gid.x);
+    for j in 0..ctx.count {
+        output[(gid.x * ctx.count + j) as usize] = ctx.draw(&mut rng, j);
+    }
 }
 
 /// Simple fragment shader to verify that the uv coordinates are correct by showing them in the red and blue channels.
@@ -0,0 +1,398 @@
+use std::path::PathBuf;
+
+use clap::{ArgAction, Args, CommandFactory, Parser, error::ErrorKind};
+
+use crate::{
+    config::LatticeSize, gpu::physics::Precision, headless::HeadlessConfig, seed::Seed,
+    simulation::SimulationOptions,
+};
+
+/// Options of the whole run. They can be given before the first simulation as well as among the options of any simulation.
+#[derive(Args, Clone, Debug, Default, PartialEq)]
+pub struct RunArgs {
+    /// Run a single Ising simulation without any window and write its final state.
+    #[arg(long)]
+    pub headless: bool,
+    /// Number of sweeps of a headless run [default: 1000].
+    #[arg(long, visible_alias = "steps")]
+    pub sweeps: Option<usize>,
+    /// Number of sweeps of a headless run performed before the `--sweeps`, without measurement, to discard the transient of the initial state [default: 0].
+    #[arg(long)]
+    pub thermalize: Option<usize>,
+    /// Output of a headless run: a `.npy` or `.csv` file, or a directory of CSV files [default: .].
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+    /// SPIR-V module of custom kernels to run in an additional column, described by the file with the same name and the extension `manifest`. Can be repeated.
+    #[arg(long, value_name = "PATH")]
+    pub spv: Vec<PathBuf>,
+}
+
+impl RunArgs {
+    /// Headless run `config`, such as the one of a configuration file, with the sweeps and the output overridden by the ones given on the command line.
+    pub fn override_config(&self, config: HeadlessConfig) -> HeadlessConfig {
+        HeadlessConfig {
+            thermalize: self.thermalize.unwrap_or(config.thermalize),
+            sweeps: self.sweeps.unwrap_or(config.sweeps),
+            output: self.output.clone().unwrap_or(config.output),
+            ..config
+        }
+    }
+    /// Merge the options given later on the command line into these ones.
+    fn merge(&mut self, other: RunArgs) {
+        self.headless |= other.headless;
+        self.sweeps = other.sweeps.or(self.sweeps);
+        self.thermalize = other.thermalize.or(self.thermalize);
+        self.output = other.output.or(self.output.take());
+        self.spv.extend(other.spv);
+    }
+}
+
+/// Command line of phase: the simulations to display side by side, each followed by its own options, such as `phase ising --size 2048 --temperature 2.269 --seed 0xdeadbeef`.
+#[derive(Parser, Debug)]
+#[command(
+    name = "phase",
+    about = "GPU simulations of phase transitions",
+    override_usage = "phase [OPTIONS] [SIMULATION [SIMULATION OPTIONS]]..."
+)]
+pub struct Cli {
+    /// List the available simulations and exit.
+    #[arg(long)]
+    pub list: bool,
+    /// Simulation to start, followed by its options, as an alternative to naming it: `--sim ising --size 512` is the same as `ising --size 512`. It can also precede the name of each of the next simulations.
+    #[arg(long, value_name = "SIMULATION")]
+    pub sim: Option<String>,
+    /// Configuration file of the run, giving the simulation, its seed, its size and its parameters, and the sweeps and the output of a headless run (see the README). The options of the whole run, such as `--sweeps`, override the ones of the file.
+    #[arg(long, value_name = "PATH", conflicts_with = "sim")]
+    pub config: Option<PathBuf>,
+    #[command(flatten)]
+    pub run: RunArgs,
+    /// Simulations to start, each followed by its options (see `phase ising --help`). Only the Ising model is started if none is given, unless custom kernels are given with `--spv`.
+    #[arg(
+        value_name = "SIMULATION",
+        trailing_var_arg = true,
+        allow_hyphen_values = true
+    )]
+    pub simulations: Vec<String>,
+}
+
+/// Options of one simulation on the command line.
+#[derive(Parser, Clone, Debug, Default, PartialEq)]
+#[command(
+    name = "SIMULATION",
+    no_binary_name = true,
+    allow_negative_numbers = true
+)]
+pub struct SimulationArgs {
+    /// Fixed size of the lattice, instead of following the size of the window: `512` for a square lattice, or `256x128` for a rectangular one, the width first.
+    #[arg(long)]
+    pub size: Option<LatticeSize>,
+    /// Temperature of the heat bath.
+    #[arg(long)]
+    pub temperature: Option<f32>,
+    /// External magnetic field.
+    #[arg(long)]
+    pub field: Option<f32>,
+    /// Seed of the random number generators, in hexadecimal.
+    #[arg(long)]
+    pub seed: Option<Seed>,
+    /// Number of independent replicas.
+    #[arg(long)]
+    pub replicas: Option<u32>,
+    /// Store the lattice in half precision.
+    #[arg(long)]
+    pub half: bool,
+    /// Parameters in the format of the query of the links of the web app, such as `"T=2.269&seed=42&size=512"`, applied after the other options. The parameters are given by the tags of the GUI, with their spaces written `+` or `%20`.
+    #[arg(long, value_name = "QUERY")]
+    pub params: Option<String>,
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+impl SimulationArgs {
+    /// Options applied to the simulation with [Simulation::apply_options](crate::simulation::Simulation::apply_options).
+    pub fn options(&self) -> SimulationOptions {
+        SimulationOptions {
+            temperature: self.temperature,
+            field: self.field,
+            replicas: self.replicas,
+            half: self.half,
+        }
+    }
+}
+
+/// Simulations selected on the command line, with the options of the whole run.
+#[derive(Debug, PartialEq)]
+pub struct Selection {
+    pub run: RunArgs,
+    pub simulations: Vec<(&'static str, SimulationArgs)>,
+}
+
+/// Names of the available simulations, see [simulations](crate::simulation::simulations). Only the Ising model is available without the `gui` feature, in headless mode.
+pub fn names() -> Vec<&'static str> {
+    #[cfg(feature = "gui")]
+    let names = crate::simulation::simulations()
+        .iter()
+        .map(|(name, _)| *name)
+        .collect();
+    #[cfg(not(feature = "gui"))]
+    let names = vec!["ising"];
+    names
+}
+
+impl Cli {
+    /// Split the trailing arguments at each simulation name in `names`, optionally preceded by `--sim`, and parse the options of each simulation. A word which is not the value of an option must be the name of a simulation, otherwise the error lists the available ones.
+    pub fn select(self, names: &[&'static str]) -> Result<Selection, clap::Error> {
+        let command = SimulationArgs::command();
+        let takes_value = |arg: &str| {
+            command.get_arguments().any(|a| {
+                matches!(a.get_action(), ArgAction::Set | ArgAction::Append)
+                    && a.get_long().is_some_and(|long| arg == format!("--{long}"))
+            })
+        };
+        let mut groups: Vec<(&'static str, Vec<String>)> = vec![];
+        let mut value_expected = false;
+        let mut name_expected = false;
+        // The `--sim` given before the trailing arguments is parsed by clap, and the options which follow it are trailing arguments of its simulation.
+        let args = self
+            .sim
+            .into_iter()
+            .flat_map(|sim| ["--sim".to_string(), sim])
+            .chain(self.simulations);
+        for arg in args {
+            if !value_expected && !name_expected && arg == "--sim" {
+                name_expected = true;
+                continue;
+            }
+            if name_expected || (!value_expected && !arg.starts_with('-')) {
+                name_expected = false;
+                let Some(&name) = names.iter().find(|&&name| name == arg) else {
+                    return Err(Cli::command().error(
+                        ErrorKind::InvalidValue,
+                        format!(
+                            "Unknown simulation \"{arg}\", the available simulations are: {}",
+                            names.join(", ")
+                        ),
+                    ));
+                };
+                groups.push((name, vec![]));
+                continue;
+            }
+            value_expected = !value_expected && takes_value(&arg);
+            match groups.last_mut() {
+                Some((_, args)) => args.push(arg),
+                None => {
+                    return Err(Cli::command().error(
+                        ErrorKind::UnknownArgument,
+                        format!("unexpected argument \"{arg}\" before the first simulation"),
+                    ));
+                }
+            }
+        }
+        if name_expected {
+            return Err(Cli::command().error(
+                ErrorKind::InvalidValue,
+                format!(
+                    "--sim expects the name of a simulation: {}",
+                    names.join(", ")
+                ),
+            ));
+        }
+        if groups.is_empty() && self.run.spv.is_empty() {
+            groups.push(("ising", vec![]));
+        }
+        let mut run = self.run;
+        let simulations = groups
+            .into_iter()
+            .map(|(name, args)| {
+                let mut args = SimulationArgs::try_parse_from(args)
+                    .map_err(|err| err.with_cmd(&SimulationArgs::command().name(name)))?;
+                run.merge(std::mem::take(&mut args.run));
+                Ok((name, args))
+            })
+            .collect::<Result<_, clap::Error>>()?;
+        Ok(Selection { run, simulations })
+    }
+}
+
+impl Selection {
+    /// Configuration of a headless run, which must be a single Ising simulation.
+    pub fn headless_config(&self) -> Result<HeadlessConfig, String> {
+        let [("ising", args)] = self.simulations.as_slice() else {
+            return Err("the headless mode runs a single ising simulation".to_string());
+        };
+        if !self.run.spv.is_empty() {
+            return Err("the headless mode does not run the custom kernels of --spv".to_string());
+        }
+        let default = HeadlessConfig::default();
+        Ok(HeadlessConfig {
+            width: args.size.map_or(default.width, |size| size.width),
+            height: args.size.map_or(default.height, |size| size.height),
+            seed: args.seed.unwrap_or(default.seed),
+            temperature: args.temperature.unwrap_or(default.temperature),
+            field: args.field.unwrap_or(default.field),
+            thermalize: self.run.thermalize.unwrap_or(default.thermalize),
+            sweeps: self.run.sweeps.unwrap_or(default.sweeps),
+            precision: if args.half {
+                Precision::F16
+            } else {
+                default.precision
+            },
+            replicas: args.replicas.unwrap_or(default.replicas),
+            init: default.init,
+            output: self.run.output.clone().unwrap_or(default.output),
+        })
+    }
+    /// Error if options of the headless mode are given without `--headless`.
+    pub fn check_gui(&self) -> Result<(), String> {
+        if self.run.sweeps.is_some() || self.run.thermalize.is_some() || self.run.output.is_some() {
+            Err(
+                "--sweeps (or --steps), --thermalize and --output are only used with --headless"
+                    .to_string(),
+            )
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Verify that the options are attached to the simulation which precedes them, and that the options of the whole run are merged.
+#[test]
+fn test_cli_select() {
+    let cli = Cli::try_parse_from([
+        "phase",
+        "ising",
+        "--size",
+        "2048",
+        "--temperature",
+        "2.269",
+        "--field",
+        "-0.5",
+        "--seed",
+        "0xdeadbeef",
+        "langevin",
+        "--headless",
+        "--sweeps",
+        "10",
+    ])
+    .unwrap();
+    let selection = cli.select(&["ising", "langevin"]).unwrap();
+    assert_eq!(selection.simulations.len(), 2);
+    let (name, ising) = &selection.simulations[0];
+    assert_eq!(*name, "ising");
+    assert_eq!(ising.size, Some(LatticeSize::square(2048)));
+    assert_eq!(ising.temperature, Some(2.269));
+    assert_eq!(ising.field, Some(-0.5));
+    assert_eq!(ising.seed, Some(Seed(0xdeadbeef)));
+    assert_eq!(
+        selection.simulations[1],
+        ("langevin", SimulationArgs::default())
+    );
+    assert!(selection.run.headless);
+    assert_eq!(selection.run.sweeps, Some(10));
+    assert!(selection.headless_config().is_err());
+}
+
+/// Verify that the Ising model is started by default, and that unknown simulations are reported with the available ones.
+#[test]
+fn test_cli_default_and_unknown() {
+    let cli = Cli::try_parse_from(["phase", "--headless", "--output", "out.npy"]).unwrap();
+    let selection = cli.select(&["ising", "langevin"]).unwrap();
+    assert_eq!(
+        selection.simulations,
+        vec![("ising", SimulationArgs::default())]
+    );
+    let config = selection.headless_config().unwrap();
+    assert_eq!(config.output, PathBuf::from("out.npy"));
+    assert_eq!(config.width, 256);
+    assert!(selection.check_gui().is_err());
+
+    let cli = Cli::try_parse_from(["phase", "life", "--size", "1024"]).unwrap();
+    let err = cli.select(&["ising", "langevin"]).unwrap_err().to_string();
+    assert!(err.contains("Unknown simulation \"life\""));
+    assert!(err.contains("ising, langevin"));
+
+    let cli = Cli::try_parse_from(["phase", "ising", "--size", "big"]).unwrap();
+    assert!(cli.select(&["ising"]).is_err());
+
+    let cli = Cli::try_parse_from(["phase", "ising", "--size", "256x128", "--headless"]).unwrap();
+    let config = cli.select(&["ising"]).unwrap().headless_config().unwrap();
+    assert_eq!((config.width, config.height), (256, 128));
+}
+
+/// Verify that the custom kernels given with `--spv` are collected from any position, that they replace the default Ising model, and that they are refused in headless mode.
+#[test]
+fn test_cli_spv() {
+    let cli = Cli::try_parse_from(["phase", "--spv", "gray_scott.spv"]).unwrap();
+    let selection = cli.select(&["ising", "langevin"]).unwrap();
+    assert!(selection.simulations.is_empty());
+    assert_eq!(selection.run.spv, vec![PathBuf::from("gray_scott.spv")]);
+
+    let cli = Cli::try_parse_from([
+        "phase",
+        "--spv",
+        "a.spv",
+        "ising",
+        "--spv",
+        "b.spv",
+        "--headless",
+    ])
+    .unwrap();
+    let selection = cli.select(&["ising", "langevin"]).unwrap();
+    assert_eq!(selection.simulations.len(), 1);
+    assert_eq!(
+        selection.run.spv,
+        vec![PathBuf::from("a.spv"), PathBuf::from("b.spv")]
+    );
+    assert!(selection.headless_config().is_err());
+}
+
+/// Verify that `--sim` selects a simulation like its name, before its options or between simulations, and that `--steps` and a CSV output are accepted for a headless run.
+#[test]
+fn test_cli_sim() {
+    let cli = Cli::try_parse_from([
+        "phase",
+        "--sim",
+        "ising",
+        "--temperature",
+        "2.5",
+        "--size",
+        "512",
+        "--sim",
+        "langevin",
+    ])
+    .unwrap();
+    let selection = cli.select(&["ising", "langevin"]).unwrap();
+    let (name, ising) = &selection.simulations[0];
+    assert_eq!(*name, "ising");
+    assert_eq!(ising.temperature, Some(2.5));
+    assert_eq!(ising.size, Some(LatticeSize::square(512)));
+    assert_eq!(
+        selection.simulations[1],
+        ("langevin", SimulationArgs::default())
+    );
+
+    let cli = Cli::try_parse_from([
+        "phase",
+        "--sim",
+        "ising",
+        "--headless",
+        "--steps",
+        "10000",
+        "--output",
+        "results.csv",
+    ])
+    .unwrap();
+    let config = cli
+        .select(&["ising", "langevin"])
+        .unwrap()
+        .headless_config()
+        .unwrap();
+    assert_eq!(config.sweeps, 10000);
+    assert_eq!(config.output, PathBuf::from("results.csv"));
+
+    let cli = Cli::try_parse_from(["phase", "--sim", "life"]).unwrap();
+    let err = cli.select(&["ising", "langevin"]).unwrap_err().to_string();
+    assert!(err.contains("Unknown simulation \"life\""));
+    let cli = Cli::try_parse_from(["phase", "ising", "--sim"]).unwrap();
+    assert!(cli.select(&["ising", "langevin"]).is_err());
+}
@@ -0,0 +1,273 @@
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+
+use crate::{error::ConfigError, seed::Seed};
+
+/// Value of a parameter of a [Config], as written in its TOML: a number for a slider, a boolean for a toggle, and a string for a choice, a text or a color.
+pub use toml::Value;
+
+/// Value as shown to the user, the strings without their quotes and the numbers without a trailing `.0`.
+pub fn show(value: &Value) -> String {
+    match value {
+        Value::String(string) => string.clone(),
+        Value::Float(number) => number.to_string(),
+        value => value.to_string(),
+    }
+}
+
+/// Number `value` of a parameter, converted through its shortest decimal form so that a slider at 2.269 is written `2.269` rather than as the f64 closest to the f32.
+pub fn number(value: f32) -> Value {
+    Value::Float(value.to_string().parse().unwrap_or(f64::NAN))
+}
+
+/// Whether `value` is the default, to leave it out of the text of a [Config].
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+/// Sweeps of a headless run, the defaults of [HeadlessConfig](crate::headless::HeadlessConfig) being used for the ones not given.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Schedule {
+    /// Sweeps performed before the measured ones, to discard the transient of the initial state.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thermalize: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sweeps: Option<usize>,
+}
+
+/// Size of a lattice fixed by the user, written `512` for a square lattice and `256x128` for a rectangular one, the width first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LatticeSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl LatticeSize {
+    /// Square lattice of `size`×`size` cells.
+    pub fn square(size: u32) -> Self {
+        LatticeSize {
+            width: size,
+            height: size,
+        }
+    }
+    pub fn is_square(&self) -> bool {
+        self.width == self.height
+    }
+}
+
+impl FromStr for LatticeSize {
+    type Err = String;
+    /// Parse a size written `512` or `256x128`, the `x` being also accepted as `X` or `×`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let side = |text: &str| {
+            text.trim()
+                .parse::<u32>()
+                .map_err(|err| format!("invalid size \"{s}\": {err}"))
+        };
+        match s.split_once(['x', 'X', '×']) {
+            Some((width, height)) => Ok(LatticeSize {
+                width: side(width)?,
+                height: side(height)?,
+            }),
+            None => side(s).map(LatticeSize::square),
+        }
+    }
+}
+
+impl fmt::Display for LatticeSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_square() {
+            write!(f, "{}", self.width)
+        } else {
+            write!(f, "{}x{}", self.width, self.height)
+        }
+    }
+}
+
+impl Serialize for LatticeSize {
+    /// A square lattice is written as its side, and a rectangular one as a string such as `"256x128"`.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.is_square() {
+            serializer.serialize_u32(self.width)
+        } else {
+            serializer.collect_str(self)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LatticeSize {
+    /// Read the side of a square lattice, or a size written as a string such as `"256x128"`.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SizeVisitor;
+        impl de::Visitor<'_> for SizeVisitor {
+            type Value = LatticeSize;
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a side such as 512 or a size such as \"256x128\"")
+            }
+            fn visit_i64<E: de::Error>(self, side: i64) -> Result<LatticeSize, E> {
+                let side = u32::try_from(side)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Signed(side), &self))?;
+                Ok(LatticeSize::square(side))
+            }
+            fn visit_u64<E: de::Error>(self, side: u64) -> Result<LatticeSize, E> {
+                let side = u32::try_from(side)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(side), &self))?;
+                Ok(LatticeSize::square(side))
+            }
+            fn visit_str<E: de::Error>(self, text: &str) -> Result<LatticeSize, E> {
+                text.parse().map_err(E::custom)
+            }
+        }
+        deserializer.deserialize_any(SizeVisitor)
+    }
+}
+
+/// Configuration of a run given by a file, such as `phase --config run.toml`: the simulation, its seed and the size of its lattice, the values of its parameters by the tags of the GUI, and for a headless run the sweeps and the output. The [Settings](crate::simulation::settings::Settings) copied from the GUI use the same schema.
+///
+/// It is written in TOML, the keys containing spaces being quoted, the size of a rectangular lattice as `"256x128"`, the choices being given by the name of the option and the colors as `"#rrggbb"`:
+/// ```text
+/// simulation = "ising"
+/// seed = "0123456789abcdef0123456789abcdef"
+/// size = 512
+/// output = "run.npy"
+///
+/// [parameters]
+/// T = 2.269
+/// init = "quasi-random"
+/// "spin up" = "#ffffff"
+///
+/// [schedule]
+/// thermalize = 1000
+/// sweeps = 10000
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub simulation: String,
+    /// Seed of the run, drawn at random if it is not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<Seed>,
+    /// Size of the lattice, which otherwise follows the size of the canvas, or takes the default size of a headless run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<LatticeSize>,
+    /// File or directory where the results of a headless run are written.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+    /// Values of the parameters, by tag in the order of the GUI, checked against the parameters of the simulation when they are applied.
+    #[serde(default)]
+    pub parameters: toml::Table,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub schedule: Schedule,
+}
+
+impl Config {
+    /// Configuration of `simulation` with its defaults.
+    pub fn new(simulation: &str) -> Self {
+        Config {
+            simulation: simulation.to_string(),
+            seed: None,
+            size: None,
+            output: None,
+            parameters: toml::Table::new(),
+            schedule: Schedule::default(),
+        }
+    }
+    /// Parse a configuration written by the [Display](fmt::Display) of [Config]. The errors give the line at fault, and list the keys expected in place of an unknown one.
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        toml::from_str(text).map_err(|err| match err.span() {
+            Some(span) => ConfigError::Line {
+                line: text[..span.start].matches('\n').count() + 1,
+                message: err.message().to_string(),
+            },
+            None => ConfigError::Invalid(err.message().to_string()),
+        })
+    }
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = toml::to_string(self).map_err(|_| fmt::Error)?;
+        write!(f, "{text}")
+    }
+}
+
+/// Verify that a configuration with all its keys is parsed back from its text, and that the errors point to the line at fault and list the keys expected in place of a misspelled one.
+#[test]
+fn test_config() {
+    let config = Config {
+        simulation: "ising".to_string(),
+        seed: Some(Seed::from_u64(7)),
+        size: Some(LatticeSize::square(128)),
+        output: Some(PathBuf::from("runs/critical \"T\".npy")),
+        parameters: [
+            ("T".to_string(), number(2.269)),
+            (
+                "init".to_string(),
+                Value::String("quasi-random".to_string()),
+            ),
+            ("half precision".to_string(), Value::Boolean(true)),
+        ]
+        .into_iter()
+        .collect(),
+        schedule: Schedule {
+            thermalize: Some(1000),
+            sweeps: Some(10000),
+        },
+    };
+    let text = config.to_string();
+    assert!(text.contains("T = 2.269\n"), "{text}");
+    assert!(text.contains("\"half precision\" = true\n"), "{text}");
+    assert_eq!(Config::parse(&text), Ok(config.clone()));
+    let defaults = Config::new("langevin");
+    assert_eq!(Config::parse(&defaults.to_string()), Ok(defaults));
+    let rectangular = Config {
+        size: Some(LatticeSize {
+            width: 256,
+            height: 128,
+        }),
+        ..Config::new("ising")
+    };
+    assert!(rectangular.to_string().contains("size = \"256x128\"\n"));
+    assert_eq!(Config::parse(&rectangular.to_string()), Ok(rectangular));
+
+    let error = |text: &str| Config::parse(text).unwrap_err().to_string();
+    let misspelled = error("simulation = \"ising\"\n# comment\nsede = \"2a\"\n");
+    assert!(
+        misspelled
+            .starts_with("line 3: unknown field `sede`, expected one of `simulation`, `seed`"),
+        "{misspelled}"
+    );
+    let section = error("simulation = \"ising\"\n[shedule]\n");
+    assert!(
+        section.starts_with("line 2: unknown field `shedule`"),
+        "{section}"
+    );
+    let negative = error("simulation = \"ising\"\n[schedule]\nsweeps = -5\n");
+    assert!(negative.starts_with("line 3: "), "{negative}");
+    assert!(error("size = 64\n").contains("missing field `simulation`"));
+    assert!(
+        error("simulation = \"ising\"\nsize = \"256x\"\n")
+            .starts_with("line 2: invalid size \"256x\": cannot parse integer from empty string")
+    );
+    assert!(error("simulation = ising\n").starts_with("line 1: "));
+}
+
+/// Verify that the sizes of the lattices are parsed in their square and rectangular forms, the width first, and written back the same way.
+#[test]
+fn test_lattice_size() {
+    let rectangle = LatticeSize {
+        width: 256,
+        height: 128,
+    };
+    assert_eq!("512".parse(), Ok(LatticeSize::square(512)));
+    assert_eq!("256x128".parse(), Ok(rectangle));
+    assert_eq!(" 256 × 128 ".parse(), Ok(rectangle));
+    assert_eq!(rectangle.to_string(), "256x128");
+    assert_eq!(LatticeSize::square(64).to_string(), "64");
+    assert_eq!("128x128".parse::<LatticeSize>().unwrap().to_string(), "128");
+    assert!("big".parse::<LatticeSize>().is_err());
+    assert!("256x128x2".parse::<LatticeSize>().is_err());
+}
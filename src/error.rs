@@ -5,17 +5,60 @@ pub enum WGPUError {
     #[error("No suitable GPU adapter found")]
     NoAdapter,
 
+    #[error("No wgpu render state available, eframe must use the wgpu renderer")]
+    NoRenderState,
+
     #[error("No suitable Vulkan device found among {0} devices")]
     NoVulkanDevice(usize),
 
     #[error("Buffer size overflow: {0} elements × {1} bytes per element")]
     BufferSizeOverflow(usize, usize),
 
+    #[error("Failed to allocate the buffers of {0} cells: {1}")]
+    AllocationFailed(usize, wgpu::Error),
+
+    #[error("The GPU device was lost: {0}")]
+    DeviceLost(String),
+
     #[error("Mapped memory size ({mapped}) is smaller than expected ({expected})")]
     InsufficientMappedMemory { mapped: u64, expected: u64 },
 
-    #[error("Failed to find kernel module: {0}")]
-    KernelNotFound(String),
+    #[error("State size ({len}) does not match the number of cells ({expected})")]
+    StateSizeMismatch { len: usize, expected: usize },
+
+    #[error("Binding {binding} appears twice in the bind group {group}")]
+    DuplicateBinding { group: usize, binding: u32 },
+
+    #[error(
+        "Dynamic binding {binding} of the bind group {group} has chunks of {size} bytes, which must be non zero and at most the {buffer} bytes of its buffer"
+    )]
+    InvalidChunkSize {
+        group: usize,
+        binding: u32,
+        size: u64,
+        buffer: u64,
+    },
+
+    #[error("Loading a state is not supported by this physics")]
+    LoadStateUnsupported,
+
+    #[error("Invalid state: {0}")]
+    InvalidState(String),
+
+    #[error("Failed to load the image: {0}")]
+    Image(#[from] image::ImageError),
+
+    #[error(
+        "Kernel entry point \"{0}\" not found, the available ones are: {}",
+        .1.join(", ")
+    )]
+    KernelNotFound(String, Vec<String>),
+
+    #[error("Invalid kernel manifest: {0}")]
+    InvalidManifest(String),
+
+    #[error("Invalid replay: {0}")]
+    InvalidReplay(String),
 
     #[error("Failed to find compute queue family")]
     NoComputeQueue,
@@ -38,3 +81,26 @@ impl From<Box<dyn std::error::Error>> for WGPUError {
         WGPUError::Other(err.to_string())
     }
 }
+
+/// Error of [Simulation::update_parameter](crate::simulation::Simulation::update_parameter), which is logged by the GUI instead of stopping the simulation.
+#[derive(Error, Debug, PartialEq)]
+pub enum ParameterError {
+    #[error("Unexpected tag in update_parameter: \"{0}\"")]
+    UnknownTag(&'static str),
+
+    #[error("Invalid value \"{value}\" for \"{tag}\", keeping the last value")]
+    InvalidValue { tag: &'static str, value: String },
+}
+
+/// Error of a configuration file, see [Config](crate::config::Config), pointing to the line or the field at fault.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    #[error("line {line}: {message}")]
+    Line { line: usize, message: String },
+
+    #[error("{field}: {message}")]
+    Field { field: String, message: String },
+
+    #[error("{0}")]
+    Invalid(String),
+}
@@ -0,0 +1,117 @@
+use std::{io::Write, path::Path};
+
+/// Write `rows` of values as CSV in the file at `path`, with a first line containing the column names `header`.
+pub fn write_csv<R: AsRef<[f64]>>(
+    path: impl AsRef<Path>,
+    header: &[&str],
+    rows: impl IntoIterator<Item = R>,
+) -> std::io::Result<()> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    writeln!(file, "{}", header.join(","))?;
+    for row in rows {
+        let row = row
+            .as_ref()
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>();
+        writeln!(file, "{}", row.join(","))?;
+    }
+    file.flush()
+}
+
+/// Write the `values` of a lattice with `width` columns as CSV in the file at `path`, one line per row and without header.
+pub fn write_lattice(path: impl AsRef<Path>, width: u32, values: &[f32]) -> std::io::Result<()> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    for row in values.chunks(width as usize) {
+        let row = row.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        writeln!(file, "{}", row.join(","))?;
+    }
+    file.flush()
+}
+
+/// Encode `values` in the NumPy `.npy` format as an array of f32 of the given `shape` in C order, such as `(frames, rows, columns)`, which can be loaded with `numpy.load`. The product of `shape` must be the number of values.
+pub fn encode_npy(shape: &[usize], values: &[f32]) -> Vec<u8> {
+    debug_assert_eq!(shape.iter().product::<usize>(), values.len());
+    // A tuple with a single element needs a trailing comma.
+    let shape = match shape {
+        [length] => format!("({length},)"),
+        _ => format!(
+            "({})",
+            shape
+                .iter()
+                .map(|length| length.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let mut header = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': {shape}, }}");
+    // The magic string, the version and the length of the header take 10 bytes, and the header ends with a newline so that the data is aligned on 64 bytes.
+    let padding = (64 - (10 + header.len() + 1) % 64) % 64;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+    let mut bytes = Vec::with_capacity(10 + header.len() + 4 * values.len());
+    bytes.extend_from_slice(b"\x93NUMPY\x01\x00");
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    bytes.extend(values.iter().flat_map(|value| value.to_le_bytes()));
+    bytes
+}
+
+/// Write the `values` of a lattice with `width` columns in the file at `path` in the NumPy `.npy` format, as an array of f32 of shape `(rows, width)`, see [encode_npy].
+pub fn write_npy(path: impl AsRef<Path>, width: u32, values: &[f32]) -> std::io::Result<()> {
+    let width = width.max(1) as usize;
+    std::fs::write(path, encode_npy(&[values.len() / width, width], values))
+}
+
+/// Verify the header of a `.npy` file, by parsing its fields, and that its data is aligned, for a lattice and for a stack of lattices.
+#[test]
+fn test_write_npy() {
+    /// Descriptor, order and shape of the header of the `.npy` file `bytes`, with the offset of the data.
+    fn parse_header(bytes: &[u8]) -> (String, String, Vec<usize>, usize) {
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+        assert!(header.ends_with('\n'));
+        let field = |key: &str| {
+            let start = header.find(&format!("'{key}': ")).unwrap() + key.len() + 4;
+            let end = if header[start..].starts_with('(') {
+                header[start..].find(')').unwrap() + 1
+            } else {
+                header[start..].find(',').unwrap()
+            };
+            header[start..start + end].to_string()
+        };
+        let shape = field("shape")
+            .trim_matches(['(', ')'])
+            .split(',')
+            .filter(|length| !length.trim().is_empty())
+            .map(|length| length.trim().parse().unwrap())
+            .collect();
+        (
+            field("descr"),
+            field("fortran_order"),
+            shape,
+            10 + header_len,
+        )
+    }
+
+    let path = std::env::temp_dir().join("phase_test_write_npy.npy");
+    write_npy(&path, 3, &[1.0, -1.0, 1.0, -1.0, 1.0, -1.0]).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    let (descr, fortran_order, shape, offset) = parse_header(&bytes);
+    assert_eq!(descr, "'<f4'");
+    assert_eq!(fortran_order, "False");
+    assert_eq!(shape, [2, 3]);
+    assert_eq!(offset % 64, 0);
+    assert_eq!(bytes.len(), offset + 6 * 4);
+    assert_eq!(&bytes[offset..offset + 4], &1.0f32.to_le_bytes());
+
+    let values = (0..2 * 3 * 4).map(|v| v as f32).collect::<Vec<_>>();
+    let bytes = encode_npy(&[2, 3, 4], &values);
+    let (_, _, shape, offset) = parse_header(&bytes);
+    assert_eq!(shape, [2, 3, 4]);
+    assert_eq!(offset % 64, 0);
+    assert_eq!(&bytes[bytes.len() - 4..], &23.0f32.to_le_bytes());
+    let (_, _, shape, _) = parse_header(&encode_npy(&[5], &[0.0; 5]));
+    assert_eq!(shape, [5]);
+}
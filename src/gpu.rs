@@ -1,2 +1,19 @@
+pub mod adapter;
+pub mod capabilities;
+pub mod command;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod compute_thread;
+pub mod diagnostics;
+pub mod entry_points;
+pub mod history;
+pub mod kernel_module;
+pub mod normalize;
+pub mod observable;
 pub mod physics;
 pub mod pipeline;
+pub mod readback;
+pub mod reduction;
+pub mod step_tuner;
+pub mod timing;
+pub mod two_field;
+pub mod uniform_ring;
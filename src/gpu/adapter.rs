@@ -0,0 +1,118 @@
+#[cfg(feature = "gui")]
+use std::sync::Arc;
+
+use wgpu::{Adapter, Instance, PowerPreference, RequestAdapterOptions};
+
+use crate::error::WGPUError;
+
+/// Request an adapter outside of eframe, for instance to run a simulation headless or in tests. The adapters are tried in the order given by `preference`: with [PowerPreference::HighPerformance] (or [PowerPreference::None]) a discrete GPU is tried first, then an integrated GPU, and finally the software fallback adapter, whereas with [PowerPreference::LowPower] the integrated GPU is tried first. The name and type of the chosen adapter are logged so that a software rasterizer is never picked silently.
+pub async fn request_adapter(
+    instance: &Instance,
+    preference: PowerPreference,
+) -> Result<Adapter, WGPUError> {
+    let hardware = match preference {
+        PowerPreference::LowPower => [PowerPreference::LowPower, PowerPreference::HighPerformance],
+        _ => [PowerPreference::HighPerformance, PowerPreference::LowPower],
+    };
+    let options = hardware
+        .map(|power_preference| (power_preference, false))
+        .into_iter()
+        .chain([(PowerPreference::None, true)]);
+    for (power_preference, force_fallback_adapter) in options {
+        let adapter = instance
+            .request_adapter(&RequestAdapterOptions {
+                power_preference,
+                force_fallback_adapter,
+                compatible_surface: None,
+            })
+            .await;
+        if let Some(adapter) = adapter {
+            let info = adapter.get_info();
+            log::info!(
+                "Using adapter \"{}\" ({:?}, {:?} backend)",
+                info.name,
+                info.device_type,
+                info.backend
+            );
+            if info.device_type == wgpu::DeviceType::Cpu {
+                log::warn!(
+                    "The chosen adapter is a software rasterizer, the simulation will be slow"
+                );
+            }
+            return Ok(adapter);
+        }
+    }
+    Err(WGPUError::NoAdapter)
+}
+
+/// Request in the devices created by egui, natively and on the web, the [OPTIONAL_FEATURES] and the limits of the adapter, see [with_adapter_capabilities].
+#[cfg(feature = "gui")]
+pub fn request_adapter_capabilities(options: &mut egui_wgpu::WgpuConfiguration) {
+    if let egui_wgpu::WgpuSetup::CreateNew(setup) = &mut options.wgpu_setup {
+        let device_descriptor = Arc::clone(&setup.device_descriptor);
+        setup.device_descriptor =
+            Arc::new(move |adapter| with_adapter_capabilities(adapter, device_descriptor(adapter)));
+    }
+}
+
+/// Features used when available: push constants (the kernels fall back to uniform buffers otherwise, which is always the case on the web), timestamp queries to measure the GPU time of the passes (which are not measured otherwise), and 16-bit floats in the shaders (the half precision storage packs the spins itself, so it does not depend on them).
+pub const OPTIONAL_FEATURES: wgpu::Features = wgpu::Features::PUSH_CONSTANTS
+    .union(wgpu::Features::TIMESTAMP_QUERY)
+    .union(wgpu::Features::TIMESTAMP_QUERY_INSIDE_PASSES)
+    .union(wgpu::Features::SHADER_F16);
+
+/// Add to `descriptor` the [OPTIONAL_FEATURES] supported by `adapter`, with the largest push constant size guaranteed by Vulkan, and raise the limits which bound the size of the lattices to the ones of the adapter. The default limits of wgpu cap the storage buffers at 128 MiB whatever the GPU, whereas the limits of the adapter are the largest that it supports; the limits already larger than the ones of the adapter, as on some downlevel backends, are left unchanged.
+pub fn with_adapter_capabilities(
+    adapter: &Adapter,
+    mut descriptor: wgpu::DeviceDescriptor<'static>,
+) -> wgpu::DeviceDescriptor<'static> {
+    let supported = adapter.limits();
+    descriptor.required_features |= adapter.features() & OPTIONAL_FEATURES;
+    if adapter.features().contains(wgpu::Features::PUSH_CONSTANTS) {
+        descriptor.required_limits.max_push_constant_size =
+            supported.max_push_constant_size.min(128);
+    }
+    let limits = &mut descriptor.required_limits;
+    limits.max_buffer_size = limits.max_buffer_size.max(supported.max_buffer_size);
+    limits.max_storage_buffer_binding_size = limits
+        .max_storage_buffer_binding_size
+        .max(supported.max_storage_buffer_binding_size);
+    limits.max_compute_workgroups_per_dimension = limits
+        .max_compute_workgroups_per_dimension
+        .max(supported.max_compute_workgroups_per_dimension);
+    descriptor
+}
+
+/// Request a device of `adapter` with the [OPTIONAL_FEATURES] and the limits of the adapter, see [with_adapter_capabilities], outside of eframe.
+pub async fn request_device(adapter: &Adapter) -> Result<(wgpu::Device, wgpu::Queue), WGPUError> {
+    let descriptor = with_adapter_capabilities(adapter, wgpu::DeviceDescriptor::default());
+    Ok(adapter.request_device(&descriptor, None).await?)
+}
+
+/// Load the SPIR-V of the kernels in `device`, logging the entry points it provides.
+pub fn create_shader_module(device: &wgpu::Device) -> wgpu::ShaderModule {
+    log::info!(
+        "Kernel entry points: {}",
+        crate::kernel_entry_points().join(", ")
+    );
+    unsafe {
+        device.create_shader_module_trusted(
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Shader module"),
+                source: wgpu::util::make_spirv(crate::SPIRV),
+            },
+            wgpu::ShaderRuntimeChecks::unchecked(),
+        )
+    }
+}
+
+/// Device and kernel shader module for the tests running on the GPU, together with the instance they were created from so that the tests can inspect its allocations.
+#[cfg(feature = "gpu_test")]
+pub(crate) fn test_device() -> (Instance, wgpu::Device, wgpu::Queue, wgpu::ShaderModule) {
+    let instance = Instance::default();
+    let adapter =
+        pollster::block_on(request_adapter(&instance, PowerPreference::HighPerformance)).unwrap();
+    let (device, queue) = pollster::block_on(request_device(&adapter)).unwrap();
+    let shader_module = create_shader_module(&device);
+    (instance, device, queue, shader_module)
+}
@@ -0,0 +1,239 @@
+use wgpu::{AdapterInfo, Features, Limits};
+
+use super::adapter::OPTIONAL_FEATURES;
+use crate::error::WGPUError;
+
+/// Limits and features of the adapter and of the device created from it, which explain for instance why a large lattice cannot be allocated or why the GPU time of the passes is not measured. The limits of the device are the ones which apply, whereas the ones of the adapter are the largest that could be requested.
+#[derive(Clone, Debug)]
+pub struct Capabilities {
+    pub info: AdapterInfo,
+    pub adapter_limits: Limits,
+    pub adapter_features: Features,
+    pub device_limits: Limits,
+    pub device_features: Features,
+}
+
+/// Query the [Capabilities] of `adapter` and of the `device` created from it.
+pub fn capabilities(adapter: &wgpu::Adapter, device: &wgpu::Device) -> Capabilities {
+    Capabilities {
+        info: adapter.get_info(),
+        adapter_limits: adapter.limits(),
+        adapter_features: adapter.features(),
+        device_limits: device.limits(),
+        device_features: device.features(),
+    }
+}
+
+/// Largest size in bytes of a storage buffer bound by a device with these `limits`.
+pub fn max_binding_size(limits: &Limits) -> u64 {
+    limits
+        .max_buffer_size
+        .min(limits.max_storage_buffer_binding_size as u64)
+}
+
+/// Check that a storage buffer of `cells` elements of `bytes_per_cell` bytes can be bound by a device with these `limits`, before trying to allocate it.
+pub fn check_buffer(limits: &Limits, cells: usize, bytes_per_cell: usize) -> Result<(), WGPUError> {
+    match cells.checked_mul(bytes_per_cell) {
+        Some(bytes) if bytes as u64 <= max_binding_size(limits) => Ok(()),
+        _ => Err(WGPUError::BufferSizeOverflow(cells, bytes_per_cell)),
+    }
+}
+
+/// Number of cells of a `width`×`height` lattice, computed with checked arithmetic and checked against `limits` for storage buffers of each of the `bytes_per_cell`, before any allocation which would otherwise fail inside wgpu.
+pub fn check_lattice(
+    limits: &Limits,
+    width: u32,
+    height: u32,
+    bytes_per_cell: &[usize],
+) -> Result<usize, WGPUError> {
+    let cells = (width as usize)
+        .checked_mul(height as usize)
+        .ok_or(WGPUError::BufferSizeOverflow(usize::MAX, size_of::<f32>()))?;
+    for &bytes in bytes_per_cell {
+        check_buffer(limits, cells, bytes)?;
+    }
+    Ok(cells)
+}
+
+/// Largest lattice with the aspect ratio of `width`×`height`, and at most this size, whose storage buffers of `bytes_per_cell` bytes per cell fit in `limits`.
+pub fn clamp_lattice(
+    limits: &Limits,
+    width: u32,
+    height: u32,
+    bytes_per_cell: usize,
+) -> (u32, u32) {
+    let max_side = limits.max_compute_workgroups_per_dimension.max(1);
+    let (width, height) = (width.clamp(1, max_side), height.clamp(1, max_side));
+    let max_cells = max_binding_size(limits) / bytes_per_cell.max(1) as u64;
+    let cells = width as u64 * height as u64;
+    if cells <= max_cells {
+        return (width, height);
+    }
+    let scale = (max_cells as f64 / cells as f64).sqrt();
+    let mut width = ((width as f64 * scale) as u32).max(1);
+    let mut height = ((height as f64 * scale) as u32).max(1);
+    // The rounding of the scale might leave the lattice slightly too large.
+    while width as u64 * height as u64 > max_cells && (width > 1 || height > 1) {
+        if width >= height {
+            width -= 1;
+        } else {
+            height -= 1;
+        }
+    }
+    (width, height)
+}
+
+impl Capabilities {
+    /// Side of the largest square lattice whose buffers of `bytes_per_cell` bytes per cell can be bound by the device, which is also limited by the number of workgroups of a dispatch since the kernels dispatch one workgroup per cell.
+    pub fn max_square_lattice(&self, bytes_per_cell: u64) -> u32 {
+        let cells = self.max_binding_size() / bytes_per_cell.max(1);
+        ((cells as f64).sqrt() as u32).min(self.device_limits.max_compute_workgroups_per_dimension)
+    }
+    /// Check that a storage buffer of `cells` elements of `bytes_per_cell` bytes can be bound by the device, see [check_buffer].
+    pub fn check_buffer(&self, cells: usize, bytes_per_cell: usize) -> Result<(), WGPUError> {
+        check_buffer(&self.device_limits, cells, bytes_per_cell)
+    }
+    fn max_binding_size(&self) -> u64 {
+        max_binding_size(&self.device_limits)
+    }
+    /// Whether the GPU time of the passes is measured, and otherwise the reason why it is not.
+    pub fn timestamps(&self) -> Result<(), &'static str> {
+        if self.device_features.contains(Features::TIMESTAMP_QUERY) {
+            Ok(())
+        } else if self.adapter_features.contains(Features::TIMESTAMP_QUERY) {
+            Err("timestamp queries supported by the adapter but not enabled on the device")
+        } else {
+            Err("timestamp queries not supported by the adapter")
+        }
+    }
+    /// Name, device limit and adapter limit of the limits which matter for the simulations.
+    pub fn limits(&self) -> Vec<(&'static str, u64, u64)> {
+        let limits = |limits: &Limits| {
+            [
+                ("max buffer size", limits.max_buffer_size),
+                (
+                    "max storage buffer binding size",
+                    limits.max_storage_buffer_binding_size as u64,
+                ),
+                (
+                    "max uniform buffer binding size",
+                    limits.max_uniform_buffer_binding_size as u64,
+                ),
+                (
+                    "max workgroups per dimension",
+                    limits.max_compute_workgroups_per_dimension as u64,
+                ),
+                (
+                    "max invocations per workgroup",
+                    limits.max_compute_invocations_per_workgroup as u64,
+                ),
+                (
+                    "max storage buffers per stage",
+                    limits.max_storage_buffers_per_shader_stage as u64,
+                ),
+                (
+                    "max push constant size",
+                    limits.max_push_constant_size as u64,
+                ),
+            ]
+        };
+        limits(&self.device_limits)
+            .into_iter()
+            .zip(limits(&self.adapter_limits))
+            .map(|((name, device), (_, adapter))| (name, device, adapter))
+            .collect()
+    }
+    /// Name of each of the [OPTIONAL_FEATURES], with whether it is supported by the adapter and enabled on the device.
+    pub fn optional_features(&self) -> Vec<(&'static str, bool, bool)> {
+        OPTIONAL_FEATURES
+            .iter_names()
+            .map(|(name, feature)| {
+                (
+                    name,
+                    self.adapter_features.contains(feature),
+                    self.device_features.contains(feature),
+                )
+            })
+            .collect()
+    }
+    /// Log the adapter, the limits and the optional features.
+    pub fn log(&self) {
+        log::info!(
+            "Adapter \"{}\" ({:?}, {:?} backend, driver \"{} {}\")",
+            self.info.name,
+            self.info.device_type,
+            self.info.backend,
+            self.info.driver,
+            self.info.driver_info
+        );
+        for (name, device, adapter) in self.limits() {
+            log::info!("{name}: {device} (adapter: {adapter})");
+        }
+        for (name, supported, enabled) in self.optional_features() {
+            log::info!("{name}: supported {supported}, enabled {enabled}");
+        }
+        if let Err(reason) = self.timestamps() {
+            log::info!("GPU time of the passes not measured: {reason}");
+        }
+    }
+}
+
+/// Verify the largest square lattice allowed by the default limits.
+#[test]
+fn test_max_square_lattice() {
+    let capabilities = Capabilities {
+        info: AdapterInfo {
+            name: String::new(),
+            vendor: 0,
+            device: 0,
+            device_type: wgpu::DeviceType::Other,
+            driver: String::new(),
+            driver_info: String::new(),
+            backend: wgpu::Backend::Empty,
+        },
+        adapter_limits: Limits::default(),
+        adapter_features: Features::TIMESTAMP_QUERY,
+        device_limits: Limits::default(),
+        device_features: Features::empty(),
+    };
+    // The default binding size of 128 MiB holds 2^25 f32, so 2^12.5 cells per side.
+    assert_eq!(capabilities.max_square_lattice(4), 5792);
+    assert_eq!(capabilities.max_square_lattice(1), 11585);
+    assert_eq!(capabilities.max_square_lattice(1 << 27), 1);
+    assert!(capabilities.check_buffer(1 << 25, 4).is_ok());
+    assert!(matches!(
+        capabilities.check_buffer((1 << 25) + 1, 4),
+        Err(WGPUError::BufferSizeOverflow(_, 4))
+    ));
+    assert!(capabilities.check_buffer(usize::MAX, 2).is_err());
+    assert!(capabilities.timestamps().is_err());
+    assert_eq!(capabilities.limits().len(), 7);
+    assert_eq!(capabilities.optional_features().len(), 4);
+}
+
+/// Verify the checked size of a lattice and its clamping with mocked limits, without any GPU.
+#[test]
+fn test_clamp_lattice() {
+    // 1 MiB holds 2^18 f32, so a 512×512 lattice.
+    let limits = Limits {
+        max_buffer_size: 1 << 20,
+        max_storage_buffer_binding_size: 1 << 20,
+        ..Limits::default()
+    };
+    assert!(matches!(
+        check_lattice(&limits, 512, 512, &[4, 1]),
+        Ok(262144)
+    ));
+    assert!(matches!(
+        check_lattice(&limits, 1024, 512, &[1, 4]),
+        Err(WGPUError::BufferSizeOverflow(524288, 4))
+    ));
+    assert!(check_lattice(&limits, u32::MAX, u32::MAX, &[4]).is_err());
+    assert_eq!(clamp_lattice(&limits, 300, 200, 4), (300, 200));
+    assert_eq!(clamp_lattice(&limits, 1024, 1024, 4), (512, 512));
+    assert_eq!(clamp_lattice(&limits, 2048, 512, 4), (1024, 256));
+    let (width, height) = clamp_lattice(&limits, 16384, 10000, 16);
+    assert!(width as u64 * height as u64 * 16 <= 1 << 20);
+    assert!(width > height);
+    assert_eq!(clamp_lattice(&limits, 100_000, 1, 4), (65535, 1));
+}
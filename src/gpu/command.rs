@@ -0,0 +1,57 @@
+use std::sync::{Arc, Mutex, mpsc};
+
+/// Sending end of the commands of a [Simulation](crate::simulation::Simulation) to its physics, see [command_channel].
+pub struct CommandSender<C>(mpsc::Sender<C>);
+
+impl<C> Clone for CommandSender<C> {
+    fn clone(&self) -> Self {
+        CommandSender(self.0.clone())
+    }
+}
+
+impl<C> CommandSender<C> {
+    /// Queue `command` until the next update of the physics. Nothing is sent once every receiver has been dropped.
+    pub fn send(&self, command: C) {
+        let _ = self.0.send(command);
+    }
+}
+
+/// Receiving end of the commands, shared by the successive physics of a simulation so that a physics recreated after a restart or a resize applies the commands which the previous one did not.
+pub struct CommandReceiver<C>(Arc<Mutex<mpsc::Receiver<C>>>);
+
+impl<C> Clone for CommandReceiver<C> {
+    fn clone(&self) -> Self {
+        CommandReceiver(Arc::clone(&self.0))
+    }
+}
+
+impl<C> CommandReceiver<C> {
+    /// Commands sent since the last call, in the order in which they were sent.
+    pub fn drain(&self) -> Vec<C> {
+        self.0.lock().unwrap().try_iter().collect()
+    }
+}
+
+/// Channel of the typed commands `C` sent by a simulation from the UI, such as a reset or a new value of a parameter, to its physics, which drains them at the top of [Physics::update](super::physics::Physics::update). Unlike parameters shared through atomics, the commands are events applied exactly once and in order, and an enum of commands cannot hold an unknown tag.
+pub fn command_channel<C>() -> (CommandSender<C>, CommandReceiver<C>) {
+    let (sender, receiver) = mpsc::channel();
+    (
+        CommandSender(sender),
+        CommandReceiver(Arc::new(Mutex::new(receiver))),
+    )
+}
+
+/// Verify that the commands are received once and in order, by whichever clone of the receiver drains them first.
+#[test]
+fn test_command_channel() {
+    let (sender, receiver) = command_channel();
+    let next = receiver.clone();
+    sender.send(1);
+    sender.clone().send(2);
+    assert_eq!(receiver.drain(), [1, 2]);
+    sender.send(3);
+    assert_eq!(next.drain(), [3]);
+    assert!(receiver.drain().is_empty());
+    drop((receiver, next));
+    sender.send(4);
+}
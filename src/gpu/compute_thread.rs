@@ -0,0 +1,86 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crate::time::now;
+
+use super::physics::{FrameInfo, SharedPhysics};
+
+/// Duration of the sleep of the thread between two updates while the simulation is paused, so that it does not spin.
+const PAUSED_SLEEP: Duration = Duration::from_millis(15);
+
+/// Thread updating a [SharedPhysics] continuously instead of once per frame, so that the throughput is not capped by the vertical synchronization and the physics goes on when the window stalls.
+///
+/// Each update is recorded in its own encoder and submitted on the queue of the device while the physics is locked, and the thread then waits for the GPU outside of the lock. The rendering locks the physics only to read the offsets of its bindings, so it shows the state left by the last submission, the submissions on the queue being ordered. The thread is stopped and joined when the [ComputeThread] is dropped.
+pub struct ComputeThread {
+    stop: Arc<AtomicBool>,
+    /// Whether the simulation is paused, given to the physics with the [FrameInfo] of each update.
+    paused: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ComputeThread {
+    /// Start updating `physics` on a new thread.
+    pub fn spawn(device: wgpu::Device, queue: wgpu::Queue, physics: SharedPhysics) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let paused = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let stop = Arc::clone(&stop);
+            let paused = Arc::clone(&paused);
+            std::thread::Builder::new()
+                .name("compute".to_string())
+                .spawn(move || {
+                    let mut frame_index = 0;
+                    let mut time = now();
+                    while !stop.load(Ordering::Relaxed) {
+                        let frame = FrameInfo {
+                            dt: time.elapsed().as_secs_f32(),
+                            frame_index,
+                            paused: paused.load(Ordering::Relaxed),
+                            steps: None,
+                        };
+                        time = now();
+                        {
+                            let mut physics = physics.lock().unwrap();
+                            let mut encoder =
+                                device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                                    label: Some("Compute thread Encoder"),
+                                });
+                            physics.update(&device, &queue, &mut encoder, &frame);
+                            queue.submit(Some(encoder.finish()));
+                        }
+                        // Waiting for the GPU bounds the work queued ahead of the rendering.
+                        let _ = device.poll(wgpu::MaintainBase::Wait);
+                        frame_index += 1;
+                        if frame.paused {
+                            std::thread::sleep(PAUSED_SLEEP);
+                        }
+                    }
+                })
+                .expect("Failed to spawn the compute thread.")
+        };
+        ComputeThread {
+            stop,
+            paused,
+            handle: Some(handle),
+        }
+    }
+    /// Pause or resume the steps of the physics.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+}
+
+impl Drop for ComputeThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(Err(_)) = self.handle.take().map(JoinHandle::join) {
+            log::error!("The compute thread panicked.");
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use kernel::RngTestCtx;
+use wgpu::{Buffer, util::DeviceExt};
+
+use super::{pipeline::Pipeline, readback::Readback};
+
+/// Number of generators compared by the [RngSelfTest].
+const GENERATORS: u32 = 256;
+/// Number of values drawn from each generator by the [RngSelfTest].
+const VALUES: u32 = 64;
+
+/// Outcome of the [RngSelfTest].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RngCheck {
+    /// The values have not been read back yet.
+    Running,
+    /// All the `checked` values of the GPU are equal to the ones of the CPU.
+    Passed { checked: usize },
+    /// `mismatches` values differ, the first one being at `index` with the value `gpu` instead of `cpu`.
+    Failed {
+        mismatches: usize,
+        index: usize,
+        gpu: u32,
+        cpu: u32,
+    },
+}
+
+/// Compare the random numbers drawn on the GPU by the `rng_self_test` kernel with the same [Philox4x32](gpu_random::philox::Philox4x32) generators on the CPU, to detect a divergence of the GPU arithmetic on the actual hardware, such as in the emulated 32×32→64 bits multiplication. The values are read back asynchronously, see [RngSelfTest::poll].
+pub struct RngSelfTest {
+    ctx: RngTestCtx,
+    /// Kept alive until the values are read back.
+    _buffers: [Buffer; 2],
+    readback: Readback,
+    check: RngCheck,
+}
+
+impl RngSelfTest {
+    /// Submit the kernel filling the values and the read back of the result.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        shader_module: &wgpu::ShaderModule,
+    ) -> Self {
+        let ctx = RngTestCtx {
+            seed: [0x243f6a88, 0x85a308d3, 0x13198a2e, 0x03707344],
+            key: [0xa4093822, 0x299f31d0],
+            count: VALUES,
+            range: 1000,
+        };
+        let ctx_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("RNG self-test ctx buffer"),
+            contents: bytemuck::bytes_of(&ctx),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let output_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("RNG self-test output buffer"),
+            size: (GENERATORS * VALUES) as u64 * size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let pipeline = Pipeline::new(
+            device,
+            shader_module,
+            "rng_self_test",
+            [
+                (0, &ctx_buffer, Some(true), None),
+                (1, &output_buffer, Some(false), None),
+            ],
+        );
+        let readback = Readback::new();
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("RNG self-test Encoder"),
+        });
+        pipeline.dispatch(&mut encoder, GENERATORS, 1, 1);
+        readback.request(
+            device,
+            &mut encoder,
+            &output_buffer,
+            0..output_buffer.size(),
+        );
+        queue.submit(Some(encoder.finish()));
+        readback.submitted();
+        RngSelfTest {
+            ctx,
+            _buffers: [ctx_buffer, output_buffer],
+            readback,
+            check: RngCheck::Running,
+        }
+    }
+    /// Result of the comparison, without blocking: [RngCheck::Running] until the values have been read back.
+    pub fn poll(&mut self, device: &wgpu::Device) -> RngCheck {
+        if self.check == RngCheck::Running {
+            if let Some(data) = self.readback.poll(device).pop() {
+                self.check = compare(&self.ctx, &data.to_vec::<u32>());
+            }
+        }
+        self.check
+    }
+}
+
+/// Compare the values `gpu` with the ones computed on the CPU for `ctx`.
+fn compare(ctx: &RngTestCtx, gpu: &[u32]) -> RngCheck {
+    let cpu = (0..GENERATORS).flat_map(|i| {
+        let mut rng = ctx.rng(i);
+        (0..ctx.count)
+            .map(|j| ctx.draw(&mut rng, j))
+            .collect::<Vec<_>>()
+    });
+    let mismatches = gpu
+        .iter()
+        .zip(cpu)
+        .enumerate()
+        .filter(|(_, (gpu, cpu))| gpu != cpu)
+        .map(|(index, (&gpu, cpu))| (index, gpu, cpu))
+        .collect::<Vec<_>>();
+    match mismatches.first() {
+        None => RngCheck::Passed { checked: gpu.len() },
+        Some(&(index, gpu, cpu)) => RngCheck::Failed {
+            mismatches: mismatches.len(),
+            index,
+            gpu,
+            cpu,
+        },
+    }
+}
+
+/// Verify that the random numbers of the GPU are the ones of the CPU on the test device.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_rng_self_test() {
+    let (_instance, device, queue, shader_module) = super::adapter::test_device();
+    let mut self_test = RngSelfTest::new(&device, &queue, &shader_module);
+    let check = loop {
+        let _ = device.poll(wgpu::MaintainBase::Wait);
+        match self_test.poll(&device) {
+            RngCheck::Running => continue,
+            check => break check,
+        }
+    };
+    assert_eq!(
+        check,
+        RngCheck::Passed {
+            checked: (GENERATORS * VALUES) as usize
+        }
+    );
+}
@@ -0,0 +1,160 @@
+use std::sync::OnceLock;
+
+use crate::error::WGPUError;
+
+/// Magic number starting a SPIR-V module, in the endianness of its words.
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+/// Number of words of the header of a SPIR-V module, before the first instruction.
+const HEADER_WORDS: usize = 5;
+/// Opcode of the `OpEntryPoint` instruction.
+const OP_ENTRY_POINT: u32 = 15;
+
+/// Names of the entry points declared by the `OpEntryPoint` instructions of the SPIR-V module `spirv`, in their order in the module.
+///
+/// Only the instructions are walked, without validating the module, which is left to wgpu.
+pub fn parse_entry_points(spirv: &[u8]) -> Result<Vec<String>, WGPUError> {
+    let malformed = |reason: &str| WGPUError::Other(format!("Malformed SPIR-V module: {reason}"));
+    if spirv.len() % 4 != 0 || spirv.len() < 4 * HEADER_WORDS {
+        return Err(malformed("truncated"));
+    }
+    let words = spirv
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+        .collect::<Vec<_>>();
+    // A module written on a big-endian machine has its words swapped.
+    let words = match words[0] {
+        SPIRV_MAGIC => words,
+        magic if magic.swap_bytes() == SPIRV_MAGIC => {
+            words.iter().map(|w| w.swap_bytes()).collect()
+        }
+        _ => return Err(malformed("wrong magic number")),
+    };
+    let mut names = vec![];
+    let mut k = HEADER_WORDS;
+    while k < words.len() {
+        let count = (words[k] >> 16) as usize;
+        let opcode = words[k] & 0xffff;
+        if count == 0 || k + count > words.len() {
+            return Err(malformed("instruction out of bounds"));
+        }
+        // The operands are the execution model, the id of the function and the name as a nul terminated string packed in little-endian words, followed by the interface.
+        if opcode == OP_ENTRY_POINT && count > 3 {
+            let bytes = words[k + 3..k + count]
+                .iter()
+                .flat_map(|word| word.to_le_bytes())
+                .take_while(|&byte| byte != 0)
+                .collect::<Vec<_>>();
+            names.push(String::from_utf8(bytes).map_err(|_| malformed("invalid name"))?);
+        }
+        k += count;
+    }
+    Ok(names)
+}
+
+/// Names of the entry points of the kernels embedded in [SPIRV](crate::SPIRV), parsed once. It is empty if the module could not be parsed, which is logged.
+pub fn kernel_entry_points() -> &'static [String] {
+    static ENTRY_POINTS: OnceLock<Vec<String>> = OnceLock::new();
+    ENTRY_POINTS.get_or_init(|| match parse_entry_points(crate::SPIRV) {
+        Ok(names) => {
+            log::debug!("Kernel entry points: {}", names.join(", "));
+            names
+        }
+        Err(err) => {
+            log::warn!("Failed to list the kernel entry points: {err}");
+            vec![]
+        }
+    })
+}
+
+/// Check that `name` is among the `entry_points` of a module, such as the [kernel_entry_points], so that a typo or a stale build of the kernels gives [WGPUError::KernelNotFound] instead of an obscure validation error of wgpu when the pipeline is created. The check is skipped if the entry points could not be listed.
+pub fn check_entry_point(entry_points: &[String], name: &str) -> Result<(), WGPUError> {
+    if entry_points.is_empty() || entry_points.iter().any(|entry| entry == name) {
+        Ok(())
+    } else {
+        Err(WGPUError::KernelNotFound(
+            name.to_string(),
+            entry_points.to_vec(),
+        ))
+    }
+}
+
+/// Verify that the entry points are read from the words of a minimal module in both endiannesses, and that a truncated module is rejected.
+#[test]
+fn test_parse_entry_points() {
+    // Name packed in words with its nul terminator, padded with zeros.
+    let name = |name: &str| {
+        let mut bytes = name.as_bytes().to_vec();
+        bytes.resize(name.len() / 4 * 4 + 4, 0);
+        bytes
+            .chunks(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect::<Vec<_>>()
+    };
+    let mut words = vec![SPIRV_MAGIC, 0x0001_0300, 0, 10, 0];
+    // OpCapability Shader, which is not an entry point.
+    words.extend([(2 << 16) | 17, 1]);
+    for (id, entry) in [(4, "ising_step"), (5, "main")] {
+        let name = name(entry);
+        words.push(((3 + name.len() as u32 + 1) << 16) | OP_ENTRY_POINT);
+        // GLCompute execution model, the function and one interface id.
+        words.extend([5, id]);
+        words.extend(name);
+        words.push(7);
+    }
+    let little = words
+        .iter()
+        .flat_map(|w| w.to_le_bytes())
+        .collect::<Vec<_>>();
+    let big = words
+        .iter()
+        .flat_map(|w| w.to_be_bytes())
+        .collect::<Vec<_>>();
+    for bytes in [little, big] {
+        assert_eq!(
+            parse_entry_points(&bytes).unwrap(),
+            vec!["ising_step".to_string(), "main".to_string()]
+        );
+    }
+    let bytes = words
+        .iter()
+        .flat_map(|w| w.to_le_bytes())
+        .collect::<Vec<_>>();
+    assert!(parse_entry_points(&bytes[..bytes.len() - 4]).is_err());
+}
+
+/// Verify that the kernels export the entry points used by the host, so that a kernel renamed on one side only is caught without a GPU.
+#[test]
+fn test_kernel_entry_points() {
+    let entry_points = kernel_entry_points();
+    for name in [
+        "ising_reset",
+        "ising_step",
+        "ising_step_push",
+        "ising_step_tiled",
+        "ising_step_tiled_push",
+        "kawasaki_step",
+        "kawasaki_step_push",
+        "ising_block",
+        "ising_bonds",
+        "ising_tempering",
+        "ising_fragment",
+        "langevin_reset",
+        "langevin_step",
+        "langevin_fragment",
+        "square_vertex",
+        "reduce_sum",
+        "reduce_min_max",
+        "reduce_range",
+        "two_field_fragment",
+        "rng_self_test",
+    ] {
+        assert!(
+            entry_points.iter().any(|entry| entry == name),
+            "missing entry point {name}"
+        );
+    }
+    assert!(matches!(
+        check_entry_point(entry_points, "ising_stpe"),
+        Err(WGPUError::KernelNotFound(..))
+    ));
+}
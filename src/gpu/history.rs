@@ -0,0 +1,145 @@
+use std::collections::VecDeque;
+
+use wgpu::{Buffer, CommandEncoder};
+
+use crate::{error::WGPUError, gpu::capabilities::check_buffer};
+
+/// Ring of the last `capacity` frames of a buffer, kept on the GPU to scrub backwards through the recent history of a simulation.
+///
+/// [History::record] records in an encoder the copy of the frame into the slot of the oldest one, and [History::show] copies a past frame in place of the live state, which is saved beforehand and copied back once the live state is shown again, so that the simulation continues from it. All the copies stay on the GPU, and the memory is bounded by the `capacity` frames allocated once by [History::new].
+pub struct History {
+    /// Frames of `frame_size` bytes one after the other.
+    frames: Buffer,
+    /// Copy of the live state while a past frame is shown in its place.
+    live: Buffer,
+    frame_size: u64,
+    capacity: usize,
+    /// Slot of the next recorded frame.
+    next: usize,
+    /// Sweep of each recorded frame, from the oldest to the newest.
+    sweeps: VecDeque<u32>,
+    /// Past frame currently shown, counted backwards from the newest one, `None` when the live state is shown.
+    shown: Option<usize>,
+}
+
+impl History {
+    /// Allocate the ring of `capacity` frames of `frame_size` bytes, which must be a multiple of [wgpu::COPY_BUFFER_ALIGNMENT].
+    pub fn new(device: &wgpu::Device, frame_size: u64, capacity: usize) -> Result<Self, WGPUError> {
+        let capacity = capacity.max(1);
+        check_buffer(&device.limits(), capacity, frame_size as usize)?;
+        let usage = wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST;
+        Ok(History {
+            frames: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("History frames buffer"),
+                size: capacity as u64 * frame_size,
+                usage,
+                mapped_at_creation: false,
+            }),
+            live: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("History live buffer"),
+                size: frame_size,
+                usage,
+                mapped_at_creation: false,
+            }),
+            frame_size,
+            capacity,
+            next: 0,
+            sweeps: VecDeque::with_capacity(capacity),
+            shown: None,
+        })
+    }
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    /// Sweep of each recorded frame, from the oldest to the newest.
+    pub fn sweeps(&self) -> impl Iterator<Item = u32> + '_ {
+        self.sweeps.iter().copied()
+    }
+    /// Past frame currently shown, see [History::show].
+    pub fn shown(&self) -> Option<usize> {
+        self.shown
+    }
+    /// Slot of the frame `back` frames before the newest one.
+    fn slot(&self, back: usize) -> usize {
+        (self.next + self.capacity - 1 - back) % self.capacity
+    }
+    /// Record in `encoder` the copy of the first `frame_size` bytes of `source`, the state at the sweep `sweep`, as the newest frame, in place of the oldest one once the ring is full. The live state must be shown, as `source` holds the past frame otherwise.
+    pub fn record(&mut self, encoder: &mut CommandEncoder, source: &Buffer, sweep: u32) {
+        debug_assert!(self.shown.is_none(), "recording a past frame");
+        encoder.copy_buffer_to_buffer(
+            source,
+            0,
+            &self.frames,
+            self.next as u64 * self.frame_size,
+            self.frame_size,
+        );
+        self.next = (self.next + 1) % self.capacity;
+        if self.sweeps.len() == self.capacity {
+            self.sweeps.pop_front();
+        }
+        self.sweeps.push_back(sweep);
+    }
+    /// Record in `encoder` the copies showing in `target` the frame `back` frames before the newest one, or the live state if `back` is `None` or beyond the recorded frames. The live state of `target` is saved when a past frame is first shown, and restored when the live state is shown again.
+    pub fn show(&mut self, encoder: &mut CommandEncoder, target: &Buffer, back: Option<usize>) {
+        let back = back.filter(|&back| back < self.sweeps.len());
+        if back == self.shown {
+            return;
+        }
+        if self.shown.is_none() {
+            encoder.copy_buffer_to_buffer(target, 0, &self.live, 0, self.frame_size);
+        }
+        match back {
+            Some(back) => encoder.copy_buffer_to_buffer(
+                &self.frames,
+                self.slot(back) as u64 * self.frame_size,
+                target,
+                0,
+                self.frame_size,
+            ),
+            None => encoder.copy_buffer_to_buffer(&self.live, 0, target, 0, self.frame_size),
+        }
+        self.shown = back;
+    }
+}
+
+/// Verify that the oldest frames are replaced once the ring is full, that a past frame is shown in place of the live state, and that the live state is restored afterwards.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_history() {
+    use wgpu::util::DeviceExt;
+
+    let (_instance, device, queue, _) = crate::gpu::adapter::test_device();
+    let state = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("History test state"),
+        contents: bytemuck::cast_slice(&[0u32; 4]),
+        usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+    });
+    let mut history = History::new(&device, 16, 2).unwrap();
+    for sweep in 1..=3u32 {
+        queue.write_buffer(&state, 0, bytemuck::cast_slice(&[sweep; 4]));
+        let mut encoder = device.create_command_encoder(&Default::default());
+        history.record(&mut encoder, &state, sweep);
+        queue.submit(Some(encoder.finish()));
+    }
+    queue.write_buffer(&state, 0, bytemuck::cast_slice(&[4u32; 4]));
+    assert_eq!(history.sweeps().collect::<Vec<_>>(), vec![2, 3]);
+    let readback = crate::gpu::readback::Readback::new();
+    let mut show = |back| {
+        let mut encoder = device.create_command_encoder(&Default::default());
+        history.show(&mut encoder, &state, back);
+        readback.request(&device, &mut encoder, &state, 0..16);
+        queue.submit(Some(encoder.finish()));
+        readback.submitted();
+        loop {
+            let _ = device.poll(wgpu::MaintainBase::Wait);
+            if let Some(data) = readback.poll(&device).pop() {
+                break (data.to_vec::<u32>(), history.shown());
+            }
+        }
+    };
+    assert_eq!(show(Some(1)), (vec![2; 4], Some(1)));
+    assert_eq!(show(Some(0)), (vec![3; 4], Some(0)));
+    // Beyond the recorded frames, the live state is shown.
+    assert_eq!(show(Some(2)), (vec![4; 4], None));
+    assert_eq!(show(None), (vec![4; 4], None));
+}
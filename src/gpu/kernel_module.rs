@@ -0,0 +1,104 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{
+    error::WGPUError,
+    gpu::{
+        adapter::create_shader_module,
+        entry_points::{check_entry_point, kernel_entry_points, parse_entry_points},
+        physics::{pop_error_scopes, push_error_scopes},
+    },
+};
+
+/// SPIR-V module read from a file at run time, for instance kernels built in another crate, see [KernelModule::external].
+#[derive(Clone, Debug)]
+pub struct SpirvFile {
+    pub path: PathBuf,
+    /// Identifier of this read of the file, distinct for each call to [SpirvFile::read], so that a module read again after a change is created again.
+    pub id: u64,
+    pub spirv: Vec<u8>,
+    /// Names of the entry points of the module, see [parse_entry_points].
+    pub entry_points: Vec<String>,
+}
+
+impl SpirvFile {
+    /// Read the module at `path` and list its entry points, which fails if the file is not a SPIR-V module.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, WGPUError> {
+        static READS: AtomicU64 = AtomicU64::new(0);
+        let path = path.as_ref();
+        let spirv = std::fs::read(path)?;
+        let entry_points = parse_entry_points(&spirv)?;
+        log::info!(
+            "Entry points of {}: {}",
+            path.display(),
+            entry_points.join(", ")
+        );
+        Ok(SpirvFile {
+            path: path.to_path_buf(),
+            id: READS.fetch_add(1, Ordering::Relaxed),
+            spirv,
+            entry_points,
+        })
+    }
+    /// Check that `name` is an entry point of the module, see [check_entry_point].
+    pub fn check(&self, name: &str) -> Result<(), WGPUError> {
+        check_entry_point(&self.entry_points, name)
+    }
+}
+
+/// Shader module created in a device together with the names of its entry points, against which the pipelines check their entry point.
+pub struct KernelModule {
+    pub module: wgpu::ShaderModule,
+    pub entry_points: Vec<String>,
+}
+
+impl KernelModule {
+    /// Module of the kernels embedded in [SPIRV](crate::SPIRV), see [create_shader_module].
+    pub fn embedded(device: &wgpu::Device) -> Self {
+        KernelModule {
+            module: create_shader_module(device),
+            entry_points: kernel_entry_points().to_vec(),
+        }
+    }
+    /// Module of the SPIR-V `file`. Unlike the embedded kernels, which are trusted, the module is validated by wgpu, and an invalid module is returned as an error.
+    pub fn external(device: &wgpu::Device, file: &SpirvFile) -> Result<Self, WGPUError> {
+        push_error_scopes(device);
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&file.path.display().to_string()),
+            source: wgpu::util::make_spirv(&file.spirv),
+        });
+        pop_error_scopes(device)?;
+        Ok(KernelModule {
+            module,
+            entry_points: file.entry_points.clone(),
+        })
+    }
+    /// Check that `name` is an entry point of the module, see [check_entry_point].
+    pub fn check(&self, name: &str) -> Result<(), WGPUError> {
+        check_entry_point(&self.entry_points, name)
+    }
+}
+
+/// Verify that a file which is not a SPIR-V module is rejected when it is read, before any module is created, and that each read has its own identifier.
+#[test]
+fn test_spirv_file() {
+    let dir = std::env::temp_dir().join("phase_test_spirv_file");
+    std::fs::create_dir_all(&dir).unwrap();
+    let text = dir.join("text.spv");
+    std::fs::write(&text, b"not a SPIR-V module").unwrap();
+    assert!(SpirvFile::read(&text).is_err());
+    assert!(SpirvFile::read(dir.join("missing.spv")).is_err());
+    let kernels = dir.join("kernels.spv");
+    std::fs::write(&kernels, crate::SPIRV).unwrap();
+    let file = SpirvFile::read(&kernels).unwrap();
+    assert_eq!(file.entry_points, kernel_entry_points());
+    assert!(file.check("ising_step").is_ok());
+    assert!(matches!(
+        file.check("custom_step"),
+        Err(WGPUError::KernelNotFound(..))
+    ));
+    assert_ne!(SpirvFile::read(&kernels).unwrap().id, file.id);
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,106 @@
+use bytemuck::bytes_of;
+use kernel::NormalizeCtx;
+use wgpu::{Buffer, CommandEncoder, util::DeviceExt};
+
+use crate::error::WGPUError;
+
+use super::pipeline::{Access, Pipeline, PipelineBuilder};
+
+/// Renormalization of the vector spins of a lattice to unit length with the `normalize_spins` kernel of the embedded kernels, see [NormalizeCtx].
+///
+/// The steps of a model of vector spins keep the length of each spin only up to rounding errors, which accumulate over a long run and bias the observables. Dispatching the renormalization every few steps keeps `|s| = 1` to the precision of an f32.
+pub struct SpinNormalization {
+    pipeline: Pipeline,
+    width: u32,
+    height: u32,
+}
+
+impl SpinNormalization {
+    /// Prepare the renormalization of the `width`×`height` spins of `spins`, each made of `stride` f32 such as 2 for a `vec2<f32>`. The `shader_module` must be the one of the embedded kernels.
+    pub fn new(
+        device: &wgpu::Device,
+        shader_module: &wgpu::ShaderModule,
+        spins: &Buffer,
+        width: u32,
+        height: u32,
+        stride: u32,
+    ) -> Result<Self, WGPUError> {
+        let ctx_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Spin normalization ctx buffer"),
+            contents: bytes_of(&NormalizeCtx {
+                width,
+                height,
+                stride,
+            }),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let pipeline = PipelineBuilder::new(device, shader_module, "normalize_spins")
+            .uniform(0, &ctx_buffer)
+            .storage(1, spins, Access::ReadWrite)
+            .build()?;
+        Ok(SpinNormalization {
+            pipeline,
+            width,
+            height,
+        })
+    }
+    /// Record the renormalization of every spin in `encoder`.
+    pub fn dispatch(&self, encoder: &mut CommandEncoder) {
+        self.pipeline.dispatch(encoder, self.width, self.height, 1);
+    }
+}
+
+/// Verify that spins drifted away from the unit circle and sphere are brought back to unit length within 1e-5, and stay there when renormalized repeatedly as in a long run.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_spin_normalization() {
+    use crate::gpu::readback::Readback;
+
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    let (width, height) = (37, 23);
+    for stride in [2, 4] {
+        // The rounding errors of many steps are mimicked by lengths spread between 0.5 and 2, the last component of the Heisenberg spins being zero.
+        let spins = (0..width * height)
+            .flat_map(|cell| {
+                let length = 0.5 + 1.5 * ((cell * 7919) % 1000) as f32 / 1000.0;
+                let (theta, phi) = (cell as f32 * 0.3, cell as f32 * 0.7);
+                match stride {
+                    2 => vec![length * theta.cos(), length * theta.sin()],
+                    _ => vec![
+                        length * theta.sin() * phi.cos(),
+                        length * theta.sin() * phi.sin(),
+                        length * theta.cos(),
+                        0.0,
+                    ],
+                }
+            })
+            .collect::<Vec<f32>>();
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&spins),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        });
+        let normalization =
+            SpinNormalization::new(&device, &shader_module, &buffer, width, height, stride)
+                .unwrap();
+        let readback = Readback::new();
+        let mut encoder = device.create_command_encoder(&Default::default());
+        // Renormalizing spins which already have unit length keeps them so.
+        for _ in 0..100 {
+            normalization.dispatch(&mut encoder);
+        }
+        readback.request(&device, &mut encoder, &buffer, 0..buffer.size());
+        queue.submit(Some(encoder.finish()));
+        readback.submitted();
+        let spins = loop {
+            let _ = device.poll(wgpu::MaintainBase::Wait);
+            if let Some(data) = readback.poll(&device).pop() {
+                break data.to_vec::<f32>();
+            }
+        };
+        for spin in spins.chunks_exact(stride as usize) {
+            let length = spin.iter().map(|v| v * v).sum::<f32>().sqrt();
+            assert!((length - 1.0).abs() < 1e-5, "{spin:?}");
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use std::sync::Arc;
+
+/// Quantity measured on the state of a [Physics](super::physics::Physics), such as the magnetization, listed by [Physics::observables](super::physics::Physics::observables) and plotted by the GUI.
+///
+/// The measurements are read back asynchronously by the physics, at its own cadence, so an observable only gives the value of the last measurement. It is held outside of the lock of the physics, and therefore reads the measurements from a state shared with it.
+pub trait Observable: Send + Sync {
+    /// Name of the observable, shown as the title of its plot.
+    fn name(&self) -> &str;
+    /// Value of the last measurement, `None` until the first one or while the measurements are disabled.
+    fn measure(&self) -> Option<f64>;
+}
+
+/// [Observable] whose value is extracted by `value` from the `source` shared with the physics, such as its parameters holding the last measurement.
+pub struct MeasuredObservable<S, F> {
+    name: &'static str,
+    source: Arc<S>,
+    value: F,
+}
+
+impl<S, F> MeasuredObservable<S, F>
+where
+    S: Send + Sync,
+    F: Fn(&S) -> Option<f64> + Send + Sync,
+{
+    pub fn new(name: &'static str, source: Arc<S>, value: F) -> Self {
+        MeasuredObservable {
+            name,
+            source,
+            value,
+        }
+    }
+}
+
+impl<S, F> Observable for MeasuredObservable<S, F>
+where
+    S: Send + Sync,
+    F: Fn(&S) -> Option<f64> + Send + Sync,
+{
+    fn name(&self) -> &str {
+        self.name
+    }
+    fn measure(&self) -> Option<f64> {
+        (self.value)(&self.source)
+    }
+}
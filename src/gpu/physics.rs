@@ -1,6 +1,169 @@
-use wgpu::{Buffer, Device, Queue};
+use std::sync::{Arc, Mutex};
 
+use kernel::CellRng;
+use wgpu::{Buffer, CommandEncoder, Device, Queue, util::StagingBelt};
+
+use crate::error::WGPUError;
+
+use super::observable::Observable;
+
+pub mod custom;
 pub mod ising;
+pub mod langevin;
+
+/// Independent random number generators for `count` cells, the key of each cell being derived from its index with [stream_key](gpu_random::stream_key). The generators only depend on `seed` and `count`, so runs are reproducible for a given seed and lattice size.
+#[cfg(not(any(feature = "small_rng", feature = "xoshiro_rng", feature = "pcg_rng")))]
+pub fn cell_rngs(seed: u128, count: usize) -> Vec<CellRng> {
+    (0..count)
+        .map(|i| {
+            let mut rng = CellRng::new(seed, 0);
+            rng.set_key(gpu_random::stream_key(seed, i as u64));
+            rng
+        })
+        .collect::<Vec<_>>()
+}
+/// Independent random number generators for `count` cells, the cell index being used as key. The key of a Philox2x32 is a single word, which is too small to be mixed like in the default variant without risking that two cells share a key, so the indices are used directly: Philox is designed so that adjacent keys give independent streams.
+#[cfg(feature = "small_rng")]
+pub fn cell_rngs(seed: u128, count: usize) -> Vec<CellRng> {
+    (0..count)
+        .map(|i| CellRng::new(seed as u64, i as u32))
+        .collect::<Vec<_>>()
+}
+/// Independent random number generators for `count` cells, the cell index being used as stream.
+#[cfg(feature = "xoshiro_rng")]
+pub fn cell_rngs(seed: u128, count: usize) -> Vec<CellRng> {
+    (0..count)
+        .map(|i| CellRng::from_seed_stream(seed as u64, i as u64))
+        .collect::<Vec<_>>()
+}
+/// Independent random number generators for `count` cells, the cell index being used as stream. The streams of PCG32 only differ by their increment, which makes the sequences of close streams related, so the state of each cell is also seeded with the [stream_key](gpu_random::stream_key) of its index.
+#[cfg(feature = "pcg_rng")]
+pub fn cell_rngs(seed: u128, count: usize) -> Vec<CellRng> {
+    (0..count)
+        .map(|i| CellRng::new_u32(gpu_random::stream_key(seed, i as u64), [i as u32, 0]))
+        .collect::<Vec<_>>()
+}
+
+/// Storage precision of the spin field of the [IsingPipeline](ising::IsingPipeline).
+///
+/// In [Precision::F16] the spins are stored as pairs of f16 packed in u32 words, converted with `pack2x16float`/`unpack2x16float` by the kernels and the fragment shader. This halves the size of the spin buffers and does not require the `SHADER_F16` feature nor 16-bit storage support, so it is available on every device. The spins being ±1, they are stored exactly and both precisions give the same trajectories for a given seed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Precision {
+    #[default]
+    F32,
+    F16,
+}
+
+impl Precision {
+    /// Number of cells stored in each u32 word of a spin buffer.
+    pub fn cells_per_word(self) -> u32 {
+        match self {
+            Precision::F32 => 1,
+            Precision::F16 => 2,
+        }
+    }
+    /// Words of a spin buffer holding the values `data`, whose length must be a multiple of [Precision::cells_per_word].
+    pub fn pack(self, data: &[f32]) -> Vec<u32> {
+        match self {
+            Precision::F32 => data.iter().map(|v| v.to_bits()).collect(),
+            // Same layout as `pack2x16float`: the first value is in the low bits.
+            Precision::F16 => data
+                .chunks_exact(2)
+                .map(|pair| f32_to_f16(pair[0]) as u32 | (f32_to_f16(pair[1]) as u32) << 16)
+                .collect(),
+        }
+    }
+    /// Values held by the `words` of a spin buffer, the inverse of [Precision::pack].
+    pub fn unpack(self, words: &[u32]) -> Vec<f32> {
+        match self {
+            Precision::F32 => words.iter().map(|&w| f32::from_bits(w)).collect(),
+            Precision::F16 => words
+                .iter()
+                .flat_map(|&w| [f16_to_f32(w as u16), f16_to_f32((w >> 16) as u16)])
+                .collect(),
+        }
+    }
+}
+
+/// Bits of the f16 nearest to `x`, rounding ties to even. Values too large for an f16 become infinite.
+pub fn f32_to_f16(x: f32) -> u16 {
+    let bits = x.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        // Infinity or NaN, keeping NaN quiet.
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    // Mantissa to shift into the 10 bits of the f16, with its implicit leading bit when the result is subnormal.
+    let (full, shift) = if exponent > 0 {
+        (mantissa, 13)
+    } else if exponent >= -10 {
+        (mantissa | 0x80_0000, (14 - exponent) as u32)
+    } else {
+        return sign;
+    };
+    let half = ((exponent.max(0) as u32) << 10) | (full >> shift);
+    let remainder = full & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    let round = (remainder > halfway || (remainder == halfway && half & 1 == 1)) as u32;
+    // A carry of the rounding into the exponent gives the correct result, up to infinity.
+    sign | (half + round) as u16
+}
+
+/// Value of the f16 with bits `h`.
+pub fn f16_to_f32(h: u16) -> f32 {
+    let sign = ((h & 0x8000) as u32) << 16;
+    let exponent = ((h >> 10) & 0x1f) as u32;
+    let mantissa = (h & 0x3ff) as u32;
+    let magnitude = match exponent {
+        0 => {
+            // Subnormal, of value mantissa·2^-24.
+            let value = mantissa as f32 / 16777216.0;
+            return if sign != 0 { -value } else { value };
+        }
+        0x1f => 0x7f80_0000 | (mantissa << 13),
+        _ => ((exponent + 112) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(sign | magnitude)
+}
+
+/// Verify the conversions between f32 and f16 on exact values, rounding, subnormals and overflows.
+#[test]
+fn test_f16_conversion() {
+    for x in [
+        0.0,
+        -0.0,
+        1.0,
+        -1.0,
+        0.5,
+        65504.0,
+        -2.0f32.powi(-14),
+        2.0f32.powi(-24),
+    ] {
+        assert_eq!(f16_to_f32(f32_to_f16(x)), x);
+        assert_eq!(
+            f16_to_f32(f32_to_f16(x)).is_sign_negative(),
+            x.is_sign_negative()
+        );
+    }
+    assert_eq!(f32_to_f16(1.0), 0x3c00);
+    assert_eq!(f32_to_f16(-2.0), 0xc000);
+    // 1 + 2^-11 is halfway between 1 and the next f16, so it is rounded to the even 1.
+    assert_eq!(f32_to_f16(1.0 + 2.0f32.powi(-11)), 0x3c00);
+    assert_eq!(f32_to_f16(1.0 + 3.0 * 2.0f32.powi(-11)), 0x3c02);
+    assert_eq!(f32_to_f16(1e6), 0x7c00);
+    assert_eq!(f32_to_f16(2.0f32.powi(-30)), 0);
+    assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+    let data = [1.0, -1.0, 0.25, 3.0];
+    for precision in [Precision::F32, Precision::F16] {
+        assert_eq!(precision.unpack(&precision.pack(&data)), data);
+    }
+}
 
 /// Entries appearing in the Fragment shader corresponding to the [fragment_entry_point](FragmentInfo::fragment_entry_point) of [FragmentInfo].
 #[derive(Clone)]
@@ -8,18 +171,154 @@ pub struct FragmentEntry<'a> {
     pub binding: u32,
     pub buffer: &'a Buffer,
     pub uniform: bool,
+    /// Size of the binding if it is bound with a dynamic offset, the offsets being given by [Physics::fragment_offsets].
+    pub dynamic_size: Option<u64>,
 }
 
 /// Fragment shader informations to be used by [RenderSquare](crate::simulation::render_square::RenderSquare) to performe the rendering of the [Physics] simulation.
 pub struct FragmentInfo<'a> {
     pub fragment_entry_point: &'a str,
+    /// Whether the fragment entry point is one of the embedded kernels, such as the one of [TwoFieldRender](super::two_field::TwoFieldRender), rather than one of the module of the physics.
+    pub embedded: bool,
     pub entries: Vec<FragmentEntry<'a>>,
 }
 
+/// Size of the chunks of the [StagingBelt] used by the physics to write their contexts, which holds the contexts of several hundreds of steps.
+pub const STAGING_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Record in `encoder` the write of `data` at `offset` in `buffer` through the staging `belt`. Unlike [Queue::write_buffer], which is performed before the whole submission, the write is ordered with the passes recorded in the same encoder, so a buffer can be written between the steps of a frame. The `belt` must be finished before the submission of `encoder` and recalled after it, see [StagingBelt].
+pub fn write_staged(
+    belt: &mut StagingBelt,
+    device: &Device,
+    encoder: &mut CommandEncoder,
+    buffer: &Buffer,
+    offset: u64,
+    data: &[u8],
+) {
+    let size = wgpu::BufferSize::new(data.len() as u64).expect("Empty staged write.");
+    belt.write_buffer(encoder, buffer, offset, size, device)
+        .copy_from_slice(data);
+}
+
+/// Push the error scopes catching the out-of-memory and validation errors of the creation of a physics, see [pop_error_scopes]. On the web the scopes cannot be awaited synchronously, so no scope is pushed and the errors are left to the uncaptured error handler of the device.
+#[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+pub fn push_error_scopes(device: &Device) {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+    }
+}
+
+/// Pop the error scopes of [push_error_scopes], returning the first error caught.
+#[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+pub fn pop_error_scopes(device: &Device) -> Result<(), wgpu::Error> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let validation = pollster::block_on(device.pop_error_scope());
+        let out_of_memory = pollster::block_on(device.pop_error_scope());
+        if let Some(err) = out_of_memory.or(validation) {
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+/// Timing of the frame given to [Physics::update], assembled by the [RenderSquare](crate::simulation::render_square::RenderSquare) from the timing of egui.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameInfo {
+    /// Duration in seconds of the previous frame.
+    pub dt: f32,
+    /// Number of frames updated before this one since the creation of the physics.
+    pub frame_index: u64,
+    /// Whether the simulation is paused, see [Simulation::paused](crate::simulation::Simulation::paused).
+    pub paused: bool,
+    /// Number of steps imposed on this update instead of the number chosen by the physics, for instance by a [Replay](crate::simulation::replay::Replay) which must stop at the sweeps of its events. `None` otherwise.
+    pub steps: Option<u32>,
+}
+
+/// Hash of a state saved by [Physics::save_state] or of the values of a lattice, to compare the final state of a run with the one of its replay or with a stored one. FNV-1a on 64 bits, which only needs to detect differences, not to resist collisions.
+pub fn state_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Value of a cell and of its 4 neighbors read back by [Physics::probe], shown by the GUI under the pointer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Probe {
+    /// Coordinates of the cell, from the bottom left corner of the lattice.
+    pub cell: [u32; 2],
+    /// Step at which the values were read back, see [Physics::steps].
+    pub step: u64,
+    pub value: f32,
+    /// Values of the left, right, bottom and top neighbors, with periodic boundaries.
+    pub neighbors: [f32; 4],
+    /// Energy of the cell in the field of its neighbors and of the external field, if the physics defines one.
+    pub energy: Option<f32>,
+}
+
+/// Physics shared between the rendering of a [RenderSquare](crate::simulation::render_square::RenderSquare), which binds its buffers, and the [ComputeThread](super::compute_thread::ComputeThread) which updates it in the background.
+pub type SharedPhysics = Arc<Mutex<Box<dyn Physics>>>;
+
 /// Physics trait to define the minimum requierement for a physics simulation to be able to compute and render in the GPU with [RenderSquare](crate::simulation::render_square::RenderSquare).
 pub trait Physics: Send + Sync + 'static {
-    /// Update the physics, which would principally be a compute pipeline.
-    fn update(&mut self, device: &Device, queue: &Queue);
+    /// Update the physics, which would principally be a compute pipeline. All the work of the frame is recorded in `encoder`, which is submitted by the caller together with the rendering of the frame once this returns, and before the next call. The operations which must wait for the submission, such as mapping a buffer read back, are therefore done at the beginning of the next call. The duration of the previous frame, for instance to adapt the number of steps per frame, and the index of the frame are given by `frame`.
+    fn update(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        frame: &FrameInfo,
+    );
     /// Necessary fragment buffer informations for the [RenderSquare](crate::simulation::render_square::RenderSquare).
     fn wgpu_fragment_info(&self) -> FragmentInfo;
+    /// Dynamic offsets of the entries of [FragmentInfo] with a [dynamic_size](FragmentEntry::dynamic_size), in the order of their bindings, for the rendering of the last update. Empty by default.
+    fn fragment_offsets(&self) -> Vec<u32> {
+        vec![]
+    }
+    /// Number of steps, such as sweeps of the lattice, performed since the creation, from which the GUI shows the number of steps per second. `None` by default.
+    fn steps(&self) -> Option<u64> {
+        None
+    }
+    /// Label and duration in milliseconds of the GPU passes measured with timestamp queries, empty if they are not measured.
+    fn gpu_timings(&self) -> Vec<(&'static str, f64)> {
+        vec![]
+    }
+    /// Overwrite the state with the values `data` of the cells given row by row, for instance to start from an exact configuration. Unsupported by default.
+    fn load_values(&mut self, _queue: &Queue, _data: &[f32]) -> Result<(), WGPUError> {
+        Err(WGPUError::LoadStateUnsupported)
+    }
+    /// Whether [Physics::reset] is implemented, so that the GUI shows a reset button. False by default.
+    fn supports_reset(&self) -> bool {
+        false
+    }
+    /// Start again from the initial state. Does nothing by default.
+    fn reset(&mut self, _device: &Device, _queue: &Queue) {}
+    /// Whether [Physics::probe] is implemented, so that the GUI can show the values under the pointer. False by default.
+    fn supports_probe(&self) -> bool {
+        false
+    }
+    /// Read back the cell `cell` and its neighbors with the next updates, as long as it is set, or stop with `None`. Only the few words of these cells are copied, whatever the size of the lattice. Does nothing by default.
+    fn probe(&mut self, _cell: Option<[u32; 2]>) {}
+    /// Last [Probe] read back since the cell was set by [Physics::probe]. `None` by default.
+    fn probed(&self) -> Option<Probe> {
+        None
+    }
+    /// Observables measured by the physics, plotted by the GUI over the steps. They share the measurements with the physics, so the list is only requested once per physics. Empty by default.
+    fn observables(&self) -> Vec<Box<dyn Observable>> {
+        vec![]
+    }
+    /// Whether [Physics::save_state] and [Physics::load_state] are implemented, so that the GUI shows the save and load buttons. False by default.
+    fn supports_state(&self) -> bool {
+        false
+    }
+    /// Serialize the whole state, read back from the GPU, so that it can be restored by [Physics::load_state]. `None` by default.
+    fn save_state(&self, _device: &Device, _queue: &Queue) -> Option<Vec<u8>> {
+        None
+    }
+    /// Restore a state serialized by [Physics::save_state]. Unsupported by default.
+    fn load_state(&mut self, _queue: &Queue, _bytes: &[u8]) -> Result<(), WGPUError> {
+        Err(WGPUError::LoadStateUnsupported)
+    }
 }
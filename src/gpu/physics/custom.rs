@@ -0,0 +1,668 @@
+use std::{
+    ops::RangeInclusive,
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU32, Ordering},
+    },
+};
+
+use wgpu::{Buffer, CommandEncoder, util::StagingBelt};
+
+use crate::{
+    error::WGPUError,
+    gpu::{
+        adapter::create_shader_module,
+        capabilities::check_lattice,
+        kernel_module::SpirvFile,
+        normalize::SpinNormalization,
+        pipeline::{Access, Pipeline, PipelineBuilder},
+        two_field::TwoFieldRender,
+        uniform_ring::UniformRing,
+    },
+    simulation::atomic_f32::AtomicF32,
+};
+
+use super::{
+    FragmentEntry, FragmentInfo, FrameInfo, Physics, STAGING_CHUNK_SIZE, pop_error_scopes,
+    push_error_scopes,
+};
+
+/// Number of u32 words of the uniform of the custom kernels, so a manifest declares a uniform of at most 4 times this number of bytes.
+pub const CUSTOM_UNIFORM_WORDS: usize = 64;
+
+/// Number of u32 words at the start of the uniform of the custom kernels: the width and the height of the lattice, the step and the low bits of the seed. The parameters of the manifest follow as f32 values.
+pub const CUSTOM_HEADER_WORDS: usize = 4;
+
+/// Largest number of storage buffers of a custom kernel, the minimum number of storage buffers per shader stage guaranteed by wgpu.
+pub const MAX_CUSTOM_BUFFERS: u32 = 8;
+
+/// Type of the elements of the storage buffers of a custom kernel, one element per cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ElementType {
+    F32,
+    U32,
+    I32,
+    Vec2F32,
+    Vec4F32,
+}
+
+impl ElementType {
+    /// Size in bytes of an element.
+    pub fn size(self) -> usize {
+        match self {
+            ElementType::F32 | ElementType::U32 | ElementType::I32 => 4,
+            ElementType::Vec2F32 => 8,
+            ElementType::Vec4F32 => 16,
+        }
+    }
+    /// Number of f32 components of a vector element, `None` for a scalar one.
+    pub fn components(self) -> Option<u32> {
+        match self {
+            ElementType::Vec2F32 => Some(2),
+            ElementType::Vec4F32 => Some(4),
+            _ => None,
+        }
+    }
+    /// Type named as in WGSL, such as `f32` or `vec2<f32>`.
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "f32" => Some(ElementType::F32),
+            "u32" => Some(ElementType::U32),
+            "i32" => Some(ElementType::I32),
+            "vec2<f32>" => Some(ElementType::Vec2F32),
+            "vec4<f32>" => Some(ElementType::Vec4F32),
+            _ => None,
+        }
+    }
+}
+
+/// Parameter of a custom kernel, displayed as a slider and written in the uniform after the header.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CustomParameter {
+    /// Name of the parameter, leaked once per load of the manifest as the tags of the UI are static.
+    pub name: &'static str,
+    pub default: f32,
+    pub range: RangeInclusive<f32>,
+}
+
+/// Description of the bindings of the kernels of a SPIR-V module loaded at run time, read from a manifest of `key = value` lines:
+/// ```text
+/// # Entry points: the reset is optional, the step runs every step and the fragment renders the lattice.
+/// reset = my_reset
+/// step = my_step
+/// fragment = my_fragment
+/// # Without fragment, the first two buffers are rendered as two fields, normalized by these ranges unless auto-scaled.
+/// range_a = 0.0 1.0
+/// range_b = 0.0 1.0
+/// # Size in bytes of the uniform, with the header of 4 words followed by the parameters.
+/// uniform_size = 24
+/// # Storage buffers of one element per cell, at the bindings 1 to storage_buffers.
+/// storage_buffers = 2
+/// element = f32
+/// # Size of the workgroups of the reset and step entry points.
+/// workgroup_size = 8 8
+/// # Steps between two renormalizations of the vectors of the first buffer to unit length, 0 to never renormalize them.
+/// normalize_every = 0
+/// # Parameters as name, default value, minimum and maximum.
+/// parameter = coupling 1.0 0.0 2.0
+/// parameter = noise 0.1 0.0 1.0
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct CustomManifest {
+    pub reset: Option<String>,
+    pub step: String,
+    /// Fragment entry point of the module, or `None` for the shared rendering of two fields of the embedded kernels, see [TwoFieldRender].
+    pub fragment: Option<String>,
+    /// Ranges `[min, max]` of the two fields used to normalize them when there is no fragment entry point and the auto-scaling is disabled.
+    pub ranges: [[f32; 2]; 2],
+    pub uniform_size: u32,
+    pub storage_buffers: u32,
+    pub element: ElementType,
+    pub workgroup_size: [u32; 2],
+    /// Default number of steps between two renormalizations of the spins of the first buffer, 0 if never, see [SpinNormalization].
+    pub normalize_every: u32,
+    pub parameters: Vec<CustomParameter>,
+}
+
+impl CustomManifest {
+    /// Parse the manifest `text`, whose errors give the line at fault. Comments start with `#`, and the element type, the size of the workgroups, the size of the uniform, the ranges of the two fields and the steps between renormalizations default to `f32`, `8 8`, the size of the header and of the parameters, `0 1` and 0. Without fragment entry point, the manifest must have at least two storage buffers of `f32`, and the renormalization needs vector elements.
+    pub fn parse(text: &str) -> Result<Self, WGPUError> {
+        let invalid = |line: usize, reason: String| {
+            WGPUError::InvalidManifest(format!("line {}: {reason}", line + 1))
+        };
+        let (mut reset, mut step, mut fragment, mut uniform_size, mut storage_buffers) =
+            (None, None, None, None, None);
+        let mut element = ElementType::F32;
+        let mut workgroup_size = [8, 8];
+        let mut normalize_every = 0;
+        let mut ranges = [[0.0, 1.0]; 2];
+        let mut parameters = vec![];
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(invalid(
+                    n,
+                    format!("expected \"key = value\", got \"{line}\""),
+                ));
+            };
+            let value = value.trim();
+            let number = |value: &str| {
+                value
+                    .parse::<u32>()
+                    .map_err(|_| invalid(n, format!("invalid number \"{value}\"")))
+            };
+            match key.trim() {
+                "reset" => reset = Some(value.to_string()),
+                "step" => step = Some(value.to_string()),
+                "fragment" => fragment = Some(value.to_string()),
+                "uniform_size" => uniform_size = Some(number(value)?),
+                "storage_buffers" => storage_buffers = Some(number(value)?),
+                "normalize_every" => normalize_every = number(value)?,
+                "element" => {
+                    element = ElementType::parse(value).ok_or_else(|| {
+                        invalid(
+                            n,
+                            format!(
+                                "unknown element type \"{value}\", expected f32, u32, i32, vec2<f32> or vec4<f32>"
+                            ),
+                        )
+                    })?
+                }
+                "workgroup_size" => {
+                    let sizes = value
+                        .split_whitespace()
+                        .map(number)
+                        .collect::<Result<Vec<_>, _>>()?;
+                    workgroup_size = match sizes[..] {
+                        [x, y] if x > 0 && y > 0 => [x, y],
+                        _ => return Err(invalid(n, "expected two non zero sizes".to_string())),
+                    };
+                }
+                "range_a" | "range_b" => {
+                    let range = value
+                        .split_whitespace()
+                        .map(|v| v.parse::<f32>())
+                        .collect::<Result<Vec<_>, _>>();
+                    let Ok(&[min, max]) = range.as_deref() else {
+                        return Err(invalid(n, "expected \"min max\"".to_string()));
+                    };
+                    ranges[(key.trim() == "range_b") as usize] = [min, max];
+                }
+                "parameter" => {
+                    let fields = value.split_whitespace().collect::<Vec<_>>();
+                    let [name, values @ ..] = &fields[..] else {
+                        return Err(invalid(n, "missing parameter name".to_string()));
+                    };
+                    let values = values
+                        .iter()
+                        .map(|v| v.parse::<f32>())
+                        .collect::<Result<Vec<_>, _>>();
+                    let Ok(&[default, min, max]) = values.as_deref() else {
+                        return Err(invalid(
+                            n,
+                            format!("expected \"{name} default min max\""),
+                        ));
+                    };
+                    if !(min <= default && default <= max) {
+                        return Err(invalid(
+                            n,
+                            format!("default value of {name} outside of its range"),
+                        ));
+                    }
+                    parameters.push(CustomParameter {
+                        name: name.to_string().leak(),
+                        default,
+                        range: min..=max,
+                    });
+                }
+                key => return Err(invalid(n, format!("unknown key \"{key}\""))),
+            }
+        }
+        let missing = |key: &str| WGPUError::InvalidManifest(format!("missing \"{key}\""));
+        let used = 4 * (CUSTOM_HEADER_WORDS + parameters.len()) as u32;
+        let uniform_size = uniform_size.unwrap_or(used);
+        if uniform_size % 4 != 0 || uniform_size < used {
+            return Err(WGPUError::InvalidManifest(format!(
+                "uniform_size {uniform_size} must be a multiple of 4 holding the {used} bytes of the header and of the parameters"
+            )));
+        }
+        if uniform_size > 4 * CUSTOM_UNIFORM_WORDS as u32 {
+            return Err(WGPUError::InvalidManifest(format!(
+                "uniform_size {uniform_size} is larger than {} bytes",
+                4 * CUSTOM_UNIFORM_WORDS
+            )));
+        }
+        let storage_buffers = storage_buffers.ok_or_else(|| missing("storage_buffers"))?;
+        if !(1..=MAX_CUSTOM_BUFFERS).contains(&storage_buffers) {
+            return Err(WGPUError::InvalidManifest(format!(
+                "storage_buffers must be between 1 and {MAX_CUSTOM_BUFFERS}"
+            )));
+        }
+        if fragment.is_none() && (storage_buffers < 2 || element != ElementType::F32) {
+            return Err(WGPUError::InvalidManifest(
+                "without fragment, two storage buffers of f32 are needed to render two fields"
+                    .to_string(),
+            ));
+        }
+        if normalize_every > 0 && element.components().is_none() {
+            return Err(WGPUError::InvalidManifest(
+                "normalize_every needs vec2<f32> or vec4<f32> elements".to_string(),
+            ));
+        }
+        Ok(CustomManifest {
+            reset,
+            step: step.ok_or_else(|| missing("step"))?,
+            fragment,
+            ranges,
+            uniform_size,
+            storage_buffers,
+            element,
+            workgroup_size,
+            normalize_every,
+            parameters,
+        })
+    }
+    /// Read and parse the manifest at `path`, see [CustomManifest::parse].
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, WGPUError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|err| match err {
+            WGPUError::InvalidManifest(reason) => {
+                WGPUError::InvalidManifest(format!("{}: {reason}", path.display()))
+            }
+            err => err,
+        })
+    }
+    /// Check that the entry points of the manifest are in the SPIR-V `file`.
+    pub fn check(&self, file: &SpirvFile) -> Result<(), WGPUError> {
+        for name in self.reset.iter().chain([&self.step]).chain(&self.fragment) {
+            file.check(name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parameters of a custom kernel shared between the [CustomKernel](crate::simulation::custom::CustomKernel) simulation which modifies them from the egui UI, and the [CustomPipeline] which reads them every frame.
+pub struct CustomParameters {
+    /// Values of the [CustomParameter]s of the manifest, in their order.
+    pub values: Vec<AtomicF32>,
+    pub steps_per_frame: AtomicU32,
+    /// Number of steps between two renormalizations of the spins of the first buffer, 0 if never.
+    pub normalize_every: AtomicU32,
+    /// Whether the two fields are normalized by their current range instead of the ranges of the manifest, when they are rendered by the [TwoFieldRender].
+    pub auto_scale: AtomicBool,
+    /// Whether a reset has been requested, which is performed at the next frame.
+    pub reset_requested: AtomicBool,
+}
+
+impl CustomParameters {
+    /// Default values of the parameters of `manifest`, with one step per frame and the renormalization of the manifest.
+    pub fn new(manifest: &CustomManifest) -> Self {
+        CustomParameters {
+            values: manifest
+                .parameters
+                .iter()
+                .map(|parameter| AtomicF32::new(parameter.default))
+                .collect(),
+            steps_per_frame: AtomicU32::new(1),
+            normalize_every: AtomicU32::new(manifest.normalize_every),
+            auto_scale: false.into(),
+            reset_requested: false.into(),
+        }
+    }
+}
+
+/// Handles the compute pipelines of the kernels of a SPIR-V module loaded at run time, whose bindings are described by a [CustomManifest].
+///
+/// The reset and step entry points bind the uniform at `binding = 0` and the storage buffers at the following bindings of `descriptor_set = 0`, and run on one invocation per cell with workgroups of the size of the manifest. The fragment entry point binds the same buffers, the storage buffers being read only, and the render options [RenderCtx](kernel::RenderCtx) at `descriptor_set = 1`. Without fragment entry point, the first two storage buffers are rendered by a [TwoFieldRender].
+pub struct CustomPipeline {
+    /// Uniform of the kernels, written before each step so that the kernels can draw different random numbers at each step.
+    ctx_ring: UniformRing<[u32; CUSTOM_UNIFORM_WORDS]>,
+    belt: StagingBelt,
+    reset_pipeline: Option<Pipeline>,
+    step_pipeline: Pipeline,
+    buffers: Vec<Buffer>,
+    fragment: Option<String>,
+    two_field: Option<TwoFieldRender>,
+    /// Renormalization of the spins of the first buffer, for vector elements.
+    normalization: Option<SpinNormalization>,
+    workgroup_size: [u32; 2],
+    width: u32,
+    height: u32,
+    seed: u32,
+    parameters: Arc<CustomParameters>,
+    step: u32,
+}
+
+impl CustomPipeline {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        shader_module: &wgpu::ShaderModule,
+        file: &SpirvFile,
+        manifest: &CustomManifest,
+        seed: u128,
+        width: u32,
+        height: u32,
+        parameters: Arc<CustomParameters>,
+    ) -> Result<Self, WGPUError> {
+        manifest.check(file)?;
+        let element = manifest.element.size();
+        let count = check_lattice(
+            &device.limits(),
+            width,
+            height,
+            &vec![element; manifest.storage_buffers as usize],
+        )?;
+        let seed = seed as u32;
+        let ctx = Self::ctx(&parameters, width, height, 0, seed);
+        let ctx_ring = UniformRing::new(device, "Custom ctx buffer", &ctx);
+        push_error_scopes(device);
+        // The buffers start zeroed, before the reset if there is one.
+        let buffers = (0..manifest.storage_buffers)
+            .map(|i| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(&format!("Custom buffer {}", i + 1)),
+                    size: (count * element) as u64,
+                    usage: wgpu::BufferUsages::STORAGE,
+                    mapped_at_creation: false,
+                })
+            })
+            .collect::<Vec<_>>();
+        let pipeline = |name: &str| {
+            let mut builder = PipelineBuilder::new(device, shader_module, name)
+                .entry_points(&file.entry_points)
+                .entry(ctx_ring.entry(0));
+            for (i, buffer) in buffers.iter().enumerate() {
+                builder = builder.storage(i as u32 + 1, buffer, Access::ReadWrite);
+            }
+            builder.build()
+        };
+        let reset_pipeline = manifest.reset.as_deref().map(&pipeline).transpose();
+        let step_pipeline = pipeline(&manifest.step);
+        // The rendering of two fields, its reductions and the renormalization are embedded kernels, not kernels of the module.
+        let embedded = create_shader_module(device);
+        let two_field = match manifest.fragment {
+            Some(_) => Ok(None),
+            None => TwoFieldRender::new(
+                device,
+                &embedded,
+                width,
+                height,
+                [&buffers[0], &buffers[1]],
+                manifest.ranges,
+            )
+            .map(Some),
+        };
+        let normalization = manifest
+            .element
+            .components()
+            .map(|stride| {
+                SpinNormalization::new(device, &embedded, &buffers[0], width, height, stride)
+            })
+            .transpose();
+        // The scopes are popped before returning any error, so that they are not left pushed.
+        let allocation = pop_error_scopes(device);
+        let p = CustomPipeline {
+            reset_pipeline: reset_pipeline?,
+            step_pipeline: step_pipeline?,
+            two_field: two_field?,
+            normalization: normalization?,
+            ctx_ring,
+            belt: StagingBelt::new(STAGING_CHUNK_SIZE),
+            buffers,
+            fragment: manifest.fragment.clone(),
+            workgroup_size: manifest.workgroup_size,
+            width,
+            height,
+            seed,
+            parameters,
+            step: 0,
+        };
+        allocation.map_err(|err| WGPUError::AllocationFailed(count, err))?;
+        p.reset(device, queue);
+        Ok(p)
+    }
+    /// Uniform of the kernels at `step`: the header followed by the values of the parameters.
+    fn ctx(
+        parameters: &CustomParameters,
+        width: u32,
+        height: u32,
+        step: u32,
+        seed: u32,
+    ) -> [u32; CUSTOM_UNIFORM_WORDS] {
+        let mut ctx = [0; CUSTOM_UNIFORM_WORDS];
+        ctx[..CUSTOM_HEADER_WORDS].copy_from_slice(&[width, height, step, seed]);
+        for (word, value) in ctx[CUSTOM_HEADER_WORDS..]
+            .iter_mut()
+            .zip(&parameters.values)
+        {
+            *word = value.load().to_bits();
+        }
+        ctx
+    }
+    /// Number of workgroups covering the lattice.
+    fn workgroups(&self) -> (u32, u32) {
+        (
+            self.width.div_ceil(self.workgroup_size[0]),
+            self.height.div_ceil(self.workgroup_size[1]),
+        )
+    }
+    /// Run the reset entry point of the manifest, if any, with the uniform of the current slot.
+    pub fn reset(&self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let Some(reset_pipeline) = &self.reset_pipeline else {
+            return;
+        };
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Custom reset Encoder"),
+        });
+        let (x, y) = self.workgroups();
+        reset_pipeline.dispatch_with_offsets(
+            &mut encoder,
+            x,
+            y,
+            1,
+            &[],
+            None,
+            &[&[self.ctx_ring.offset()]],
+        );
+        queue.submit(Some(encoder.finish()));
+    }
+    /// Record in `encoder` the write of the uniform at the current step and a dispatch of the step entry point, or of the reset one if `reset`.
+    fn dispatch(&mut self, device: &wgpu::Device, encoder: &mut CommandEncoder, reset: bool) {
+        if reset && self.reset_pipeline.is_none() {
+            return;
+        }
+        let ctx = Self::ctx(
+            &self.parameters,
+            self.width,
+            self.height,
+            self.step,
+            self.seed,
+        );
+        self.ctx_ring.write(&mut self.belt, device, encoder, &ctx);
+        let (x, y) = self.workgroups();
+        let pipeline = match &self.reset_pipeline {
+            Some(reset_pipeline) if reset => reset_pipeline,
+            _ => &self.step_pipeline,
+        };
+        pipeline.dispatch_with_offsets(encoder, x, y, 1, &[], None, &[&[self.ctx_ring.offset()]]);
+    }
+}
+
+impl Physics for CustomPipeline {
+    fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut CommandEncoder,
+        frame: &FrameInfo,
+    ) {
+        // The encoder of the previous frame has been submitted, so its staging buffers can be reused.
+        self.belt.recall();
+        self.ctx_ring.advance();
+        if self
+            .parameters
+            .reset_requested
+            .swap(false, Ordering::Relaxed)
+        {
+            self.step = 0;
+            self.dispatch(device, encoder, true);
+        }
+        let normalize_every = self.parameters.normalize_every.load(Ordering::Relaxed);
+        let steps = frame
+            .steps
+            .unwrap_or(self.parameters.steps_per_frame.load(Ordering::Relaxed));
+        for _ in 0..steps {
+            self.dispatch(device, encoder, false);
+            self.step = self.step.wrapping_add(1);
+            match &self.normalization {
+                Some(normalization) if normalize_every > 0 && self.step % normalize_every == 0 => {
+                    normalization.dispatch(encoder)
+                }
+                _ => {}
+            }
+        }
+        // The fragment reads the uniform of the last step, or of the current step when paused.
+        if steps == 0 {
+            let ctx = Self::ctx(
+                &self.parameters,
+                self.width,
+                self.height,
+                self.step,
+                self.seed,
+            );
+            self.ctx_ring.write(&mut self.belt, device, encoder, &ctx);
+        }
+        if let Some(two_field) = &mut self.two_field {
+            let auto_scale = self.parameters.auto_scale.load(Ordering::Relaxed);
+            two_field.update(queue, encoder, auto_scale);
+        }
+        self.belt.finish();
+    }
+    fn steps(&self) -> Option<u64> {
+        Some(self.step as u64)
+    }
+    fn fragment_offsets(&self) -> Vec<u32> {
+        match self.two_field {
+            Some(_) => vec![],
+            None => vec![self.ctx_ring.offset()],
+        }
+    }
+    fn wgpu_fragment_info(&self) -> FragmentInfo {
+        let Some(fragment) = &self.fragment else {
+            return self
+                .two_field
+                .as_ref()
+                .expect("The two fields are rendered without fragment entry point.")
+                .fragment_info();
+        };
+        let mut entries = vec![FragmentEntry {
+            binding: 0,
+            buffer: self.ctx_ring.buffer(),
+            uniform: true,
+            dynamic_size: Some(self.ctx_ring.binding_size()),
+        }];
+        entries.extend(
+            self.buffers
+                .iter()
+                .enumerate()
+                .map(|(i, buffer)| FragmentEntry {
+                    binding: i as u32 + 1,
+                    buffer,
+                    uniform: false,
+                    dynamic_size: None,
+                }),
+        );
+        FragmentInfo {
+            fragment_entry_point: fragment,
+            embedded: false,
+            entries,
+        }
+    }
+}
+
+/// Verify that a complete manifest is parsed with its parameters, that the optional keys have defaults, and that the invalid manifests are rejected with the line at fault.
+#[test]
+fn test_custom_manifest() {
+    let manifest = CustomManifest::parse(
+        "# Gray-Scott model\n\
+         reset = gs_reset\n\
+         step = gs_step   # one Euler step\n\
+         fragment = gs_fragment\n\
+         uniform_size = 32\n\
+         storage_buffers = 2\n\
+         element = vec2<f32>\n\
+         workgroup_size = 16 4\n\
+         normalize_every = 10\n\
+         parameter = feed 0.055 0.0 0.1\n\
+         parameter = kill 0.062 0.0 0.1\n",
+    )
+    .unwrap();
+    assert_eq!(manifest.reset.as_deref(), Some("gs_reset"));
+    assert_eq!(manifest.step, "gs_step");
+    assert_eq!(manifest.fragment.as_deref(), Some("gs_fragment"));
+    assert_eq!(manifest.uniform_size, 32);
+    assert_eq!(manifest.storage_buffers, 2);
+    assert_eq!(manifest.element, ElementType::Vec2F32);
+    assert_eq!(manifest.workgroup_size, [16, 4]);
+    assert_eq!(manifest.normalize_every, 10);
+    assert_eq!(manifest.parameters.len(), 2);
+    assert_eq!(manifest.parameters[1].name, "kill");
+    assert_eq!(manifest.parameters[1].range, 0.0..=0.1);
+
+    let manifest =
+        CustomManifest::parse("step = s\nfragment = f\nstorage_buffers = 1\nparameter = a 1 0 2")
+            .unwrap();
+    assert_eq!(manifest.reset, None);
+    assert_eq!(manifest.element, ElementType::F32);
+    assert_eq!(manifest.workgroup_size, [8, 8]);
+    assert_eq!(manifest.uniform_size, 20);
+    assert_eq!(manifest.ranges, [[0.0, 1.0]; 2]);
+    assert_eq!(manifest.normalize_every, 0);
+
+    let manifest =
+        CustomManifest::parse("step = s\nstorage_buffers = 2\nrange_a = 0 1\nrange_b = 0 0.5\n")
+            .unwrap();
+    assert_eq!(manifest.fragment, None);
+    assert_eq!(manifest.ranges, [[0.0, 1.0], [0.0, 0.5]]);
+
+    for (text, reason) in [
+        ("step = s\nfragment = f", "missing \"storage_buffers\""),
+        ("fragment = f\nstorage_buffers = 1", "missing \"step\""),
+        ("step = s\nsize = 3", "line 2: unknown key \"size\""),
+        ("step s", "line 1: expected \"key = value\""),
+        ("element = f64", "line 1: unknown element type \"f64\""),
+        ("range_b = 1", "line 1: expected \"min max\""),
+        (
+            "step = s\nstorage_buffers = 1",
+            "without fragment, two storage buffers of f32",
+        ),
+        (
+            "step = s\nstorage_buffers = 2\nelement = u32",
+            "without fragment, two storage buffers of f32",
+        ),
+        (
+            "step = s\nfragment = f\nstorage_buffers = 1\nnormalize_every = 5",
+            "normalize_every needs vec2<f32> or vec4<f32> elements",
+        ),
+        ("workgroup_size = 8", "line 1: expected two non zero sizes"),
+        ("parameter = a 3 0 2", "line 1: default value of a outside"),
+        (
+            "step = s\nfragment = f\nstorage_buffers = 1\nuniform_size = 12",
+            "uniform_size 12 must be a multiple of 4 holding the 16 bytes",
+        ),
+        (
+            "step = s\nfragment = f\nstorage_buffers = 9",
+            "storage_buffers must be between 1 and 8",
+        ),
+    ] {
+        let err = CustomManifest::parse(text).unwrap_err().to_string();
+        assert!(err.contains(reason), "{err} should contain {reason}");
+    }
+}
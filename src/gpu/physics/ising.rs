@@ -1,33 +1,460 @@
-use std::sync::Arc;
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU32, Ordering},
+    },
+};
 
-use bytemuck::bytes_of;
-use instant::Instant;
-use kernel::IsingCtx;
-use rand_gpu_wasm::philox::Philox4x32;
-use wgpu::{Buffer, CommandEncoder, util::DeviceExt};
+use bytemuck::{bytes_of, cast_slice, pod_read_unaligned};
+use kernel::{
+    CellRng, DYNAMICS_KAWASAKI, DYNAMICS_SPIN_FLIP, INIT_DOWN, INIT_QUASI_RANDOM, INIT_RANDOM,
+    INIT_STRIPE, INIT_UP, IsingCtx, KAWASAKI_PASSES, KawasakiPass, NOT_PINNED, PROPOSAL_RESAMPLE,
+    RULE_GLAUBER, RULE_HEAT_BATH, RULE_METROPOLIS, StepConstants, TILE, unpack_rgba,
+};
+use wgpu::{
+    Buffer, CommandEncoder,
+    util::{DeviceExt, StagingBelt},
+};
 
-use crate::{gpu::pipeline::Pipeline, simulation::atomic_f32::AtomicF32};
+use crate::{
+    error::WGPUError,
+    gpu::{
+        capabilities::{check_lattice, max_binding_size},
+        command::CommandReceiver,
+        history::History,
+        observable::{MeasuredObservable, Observable},
+        pipeline::{Access, Pipeline, PipelineBuilder},
+        readback::{Readback, ReadbackHandle},
+        reduction::Reduction,
+        step_tuner::{DEFAULT_TARGET_FPS, StepTuner},
+        timing::GpuTimer,
+        uniform_ring::UniformRing,
+    },
+    import::{ImageMapping, ImageSource, image_to_values},
+    quasi_random::quasi_random_spins,
+    simulation::{atomic_f32::AtomicF32, protocol::ProtocolRunner},
+};
 
-use super::{FragmentEntry, FragmentInfo, Physics};
+use super::{
+    FragmentEntry, FragmentInfo, FrameInfo, Physics, Precision, Probe, STAGING_CHUNK_SIZE,
+    cell_rngs, pop_error_scopes, push_error_scopes,
+};
+use tempering::{Tempering, ladder};
+
+pub mod tempering;
+
+/// Parameters of the Ising model shared between the [Ising](crate::simulation::ising::Ising) simulation which modifies them from the egui UI, and the [IsingPipeline] which reads them every frame.
+pub struct IsingParameters {
+    /// Temperature and external field of the physics, changed by the [IsingCommand]s of the UI as well as by the protocols and the loaded states.
+    pub temperature: AtomicF32,
+    pub external_field: AtomicF32,
+    pub drive: DriveParameters,
+    pub top_pin: AtomicF32,
+    pub bottom_pin: AtomicF32,
+    /// Size of the coarse-graining blocks, 0 meaning that the coarse-grained view is disabled.
+    pub block_size: AtomicU32,
+    pub block_iterate: AtomicBool,
+    /// Acceptance rule of the steps, one of the `RULE_*` constants of the kernel.
+    pub rule: AtomicU32,
+    /// Candidate of the steps, one of the `PROPOSAL_*` constants of the kernel.
+    pub proposal: AtomicU32,
+    /// Coupling `J` between neighbors. Contrary to the other parameters which are written to the uniform context every frame, it is a specialization constant of the step kernel, so changing it rebuilds the step pipelines.
+    pub coupling: AtomicF32,
+    /// Whether the steps use the tiled kernels, which load the spins of a workgroup and of their neighbors into workgroup memory, instead of the kernels with one invocation per workgroup. Both give the same result, so this only changes the speed, and changing it rebuilds the step pipelines.
+    pub tiled: AtomicBool,
+    /// Dynamics of the steps, one of the `DYNAMICS_*` constants of the kernel. The acceptance rule, the proposal and the tiled kernels only apply to the spin flip dynamics, the Kawasaki dynamics always using the Metropolis rule.
+    pub dynamics: AtomicU32,
+    /// Initialization of the spins by the resets, one of the `INIT_*` constants of the kernel.
+    pub init: AtomicU32,
+    /// Protocol driving the parameters, such as a [Quench](crate::simulation::ising::quench::Quench).
+    pub protocol: Mutex<ProtocolRunner<IsingParameters, IsingObservables>>,
+    pub measurements: MeasurementParameters,
+    pub tuner: TunerParameters,
+    pub tempering: TemperingParameters,
+    /// Colors of the spins up and down in the rendering, as RGBA with 8 bits per channel, red in the low bits.
+    pub up_color: AtomicU32,
+    pub down_color: AtomicU32,
+    pub history: HistoryParameters,
+    pub export: ExportParameters,
+    /// Sweeps of thermalization requested by [IsingCommand::Thermalize] which remain to be performed, shown as the progress of the thermalization.
+    pub thermalizing: AtomicU32,
+}
+
+/// Oscillation h(t) = h + A sin(2π t/P) of the external field of the [IsingParameters], with the amplitude A and the period P in sweeps.
+pub struct DriveParameters {
+    pub enable: AtomicBool,
+    pub amplitude: AtomicF32,
+    pub period: AtomicF32,
+    /// Field h(t) and magnetization of the last [HYSTERESIS_POINTS] measurements while the drive is enabled, which trace the hysteresis loop. Cleared when the drive is disabled.
+    pub hysteresis: Mutex<VecDeque<[f64; 2]>>,
+}
+
+/// Measurements of the observables of the [IsingParameters] displayed in the UI.
+pub struct MeasurementParameters {
+    /// Number of frames between two measurements, 0 meaning that they are not measured. Each measurement stalls the frame while the result is read back, so a larger cadence reduces the time per frame.
+    pub every: AtomicU32,
+    /// Last measured observables, held until the next measurement.
+    pub observables: Mutex<Option<IsingObservables>>,
+    /// Moments of the magnetization accumulated over the measurements, from which the Binder cumulant is computed.
+    pub moments: Mutex<MagnetizationMoments>,
+}
+
+/// Bounds of the number of steps per frame chosen by the [StepTuner] of the [IsingPipeline].
+pub struct TunerParameters {
+    /// Framerate aimed for.
+    pub target_fps: AtomicF32,
+    /// Minimum number of steps per frame.
+    pub min_steps: AtomicU32,
+    /// Maximum number of steps per frame.
+    pub max_steps: AtomicU32,
+    /// Whether the framerate is aimed for with the GPU time of the steps instead of the wall-clock time of the frames, when the timestamp queries are supported.
+    pub gpu_time: AtomicBool,
+    /// Whether the steps of a frame are bounded by the [budget](StepTuner::budget) of the frame, the remaining ones being carried over to the next frame, see [StepTuner::within_budget].
+    pub frame_budget: AtomicBool,
+}
+
+/// Parallel tempering of the replicas of the [IsingParameters], see [Tempering].
+pub struct TemperingParameters {
+    /// Whether the replicas evolve at the temperatures of a ladder from `low` to `high` with exchanges of their temperatures. The temperature [IsingParameters::temperature] is then ignored.
+    pub enable: AtomicBool,
+    pub low: AtomicF32,
+    pub high: AtomicF32,
+    /// Number of sweeps between two attempts of exchanges.
+    pub swap_every: AtomicU32,
+    /// Pairs of adjacent temperatures of the ladder with the acceptance rate of their exchanges, updated with the measurements.
+    pub exchange_rates: Mutex<Vec<([f32; 2], f64)>>,
+}
+
+/// Frames of the live state recorded by the [History] of the [IsingPipeline], which can be shown while paused.
+pub struct HistoryParameters {
+    /// Whether the steps are suspended, the frames of the [History] being shown instead of the live state. It reaches the [IsingPipeline] through [FrameInfo::paused].
+    pub paused: AtomicBool,
+    /// Number of frames kept, 0 meaning that the history is disabled.
+    pub capacity: AtomicU32,
+    /// Number of sweeps between two frames recorded.
+    pub every: AtomicU32,
+    /// Frame shown while paused, counted backwards from the newest one starting at 1, 0 meaning the live state.
+    pub shown: AtomicU32,
+    /// Sweep of each frame, from the oldest to the newest, updated at each recording.
+    pub sweeps: Mutex<Vec<u32>>,
+}
+
+/// States of the first replica read back for the exports as `.npy` files.
+pub struct ExportParameters {
+    /// Number of sweeps between two frames read back as a time series, 0 meaning that no series is recorded.
+    pub every: AtomicU32,
+    /// States read back, either requested by [IsingCommand::Export] or recorded as a time series, until they are taken by the UI.
+    pub snapshots: Mutex<Vec<Snapshot>>,
+}
+
+/// State of the first replica read back for an export.
+pub struct Snapshot {
+    pub sweep: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Values of the cells, row by row.
+    pub vals: Vec<f32>,
+    /// Whether the state is a frame of the time series recorded every [ExportParameters::every] sweeps, rather than requested by [IsingCommand::Export].
+    pub series: bool,
+}
+
+/// Commands of the [Ising](crate::simulation::ising::Ising) simulation to its [IsingPipeline], applied in order at the top of the next update, see [command_channel](crate::gpu::command::command_channel).
+#[derive(Clone, Debug, PartialEq)]
+pub enum IsingCommand {
+    Temperature(f32),
+    Field(f32),
+    /// Reset the spins of every replica with the initialization [IsingParameters::init].
+    Reset,
+    /// Replace the state with the image, fitted into the lattice and mapped to spins, see [IsingPipeline::load_from_image].
+    LoadImage(ImageSource, ImageMapping),
+    /// Read back the state shown at the end of the next update as a [Snapshot].
+    Export,
+    /// Perform the sweeps over the next frames, by chunks of at most [RUN_CHUNK] sweeps replacing the steps of the frames, without measuring nor recording the history, see [IsingParameters::thermalizing].
+    Thermalize(u32),
+}
+
+/// Observables of the Ising model measured on the GPU.
+#[derive(Clone, Copy)]
+pub struct IsingObservables {
+    /// Mean value of the spins, averaged over the replicas.
+    pub magnetization: f64,
+    /// Standard error of the average of the magnetization over the replicas, 0 with a single replica.
+    pub magnetization_error: f64,
+    /// Fraction of the bonds between neighbors with opposite spins, averaged over the replicas.
+    pub interface_density: f64,
+    /// Energy per spin, averaged over the replicas.
+    pub energy: f64,
+    /// External field at the measured sweep, which oscillates as h(t) when the drive is enabled.
+    pub field: f64,
+    /// Averages of m² and m⁴ over the replicas, where m is the magnetization of a replica.
+    pub magnetization_squared: f64,
+    pub magnetization_fourth: f64,
+}
+
+impl IsingObservables {
+    /// [Binder cumulant](binder_cumulant) of the ensemble of replicas, which is only meaningful with many replicas.
+    pub fn binder_cumulant(&self) -> f64 {
+        binder_cumulant(self.magnetization_squared, self.magnetization_fourth)
+    }
+}
+
+/// Binder cumulant U = 1 - ⟨m⁴⟩/(3⟨m²⟩²) from the moments `m2` = ⟨m²⟩ and `m4` = ⟨m⁴⟩ of the magnetization. It tends to 2/3 in the ordered phase and to 0 in the disordered phase, where the magnetization is Gaussian, and its curves U(T) for different sizes cross at the critical temperature. NaN if `m2` is 0.
+pub fn binder_cumulant(m2: f64, m4: f64) -> f64 {
+    1.0 - m4 / (3.0 * m2 * m2)
+}
+
+/// Moments ⟨m²⟩ and ⟨m⁴⟩ of the magnetization m averaged over the measurements at a given temperature and field.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MagnetizationMoments {
+    /// Temperature and field of the measurements, the moments restarting when they change.
+    at: Option<[f32; 2]>,
+    measurements: u32,
+    m2: f64,
+    m4: f64,
+}
+
+impl MagnetizationMoments {
+    /// Add the moments over the replicas of `observables`, measured at `temperature` and `field`. The previous measurements are discarded if they were taken at another temperature or field.
+    pub fn push(&mut self, temperature: f32, field: f32, observables: &IsingObservables) {
+        if self.at != Some([temperature, field]) {
+            *self = MagnetizationMoments {
+                at: Some([temperature, field]),
+                ..Default::default()
+            };
+        }
+        self.measurements += 1;
+        self.m2 += observables.magnetization_squared;
+        self.m4 += observables.magnetization_fourth;
+    }
+    /// Discard the measurements, for instance when the state is replaced.
+    pub fn clear(&mut self) {
+        *self = MagnetizationMoments::default();
+    }
+    /// Number of measurements accumulated.
+    pub fn measurements(&self) -> u32 {
+        self.measurements
+    }
+    /// [Binder cumulant](binder_cumulant) of the measurements, if there is any.
+    pub fn binder_cumulant(&self) -> Option<f64> {
+        let n = self.measurements as f64;
+        (self.measurements > 0).then(|| binder_cumulant(self.m2 / n, self.m4 / n))
+    }
+}
+
+/// Number of measurements kept in [DriveParameters::hysteresis], enough for a few periods of the drive at the default cadence of the measurements.
+const HYSTERESIS_POINTS: usize = 2000;
+/// Mean of `values` and its standard error, 0 if there is a single value.
+fn mean_and_error(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    if values.len() < 2 {
+        return (mean, 0.0);
+    }
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    (mean, (variance / n).sqrt())
+}
+
+impl Default for IsingParameters {
+    /// Parameters at the critical temperature of the square lattice, without field nor drive.
+    fn default() -> Self {
+        IsingParameters {
+            temperature: AtomicF32::new(2.2691853142),
+            external_field: AtomicF32::new(0.0),
+            drive: DriveParameters {
+                enable: false.into(),
+                amplitude: AtomicF32::new(0.5),
+                period: AtomicF32::new(1000.0),
+                hysteresis: Mutex::new(VecDeque::new()),
+            },
+            top_pin: AtomicF32::new(NOT_PINNED),
+            bottom_pin: AtomicF32::new(NOT_PINNED),
+            block_size: AtomicU32::new(0),
+            block_iterate: false.into(),
+            rule: AtomicU32::new(RULE_GLAUBER),
+            proposal: AtomicU32::new(PROPOSAL_RESAMPLE),
+            coupling: AtomicF32::new(1.0),
+            tiled: false.into(),
+            dynamics: AtomicU32::new(DYNAMICS_SPIN_FLIP),
+            init: AtomicU32::new(INIT_RANDOM),
+            protocol: Mutex::new(ProtocolRunner::new()),
+            measurements: MeasurementParameters {
+                every: AtomicU32::new(30),
+                observables: Mutex::new(None),
+                moments: Mutex::new(MagnetizationMoments::default()),
+            },
+            tuner: TunerParameters {
+                target_fps: AtomicF32::new(DEFAULT_TARGET_FPS),
+                min_steps: AtomicU32::new(1),
+                max_steps: AtomicU32::new(10),
+                gpu_time: false.into(),
+                frame_budget: false.into(),
+            },
+            tempering: TemperingParameters {
+                enable: false.into(),
+                low: AtomicF32::new(2.0),
+                high: AtomicF32::new(2.6),
+                swap_every: AtomicU32::new(10),
+                exchange_rates: Mutex::new(vec![]),
+            },
+            up_color: AtomicU32::new(0xffff0000),
+            down_color: AtomicU32::new(0xffffffff),
+            history: HistoryParameters {
+                paused: false.into(),
+                capacity: AtomicU32::new(0),
+                every: AtomicU32::new(100),
+                shown: AtomicU32::new(0),
+                sweeps: Mutex::new(vec![]),
+            },
+            export: ExportParameters {
+                every: AtomicU32::new(0),
+                snapshots: Mutex::new(vec![]),
+            },
+            thermalizing: AtomicU32::new(0),
+        }
+    }
+}
+
+impl IsingParameters {
+    /// Build the [IsingCtx] corresponding to the current parameters, for spins stored with `precision`.
+    fn ctx(&self, width: u32, height: u32, sweep: u32, precision: Precision) -> IsingCtx {
+        let block_size = self.block_size.load(Ordering::Relaxed);
+        let block_levels = 1 + self.block_iterate.load(Ordering::Relaxed) as u32;
+        // Only enable the coarse-graining if at least one block fits in the lattice.
+        let span = block_size.pow(block_levels);
+        let (block_size, block_levels) = if block_size > 1 && span <= width && span <= height {
+            (block_size, block_levels)
+        } else {
+            (0, 0)
+        };
+        IsingCtx {
+            width,
+            height,
+            temperature: self.temperature.load(),
+            external_field: self.external_field.load(),
+            sweep,
+            drive_enable: self.drive.enable.load(Ordering::Relaxed) as u32,
+            drive_amplitude: self.drive.amplitude.load(),
+            drive_period: self.drive.period.load(),
+            top_pin: self.top_pin.load(),
+            bottom_pin: self.bottom_pin.load(),
+            block_size,
+            block_levels,
+            half: (precision == Precision::F16) as u32,
+            proposal: self.proposal.load(Ordering::Relaxed),
+            tempering: self.tempering.enable.load(Ordering::Relaxed) as u32,
+            _padding: 0,
+            up_color: unpack_rgba(self.up_color.load(Ordering::Relaxed)),
+            down_color: unpack_rgba(self.down_color.load(Ordering::Relaxed)),
+        }
+    }
+}
 
 /// Handles the compute pipeline for the Ising model simulation.
 pub struct IsingPipeline {
-    ctx_buffer: Buffer,
-    reset_pipeline: Pipeline,
-    step_pipeline: Pipeline,
+    /// Context of the kernels, written in a new slot each frame so that it does not wait for the rendering of the previous frame.
+    ctx_ring: UniformRing<IsingCtx>,
+    /// Context held by the current slot of `ctx_ring`, so that it is only written again when it changed.
+    written_ctx: IsingCtx,
+    /// Staging of the contexts written in the encoder of the frame. When push constants are not supported, the context of each step is written into the slot of the frame between the compute passes so that the sweep counter seen by the kernel is exact.
+    belt: StagingBelt,
+    /// Whether the sweep counter of each step is given as push constants to `ising_step_push`, instead of being written in `ctx_ring` between the steps.
+    push_constants: bool,
+    /// Reset pipeline specialized for each initialization, indexed by the initialization.
+    reset_pipelines: [Pipeline; 4],
+    /// Step pipeline specialized for each acceptance rule, indexed by the rule, and for the coupling `coupling`.
+    step_pipelines: [Pipeline; 3],
+    /// Pipeline of the Kawasaki dynamics for the coupling `coupling`, see [IsingParameters::dynamics].
+    kawasaki_pipeline: Pipeline,
+    /// Slots of the pass of each of the [KAWASAKI_PASSES] of the Kawasaki dynamics, `kawasaki_stride` bytes apart.
+    kawasaki_passes: Buffer,
+    kawasaki_stride: u32,
+    coupling: f32,
+    /// Whether the step pipelines are the tiled ones, see [IsingParameters::tiled].
+    tiled: bool,
+    shader_module: wgpu::ShaderModule,
+    block_pipeline: Pipeline,
+    bonds_pipeline: Pipeline,
+    magnetization: Reduction,
+    domain_walls: Reduction,
+    /// Sweep at which the measurement being read back was performed.
+    measured_sweep: Option<u32>,
+    /// Whether the reductions of the measurement were recorded in the encoder of the last frame, and must be mapped once it is submitted.
+    measure_encoded: bool,
+    measured: (Option<Vec<f64>>, Option<Vec<f64>>),
     vals_buffer: Buffer,
     new_vals_buffer: Buffer,
+    coarse_vals_buffer: Buffer,
+    rngs_buffer: Buffer,
+    tempering: Tempering,
+    /// Recent frames of the first replica, if enabled by [HistoryParameters::capacity].
+    history: Option<History>,
+    /// Sweep of the last frame recorded in the [History].
+    recorded_sweep: u32,
+    /// Read backs of the [Snapshot]s, with the sweep of each request and whether it is a frame of the time series.
+    export_readback: Readback,
+    export_requests: Vec<(ReadbackHandle, u32, bool)>,
+    /// Sweep of the last frame of the time series of the exports.
+    exported_sweep: u32,
+    /// Cell whose value and neighbors are read back at each update, see [Physics::probe].
+    probe_cell: Option<[u32; 2]>,
+    /// Words of the probed cell and of its 4 neighbors, gathered from the first replica before their read back.
+    probe_buffer: Buffer,
+    /// Read back of the `probe_buffer`, with the cell, the sweep and the external field of the request in flight.
+    probe_readback: Readback,
+    probe_request: Option<(ReadbackHandle, [u32; 2], u32, f32)>,
+    probed: Option<Probe>,
+    precision: Precision,
     width: u32,
     height: u32,
-    temperature: Arc<AtomicF32>,
-    external_field: Arc<AtomicF32>,
-    step_per_frames: usize,
-    time_history: [f32; 10],
-    current_time: usize,
-    time: Instant,
+    /// Number of independent lattices simulated together, stored one after the other in the buffers.
+    replicas: u32,
+    parameters: Arc<IsingParameters>,
+    /// Commands of the simulation, if any, see [IsingPipeline::with_commands].
+    commands: Option<CommandReceiver<IsingCommand>>,
+    /// Image to load as the state, which waits until the live state is shown again if a frame of the history is shown.
+    image_requested: Option<(ImageSource, ImageMapping)>,
+    /// Whether a reset with [INIT_QUASI_RANDOM] was requested, whose spins are written in the queue like the image.
+    quasi_random_requested: bool,
+    /// Seed of the generators, which also rotates the sequence of [INIT_QUASI_RANDOM].
+    seed: u128,
+    sweep: u32,
+    tuner: StepTuner,
+    /// GPU duration of the passes, indexed by the `PASS_*` constants, if the timestamp queries are supported.
+    timer: Option<GpuTimer>,
 }
 
+/// Index of the steps in the [GpuTimer] of the [IsingPipeline].
+const PASS_STEP: usize = 0;
+/// Index of the coarse-graining in the [GpuTimer] of the [IsingPipeline].
+const PASS_BLOCK: usize = 1;
+/// Index of the measurement of the domain walls in the [GpuTimer] of the [IsingPipeline].
+const PASS_BONDS: usize = 2;
+/// Number of cells read back by [IsingPipeline::request_probe]: the probed cell and its 4 neighbors.
+const PROBE_CELLS: usize = 5;
+
+/// Maximum number of sweeps recorded in a single submission by [IsingPipeline::thermalize], or in a single frame by [IsingCommand::Thermalize].
+const RUN_CHUNK: usize = 1000;
+/// Name of each initialization of the spins by the resets, as chosen in the GUI and in the configuration files.
+pub const INITS: [(&str, u32); 5] = [
+    ("random", INIT_RANDOM),
+    ("all up", INIT_UP),
+    ("all down", INIT_DOWN),
+    ("stripe", INIT_STRIPE),
+    ("quasi-random", INIT_QUASI_RANDOM),
+];
+/// First word of the states saved by [Physics::save_state], `ISNG` in ASCII.
+pub const STATE_MAGIC: u32 = u32::from_le_bytes(*b"ISNG");
+/// Version of the layout of the saved states, see [IsingPipeline::save_state].
+const STATE_VERSION: u32 = 2;
+/// Number of u32 words of the header of the saved states: the magic, the version, the sweep and the number of replicas.
+const STATE_HEADER_WORDS: usize = 4;
+/// Number of u32 words of the parameters of the saved states which are not held by the [IsingCtx]: the rule, the proposal, the dynamics, the initialization, the bits of the coupling and whether the steps are tiled.
+const STATE_PARAMETER_WORDS: usize = 6;
+
 impl IsingPipeline {
+    /// Create the buffers and pipelines of `replicas` independent `width`×`height` lattices with spins stored in `precision`. In [Precision::F16] the spins are packed by pairs along the rows, so an odd `width` is rounded down to an even one. The number of replicas is reduced if the buffers would not fit in the limits of `device`.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
@@ -35,122 +462,572 @@ impl IsingPipeline {
         seed: u128,
         width: u32,
         height: u32,
-        temperature: Arc<AtomicF32>,
-        external_field: Arc<AtomicF32>,
-    ) -> Self {
-        let ctx = IsingCtx {
+        parameters: Arc<IsingParameters>,
+        precision: Precision,
+        replicas: u32,
+    ) -> Result<Self, WGPUError> {
+        let cells = precision.cells_per_word();
+        let width = if width % cells != 0 {
+            log::warn!("Odd width {width} rounded down for the half precision storage.");
+            (width / cells * cells).max(cells)
+        } else {
+            width
+        };
+        let ctx = parameters.ctx(width, height, 0, precision);
+        let ctx_ring = UniformRing::new(device, "Ising ctx buffer", &ctx);
+
+        // A lattice too large for the limits of the device is rejected before any allocation, which would otherwise fail inside wgpu.
+        let limits = device.limits();
+        let count = check_lattice(
+            &limits,
             width,
             height,
-            temperature: temperature.load(),
-            external_field: external_field.load(),
+            &[size_of::<f32>(), size_of::<CellRng>()],
+        )?;
+        let words = count / cells as usize;
+        // The random number generators are the largest buffer.
+        let max_replicas =
+            max_binding_size(&limits) as usize / (count * size_of::<CellRng>()).max(1);
+        let replicas = if replicas as usize > max_replicas {
+            log::warn!("{replicas} replicas do not fit in the buffers, reduced to {max_replicas}.");
+            max_replicas.max(1) as u32
+        } else {
+            replicas.max(1)
         };
-        let ctx_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Ising ctx buffer"),
-            contents: bytes_of(&ctx),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let count = (width * height) as usize;
+        let all_words = words * replicas as usize;
+        let all_cells = count * replicas as usize;
+        // The allocation failures of huge lattices are returned instead of being reported to the uncaptured error handler.
+        push_error_scopes(device);
 
         let vals_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Ising vals buffer"),
-            size: count as u64 * size_of::<f32>() as u64,
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            size: all_words as u64 * size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let probe_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Ising probe buffer"),
+            size: PROBE_CELLS as u64 * size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
             mapped_at_creation: false,
         });
 
         let new_vals_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Ising new vals buffer"),
-            size: count as u64 * size_of::<f32>() as u64,
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            size: all_words as u64 * size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
 
-        let rngs = (0..count)
-            .map(|i| Philox4x32::new(seed, i as u64))
-            .collect::<Vec<_>>();
+        // A coarse-grained lattice contains at most a quarter of the cells as the blocks are at least 2×2.
+        let coarse_vals_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Ising coarse vals buffer"),
+            size: (count / 4).max(1) as u64 * size_of::<f32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        // The pass of each dispatch of the Kawasaki dynamics is selected by a dynamic offset, which must be aligned.
+        let kawasaki_stride = limits
+            .min_uniform_buffer_offset_alignment
+            .max(size_of::<KawasakiPass>() as u32);
+        let mut passes = vec![0; (KAWASAKI_PASSES * kawasaki_stride) as usize];
+        for pass in 0..KAWASAKI_PASSES {
+            let offset = (pass * kawasaki_stride) as usize;
+            passes[offset..offset + size_of::<KawasakiPass>()].copy_from_slice(bytes_of(
+                &KawasakiPass {
+                    pass,
+                    _padding: [0; 3],
+                },
+            ));
+        }
+        let kawasaki_passes = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Ising Kawasaki passes buffer"),
+            contents: &passes,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bonds_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Ising bonds buffer"),
+            size: all_cells as u64 * size_of::<f32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        // The key of each generator differs per cell and per replica, the first replica using the same generators as a single lattice.
+        let mut rngs = cell_rngs(seed, all_cells + 1);
+        // The generator following the ones of the cells draws the exchanges of the tempering.
+        let tempering_rng = rngs.pop().unwrap();
         let rngs_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Ising rngs buffer"),
             contents: bytemuck::cast_slice(&rngs),
-            usage: wgpu::BufferUsages::STORAGE,
+            // The generators are copied by the saved states, so that a restored run draws the same random numbers.
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
         });
 
+        let tuner = StepTuner::new().with_bounds(
+            parameters.tuner.min_steps.load(Ordering::Relaxed) as usize,
+            parameters.tuner.max_steps.load(Ordering::Relaxed) as usize,
+        );
+
+        let coupling = parameters.coupling.load();
+        let tiled = parameters.tiled.load(Ordering::Relaxed);
+        let push_constants =
+            Pipeline::push_constants_supported(device, size_of::<StepConstants>() as u32);
+        if !push_constants {
+            log::warn!(
+                "Push constants are not supported, the sweep counter is copied into a uniform buffer at each step."
+            );
+        }
+
+        let magnetization = Reduction::new(
+            device,
+            shader_module,
+            &vals_buffer,
+            words as u32,
+            replicas,
+            precision,
+        );
+        let domain_walls = Reduction::new(
+            device,
+            shader_module,
+            &bonds_buffer,
+            count as u32,
+            replicas,
+            Precision::F32,
+        );
+        let tempering = Tempering::new(
+            device,
+            shader_module,
+            replicas,
+            count as u32,
+            tempering_rng,
+            &magnetization,
+            &domain_walls,
+        );
+
+        let reset_pipelines = [INIT_RANDOM, INIT_UP, INIT_DOWN, INIT_STRIPE].map(|init| {
+            PipelineBuilder::new(device, shader_module, "ising_reset")
+                .entry(ctx_ring.entry(0))
+                .storage(1, &vals_buffer, Access::ReadWrite)
+                .storage(2, &rngs_buffer, Access::ReadWrite)
+                .constant(0, init as f64)
+                .build()
+        });
+        let step_pipelines = Self::new_step_pipelines(
+            device,
+            shader_module,
+            &ctx_ring,
+            [
+                &vals_buffer,
+                &new_vals_buffer,
+                &rngs_buffer,
+                tempering.temperatures_buffer(),
+            ],
+            coupling,
+            [width, height],
+            push_constants,
+            tiled,
+        );
+        let kawasaki_pipeline = Self::new_kawasaki_pipeline(
+            device,
+            shader_module,
+            &ctx_ring,
+            [&vals_buffer, &rngs_buffer, tempering.temperatures_buffer()],
+            (&kawasaki_passes, kawasaki_stride),
+            coupling,
+            push_constants,
+        );
+        let block_pipeline = PipelineBuilder::new(device, shader_module, "ising_block")
+            .entry(ctx_ring.entry(0))
+            .storage(1, &vals_buffer, Access::ReadOnly)
+            .storage(2, &coarse_vals_buffer, Access::ReadWrite)
+            .build();
+        let bonds_pipeline = PipelineBuilder::new(device, shader_module, "ising_bonds")
+            .entry(ctx_ring.entry(0))
+            .storage(1, &vals_buffer, Access::ReadOnly)
+            .storage(2, &bonds_buffer, Access::ReadWrite)
+            .build();
+        let timer = GpuTimer::new(
+            device,
+            queue,
+            &["step", "block", "bonds"],
+            wgpu::Features::TIMESTAMP_QUERY,
+        );
+        // The invalid pipelines are only reported once the error scopes are popped.
+        pop_error_scopes(device).map_err(|err| WGPUError::AllocationFailed(all_cells, err))?;
+        let [random, up, down, stripe] = reset_pipelines;
         let p = IsingPipeline {
-            reset_pipeline: Pipeline::new(
-                device,
-                shader_module,
-                "ising_reset",
-                [
-                    (0, &ctx_buffer, None, None),
-                    (1, &vals_buffer, Some(false), None),
-                    (2, &rngs_buffer, Some(false), None),
-                ],
-            ),
-            step_pipeline: Pipeline::new(
-                device,
-                shader_module,
-                "ising_step",
-                [
-                    (0, &ctx_buffer, None, None),
-                    (1, &vals_buffer, Some(true), None),
-                    (2, &new_vals_buffer, Some(false), None),
-                    (3, &rngs_buffer, Some(false), None),
-                ],
-            ),
-            ctx_buffer,
+            reset_pipelines: [random?, up?, down?, stripe?],
+            step_pipelines: step_pipelines?,
+            kawasaki_pipeline: kawasaki_pipeline?,
+            kawasaki_passes,
+            kawasaki_stride,
+            coupling,
+            tiled,
+            shader_module: shader_module.clone(),
+            block_pipeline: block_pipeline?,
+            bonds_pipeline: bonds_pipeline?,
+            magnetization,
+            domain_walls,
+            measured_sweep: None,
+            measure_encoded: false,
+            measured: (None, None),
+            ctx_ring,
+            written_ctx: ctx,
+            belt: StagingBelt::new(STAGING_CHUNK_SIZE),
+            push_constants,
             vals_buffer,
             new_vals_buffer,
+            coarse_vals_buffer,
+            rngs_buffer,
+            tempering,
+            history: None,
+            recorded_sweep: 0,
+            export_readback: Readback::new(),
+            export_requests: vec![],
+            exported_sweep: 0,
+            probe_cell: None,
+            probe_buffer,
+            probe_readback: Readback::new(),
+            probe_request: None,
+            probed: None,
+            precision,
             width,
             height,
-            temperature,
-            external_field,
-            step_per_frames: 1,
-            time_history: Default::default(),
-            current_time: 0,
-            time: Instant::now(),
+            replicas,
+            parameters,
+            commands: None,
+            image_requested: None,
+            quasi_random_requested: false,
+            seed,
+            sweep: 0,
+            tuner,
+            timer,
         };
-        p.reset(device, queue);
-        p
+        p.reset(device, queue, p.parameters.init.load(Ordering::Relaxed));
+        Ok(p)
     }
-    fn dispatch(
-        &self,
+    /// Apply the `commands` at the top of each update, see [IsingCommand].
+    pub fn with_commands(self, commands: CommandReceiver<IsingCommand>) -> Self {
+        IsingPipeline {
+            commands: Some(commands),
+            ..self
+        }
+    }
+    /// Build the step pipeline of each acceptance rule for the given `coupling` and the lattice of `width`×`height` cells, all given as specialization constants, with the context `ctx_ring` followed by the `buffers` bound in order. The entry point is `ising_step_push` if `push_constants` is set and `ising_step` otherwise, or their `tiled` versions `ising_step_tiled_push` and `ising_step_tiled`.
+    fn new_step_pipelines(
         device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        with_encoder: impl Fn(&mut CommandEncoder),
+        shader_module: &wgpu::ShaderModule,
+        ctx_ring: &UniformRing<IsingCtx>,
+        [
+            vals_buffer,
+            new_vals_buffer,
+            rngs_buffer,
+            temperatures_buffer,
+        ]: [&Buffer; 4],
+        coupling: f32,
+        [width, height]: [u32; 2],
+        push_constants: bool,
+        tiled: bool,
+    ) -> Result<[Pipeline; 3], WGPUError> {
+        let (name, push_constant_size) = match (tiled, push_constants) {
+            (false, true) => ("ising_step_push", size_of::<StepConstants>() as u32),
+            (false, false) => ("ising_step", 0),
+            (true, true) => ("ising_step_tiled_push", size_of::<StepConstants>() as u32),
+            (true, false) => ("ising_step_tiled", 0),
+        };
+        let [glauber, metropolis, heat_bath] =
+            [RULE_GLAUBER, RULE_METROPOLIS, RULE_HEAT_BATH].map(|rule| {
+                PipelineBuilder::new(device, shader_module, name)
+                    .entry(ctx_ring.entry(0))
+                    .storage(1, vals_buffer, Access::ReadOnly)
+                    .storage(2, new_vals_buffer, Access::ReadWrite)
+                    .storage(3, rngs_buffer, Access::ReadWrite)
+                    .storage(4, temperatures_buffer, Access::ReadOnly)
+                    .constant(0, rule as f64)
+                    .constant(1, coupling.to_bits() as f64)
+                    .constant(2, width as f64)
+                    .constant(3, height as f64)
+                    .push_constants(push_constant_size)
+                    .build()
+            });
+        Ok([glauber?, metropolis?, heat_bath?])
+    }
+    /// Build the pipeline of the Kawasaki dynamics for the given `coupling`, with the context `ctx_ring` followed by the `buffers` bound in order and the `passes` buffer with its stride. The entry point is `kawasaki_step_push` if `push_constants` is set and `kawasaki_step` otherwise.
+    fn new_kawasaki_pipeline(
+        device: &wgpu::Device,
+        shader_module: &wgpu::ShaderModule,
+        ctx_ring: &UniformRing<IsingCtx>,
+        [vals_buffer, rngs_buffer, temperatures_buffer]: [&Buffer; 3],
+        (passes, stride): (&Buffer, u32),
+        coupling: f32,
+        push_constants: bool,
+    ) -> Result<Pipeline, WGPUError> {
+        let (name, push_constant_size) = if push_constants {
+            ("kawasaki_step_push", size_of::<StepConstants>() as u32)
+        } else {
+            ("kawasaki_step", 0)
+        };
+        debug_assert!(stride as usize >= size_of::<KawasakiPass>());
+        PipelineBuilder::new(device, shader_module, name)
+            .entry(ctx_ring.entry(0))
+            .storage(1, vals_buffer, Access::ReadWrite)
+            .storage(2, rngs_buffer, Access::ReadWrite)
+            .storage(3, temperatures_buffer, Access::ReadOnly)
+            .dynamic_uniform(4, passes, size_of::<KawasakiPass>() as u64)
+            .constant(1, coupling.to_bits() as f64)
+            .push_constants(push_constant_size)
+            .build()
+    }
+    /// Timestamp writes of the compute pass `repetition` among `repetitions` measured as the pass `pass` of the [GpuTimer], if any.
+    fn timestamp_writes(
+        &self,
+        pass: Option<usize>,
+        repetition: usize,
         repetitions: usize,
+    ) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        let timer = self.timer.as_ref()?;
+        Some(timer.compute_pass_writes(pass?, repetition, repetitions))
+    }
+    /// Record in `encoder` a compute pass of the `pipeline` on `workgroups` (x,y,z) workgroups, reading the current slot of the context, measured as the pass `pass` of the [GpuTimer], if any.
+    fn dispatch(
+        &self,
+        encoder: &mut CommandEncoder,
         pipeline: &Pipeline,
+        workgroups: (u32, u32, u32),
+        pass: Option<usize>,
     ) {
+        pipeline.dispatch_with_offsets(
+            encoder,
+            workgroups.0,
+            workgroups.1,
+            workgroups.2,
+            &[],
+            self.timestamp_writes(pass, 0, 1),
+            &[&[self.ctx_ring.offset()]],
+        );
+    }
+    /// Reset the spins of every replica with the initialization `init`, one of the `INIT_*` constants of the kernel.
+    pub fn reset(&self, device: &wgpu::Device, queue: &wgpu::Queue, init: u32) {
+        if init == INIT_QUASI_RANDOM {
+            self.write_quasi_random(queue);
+            return;
+        }
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some(&format!("{} Encoder", pipeline.name)),
+            label: Some("Ising reset Encoder"),
         });
-
-        for _ in 0..repetitions {
-            {
-                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                    label: Some(&format!("{} Pass", pipeline.name)),
-                    timestamp_writes: None,
-                });
-
-                compute_pass.set_pipeline(&pipeline.pipeline);
-                compute_pass.set_bind_group(0, &pipeline.bind_group, &[]);
-
-                compute_pass.dispatch_workgroups(self.width, self.height, 1);
-            }
-
-            with_encoder(&mut encoder);
-        }
-
+        self.record_reset(&mut encoder, init);
         queue.submit(Some(encoder.finish()));
-        let _ = device.poll(wgpu::MaintainBase::Wait);
     }
-    pub fn reset(&self, device: &wgpu::Device, queue: &wgpu::Queue) {
-        self.dispatch(device, queue, |_| {}, 1, &self.reset_pipeline)
+    /// Overwrite the spins of every replica with the [INIT_QUASI_RANDOM] initialization computed by [quasi_random_spins], the pinned rows taking their pinned value. The sequence is rotated by a key of the seed and the sweep, so that resetting again at another sweep gives another state, but every replica starts from the same state.
+    fn write_quasi_random(&self, queue: &wgpu::Queue) {
+        // The keys are indexed from the last index down, away from the indices of the cells given to [cell_rngs].
+        let rotation = gpu_random::stream_key(self.seed, !(self.sweep as u64));
+        let mut spins = quasi_random_spins(self.width, self.height, rotation);
+        let ctx = self
+            .parameters
+            .ctx(self.width, self.height, self.sweep, self.precision);
+        for (iy, row) in spins.chunks_mut(self.width as usize).enumerate() {
+            let pin = ctx.pin(iy as u32);
+            if pin != NOT_PINNED {
+                row.fill(pin);
+            }
+        }
+        self.write_vals(queue, &spins)
+            .expect("The quasi-random spins cover the lattice.");
     }
-    pub fn step(&mut self, repetitions: usize, device: &wgpu::Device, queue: &wgpu::Queue) {
+    /// Record in `encoder` the reset of the spins of every replica with the initialization `init`, reading the current slot of the context. The initializations computed on the host, [INIT_QUASI_RANDOM], are written by [IsingPipeline::reset] instead.
+    fn record_reset(&self, encoder: &mut CommandEncoder, init: u32) {
         self.dispatch(
-            device,
+            encoder,
+            &self.reset_pipelines[init as usize],
+            (
+                self.width / self.precision.cells_per_word(),
+                self.height,
+                self.replicas,
+            ),
+            None,
+        );
+    }
+    /// Overwrite the spins of every replica with `data`, the values of the `width`×`height` cells given row by row. Both the current and the next values are written, so that the upload is consistent whichever buffer the next step reads.
+    pub fn write_vals(&self, queue: &wgpu::Queue, data: &[f32]) -> Result<(), WGPUError> {
+        let expected = (self.width * self.height) as usize;
+        if data.len() != expected {
+            return Err(WGPUError::StateSizeMismatch {
+                len: data.len(),
+                expected,
+            });
+        }
+        let words = self.precision.pack(data).repeat(self.replicas as usize);
+        for buffer in [&self.vals_buffer, &self.new_vals_buffer] {
+            queue.write_buffer(buffer, 0, cast_slice(&words));
+        }
+        Ok(())
+    }
+    /// Overwrite the spins of every replica with the image of `source`, fitted into the lattice and mapped to spins according to `mapping`, see [image_to_values].
+    pub fn load_from_image(
+        &self,
+        source: &ImageSource,
+        mapping: &ImageMapping,
+        queue: &wgpu::Queue,
+    ) -> Result<(), WGPUError> {
+        let image = source.decode()?;
+        self.write_vals(
             queue,
-            |encoder| {
+            &image_to_values(&image, self.width, self.height, mapping),
+        )
+    }
+    /// Record in `encoder` the coarse-graining of the first replica into `coarse_vals_buffer` if enabled in `ctx`.
+    pub fn block(&self, encoder: &mut CommandEncoder, ctx: &IsingCtx) {
+        if ctx.block_size != 0 {
+            let span = ctx.block_span();
+            self.dispatch(
+                encoder,
+                &self.block_pipeline,
+                (self.width / span, self.height / span, 1),
+                Some(PASS_BLOCK),
+            )
+        }
+    }
+    /// Measure the observables of the current state, averaged over the replicas. The measurement is recorded in `encoder`, and the result is returned together with the sweep at which it was measured once it has been read back from the GPU, which takes at least one more frame as the read back can only be mapped once the encoder has been submitted.
+    pub fn measure(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut CommandEncoder,
+    ) -> Option<(u32, IsingObservables)> {
+        if self.measured_sweep.is_none() {
+            self.dispatch(
+                encoder,
+                &self.bonds_pipeline,
+                (self.width, self.height, self.replicas),
+                Some(PASS_BONDS),
+            );
+            self.magnetization.encode(encoder);
+            self.domain_walls.encode(encoder);
+            self.measure_encoded = true;
+            self.measured_sweep = Some(self.sweep);
+        }
+        let _ = device.poll(wgpu::MaintainBase::Poll);
+        if let Some(sums) = self.magnetization.try_read() {
+            self.measured.0 = Some(sums);
+        }
+        if let Some(sums) = self.domain_walls.try_read() {
+            self.measured.1 = Some(sums);
+        }
+        let (Some(magnetizations), Some(domain_walls)) = &self.measured else {
+            return None;
+        };
+        let count = (self.width * self.height) as f64;
+        let magnetizations = magnetizations.iter().map(|m| m / count).collect::<Vec<_>>();
+        let (magnetization, magnetization_error) = mean_and_error(&magnetizations);
+        let moment = |power| {
+            magnetizations.iter().map(|m| m.powi(power)).sum::<f64>() / magnetizations.len() as f64
+        };
+        let interface_density =
+            domain_walls.iter().sum::<f64>() / (2.0 * count * domain_walls.len() as f64);
+        self.measured = (None, None);
+        let sweep = self.measured_sweep.take()?;
+        // Each of the 2 bonds per spin contributes -J if the spins are aligned and +J otherwise.
+        let field = self
+            .parameters
+            .ctx(self.width, self.height, sweep, self.precision)
+            .field() as f64;
+        let energy =
+            -2.0 * self.coupling as f64 * (1.0 - 2.0 * interface_density) - field * magnetization;
+        Some((
+            sweep,
+            IsingObservables {
+                magnetization,
+                magnetization_error,
+                interface_density,
+                energy,
+                field,
+                magnetization_squared: moment(2),
+                magnetization_fourth: moment(4),
+            },
+        ))
+    }
+    /// Record in `encoder` `repetitions` sweeps, incrementing the sweep counter of the context between each of them. With the [Tempering], the exchanges are attempted after every [TemperingParameters::swap_every] sweeps.
+    pub fn step(
+        &mut self,
+        repetitions: usize,
+        device: &wgpu::Device,
+        encoder: &mut CommandEncoder,
+    ) {
+        let pipeline = &self.step_pipelines[self.parameters.rule.load(Ordering::Relaxed) as usize];
+        let words = self.width / self.precision.cells_per_word();
+        // The tiled kernels update a tile of TILE×TILE words per workgroup.
+        let workgroups = if self.tiled {
+            (
+                words.div_ceil(TILE),
+                self.height.div_ceil(TILE),
+                self.replicas,
+            )
+        } else {
+            (words, self.height, self.replicas)
+        };
+        let swap_every = self
+            .parameters
+            .tempering
+            .enable
+            .load(Ordering::Relaxed)
+            .then(|| {
+                self.parameters
+                    .tempering
+                    .swap_every
+                    .load(Ordering::Relaxed)
+                    .max(1)
+            });
+        let kawasaki = self.parameters.dynamics.load(Ordering::Relaxed) == DYNAMICS_KAWASAKI;
+        for r in 0..repetitions {
+            let sweep = self.sweep.wrapping_add(r as u32);
+            // The sweep counter is the only value of the context changing between the steps of a frame, so it is set by the driver at each dispatch when push constants are supported.
+            let constants = StepConstants { sweep };
+            let push_constants = if self.push_constants {
+                bytes_of(&constants)
+            } else {
+                &[]
+            };
+            if kawasaki {
+                // The classes of bonds are updated in place one after the other, see `kawasaki_step`.
+                let passes = KAWASAKI_PASSES as usize;
+                for pass in 0..KAWASAKI_PASSES {
+                    let (along, across) = if pass < KAWASAKI_PASSES / 2 {
+                        (self.width, self.height)
+                    } else {
+                        (self.height, self.width)
+                    };
+                    self.kawasaki_pipeline.dispatch_with_offsets(
+                        encoder,
+                        along / 4,
+                        across / 2,
+                        self.replicas,
+                        push_constants,
+                        self.timestamp_writes(
+                            Some(PASS_STEP),
+                            r * passes + pass as usize,
+                            repetitions * passes,
+                        ),
+                        &[&[self.ctx_ring.offset(), pass * self.kawasaki_stride]],
+                    );
+                }
+            } else {
+                pipeline.dispatch_with_offsets(
+                    encoder,
+                    workgroups.0,
+                    workgroups.1,
+                    workgroups.2,
+                    push_constants,
+                    self.timestamp_writes(Some(PASS_STEP), r, repetitions),
+                    &[&[self.ctx_ring.offset()]],
+                );
                 encoder.copy_buffer_to_buffer(
                     &self.new_vals_buffer,
                     0,
@@ -158,56 +1035,1581 @@ impl IsingPipeline {
                     0,
                     self.vals_buffer.size(),
                 );
-            },
-            repetitions,
-            &self.step_pipeline,
-        )
+            }
+            let next = sweep.wrapping_add(1);
+            if swap_every.is_some_and(|every| next % every == 0) {
+                // The energies of the replicas are computed from the domain walls and the magnetization of the new state.
+                self.dispatch(
+                    encoder,
+                    &self.bonds_pipeline,
+                    (self.width, self.height, self.replicas),
+                    None,
+                );
+                self.magnetization.dispatch(encoder);
+                self.domain_walls.dispatch(encoder);
+                let field = self
+                    .parameters
+                    .ctx(self.width, self.height, next, self.precision)
+                    .field();
+                self.tempering.exchange(
+                    &mut self.belt,
+                    device,
+                    encoder,
+                    next,
+                    self.coupling,
+                    field,
+                );
+            }
+            if !self.push_constants {
+                // The context of the next step is written after this one, the context of the first step being the one already written in the slot of the frame.
+                let ctx = self
+                    .parameters
+                    .ctx(self.width, self.height, next, self.precision);
+                self.ctx_ring.write(&mut self.belt, device, encoder, &ctx);
+                self.written_ctx = ctx;
+            }
+        }
+        self.sweep = self.sweep.wrapping_add(repetitions as u32);
+    }
+    /// Complete the operations which wait for the submission of the encoder of the previous frame: the mapping of the read backs and the recycling of the staging buffers.
+    fn submitted(&mut self) {
+        self.belt.recall();
+        self.tempering.submitted();
+        self.export_readback.submitted();
+        self.probe_readback.submitted();
+        if self.measure_encoded {
+            self.measure_encoded = false;
+            self.magnetization.map();
+            self.domain_walls.map();
+        }
+        if let Some(timer) = &mut self.timer {
+            timer.submitted();
+        }
+    }
+    /// Record in `encoder` the write of `ctx` into the next slot of `ctx_ring`, unless the current slot already holds it, which is the case as long as the parameters do not change: without push constants the steps of the previous frame left the context of the current sweep in the slot, and with push constants the sweep counter of the context is only read on the CPU, so it is not compared.
+    fn write_ctx(&mut self, device: &wgpu::Device, encoder: &mut CommandEncoder, ctx: IsingCtx) {
+        let mut compared = ctx;
+        if self.push_constants {
+            compared.sweep = self.written_ctx.sweep;
+        }
+        if bytes_of(&compared) == bytes_of(&self.written_ctx) {
+            return;
+        }
+        self.ctx_ring.advance();
+        self.ctx_ring.write(&mut self.belt, device, encoder, &ctx);
+        self.written_ctx = ctx;
+    }
+    /// Record in `encoder` the writes of the ladder of the [Tempering] if it is enabled and its temperatures changed.
+    fn update_ladder(&mut self, device: &wgpu::Device, encoder: &mut CommandEncoder) {
+        if self.parameters.tempering.enable.load(Ordering::Relaxed) {
+            let ladder = ladder(
+                self.parameters.tempering.low.load(),
+                self.parameters.tempering.high.load(),
+                self.replicas,
+            );
+            self.tempering
+                .set_ladder(&mut self.belt, device, encoder, ladder);
+        }
+    }
+    /// Follow the [IsingParameters] of the [History]: reallocate it when its capacity changed, and record in `encoder` the copies showing the selected frame while `paused`, or the live state otherwise.
+    fn show_history(&mut self, device: &wgpu::Device, encoder: &mut CommandEncoder, paused: bool) {
+        let capacity = self.parameters.history.capacity.load(Ordering::Relaxed) as usize;
+        if self.history.as_ref().map_or(0, History::capacity) != capacity {
+            // The live state is restored before the frames are dropped.
+            if let Some(history) = &mut self.history {
+                history.show(encoder, &self.vals_buffer, None);
+            }
+            let words = (self.width * self.height / self.precision.cells_per_word()) as u64;
+            self.history = match capacity {
+                0 => None,
+                capacity => match History::new(device, words * size_of::<u32>() as u64, capacity) {
+                    Ok(history) => Some(history),
+                    Err(err) => {
+                        log::error!("History disabled: {err}");
+                        self.parameters.history.capacity.store(0, Ordering::Relaxed);
+                        None
+                    }
+                },
+            };
+            self.recorded_sweep = self.sweep;
+            self.parameters.history.sweeps.lock().unwrap().clear();
+        }
+        if let Some(history) = &mut self.history {
+            let back = match self.parameters.history.shown.load(Ordering::Relaxed) {
+                shown if paused && shown > 0 => Some(shown as usize - 1),
+                _ => None,
+            };
+            history.show(encoder, &self.vals_buffer, back);
+        }
+    }
+    /// Record in `encoder` the current state of the first replica in the [History] if [HistoryParameters::every] sweeps have passed since the last recorded frame.
+    fn record_history(&mut self, encoder: &mut CommandEncoder) {
+        let every = self.parameters.history.every.load(Ordering::Relaxed).max(1);
+        if let Some(history) = &mut self.history {
+            if self.sweep.wrapping_sub(self.recorded_sweep) >= every {
+                history.record(encoder, &self.vals_buffer, self.sweep);
+                self.recorded_sweep = self.sweep;
+                *self.parameters.history.sweeps.lock().unwrap() = history.sweeps().collect();
+            }
+        }
+    }
+    /// Record in `encoder` the read back of the state of the first replica for a [Snapshot] at the current sweep.
+    fn request_snapshot(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut CommandEncoder,
+        series: bool,
+    ) {
+        let words = (self.width * self.height / self.precision.cells_per_word()) as u64;
+        let handle = self.export_readback.request(
+            device,
+            encoder,
+            &self.vals_buffer,
+            0..words * size_of::<u32>() as u64,
+        );
+        self.export_requests.push((handle, self.sweep, series));
+    }
+    /// Move the [Snapshot]s read back since the last call to [ExportParameters::snapshots].
+    fn poll_snapshots(&mut self, device: &wgpu::Device) {
+        for data in self.export_readback.poll(device) {
+            let Some(index) = self
+                .export_requests
+                .iter()
+                .position(|&(handle, _, _)| handle == data.handle)
+            else {
+                continue;
+            };
+            let (_, sweep, series) = self.export_requests.swap_remove(index);
+            self.parameters
+                .export
+                .snapshots
+                .lock()
+                .unwrap()
+                .push(Snapshot {
+                    sweep,
+                    width: self.width,
+                    height: self.height,
+                    vals: self.precision.unpack(&data.to_vec::<u32>()),
+                    series,
+                });
+        }
+    }
+    /// The probed `cell` followed by its left, right, bottom and top neighbors, with periodic boundaries.
+    fn probe_cells(&self, [x, y]: [u32; 2]) -> [[u32; 2]; PROBE_CELLS] {
+        let (width, height) = (self.width, self.height);
+        [
+            [x, y],
+            [(x + width - 1) % width, y],
+            [(x + 1) % width, y],
+            [x, (y + height - 1) % height],
+            [x, (y + 1) % height],
+        ]
+    }
+    /// Record in `encoder` the copies of the words holding the [probed](Physics::probe) cell and its neighbors in the first replica into the `probe_buffer`, and its read back, unless a read back is already in flight. The cell is ignored if it is outside of the lattice, which might have been resized.
+    fn request_probe(&mut self, device: &wgpu::Device, encoder: &mut CommandEncoder) {
+        let Some(cell) = self.probe_cell else {
+            return;
+        };
+        if self.probe_request.is_some() || cell[0] >= self.width || cell[1] >= self.height {
+            return;
+        }
+        let word = size_of::<u32>() as u64;
+        for (i, [x, y]) in self.probe_cells(cell).into_iter().enumerate() {
+            let index = (x + self.width * y) / self.precision.cells_per_word();
+            encoder.copy_buffer_to_buffer(
+                &self.vals_buffer,
+                index as u64 * word,
+                &self.probe_buffer,
+                i as u64 * word,
+                word,
+            );
+        }
+        let handle = self.probe_readback.request(
+            device,
+            encoder,
+            &self.probe_buffer,
+            0..self.probe_buffer.size(),
+        );
+        let field = self
+            .parameters
+            .ctx(self.width, self.height, self.sweep, self.precision)
+            .field();
+        self.probe_request = Some((handle, cell, self.sweep, field));
+    }
+    /// Unpack the [Probe] read back since the last call, whose energy is the one of the cell in the field of its neighbors and of the external field, `-s (J Σ s' + h)`.
+    fn poll_probe(&mut self, device: &wgpu::Device) {
+        let Some((handle, cell, sweep, field)) = self.probe_request else {
+            return;
+        };
+        let Some(data) = self
+            .probe_readback
+            .poll(device)
+            .into_iter()
+            .find(|data| data.handle == handle)
+        else {
+            return;
+        };
+        self.probe_request = None;
+        // The probe might have been stopped while the read back was in flight.
+        if self.probe_cell.is_none() {
+            return;
+        }
+        let words = data.to_vec::<u32>();
+        let cells_per_word = self.precision.cells_per_word();
+        let cells = self.probe_cells(cell);
+        // A word of packed cells holds the cell of even index in its low bits.
+        let [value, neighbors @ ..]: [f32; PROBE_CELLS] = std::array::from_fn(|i| {
+            let [x, y] = cells[i];
+            self.precision.unpack(&words[i..i + 1])
+                [((x + self.width * y) % cells_per_word) as usize]
+        });
+        self.probed = Some(Probe {
+            cell,
+            step: sweep as u64,
+            value,
+            neighbors,
+            energy: Some(-value * (self.coupling * neighbors.iter().sum::<f32>() + field)),
+        });
+    }
+    /// Read back the whole content of each of `buffers`, blocking until they are mapped.
+    fn read_buffers(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        buffers: &[&Buffer],
+    ) -> Vec<Vec<u8>> {
+        let readback = Readback::new();
+        let mut encoder = device.create_command_encoder(&Default::default());
+        let handles = buffers
+            .iter()
+            .map(|buffer| readback.request(device, &mut encoder, buffer, 0..buffer.size()))
+            .collect::<Vec<_>>();
+        queue.submit(Some(encoder.finish()));
+        readback.submitted();
+        let mut read = vec![];
+        while read.len() < handles.len() {
+            let _ = device.poll(wgpu::MaintainBase::Wait);
+            read.extend(readback.poll(device));
+        }
+        handles
+            .iter()
+            .map(|handle| {
+                let i = read.iter().position(|data| data.handle == *handle).unwrap();
+                read.swap_remove(i).bytes
+            })
+            .collect()
+    }
+    /// Size of the lattice, whose width might have been rounded down by [IsingPipeline::new].
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+    /// Perform `sweeps` sweeps with the current parameters outside of the frames of [Physics::update], without measuring nor recording the history, to discard the transient before the measurements. The sweeps are split into submissions of at most [RUN_CHUNK] sweeps so that a long thermalization does not hold the GPU in a single one, and the call blocks until they are done, except on the web where the device cannot be waited for.
+    pub fn thermalize(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, sweeps: usize) {
+        let mut remaining = sweeps;
+        loop {
+            let repetitions = remaining.min(RUN_CHUNK);
+            remaining -= repetitions;
+            self.submit_sweeps(device, queue, repetitions);
+            let _ = device.poll(wgpu::MaintainBase::Wait);
+            if remaining == 0 {
+                break;
+            }
+        }
+    }
+    /// Submit `sweeps` sweeps with the current parameters in a single submission, like [IsingPipeline::thermalize] but without waiting for them, so that several submissions are queued back to back. The benchmarks use it to measure the throughput of the steps rather than the latency of the synchronizations.
+    pub fn submit_sweeps(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, sweeps: usize) {
+        let ctx = self
+            .parameters
+            .ctx(self.width, self.height, self.sweep, self.precision);
+        let mut encoder = device.create_command_encoder(&Default::default());
+        self.write_ctx(device, &mut encoder, ctx);
+        self.update_ladder(device, &mut encoder);
+        self.step(sweeps, device, &mut encoder);
+        self.belt.finish();
+        queue.submit(Some(encoder.finish()));
+        self.submitted();
+    }
+    /// Perform `sweeps` sweeps with the current parameters outside of the frames of [Physics::update], for instance in a [headless](crate::headless) run, and block until the final values of the cells of the first replica (row by row) and the observables averaged over the replicas are read back.
+    pub fn run(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        sweeps: usize,
+    ) -> (Vec<f32>, IsingObservables) {
+        self.thermalize(device, queue, sweeps);
+        let mut encoder = device.create_command_encoder(&Default::default());
+        self.measure(device, &mut encoder);
+        let readback = Readback::new();
+        let words = (self.width * self.height / self.precision.cells_per_word()) as u64;
+        readback.request(
+            device,
+            &mut encoder,
+            &self.vals_buffer,
+            0..words * size_of::<u32>() as u64,
+        );
+        self.belt.finish();
+        queue.submit(Some(encoder.finish()));
+        self.submitted();
+        readback.submitted();
+        let _ = device.poll(wgpu::MaintainBase::Wait);
+        let vals = loop {
+            if let Some(data) = readback.poll(device).pop() {
+                break self.precision.unpack(&data.to_vec::<u32>());
+            }
+            let _ = device.poll(wgpu::MaintainBase::Wait);
+        };
+        let observables = loop {
+            // The encoder is left empty as the measurement has already been recorded.
+            let mut encoder = device.create_command_encoder(&Default::default());
+            if let Some((_, observables)) = self.measure(device, &mut encoder) {
+                break observables;
+            }
+            let _ = device.poll(wgpu::MaintainBase::Wait);
+        };
+        (vals, observables)
     }
 }
 
 impl Physics for IsingPipeline {
-    fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
-        let ctx = IsingCtx {
-            width: self.width,
-            height: self.height,
-            temperature: self.temperature.load(),
-            external_field: self.external_field.load(),
+    fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut CommandEncoder,
+        frame: &FrameInfo,
+    ) {
+        self.submitted();
+        let commands = self
+            .commands
+            .as_ref()
+            .map(CommandReceiver::drain)
+            .unwrap_or_default();
+        self.poll_snapshots(device);
+        self.poll_probe(device);
+        // The reset is recorded below, once the live state has been restored.
+        let mut reset = false;
+        let mut export = false;
+        for command in commands {
+            match command {
+                IsingCommand::Temperature(temperature) => {
+                    self.parameters.temperature.store(temperature)
+                }
+                IsingCommand::Field(field) => self.parameters.external_field.store(field),
+                IsingCommand::Reset => reset = true,
+                IsingCommand::LoadImage(source, mapping) => {
+                    self.image_requested = Some((source, mapping))
+                }
+                IsingCommand::Thermalize(sweeps) => {
+                    self.parameters
+                        .thermalizing
+                        .fetch_add(sweeps, Ordering::Relaxed);
+                }
+                IsingCommand::Export => export = true,
+            }
+        }
+        // The pipelines are rebuilt before any step.
+        let coupling = self.parameters.coupling.load();
+        let tiled = self.parameters.tiled.load(Ordering::Relaxed);
+        if coupling != self.coupling || tiled != self.tiled {
+            let step_pipelines = Self::new_step_pipelines(
+                device,
+                &self.shader_module,
+                &self.ctx_ring,
+                [
+                    &self.vals_buffer,
+                    &self.new_vals_buffer,
+                    &self.rngs_buffer,
+                    self.tempering.temperatures_buffer(),
+                ],
+                coupling,
+                [self.width, self.height],
+                self.push_constants,
+                tiled,
+            );
+            let kawasaki_pipeline = Self::new_kawasaki_pipeline(
+                device,
+                &self.shader_module,
+                &self.ctx_ring,
+                [
+                    &self.vals_buffer,
+                    &self.rngs_buffer,
+                    self.tempering.temperatures_buffer(),
+                ],
+                (&self.kawasaki_passes, self.kawasaki_stride),
+                coupling,
+                self.push_constants,
+            );
+            match step_pipelines.and_then(|step| Ok((step, kawasaki_pipeline?))) {
+                Ok((step_pipelines, kawasaki_pipeline)) => {
+                    self.step_pipelines = step_pipelines;
+                    self.kawasaki_pipeline = kawasaki_pipeline;
+                    self.coupling = coupling;
+                    self.tiled = tiled;
+                }
+                // The previous pipelines are kept, with the parameters they were built with.
+                Err(err) => log::error!("Failed to rebuild the step pipelines: {err}"),
+            }
+        }
+        let parameters = Arc::clone(&self.parameters);
+        let mut protocol = parameters.protocol.lock().unwrap();
+        let requested = protocol.running() && protocol.wants_measurement(self.sweep);
+        // The observables are not measured during the thermalization, whose transient they would average.
+        let thermalizing = parameters.thermalizing.load(Ordering::Relaxed) as usize;
+        let due = match parameters.measurements.every.load(Ordering::Relaxed) {
+            0 => false,
+            every => thermalizing == 0 && frame.frame_index % every as u64 == 0,
+        };
+        // A measurement in flight is polled every frame until it has been read back.
+        let measurement = if requested || due || self.measured_sweep.is_some() {
+            self.measure(device, encoder)
+        } else {
+            None
         };
-        queue.write_buffer(&self.ctx_buffer, 0, bytes_of(&ctx));
-        self.step(self.step_per_frames, device, queue);
+        let drive = parameters.drive.enable.load(Ordering::Relaxed);
+        if !drive {
+            parameters.drive.hysteresis.lock().unwrap().clear();
+        }
+        if let Some((_, observables)) = measurement {
+            *parameters.measurements.observables.lock().unwrap() = Some(observables);
+            if drive {
+                let mut hysteresis = parameters.drive.hysteresis.lock().unwrap();
+                hysteresis.push_back([observables.field, observables.magnetization]);
+                if hysteresis.len() > HYSTERESIS_POINTS {
+                    hysteresis.pop_front();
+                }
+            }
+            // The replicas are not at the same temperature with the tempering, nor in equilibrium with the drive.
+            let mut moments = parameters.measurements.moments.lock().unwrap();
+            if parameters.tempering.enable.load(Ordering::Relaxed) || drive {
+                moments.clear();
+            } else {
+                moments.push(
+                    parameters.temperature.load(),
+                    parameters.external_field.load(),
+                    &observables,
+                );
+            }
+        }
+        if let Some(rates) = self.tempering.poll(device) {
+            *parameters.tempering.exchange_rates.lock().unwrap() = self
+                .tempering
+                .ladder()
+                .windows(2)
+                .zip(rates)
+                .map(|(pair, rate)| ([pair[0], pair[1]], rate))
+                .collect();
+        }
+        if protocol.running() {
+            let measurement = measurement
+                .as_ref()
+                .filter(|(sweep, _)| protocol.wants_measurement(*sweep))
+                .map(|(sweep, o)| (*sweep, o));
+            protocol.update(self.sweep, &parameters, measurement);
+        }
+        drop(protocol);
 
-        // Automatically handle performance by looking at the time taken by an entire frame (aiming for 60 fps). Increase the number of steps per frames if the average time of the 10 last frames is bellow 0.017 (just above 0.016666=1/60), and decrease if the time exceeds 0.017*1.05. The gap between 0.017 and 0.017*1.05 is to avoible oscillations of the number of steps per frames.
-        self.time_history[self.current_time] = self.time.elapsed().as_secs_f32();
-        self.current_time += 1;
-        self.time = Instant::now();
-        let len = self.time_history.len();
-        if self.current_time == len {
-            self.current_time = 0;
-            let elapsed = self.time_history.iter().cloned().sum::<f32>() / len as f32;
-            let limit = 0.017;
-            if elapsed < limit {
-                self.step_per_frames = (self.step_per_frames + 1).min(10);
-            } else if elapsed > limit * 1.05 {
-                self.step_per_frames = (self.step_per_frames - 1).max(1);
+        let ctx = self
+            .parameters
+            .ctx(self.width, self.height, self.sweep, self.precision);
+        // The measurement above reads the slot of the previous frame, which holds the context of the current sweep.
+        self.write_ctx(device, encoder, ctx);
+        self.update_ladder(device, encoder);
+        // While paused, the frame of the history selected is shown instead of the live state, which is restored before the steps resume.
+        let paused = frame.paused;
+        self.show_history(device, encoder, paused);
+        // The reset follows the restoration of the live state, which it replaces.
+        if reset {
+            match self.parameters.init.load(Ordering::Relaxed) {
+                INIT_QUASI_RANDOM => self.quasi_random_requested = true,
+                init => self.record_reset(encoder, init),
+            }
+            self.parameters.measurements.moments.lock().unwrap().clear();
+        }
+        // The image is written in the queue before the encoder of the frame runs, so it waits until the live state is shown, which would otherwise be restored over it.
+        let live = self
+            .history
+            .as_ref()
+            .is_none_or(|history| history.shown().is_none());
+        if live && std::mem::take(&mut self.quasi_random_requested) {
+            self.write_quasi_random(queue);
+        }
+        let image = live.then(|| self.image_requested.take()).flatten();
+        if let Some((source, mapping)) = image {
+            match self.load_from_image(&source, &mapping, queue) {
+                Ok(()) => {
+                    log::info!("Loaded the state from {source}.");
+                    self.parameters.measurements.moments.lock().unwrap().clear();
+                }
+                Err(err) => log::error!("Failed to load the state from {source}: {err}"),
+            }
+        }
+        // Like the image, the thermalization waits until the live state is shown. Its chunk replaces the steps of the frame and is not counted by the tuner, and it stops at the steps imposed on the frame so that a replay reaches its events at the same sweeps.
+        let chunk = match frame.steps {
+            _ if !live => 0,
+            Some(steps) => thermalizing.min(RUN_CHUNK).min(steps as usize),
+            None => thermalizing.min(RUN_CHUNK),
+        };
+        if chunk > 0 {
+            self.step(chunk, device, encoder);
+            self.parameters
+                .thermalizing
+                .fetch_sub(chunk as u32, Ordering::Relaxed);
+            if chunk == thermalizing {
+                self.parameters.measurements.moments.lock().unwrap().clear();
+                log::info!("Thermalized up to sweep {}.", self.sweep);
+            }
+        } else if !paused {
+            // With the frame budget, the steps chosen by the tuner are bounded by the time per step measured on the previous frames, without waiting for the GPU, and the rest is carried over to the next frames.
+            let steps = match frame.steps {
+                Some(steps) => steps as usize,
+                None if self.parameters.tuner.frame_budget.load(Ordering::Relaxed) => {
+                    let steps = self.tuner.within_budget();
+                    self.tuner.performed(steps);
+                    steps
+                }
+                None => self.tuner.step_per_frames(),
+            };
+            self.step(steps, device, encoder);
+            self.record_history(encoder);
+            let every = self.parameters.export.every.load(Ordering::Relaxed);
+            if every > 0 && self.sweep.wrapping_sub(self.exported_sweep) >= every {
+                self.request_snapshot(device, encoder, true);
+                self.exported_sweep = self.sweep;
             }
         }
+        // The state exported is the one shown, which is a frame of the history while paused.
+        if export {
+            self.request_snapshot(device, encoder, false);
+        }
+        // Like the exports, the probe reads the state shown.
+        self.request_probe(device, encoder);
+        self.block(encoder, &ctx);
+        if due && self.parameters.tempering.enable.load(Ordering::Relaxed) {
+            self.tempering.request_rates(device, encoder);
+        }
+
+        if let Some(timer) = &mut self.timer {
+            timer.poll(device);
+            timer.resolve(device, encoder);
+        }
+        self.belt.finish();
+
+        self.tuner
+            .set_target_fps(self.parameters.tuner.target_fps.load());
+        self.tuner.set_bounds(
+            self.parameters.tuner.min_steps.load(Ordering::Relaxed) as usize,
+            self.parameters.tuner.max_steps.load(Ordering::Relaxed) as usize,
+        );
+        let step_time = self
+            .timer
+            .as_ref()
+            .and_then(|timer| timer.timing(PASS_STEP))
+            .filter(|_| self.parameters.tuner.gpu_time.load(Ordering::Relaxed));
+        match step_time {
+            _ if chunk > 0 => {}
+            Some(milliseconds) => self.tuner.frame(milliseconds as f32 * 1e-3),
+            None => self.tuner.frame(frame.dt),
+        }
+    }
+    fn steps(&self) -> Option<u64> {
+        Some(self.sweep as u64)
+    }
+    fn fragment_offsets(&self) -> Vec<u32> {
+        vec![self.ctx_ring.offset()]
+    }
+    fn gpu_timings(&self) -> Vec<(&'static str, f64)> {
+        self.timer
+            .as_ref()
+            .map(GpuTimer::timings)
+            .unwrap_or_default()
+    }
+    fn load_values(&mut self, queue: &wgpu::Queue, data: &[f32]) -> Result<(), WGPUError> {
+        self.write_vals(queue, data)
+    }
+    fn supports_reset(&self) -> bool {
+        true
+    }
+    /// Reset the spins with the initialization selected in the parameters, like the reset button of the [Ising](crate::simulation::ising::Ising) panel.
+    fn reset(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        // The reset reads the pinned rows from the context, which is written first in case they changed since the last frame.
+        let ctx = self
+            .parameters
+            .ctx(self.width, self.height, self.sweep, self.precision);
+        let mut encoder = device.create_command_encoder(&Default::default());
+        self.write_ctx(device, &mut encoder, ctx);
+        self.belt.finish();
+        queue.submit(Some(encoder.finish()));
+        self.submitted();
+        IsingPipeline::reset(
+            self,
+            device,
+            queue,
+            self.parameters.init.load(Ordering::Relaxed),
+        );
+        self.parameters.measurements.moments.lock().unwrap().clear();
+    }
+    fn supports_probe(&self) -> bool {
+        true
+    }
+    fn probe(&mut self, cell: Option<[u32; 2]>) {
+        if cell.is_none() {
+            self.probed = None;
+        }
+        self.probe_cell = cell;
+    }
+    fn probed(&self) -> Option<Probe> {
+        self.probed
+    }
+    fn observables(&self) -> Vec<Box<dyn Observable>> {
+        let observable = |name, value: fn(&IsingObservables) -> f64| -> Box<dyn Observable> {
+            Box::new(MeasuredObservable::new(
+                name,
+                Arc::clone(&self.parameters),
+                move |parameters: &IsingParameters| {
+                    parameters
+                        .measurements
+                        .observables
+                        .lock()
+                        .unwrap()
+                        .as_ref()
+                        .map(value)
+                },
+            ))
+        };
+        vec![
+            observable("magnetization", |o| o.magnetization),
+            observable("interface density", |o| o.interface_density),
+            observable("energy", |o| o.energy),
+            observable("Binder cumulant", IsingObservables::binder_cumulant),
+        ]
+    }
+    /// The state is read back by polling the device until it is mapped, which cannot be done on the web.
+    fn supports_state(&self) -> bool {
+        cfg!(not(target_arch = "wasm32"))
+    }
+    /// Read back the spins and the random number generators of every replica and serialize them after a header of [STATE_HEADER_WORDS] words (magic, version, sweep and number of replicas), the [IsingCtx] of the current parameters and the [STATE_PARAMETER_WORDS] words of the other parameters of the dynamics. Once loaded, the run continues from the same sweep with the same generators, and therefore draws the same random numbers as if it had never been interrupted. The state of the exchanges of the [Tempering] is not saved.
+    fn save_state(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Vec<u8>> {
+        let [vals, rngs] = self
+            .read_buffers(device, queue, &[&self.vals_buffer, &self.rngs_buffer])
+            .try_into()
+            .unwrap();
+        let parameters = &self.parameters;
+        let ctx = parameters.ctx(self.width, self.height, self.sweep, self.precision);
+        let header = [STATE_MAGIC, STATE_VERSION, self.sweep, self.replicas];
+        let dynamics: [u32; STATE_PARAMETER_WORDS] = [
+            parameters.rule.load(Ordering::Relaxed),
+            parameters.proposal.load(Ordering::Relaxed),
+            parameters.dynamics.load(Ordering::Relaxed),
+            parameters.init.load(Ordering::Relaxed),
+            parameters.coupling.load().to_bits(),
+            parameters.tiled.load(Ordering::Relaxed) as u32,
+        ];
+        let mut state = cast_slice(&header).to_vec();
+        state.extend_from_slice(bytes_of(&ctx));
+        state.extend_from_slice(cast_slice(&dynamics));
+        state.extend(vals);
+        state.extend(rngs);
+        Some(state)
+    }
+    /// Restore a state saved by [IsingPipeline::save_state] with the same size, number of replicas and precision: the spins, the random number generators, the sweep and the parameters of the dynamics (temperature, field, drive, pins, rule, proposal, dynamics, initialization, coupling and tiling). A state of another lattice is refused, the lattice being resized with the size of the canvas instead.
+    fn load_state(&mut self, queue: &wgpu::Queue, bytes: &[u8]) -> Result<(), WGPUError> {
+        let invalid = |reason: String| Err(WGPUError::InvalidState(reason));
+        let header_size = STATE_HEADER_WORDS * size_of::<u32>();
+        let ctx_end = header_size + size_of::<IsingCtx>();
+        let vals_offset = ctx_end + STATE_PARAMETER_WORDS * size_of::<u32>();
+        if bytes.len() < vals_offset {
+            return invalid(format!("{} bytes is too short", bytes.len()));
+        }
+        let header: [u32; STATE_HEADER_WORDS] = pod_read_unaligned(&bytes[..header_size]);
+        let [magic, version, sweep, replicas] = header;
+        if magic != STATE_MAGIC || version != STATE_VERSION {
+            return invalid("not a state of the Ising model of this version".to_string());
+        }
+        let ctx: IsingCtx = pod_read_unaligned(&bytes[header_size..ctx_end]);
+        let [rule, proposal, dynamics, init, coupling, tiled]: [u32; STATE_PARAMETER_WORDS] =
+            pod_read_unaligned(&bytes[ctx_end..vals_offset]);
+        let half = (self.precision == Precision::F16) as u32;
+        if (ctx.width, ctx.height, ctx.half, replicas)
+            != (self.width, self.height, half, self.replicas)
+        {
+            return invalid(format!(
+                "saved for {replicas} replicas of {}×{} with half = {}, not {} replicas of {}×{} with half = {half}",
+                ctx.width, ctx.height, ctx.half, self.replicas, self.width, self.height
+            ));
+        }
+        let (vals_size, rngs_size) = (self.vals_buffer.size(), self.rngs_buffer.size());
+        if (bytes.len() - vals_offset) as u64 != vals_size + rngs_size {
+            return invalid(format!(
+                "{} bytes of spins and generators instead of {}",
+                bytes.len() - vals_offset,
+                vals_size + rngs_size
+            ));
+        }
+        let (vals, rngs) = bytes[vals_offset..].split_at(vals_size as usize);
+        for buffer in [&self.vals_buffer, &self.new_vals_buffer] {
+            queue.write_buffer(buffer, 0, vals);
+        }
+        queue.write_buffer(&self.rngs_buffer, 0, rngs);
+        self.sweep = sweep;
+        let parameters = &self.parameters;
+        parameters.temperature.store(ctx.temperature);
+        parameters.external_field.store(ctx.external_field);
+        parameters
+            .drive
+            .enable
+            .store(ctx.drive_enable != 0, Ordering::Relaxed);
+        parameters.drive.amplitude.store(ctx.drive_amplitude);
+        parameters.drive.period.store(ctx.drive_period);
+        parameters.top_pin.store(ctx.top_pin);
+        parameters.bottom_pin.store(ctx.bottom_pin);
+        parameters.rule.store(rule, Ordering::Relaxed);
+        parameters.proposal.store(proposal, Ordering::Relaxed);
+        parameters.dynamics.store(dynamics, Ordering::Relaxed);
+        parameters.init.store(init, Ordering::Relaxed);
+        parameters.coupling.store(f32::from_bits(coupling));
+        parameters.tiled.store(tiled != 0, Ordering::Relaxed);
+        parameters.measurements.moments.lock().unwrap().clear();
+        Ok(())
     }
     fn wgpu_fragment_info(&self) -> FragmentInfo {
-        // The fragment shader kernel to render the value computed by the IsingPipeline is the function located in kernel/src/lib.rs called `ising_fragment`. It takes the context and values so `self.ctx_buffer` and `self.vals_buffer`.
+        // The fragment shader kernel to render the value computed by the IsingPipeline is the function located in kernel/src/lib.rs called `ising_fragment`. It takes the context and values so `self.ctx_ring` and `self.vals_buffer`, as well as the coarse-grained values `self.coarse_vals_buffer`.
         FragmentInfo {
             fragment_entry_point: "ising_fragment",
+            embedded: false,
             entries: vec![
                 FragmentEntry {
                     binding: 0,
-                    buffer: &self.ctx_buffer,
+                    buffer: self.ctx_ring.buffer(),
                     uniform: true,
+                    dynamic_size: Some(self.ctx_ring.binding_size()),
                 },
                 FragmentEntry {
                     binding: 1,
                     buffer: &self.vals_buffer,
                     uniform: false,
+                    dynamic_size: None,
+                },
+                FragmentEntry {
+                    binding: 2,
+                    buffer: &self.coarse_vals_buffer,
+                    uniform: false,
+                    dynamic_size: None,
                 },
             ],
         }
     }
 }
+
+/// Verify that the half precision storage halves the spin buffers and gives the same magnetization curve as the f32 storage for the same seed.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_half_precision() {
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    let seed = crate::seed::Seed::from_u64(1).0;
+    let curve = |precision| {
+        let parameters = Arc::new(IsingParameters::default());
+        let mut pipeline = IsingPipeline::new(
+            &device,
+            &queue,
+            &shader_module,
+            seed,
+            64,
+            64,
+            parameters,
+            precision,
+            1,
+        )
+        .unwrap();
+        let size = pipeline.vals_buffer.size();
+        let curve = (0..10)
+            .map(|_| {
+                let mut encoder = device.create_command_encoder(&Default::default());
+                pipeline.step(10, &device, &mut encoder);
+                pipeline.measure(&device, &mut encoder);
+                pipeline.belt.finish();
+                queue.submit(Some(encoder.finish()));
+                pipeline.submitted();
+                loop {
+                    let _ = device.poll(wgpu::MaintainBase::Wait);
+                    let mut encoder = device.create_command_encoder(&Default::default());
+                    if let Some((_, observables)) = pipeline.measure(&device, &mut encoder) {
+                        break observables.magnetization;
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        (size, curve)
+    };
+    let (size_f32, curve_f32) = curve(Precision::F32);
+    let (size_f16, curve_f16) = curve(Precision::F16);
+    assert_eq!(2 * size_f16, size_f32);
+    assert_eq!(curve_f16, curve_f32);
+}
+
+/// Verify that an uploaded state is read back unchanged from both spin buffers in both precisions, and that a state of the wrong size is rejected.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_write_vals_round_trip() {
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    for precision in [Precision::F32, Precision::F16] {
+        let mut pipeline = IsingPipeline::new(
+            &device,
+            &queue,
+            &shader_module,
+            0,
+            32,
+            16,
+            Arc::new(IsingParameters::default()),
+            precision,
+            1,
+        )
+        .unwrap();
+        let pattern = (0..32 * 16)
+            .map(|i| if (i * 7) % 5 < 2 { 1.0 } else { -1.0 })
+            .collect::<Vec<f32>>();
+        pipeline.load_values(&queue, &pattern).unwrap();
+        let readback = Readback::new();
+        let mut encoder = device.create_command_encoder(&Default::default());
+        for buffer in [&pipeline.vals_buffer, &pipeline.new_vals_buffer] {
+            readback.request(&device, &mut encoder, buffer, 0..buffer.size());
+        }
+        queue.submit(Some(encoder.finish()));
+        readback.submitted();
+        let mut read = 0;
+        while read < 2 {
+            let _ = device.poll(wgpu::MaintainBase::Wait);
+            for data in readback.poll(&device) {
+                assert_eq!(precision.unpack(&data.to_vec::<u32>()), pattern);
+                read += 1;
+            }
+        }
+        assert!(matches!(
+            pipeline.write_vals(&queue, &pattern[1..]),
+            Err(WGPUError::StateSizeMismatch { .. })
+        ));
+    }
+}
+
+/// Verify that a saved state is restored exactly, with its sweep and temperature, after the lattice was reset, and that a state of another lattice or a truncated one is rejected.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_save_load_state() {
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    let parameters = Arc::new(IsingParameters::default());
+    let new = |width| {
+        IsingPipeline::new(
+            &device,
+            &queue,
+            &shader_module,
+            3,
+            width,
+            16,
+            Arc::clone(&parameters),
+            Precision::F32,
+            2,
+        )
+        .unwrap()
+    };
+    let mut pipeline = new(32);
+    pipeline.run(&device, &queue, 20);
+    let state = pipeline.save_state(&device, &queue).unwrap();
+    let sweep = pipeline.sweep;
+    Physics::reset(&mut pipeline, &device, &queue);
+    pipeline.run(&device, &queue, 5);
+    parameters.temperature.store(4.0);
+    pipeline.load_state(&queue, &state).unwrap();
+    assert_eq!(pipeline.sweep, sweep);
+    assert_eq!(parameters.temperature.load(), 2.2691853142);
+    assert_eq!(pipeline.save_state(&device, &queue).unwrap(), state);
+
+    assert!(matches!(
+        new(48).load_state(&queue, &state),
+        Err(WGPUError::InvalidState(_))
+    ));
+    assert!(matches!(
+        pipeline.load_state(&queue, &state[..state.len() - 4]),
+        Err(WGPUError::InvalidState(_))
+    ));
+    assert!(matches!(
+        pipeline.load_state(&queue, &[0; 8]),
+        Err(WGPUError::InvalidState(_))
+    ));
+}
+
+/// Verify that a run restored from a saved state after being perturbed continues bitwise like the run which was never interrupted, the generators of the cells being restored with the spins.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_state_continues_run() {
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    let parameters = Arc::new(IsingParameters::default());
+    let mut pipeline = IsingPipeline::new(
+        &device,
+        &queue,
+        &shader_module,
+        7,
+        32,
+        24,
+        Arc::clone(&parameters),
+        Precision::F32,
+        2,
+    )
+    .unwrap();
+    pipeline.run(&device, &queue, 50);
+    let state = pipeline.save_state(&device, &queue).unwrap();
+    let (uninterrupted, _) = pipeline.run(&device, &queue, 100);
+
+    parameters.temperature.store(4.0);
+    parameters.rule.store(RULE_METROPOLIS, Ordering::Relaxed);
+    Physics::reset(&mut pipeline, &device, &queue);
+    pipeline.run(&device, &queue, 37);
+    pipeline.load_state(&queue, &state).unwrap();
+    assert_eq!(parameters.rule.load(Ordering::Relaxed), RULE_GLAUBER);
+    let (restored, _) = pipeline.run(&device, &queue, 100);
+    assert_eq!(
+        cast_slice::<f32, u32>(&restored),
+        cast_slice::<f32, u32>(&uninterrupted)
+    );
+}
+
+/// Verify that a thermalization requested by a command is spread over the next frames by chunks of [RUN_CHUNK] sweeps replacing their steps, giving the same state as an uninterrupted run of as many sweeps, and that it restarts the averages of the measurements.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_thermalize_command() {
+    use crate::gpu::command::command_channel;
+
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    let new_pipeline = |parameters: &Arc<IsingParameters>| {
+        IsingPipeline::new(
+            &device,
+            &queue,
+            &shader_module,
+            11,
+            32,
+            32,
+            Arc::clone(parameters),
+            Precision::F32,
+            1,
+        )
+        .unwrap()
+    };
+    let sweeps = 2 * RUN_CHUNK + 300;
+    let parameters = Arc::new(IsingParameters::default());
+    let (uninterrupted, _) = new_pipeline(&parameters).run(&device, &queue, sweeps);
+
+    let parameters = Arc::new(IsingParameters::default());
+    let (commands, receiver) = command_channel();
+    let mut pipeline = new_pipeline(&parameters).with_commands(receiver);
+    parameters.measurements.moments.lock().unwrap().push(
+        2.0,
+        0.0,
+        &IsingObservables {
+            magnetization: 0.5,
+            magnetization_error: 0.0,
+            interface_density: 0.25,
+            energy: -1.0,
+            field: 0.0,
+            magnetization_squared: 0.25,
+            magnetization_fourth: 0.0625,
+        },
+    );
+    commands.send(IsingCommand::Thermalize(sweeps as u32));
+    for frame_index in 1..=3 {
+        let mut encoder = device.create_command_encoder(&Default::default());
+        let frame = FrameInfo {
+            dt: 0.016,
+            frame_index,
+            paused: false,
+            steps: None,
+        };
+        pipeline.update(&device, &queue, &mut encoder, &frame);
+        queue.submit(Some(encoder.finish()));
+        let performed = (frame_index as usize * RUN_CHUNK).min(sweeps);
+        assert_eq!(pipeline.steps(), Some(performed as u64));
+        assert_eq!(
+            parameters.thermalizing.load(Ordering::Relaxed) as usize,
+            sweeps - performed
+        );
+    }
+    assert_eq!(
+        parameters
+            .measurements
+            .moments
+            .lock()
+            .unwrap()
+            .measurements(),
+        0
+    );
+    let (thermalized, _) = pipeline.run(&device, &queue, 0);
+    assert_eq!(
+        cast_slice::<f32, u32>(&thermalized),
+        cast_slice::<f32, u32>(&uninterrupted)
+    );
+}
+
+/// Verify that the state requested by an export is read back as a snapshot equal to the state of the lattice, and that the time series records a frame every [ExportParameters::every] sweeps.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_export_snapshots() {
+    use crate::gpu::command::command_channel;
+
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    let parameters = Arc::new(IsingParameters::default());
+    let (commands, receiver) = command_channel();
+    let mut pipeline = IsingPipeline::new(
+        &device,
+        &queue,
+        &shader_module,
+        5,
+        32,
+        16,
+        Arc::clone(&parameters),
+        Precision::F16,
+        2,
+    )
+    .unwrap()
+    .with_commands(receiver);
+    let (vals, _) = pipeline.run(&device, &queue, 20);
+    let mut update = |frame_index: u64, steps: u32| {
+        let mut encoder = device.create_command_encoder(&Default::default());
+        let frame = FrameInfo {
+            dt: 0.016,
+            frame_index,
+            paused: false,
+            steps: Some(steps),
+        };
+        pipeline.update(&device, &queue, &mut encoder, &frame);
+        queue.submit(Some(encoder.finish()));
+        let _ = device.poll(wgpu::MaintainBase::Wait);
+    };
+    commands.send(IsingCommand::Export);
+    update(1, 0);
+    parameters.export.every.store(5, Ordering::Relaxed);
+    for frame_index in 2..5 {
+        update(frame_index, 5);
+    }
+    parameters.export.every.store(0, Ordering::Relaxed);
+    // The read backs are mapped once their frame is submitted, and taken at the top of a later update.
+    for frame_index in 5..8 {
+        update(frame_index, 0);
+    }
+    let snapshots = std::mem::take(&mut *parameters.export.snapshots.lock().unwrap());
+    let [snapshot, frames @ ..] = snapshots.as_slice() else {
+        panic!("no snapshot read back");
+    };
+    assert!(!snapshot.series);
+    assert_eq!(
+        (snapshot.sweep, snapshot.width, snapshot.height),
+        (20, 32, 16)
+    );
+    assert_eq!(snapshot.vals, vals);
+    assert!(frames.iter().all(|frame| frame.series));
+    assert_eq!(
+        frames.iter().map(|frame| frame.sweep).collect::<Vec<_>>(),
+        [25, 30, 35]
+    );
+}
+
+/// Verify that the tiled step gives exactly the same states as the naive one for the same seed in both precisions, on a lattice whose size is not a multiple of the tile so that the workgroups on the borders are partial.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_tiled_step() {
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    let seed = crate::seed::Seed::from_u64(3).0;
+    let state = |precision, tiled| {
+        let parameters = Arc::new(IsingParameters::default());
+        parameters.tiled.store(tiled, Ordering::Relaxed);
+        let mut pipeline = IsingPipeline::new(
+            &device,
+            &queue,
+            &shader_module,
+            seed,
+            40,
+            24,
+            parameters,
+            precision,
+            2,
+        )
+        .unwrap();
+        let readback = Readback::new();
+        let mut encoder = device.create_command_encoder(&Default::default());
+        pipeline.step(25, &device, &mut encoder);
+        pipeline.belt.finish();
+        let buffer = &pipeline.vals_buffer;
+        readback.request(&device, &mut encoder, buffer, 0..buffer.size());
+        queue.submit(Some(encoder.finish()));
+        pipeline.submitted();
+        readback.submitted();
+        loop {
+            let _ = device.poll(wgpu::MaintainBase::Wait);
+            if let Some(data) = readback.poll(&device).pop() {
+                break precision.unpack(&data.to_vec::<u32>());
+            }
+        }
+    };
+    for precision in [Precision::F32, Precision::F16] {
+        assert_eq!(state(precision, true), state(precision, false));
+    }
+}
+
+/// Verify that each initialization of the resets gives the expected spins in both precisions, that a random reset still randomizes the spins after a deterministic one, and that the quasi-random reset uploads the spins computed on the host.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_reset_inits() {
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    for precision in [Precision::F32, Precision::F16] {
+        let pipeline = IsingPipeline::new(
+            &device,
+            &queue,
+            &shader_module,
+            0,
+            16,
+            8,
+            Arc::new(IsingParameters::default()),
+            precision,
+            1,
+        )
+        .unwrap();
+        let reset = |init| {
+            pipeline.reset(&device, &queue, init);
+            let readback = Readback::new();
+            let mut encoder = device.create_command_encoder(&Default::default());
+            let buffer = &pipeline.vals_buffer;
+            readback.request(&device, &mut encoder, buffer, 0..buffer.size());
+            queue.submit(Some(encoder.finish()));
+            readback.submitted();
+            loop {
+                let _ = device.poll(wgpu::MaintainBase::Wait);
+                if let Some(data) = readback.poll(&device).pop() {
+                    break precision.unpack(&data.to_vec::<u32>());
+                }
+            }
+        };
+        assert_eq!(reset(INIT_UP), vec![1.0; 16 * 8]);
+        assert_eq!(reset(INIT_DOWN), vec![-1.0; 16 * 8]);
+        let stripe = (0..8)
+            .flat_map(|y| [if (2..6).contains(&y) { 1.0 } else { -1.0 }; 16])
+            .collect::<Vec<f32>>();
+        assert_eq!(reset(INIT_STRIPE), stripe);
+        let random = reset(INIT_RANDOM);
+        assert!(random.iter().all(|&v| v == 1.0 || v == -1.0));
+        assert!(random.contains(&1.0) && random.contains(&-1.0));
+        let quasi_random = quasi_random_spins(16, 8, gpu_random::stream_key(0, !0));
+        assert_eq!(reset(INIT_QUASI_RANDOM), quasi_random);
+        assert_eq!(quasi_random.iter().sum::<f32>(), 0.0);
+    }
+}
+
+/// Verify that the Kawasaki dynamics conserves the magnetization of each replica exactly while changing the state, in both precisions and for sides which are not multiples of the classes of bonds.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_kawasaki_conservation() {
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    for precision in [Precision::F32, Precision::F16] {
+        for (width, height) in [(32, 32), (30, 18)] {
+            let parameters = Arc::new(IsingParameters::default());
+            parameters
+                .dynamics
+                .store(DYNAMICS_KAWASAKI, Ordering::Relaxed);
+            let mut pipeline = IsingPipeline::new(
+                &device,
+                &queue,
+                &shader_module,
+                crate::seed::Seed::from_u64(4).0,
+                width,
+                height,
+                parameters,
+                precision,
+                2,
+            )
+            .unwrap();
+            let read = |pipeline: &mut IsingPipeline, sweeps| {
+                let readback = Readback::new();
+                let mut encoder = device.create_command_encoder(&Default::default());
+                pipeline.step(sweeps, &device, &mut encoder);
+                pipeline.belt.finish();
+                let buffer = &pipeline.vals_buffer;
+                readback.request(&device, &mut encoder, buffer, 0..buffer.size());
+                queue.submit(Some(encoder.finish()));
+                pipeline.submitted();
+                readback.submitted();
+                loop {
+                    let _ = device.poll(wgpu::MaintainBase::Wait);
+                    if let Some(data) = readback.poll(&device).pop() {
+                        break precision.unpack(&data.to_vec::<u32>());
+                    }
+                }
+            };
+            let magnetizations = |state: &[f32]| {
+                state
+                    .chunks((width * height) as usize)
+                    .map(|replica| replica.iter().sum::<f32>())
+                    .collect::<Vec<_>>()
+            };
+            let before = read(&mut pipeline, 0);
+            let after = read(&mut pipeline, 50);
+            assert_ne!(before, after);
+            assert!(after.iter().all(|&v| v == 1.0 || v == -1.0));
+            assert_eq!(magnetizations(&before), magnetizations(&after));
+        }
+    }
+}
+
+/// Verify that a lattice larger than the limits of the device is rejected before its buffers are allocated.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_oversized_lattice() {
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    let max_cells = max_binding_size(&device.limits()) / 4;
+    let side = (max_cells as f64).sqrt() as u32 + 1;
+    let pipeline = IsingPipeline::new(
+        &device,
+        &queue,
+        &shader_module,
+        0,
+        side,
+        side,
+        Arc::new(IsingParameters::default()),
+        Precision::F32,
+        1,
+    );
+    assert!(matches!(
+        pipeline,
+        Err(WGPUError::BufferSizeOverflow(cells, 4)) if cells == side as usize * side as usize
+    ));
+}
+
+/// Verify that the context is only written in a new slot when it changed: not while paused with the same parameters, but as soon as a parameter is changed.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_ctx_written_when_changed() {
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    let parameters = Arc::new(IsingParameters::default());
+    let mut pipeline = IsingPipeline::new(
+        &device,
+        &queue,
+        &shader_module,
+        0,
+        32,
+        32,
+        Arc::clone(&parameters),
+        Precision::F32,
+        1,
+    )
+    .unwrap();
+    let mut frame_index = 0;
+    let mut frame = |pipeline: &mut IsingPipeline| {
+        let mut encoder = device.create_command_encoder(&Default::default());
+        let frame = FrameInfo {
+            dt: 0.016,
+            frame_index,
+            paused: true,
+            steps: None,
+        };
+        frame_index += 1;
+        pipeline.update(&device, &queue, &mut encoder, &frame);
+        queue.submit(Some(encoder.finish()));
+        pipeline.ctx_ring.offset()
+    };
+    let offset = frame(&mut pipeline);
+    assert_eq!(frame(&mut pipeline), offset);
+    assert_eq!(frame(&mut pipeline), offset);
+    parameters.temperature.store(1.5);
+    let changed = frame(&mut pipeline);
+    assert_ne!(changed, offset);
+    assert_eq!(frame(&mut pipeline), changed);
+    assert_eq!(pipeline.written_ctx.temperature, 1.5);
+}
+
+/// Verify that the replicas evolve independently: their magnetizations differ, so the ensemble average has a non zero standard error.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_replicas() {
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    let mut pipeline = IsingPipeline::new(
+        &device,
+        &queue,
+        &shader_module,
+        crate::seed::Seed::from_u64(2).0,
+        32,
+        32,
+        Arc::new(IsingParameters::default()),
+        Precision::F32,
+        8,
+    )
+    .unwrap();
+    assert_eq!(pipeline.replicas, 8);
+    let mut encoder = device.create_command_encoder(&Default::default());
+    pipeline.step(10, &device, &mut encoder);
+    pipeline.measure(&device, &mut encoder);
+    pipeline.belt.finish();
+    queue.submit(Some(encoder.finish()));
+    pipeline.submitted();
+    let observables = loop {
+        let _ = device.poll(wgpu::MaintainBase::Wait);
+        let mut encoder = device.create_command_encoder(&Default::default());
+        if let Some((_, observables)) = pipeline.measure(&device, &mut encoder) {
+            break observables;
+        }
+    };
+    assert!(observables.magnetization.abs() < 1.0);
+    assert!(observables.magnetization_error > 0.0);
+    assert!((0.0..=1.0).contains(&observables.interface_density));
+}
+
+/// Verify that the Binder cumulant is 2/3 when the magnetizations have the same magnitude, as deep in the ordered phase, and about 0 when they are Gaussian, as in the disordered phase.
+#[test]
+fn test_binder_cumulant() {
+    let observables = |magnetizations: &[f64]| {
+        let moment = |power| {
+            magnetizations
+                .iter()
+                .map(|m: &f64| m.powi(power))
+                .sum::<f64>()
+                / magnetizations.len() as f64
+        };
+        IsingObservables {
+            magnetization: moment(1),
+            magnetization_error: 0.0,
+            interface_density: 0.0,
+            energy: 0.0,
+            field: 0.0,
+            magnetization_squared: moment(2),
+            magnetization_fourth: moment(4),
+        }
+    };
+    let ordered = observables(&[0.98, -0.98, 0.98, 0.98]);
+    assert!((ordered.binder_cumulant() - 2.0 / 3.0).abs() < 1e-12);
+    // Gaussian magnetizations from a Box-Muller transform of a regular grid.
+    let n = 200;
+    let grid = (0..n).map(|i| (i as f64 + 0.5) / n as f64);
+    let gaussian = grid
+        .clone()
+        .flat_map(|u1| {
+            grid.clone()
+                .map(move |u2| 0.1 * (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos())
+        })
+        .collect::<Vec<_>>();
+    let mut moments = MagnetizationMoments::default();
+    assert_eq!(moments.binder_cumulant(), None);
+    for chunk in gaussian.chunks(n) {
+        moments.push(5.0, 0.0, &observables(chunk));
+    }
+    assert_eq!(moments.measurements(), n as u32);
+    assert!(moments.binder_cumulant().unwrap().abs() < 0.02);
+    // A measurement at another temperature restarts the moments.
+    moments.push(1.0, 0.0, &ordered);
+    assert_eq!(moments.measurements(), 1);
+    assert!((moments.binder_cumulant().unwrap() - 2.0 / 3.0).abs() < 1e-12);
+    moments.clear();
+    assert_eq!(moments.binder_cumulant(), None);
+}
+
+/// Verify that the Binder cumulant of the replicas tends to 2/3 deep in the ordered phase and to 0 deep in the disordered phase.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_binder_phases() {
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    let cumulant = |temperature: f32, init, measurements| {
+        let parameters = Arc::new(IsingParameters::default());
+        parameters.temperature.store(temperature);
+        let mut pipeline = IsingPipeline::new(
+            &device,
+            &queue,
+            &shader_module,
+            crate::seed::Seed::from_u64(3).0,
+            16,
+            16,
+            Arc::clone(&parameters),
+            Precision::F32,
+            64,
+        )
+        .unwrap();
+        pipeline.reset(&device, &queue, init);
+        let mut moments = MagnetizationMoments::default();
+        for _ in 0..measurements {
+            let (_, observables) = pipeline.run(&device, &queue, 20);
+            moments.push(temperature, 0.0, &observables);
+        }
+        moments.binder_cumulant().unwrap()
+    };
+    assert!((cumulant(1.0, INIT_UP, 1) - 2.0 / 3.0).abs() < 0.01);
+    assert!(cumulant(10.0, INIT_RANDOM, 20).abs() < 0.25);
+}
+
+/// Verify that both proposals sample the same equilibrium magnetization `tanh((J·n + h)/T)` of a spin whose neighbors sum to `n`, and that the resampling proposes the current value half of the time, so that it flips the spin half as often as the flip proposal.
+#[test]
+fn test_proposal_acceptance() {
+    use kernel::{PROPOSAL_FLIP, update_spin};
+
+    let sweeps = 200_000;
+    let parameters = IsingParameters::default();
+    parameters.temperature.store(2.0);
+    parameters.external_field.store(0.5);
+    let neighbors = 0.0;
+    let expected = ((neighbors + 0.5) / 2.0f32).tanh() as f64;
+    for rule in [RULE_GLAUBER, RULE_METROPOLIS] {
+        let run = |proposal| {
+            parameters.proposal.store(proposal, Ordering::Relaxed);
+            let mut rng = cell_rngs(1, 1).remove(0);
+            let (mut v, mut flips, mut sum) = (1.0, 0, 0.0);
+            for sweep in 0..sweeps {
+                let ctx = parameters.ctx(1, 1, sweep, Precision::F32);
+                let new = update_spin(&ctx, rule, 1.0, v, neighbors, &mut rng);
+                flips += (new != v) as u32;
+                sum += new as f64;
+                v = new;
+            }
+            (flips as f64 / sweeps as f64, sum / sweeps as f64)
+        };
+        let (resample_rate, resample_m) = run(PROPOSAL_RESAMPLE);
+        let (flip_rate, flip_m) = run(PROPOSAL_FLIP);
+        assert!(
+            (resample_rate / flip_rate - 0.5).abs() < 0.02,
+            "{resample_rate} {flip_rate}"
+        );
+        assert!((resample_m - expected).abs() < 0.02, "{resample_m}");
+        assert!((flip_m - expected).abs() < 0.02, "{flip_m}");
+    }
+}
+
+/// Verify that 500 sweeps of a 64×64 lattice from a fixed seed with the Metropolis rule end in the configuration stored in `ising/golden.txt`, by its hash, its number of spins up and its number of unsatisfied bonds, so that a refactoring of the kernels or of the pipeline cannot change the trajectories silently. It needs a GPU, so it is ignored by default and run with `cargo test golden -- --ignored`. After a deliberate change of the trajectories, the file is regenerated by running it with the environment variable `PHASE_UPDATE_GOLDEN` set.
+#[test]
+#[ignore]
+fn test_golden_trajectory() {
+    use crate::gpu::adapter::{create_shader_module, request_adapter, request_device};
+
+    let (width, height, sweeps) = (64, 64, 500);
+    let instance = wgpu::Instance::default();
+    let adapter = pollster::block_on(request_adapter(
+        &instance,
+        wgpu::PowerPreference::HighPerformance,
+    ))
+    .unwrap();
+    let (device, queue) = pollster::block_on(request_device(&adapter)).unwrap();
+    let shader_module = create_shader_module(&device);
+    let parameters = Arc::new(IsingParameters::default());
+    parameters.rule.store(RULE_METROPOLIS, Ordering::Relaxed);
+    let mut pipeline = IsingPipeline::new(
+        &device,
+        &queue,
+        &shader_module,
+        crate::seed::Seed::from_u64(1).0,
+        width,
+        height,
+        parameters,
+        Precision::F32,
+        1,
+    )
+    .unwrap();
+    let (vals, _) = pipeline.run(&device, &queue, sweeps);
+
+    let (w, h) = (width as usize, height as usize);
+    let up = vals.iter().filter(|&&v| v > 0.0).count();
+    let unsatisfied: usize = (0..w * h)
+        .map(|i| {
+            let (x, y) = (i % w, i / w);
+            [vals[(x + 1) % w + w * y], vals[x + w * ((y + 1) % h)]]
+                .iter()
+                .filter(|&&neighbor| neighbor != vals[i])
+                .count()
+        })
+        .sum();
+    let summary = format!(
+        "# {width}x{height} lattice after {sweeps} Metropolis sweeps from the seed 1, regenerate with: PHASE_UPDATE_GOLDEN=1 cargo test golden -- --ignored\n\
+        hash = {:016x}\n\
+        spins up = {up}\n\
+        unsatisfied bonds = {unsatisfied}\n",
+        super::state_hash(cast_slice(&vals))
+    );
+    let path = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/gpu/physics/ising/golden.txt"
+    );
+    if std::env::var_os("PHASE_UPDATE_GOLDEN").is_some() {
+        std::fs::write(path, summary).unwrap();
+        return;
+    }
+    assert_eq!(
+        summary,
+        std::fs::read_to_string(path).unwrap(),
+        "The trajectory changed, regenerate {path} if this is deliberate."
+    );
+}
+
+/// Verify that the probe reads back the same values as the whole lattice for cells on the edges, whose neighbors wrap around, in both halves of the packed words, and that its local energy follows from them.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_probe() {
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    let (width, height) = (32, 16);
+    let mut pipeline = IsingPipeline::new(
+        &device,
+        &queue,
+        &shader_module,
+        crate::seed::Seed::from_u64(5).0,
+        width,
+        height,
+        Arc::new(IsingParameters::default()),
+        Precision::F16,
+        1,
+    )
+    .unwrap();
+    let (vals, _) = pipeline.run(&device, &queue, 10);
+    let at = |x: u32, y: u32| vals[(x + width * y) as usize];
+    let field = pipeline
+        .parameters
+        .ctx(width, height, pipeline.sweep, pipeline.precision)
+        .field();
+    for [x, y] in [[0, 0], [31, 0], [0, 15], [31, 15], [16, 7], [17, 8]] {
+        pipeline.probe(Some([x, y]));
+        let mut encoder = device.create_command_encoder(&Default::default());
+        pipeline.request_probe(&device, &mut encoder);
+        queue.submit(Some(encoder.finish()));
+        pipeline.submitted();
+        let _ = device.poll(wgpu::MaintainBase::Wait);
+        pipeline.poll_probe(&device);
+        let probed = pipeline.probed().unwrap();
+        let neighbors = [
+            at((x + width - 1) % width, y),
+            at((x + 1) % width, y),
+            at(x, (y + height - 1) % height),
+            at(x, (y + 1) % height),
+        ];
+        assert_eq!(probed.cell, [x, y]);
+        assert_eq!(probed.step, 10);
+        assert_eq!(probed.value, at(x, y));
+        assert_eq!(probed.neighbors, neighbors);
+        let energy = -at(x, y) * (pipeline.coupling * neighbors.iter().sum::<f32>() + field);
+        assert_eq!(probed.energy, Some(energy));
+    }
+    pipeline.probe(None);
+    assert_eq!(pipeline.probed(), None);
+}
+
+/// Verify that the measurements record the field h(t) of the drive at their sweep, from which the hysteresis loop is plotted.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_driven_field_measured() {
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    let parameters = Arc::new(IsingParameters::default());
+    parameters.drive.enable.store(true, Ordering::Relaxed);
+    parameters.drive.amplitude.store(0.5);
+    parameters.drive.period.store(40.0);
+    let mut pipeline = IsingPipeline::new(
+        &device,
+        &queue,
+        &shader_module,
+        crate::seed::Seed::from_u64(6).0,
+        32,
+        32,
+        Arc::clone(&parameters),
+        Precision::F32,
+        1,
+    )
+    .unwrap();
+    for sweeps in [10, 20] {
+        let (_, observables) = pipeline.run(&device, &queue, sweeps);
+        let phase = 2.0 * std::f32::consts::PI * pipeline.sweep as f32 / 40.0;
+        assert!((observables.field - 0.5 * phase.sin() as f64).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,256 @@
+use bytemuck::{bytes_of, cast_slice};
+use kernel::{CellRng, TemperingCtx};
+use wgpu::{
+    Buffer, CommandEncoder,
+    util::{DeviceExt, StagingBelt},
+};
+
+use crate::gpu::{
+    physics::write_staged, pipeline::Pipeline, readback::Readback, reduction::Reduction,
+};
+
+/// Temperatures of a geometric ladder of `replicas` temperatures from `low` to `high`, which gives roughly uniform acceptance rates of the exchanges when the specific heat does not vary much.
+pub fn ladder(low: f32, high: f32, replicas: u32) -> Vec<f32> {
+    if replicas < 2 {
+        return vec![low];
+    }
+    (0..replicas)
+        .map(|k| low * (high / low).powf(k as f32 / (replicas - 1) as f32))
+        .collect()
+}
+
+/// Parallel tempering of the replicas of an [IsingPipeline](super::IsingPipeline): the replicas evolve at the temperatures of a ladder, and the `ising_tempering` kernel attempts to exchange the temperatures of the replicas at adjacent temperatures from their energies, without any read back. The acceptance rates of the exchanges are read back asynchronously with [Tempering::poll].
+pub struct Tempering {
+    pipeline: Pipeline,
+    ctx_buffer: Buffer,
+    ladder_buffer: Buffer,
+    /// Temperature of each replica, read by the step kernels.
+    temperatures_buffer: Buffer,
+    /// Number of attempts, replica at each temperature of the ladder, and number of accepted exchanges of each pair of adjacent temperatures.
+    state_buffer: Buffer,
+    /// Random number generator of the exchanges, kept alive for the bind group.
+    _rng_buffer: Buffer,
+    replicas: u32,
+    partials: [u32; 2],
+    cells: u32,
+    /// Ladder currently written on the GPU, empty until [Tempering::set_ladder] is called.
+    ladder: Vec<f32>,
+    readback: Readback,
+}
+
+impl Tempering {
+    /// Prepare the exchanges between `replicas` replicas of `cells` cells, whose energies are computed from the partial sums of the `magnetization` and of the `domain_walls`. The exchanges draw their random numbers from `rng`.
+    pub fn new(
+        device: &wgpu::Device,
+        shader_module: &wgpu::ShaderModule,
+        replicas: u32,
+        cells: u32,
+        rng: CellRng,
+        magnetization: &Reduction,
+        domain_walls: &Reduction,
+    ) -> Self {
+        let ctx_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Tempering ctx buffer"),
+            size: size_of::<TemperingCtx>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let ladder_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Tempering ladder buffer"),
+            size: replicas as u64 * size_of::<f32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let temperatures_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Tempering temperatures buffer"),
+            size: replicas as u64 * size_of::<f32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let state_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Tempering state buffer"),
+            size: (2 * replicas) as u64 * size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let rng_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Tempering rng buffer"),
+            contents: bytes_of(&rng),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let pipeline = Pipeline::new(
+            device,
+            shader_module,
+            "ising_tempering",
+            [
+                (0, &ctx_buffer, None, None),
+                (1, magnetization.partials_buffer(), Some(true), None),
+                (2, domain_walls.partials_buffer(), Some(true), None),
+                (3, &ladder_buffer, Some(true), None),
+                (4, &temperatures_buffer, Some(false), None),
+                (5, &state_buffer, Some(false), None),
+                (6, &rng_buffer, Some(false), None),
+            ],
+        );
+        Tempering {
+            pipeline,
+            ctx_buffer,
+            ladder_buffer,
+            temperatures_buffer,
+            state_buffer,
+            _rng_buffer: rng_buffer,
+            replicas,
+            partials: [magnetization.partials(), domain_walls.partials()],
+            cells,
+            ladder: vec![],
+            readback: Readback::new(),
+        }
+    }
+    /// Temperature of each replica, to be bound to the step kernels.
+    pub fn temperatures_buffer(&self) -> &Buffer {
+        &self.temperatures_buffer
+    }
+    /// Record in `encoder` the writes of a new `ladder` if it differs from the current one. The replicas then start again from the `k`-th replica at the `k`-th temperature, and the counts of the exchanges are reset.
+    pub fn set_ladder(
+        &mut self,
+        belt: &mut StagingBelt,
+        device: &wgpu::Device,
+        encoder: &mut CommandEncoder,
+        ladder: Vec<f32>,
+    ) {
+        if ladder == self.ladder || ladder.len() != self.replicas as usize {
+            return;
+        }
+        let state = [0]
+            .into_iter()
+            .chain(0..self.replicas)
+            .chain((1..self.replicas).map(|_| 0))
+            .collect::<Vec<u32>>();
+        write_staged(
+            belt,
+            device,
+            encoder,
+            &self.ladder_buffer,
+            0,
+            cast_slice(&ladder),
+        );
+        write_staged(
+            belt,
+            device,
+            encoder,
+            &self.temperatures_buffer,
+            0,
+            cast_slice(&ladder),
+        );
+        write_staged(
+            belt,
+            device,
+            encoder,
+            &self.state_buffer,
+            0,
+            cast_slice(&state),
+        );
+        self.ladder = ladder;
+    }
+    /// Temperatures of the current ladder.
+    pub fn ladder(&self) -> &[f32] {
+        &self.ladder
+    }
+    /// Record in `encoder` an attempt of exchange of each pair of adjacent temperatures, at `sweep` with the given `coupling` and `field`. The partial sums of the magnetization and of the domain walls of the current state must have been recorded before, see [Reduction::dispatch].
+    pub fn exchange(
+        &self,
+        belt: &mut StagingBelt,
+        device: &wgpu::Device,
+        encoder: &mut CommandEncoder,
+        sweep: u32,
+        coupling: f32,
+        field: f32,
+    ) {
+        let ctx = TemperingCtx {
+            replicas: self.replicas,
+            cells: self.cells,
+            magnetization_partials: self.partials[0],
+            walls_partials: self.partials[1],
+            coupling,
+            field,
+            sweep,
+            _padding: 0,
+        };
+        write_staged(belt, device, encoder, &self.ctx_buffer, 0, bytes_of(&ctx));
+        self.pipeline.dispatch(encoder, 1, 1, 1);
+    }
+    /// Record in `encoder` the read back of the counts of the exchanges. [Tempering::submitted] must be called once `encoder` has been submitted.
+    pub fn request_rates(&self, device: &wgpu::Device, encoder: &mut CommandEncoder) {
+        self.readback.request(
+            device,
+            encoder,
+            &self.state_buffer,
+            0..self.state_buffer.size(),
+        );
+    }
+    pub fn submitted(&self) {
+        self.readback.submitted();
+    }
+    /// Acceptance rate of the exchanges of each pair of adjacent temperatures of the ladder since it was set, once the counts requested by [Tempering::request_rates] have been read back.
+    pub fn poll(&self, device: &wgpu::Device) -> Option<Vec<f64>> {
+        let state = self.readback.poll(device).pop()?.to_vec::<u32>();
+        let attempts = state[0].max(1) as f64;
+        let accepted = &state[1 + self.replicas as usize..];
+        Some(accepted.iter().map(|&a| a as f64 / attempts).collect())
+    }
+}
+
+/// Verify that the ladder is geometric between its bounds.
+#[test]
+fn test_ladder() {
+    let temperatures = ladder(1.0, 4.0, 3);
+    assert_eq!(temperatures.len(), 3);
+    assert!((temperatures[0] - 1.0).abs() < 1e-6);
+    assert!((temperatures[1] - 2.0).abs() < 1e-6);
+    assert!((temperatures[2] - 4.0).abs() < 1e-6);
+    assert_eq!(ladder(2.0, 3.0, 1), vec![2.0]);
+}
+
+/// Verify that the exchanges between 4 replicas around the critical temperature are attempted and sometimes accepted.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_exchanges() {
+    use std::sync::{Arc, atomic::Ordering};
+
+    use super::{IsingParameters, IsingPipeline};
+    use crate::gpu::physics::Precision;
+
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    let parameters = Arc::new(IsingParameters::default());
+    parameters.tempering.enable.store(true, Ordering::Relaxed);
+    parameters.tempering.swap_every.store(1, Ordering::Relaxed);
+    let mut pipeline = IsingPipeline::new(
+        &device,
+        &queue,
+        &shader_module,
+        crate::seed::Seed::from_u64(3).0,
+        32,
+        32,
+        parameters,
+        Precision::F32,
+        4,
+    )
+    .unwrap();
+    pipeline.run(&device, &queue, 200);
+    let mut encoder = device.create_command_encoder(&Default::default());
+    pipeline.tempering.request_rates(&device, &mut encoder);
+    queue.submit(Some(encoder.finish()));
+    pipeline.tempering.submitted();
+    let rates = loop {
+        let _ = device.poll(wgpu::MaintainBase::Wait);
+        if let Some(rates) = pipeline.tempering.poll(&device) {
+            break rates;
+        }
+    };
+    assert_eq!(pipeline.tempering.ladder().len(), 4);
+    assert_eq!(rates.len(), 3);
+    assert!(rates.iter().all(|rate| (0.0..=1.0).contains(rate)));
+    assert!(rates.iter().any(|&rate| rate > 0.0));
+}
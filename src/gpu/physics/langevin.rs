@@ -0,0 +1,478 @@
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, AtomicU32, Ordering},
+};
+
+use bytemuck::bytes_of;
+use kernel::{CellRng, LangevinCtx, RangeCtx};
+use wgpu::{
+    Buffer, CommandEncoder,
+    util::{DeviceExt, StagingBelt},
+};
+
+use crate::{
+    error::WGPUError,
+    gpu::{
+        capabilities::check_lattice,
+        command::CommandReceiver,
+        pipeline::Pipeline,
+        reduction::{RangeReduction, Reduction, Stats},
+        step_tuner::StepTuner,
+        uniform_ring::UniformRing,
+    },
+    import::{ImageMapping, ImageSource, image_to_values},
+    simulation::atomic_f32::AtomicF32,
+};
+
+use super::{
+    FragmentEntry, FragmentInfo, FrameInfo, Physics, Precision, STAGING_CHUNK_SIZE, cell_rngs,
+};
+
+/// Parameters of the Langevin dynamics shared between the [Langevin](crate::simulation::langevin::Langevin) simulation which modifies them from the egui UI, and the [LangevinPipeline] which reads them every frame. See [LangevinCtx] for their meaning.
+pub struct LangevinParameters {
+    pub r: AtomicF32,
+    pub u: AtomicF32,
+    pub gamma: AtomicF32,
+    pub dt: AtomicF32,
+    pub noise: AtomicF32,
+    /// Framerate aimed for by the [StepTuner].
+    pub target_fps: AtomicF32,
+    /// Minimum number of steps per frame chosen by the [StepTuner].
+    pub min_steps: AtomicU32,
+    /// Maximum number of steps per frame chosen by the [StepTuner].
+    pub max_steps: AtomicU32,
+    /// Whether the colormap spans the current range of the field instead of the fixed range `[range_min, range_max]`, see [RangeCtx].
+    pub auto_scale: AtomicBool,
+    pub range_min: AtomicF32,
+    pub range_max: AtomicF32,
+    /// Whether the steps are suspended.
+    pub paused: AtomicBool,
+    /// Number of frames between two checks that the field is finite, 0 meaning that it is not checked. A check reads back the [Stats] of the field a few frames later without stalling the frame.
+    pub check_every: AtomicU32,
+    /// Stats of the field when the last check found a NaN or an infinite value, after which the steps are paused. `None` while the field is stable.
+    pub unstable: Mutex<Option<Stats>>,
+    /// Whether a state found finite by a check has been saved, which [LangevinCommand::RestoreStable] restores.
+    pub stable_saved: AtomicBool,
+}
+
+/// Commands of the [Langevin](crate::simulation::langevin::Langevin) simulation to its [LangevinPipeline], see [command_channel](crate::gpu::command::command_channel).
+#[derive(Clone, Debug, PartialEq)]
+pub enum LangevinCommand {
+    /// Draw a new initial field.
+    Reset,
+    /// Replace the field with the image, fitted into the lattice and mapped to the field, see [image_to_values].
+    LoadImage(ImageSource, ImageMapping),
+    /// Replace the field with the last state found finite by a check, see [LangevinParameters::check_every].
+    RestoreStable,
+}
+
+impl LangevinParameters {
+    /// Build the [LangevinCtx] corresponding to the current parameters, with the time step clamped to [LangevinCtx::max_dt] to avoid the divergence of the explicit integration.
+    fn ctx(&self, width: u32, height: u32, step: u32) -> LangevinCtx {
+        let mut ctx = LangevinCtx {
+            width,
+            height,
+            r: self.r.load(),
+            u: self.u.load(),
+            gamma: self.gamma.load(),
+            dt: self.dt.load(),
+            noise: self.noise.load(),
+            step,
+        };
+        ctx.dt = ctx.dt.min(ctx.max_dt());
+        ctx
+    }
+}
+
+/// Handles the compute pipeline for the Langevin dynamics of a continuous scalar field.
+pub struct LangevinPipeline {
+    /// Context of the kernels, written in a new slot each frame so that it does not wait for the rendering of the previous frame.
+    ctx_ring: UniformRing<LangevinCtx>,
+    /// Staging of the context written before each step in the encoder of the frame.
+    belt: StagingBelt,
+    reset_pipeline: Pipeline,
+    step_pipeline: Pipeline,
+    vals_buffer: Buffer,
+    new_vals_buffer: Buffer,
+    /// Range of the colormap of the fragment, a [RangeCtx].
+    range_buffer: Buffer,
+    range_reduction: RangeReduction,
+    /// Fixed range held by `range_buffer`, `None` when it holds the range computed by `range_reduction`.
+    written_range: Option<[f32; 2]>,
+    /// Stats of the field computed by the stability checks.
+    stats: Reduction,
+    /// Copy of the field at the last check, promoted to `stable_buffer` once the check found it finite.
+    checked_buffer: Buffer,
+    stable_buffer: Buffer,
+    /// Whether a check has been recorded and not read back yet, and whether it was recorded in the encoder of the previous frame, so that its read back can be mapped.
+    checking: bool,
+    check_encoded: bool,
+    /// Whether the check in flight was recorded before a reset or a restoration of the field, so that its result is ignored.
+    discard_check: bool,
+    /// Commands of the simulation, if any, see [LangevinPipeline::with_commands].
+    commands: Option<CommandReceiver<LangevinCommand>>,
+    width: u32,
+    height: u32,
+    parameters: Arc<LangevinParameters>,
+    step: u32,
+    tuner: StepTuner,
+}
+
+impl LangevinPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        shader_module: &wgpu::ShaderModule,
+        seed: u128,
+        width: u32,
+        height: u32,
+        parameters: Arc<LangevinParameters>,
+    ) -> Result<Self, WGPUError> {
+        let ctx = parameters.ctx(width, height, 0);
+        let ctx_ring = UniformRing::new(device, "Langevin ctx buffer", &ctx);
+
+        let count = check_lattice(
+            &device.limits(),
+            width,
+            height,
+            &[size_of::<f32>(), size_of::<CellRng>()],
+        )?;
+
+        let vals_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Langevin vals buffer"),
+            size: count as u64 * size_of::<f32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let new_vals_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Langevin new vals buffer"),
+            size: count as u64 * size_of::<f32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let snapshot_buffer = |label| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: count as u64 * size_of::<f32>() as u64,
+                usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+        let checked_buffer = snapshot_buffer("Langevin checked buffer");
+        let stable_buffer = snapshot_buffer("Langevin stable buffer");
+
+        let rngs = cell_rngs(seed, count);
+        let rngs_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Langevin rngs buffer"),
+            contents: bytemuck::cast_slice(&rngs),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let range = [parameters.range_min.load(), parameters.range_max.load()];
+        let range_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Langevin range buffer"),
+            contents: bytes_of(&RangeCtx {
+                min: range[0],
+                max: range[1],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let range_reduction =
+            RangeReduction::new(device, shader_module, &vals_buffer, count as u32)?;
+        let stats = Reduction::new_stats(
+            device,
+            shader_module,
+            &vals_buffer,
+            count as u32,
+            1,
+            Precision::F32,
+        );
+        *parameters.unstable.lock().unwrap() = None;
+        parameters.stable_saved.store(false, Ordering::Relaxed);
+
+        let tuner = StepTuner::new().with_bounds(
+            parameters.min_steps.load(Ordering::Relaxed) as usize,
+            parameters.max_steps.load(Ordering::Relaxed) as usize,
+        );
+
+        let p = LangevinPipeline {
+            reset_pipeline: Pipeline::new(
+                device,
+                shader_module,
+                "langevin_reset",
+                [
+                    ctx_ring.entry(0),
+                    (1, &vals_buffer, Some(false), None),
+                    (2, &rngs_buffer, Some(false), None),
+                ],
+            ),
+            step_pipeline: Pipeline::new(
+                device,
+                shader_module,
+                "langevin_step",
+                [
+                    ctx_ring.entry(0),
+                    (1, &vals_buffer, Some(true), None),
+                    (2, &new_vals_buffer, Some(false), None),
+                    (3, &rngs_buffer, Some(false), None),
+                ],
+            ),
+            ctx_ring,
+            belt: StagingBelt::new(STAGING_CHUNK_SIZE),
+            vals_buffer,
+            new_vals_buffer,
+            range_buffer,
+            range_reduction,
+            written_range: Some(range),
+            stats,
+            checked_buffer,
+            stable_buffer,
+            checking: false,
+            check_encoded: false,
+            discard_check: false,
+            commands: None,
+            width,
+            height,
+            parameters,
+            step: 0,
+            tuner,
+        };
+        p.reset(device, queue);
+        Ok(p)
+    }
+    /// Apply the `commands` at the top of each update, see [LangevinCommand].
+    pub fn with_commands(self, commands: CommandReceiver<LangevinCommand>) -> Self {
+        LangevinPipeline {
+            commands: Some(commands),
+            ..self
+        }
+    }
+    pub fn reset(&self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Langevin reset Encoder"),
+        });
+        self.record_reset(&mut encoder);
+        queue.submit(Some(encoder.finish()));
+    }
+    /// Record in `encoder` the drawing of a new initial field.
+    fn record_reset(&self, encoder: &mut CommandEncoder) {
+        self.reset_pipeline.dispatch_with_offsets(
+            encoder,
+            self.width,
+            self.height,
+            1,
+            &[],
+            None,
+            &[&[self.ctx_ring.offset()]],
+        );
+    }
+    /// Check that the field is finite every [LangevinParameters::check_every] frames. The [Stats] of the field are recorded in `encoder` along with a copy of the field, which is saved as the last stable state once the stats read back are finite. Otherwise the stats are reported in [LangevinParameters::unstable] and the steps are paused.
+    fn check_stability(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut CommandEncoder,
+        frame: &FrameInfo,
+    ) {
+        if self.checking {
+            let _ = device.poll(wgpu::MaintainBase::Poll);
+            if let Some(stats) = self.stats.try_read_stats() {
+                self.checking = false;
+                let stats = stats[0];
+                let discarded = std::mem::take(&mut self.discard_check);
+                if discarded {
+                    log::debug!("Discarded the stability check of a replaced field.");
+                } else if stats.is_finite() {
+                    encoder.copy_buffer_to_buffer(
+                        &self.checked_buffer,
+                        0,
+                        &self.stable_buffer,
+                        0,
+                        self.stable_buffer.size(),
+                    );
+                    self.parameters.stable_saved.store(true, Ordering::Relaxed);
+                } else {
+                    log::warn!(
+                        "The Langevin field diverged (min {}, max {}), the simulation is paused.",
+                        stats.min,
+                        stats.max
+                    );
+                    *self.parameters.unstable.lock().unwrap() = Some(stats);
+                    self.parameters.paused.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+        let due = match self.parameters.check_every.load(Ordering::Relaxed) {
+            0 => false,
+            every => frame.frame_index % every as u64 == 0,
+        };
+        if due && !self.checking && !frame.paused {
+            self.stats.encode(encoder);
+            encoder.copy_buffer_to_buffer(
+                &self.vals_buffer,
+                0,
+                &self.checked_buffer,
+                0,
+                self.checked_buffer.size(),
+            );
+            self.checking = true;
+            self.check_encoded = true;
+        }
+    }
+    /// Record in `encoder` one step of the dynamics. The ctx is written before each step so that the noise of each step is drawn from a different counter.
+    pub fn step(&mut self, device: &wgpu::Device, encoder: &mut CommandEncoder) {
+        let ctx = self.parameters.ctx(self.width, self.height, self.step);
+        self.ctx_ring.write(&mut self.belt, device, encoder, &ctx);
+        self.step_pipeline.dispatch_with_offsets(
+            encoder,
+            self.width,
+            self.height,
+            1,
+            &[],
+            None,
+            &[&[self.ctx_ring.offset()]],
+        );
+        encoder.copy_buffer_to_buffer(
+            &self.new_vals_buffer,
+            0,
+            &self.vals_buffer,
+            0,
+            self.vals_buffer.size(),
+        );
+        self.step = self.step.wrapping_add(1);
+    }
+}
+
+impl Physics for LangevinPipeline {
+    fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut CommandEncoder,
+        frame: &FrameInfo,
+    ) {
+        // The encoder of the previous frame has been submitted, so its staging buffers can be reused.
+        self.belt.recall();
+        if std::mem::take(&mut self.check_encoded) {
+            self.stats.map();
+        }
+        self.ctx_ring.advance();
+        // The reset and the fragment read the context of the current step even when no step is performed, for instance while paused.
+        let ctx = self.parameters.ctx(self.width, self.height, self.step);
+        self.ctx_ring.write(&mut self.belt, device, encoder, &ctx);
+        let commands = self
+            .commands
+            .as_ref()
+            .map(CommandReceiver::drain)
+            .unwrap_or_default();
+        for command in commands {
+            match command {
+                LangevinCommand::Reset => self.record_reset(encoder),
+                // The field is written in the queue, before the encoder of the frame.
+                LangevinCommand::LoadImage(source, mapping) => {
+                    let loaded = source.decode().map_err(WGPUError::from).and_then(|image| {
+                        let values = image_to_values(&image, self.width, self.height, &mapping);
+                        self.load_values(queue, &values)
+                    });
+                    match loaded {
+                        Ok(()) => log::info!("Loaded the field from {source}."),
+                        Err(err) => log::error!("Failed to load the field from {source}: {err}"),
+                    }
+                }
+                LangevinCommand::RestoreStable => {
+                    if self.parameters.stable_saved.load(Ordering::Relaxed) {
+                        encoder.copy_buffer_to_buffer(
+                            &self.stable_buffer,
+                            0,
+                            &self.vals_buffer,
+                            0,
+                            self.vals_buffer.size(),
+                        );
+                    }
+                }
+            }
+            *self.parameters.unstable.lock().unwrap() = None;
+            self.discard_check = self.checking;
+        }
+        self.check_stability(device, encoder, frame);
+        if !frame.paused {
+            let steps = frame
+                .steps
+                .map_or(self.tuner.step_per_frames(), |steps| steps as usize);
+            for _ in 0..steps {
+                self.step(device, encoder);
+            }
+        }
+        // The colormap spans the range of the field after the steps of the frame, or the fixed range which is only written when it changes.
+        if self.parameters.auto_scale.load(Ordering::Relaxed) {
+            self.range_reduction.copy_to(encoder, &self.range_buffer, 0);
+            self.written_range = None;
+        } else {
+            let range = [
+                self.parameters.range_min.load(),
+                self.parameters.range_max.load(),
+            ];
+            if self.written_range != Some(range) {
+                let ctx = RangeCtx {
+                    min: range[0],
+                    max: range[1],
+                };
+                queue.write_buffer(&self.range_buffer, 0, bytes_of(&ctx));
+                self.written_range = Some(range);
+            }
+        }
+        self.belt.finish();
+        self.tuner.set_target_fps(self.parameters.target_fps.load());
+        self.tuner.set_bounds(
+            self.parameters.min_steps.load(Ordering::Relaxed) as usize,
+            self.parameters.max_steps.load(Ordering::Relaxed) as usize,
+        );
+        self.tuner.frame(frame.dt);
+    }
+    fn steps(&self) -> Option<u64> {
+        Some(self.step as u64)
+    }
+    fn load_values(&mut self, queue: &wgpu::Queue, data: &[f32]) -> Result<(), WGPUError> {
+        let expected = (self.width * self.height) as usize;
+        if data.len() != expected {
+            return Err(WGPUError::StateSizeMismatch {
+                len: data.len(),
+                expected,
+            });
+        }
+        queue.write_buffer(&self.vals_buffer, 0, bytemuck::cast_slice(data));
+        Ok(())
+    }
+    fn fragment_offsets(&self) -> Vec<u32> {
+        vec![self.ctx_ring.offset()]
+    }
+    fn wgpu_fragment_info(&self) -> FragmentInfo {
+        // The fragment shader kernel is the function located in kernel/src/lib.rs called `langevin_fragment`, which takes the context, the values of the field and the range of the colormap.
+        FragmentInfo {
+            fragment_entry_point: "langevin_fragment",
+            embedded: false,
+            entries: vec![
+                FragmentEntry {
+                    binding: 0,
+                    buffer: self.ctx_ring.buffer(),
+                    uniform: true,
+                    dynamic_size: Some(self.ctx_ring.binding_size()),
+                },
+                FragmentEntry {
+                    binding: 1,
+                    buffer: &self.vals_buffer,
+                    uniform: false,
+                    dynamic_size: None,
+                },
+                FragmentEntry {
+                    binding: 2,
+                    buffer: &self.range_buffer,
+                    uniform: true,
+                    dynamic_size: None,
+                },
+            ],
+        }
+    }
+}
@@ -1,9 +1,172 @@
-use std::num::NonZero;
+use std::{collections::HashMap, num::NonZero};
+
+use crate::{
+    error::WGPUError,
+    gpu::entry_points::{check_entry_point, kernel_entry_points},
+};
+
+/// Entry of a bind group as `(binding, buffer, storage type, dynamic offset)`, see [Pipeline::new].
+pub type PipelineEntry<'a> = (u32, &'a wgpu::Buffer, Option<bool>, Option<u64>);
+
+/// Access of a kernel to a storage buffer, see [PipelineBuilder::storage].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    ReadOnly,
+    ReadWrite,
+}
+
+/// Builder of a [Pipeline] whose entries are added one by one, so that their number can be decided at run time, for instance for optional buffers.
+///
+/// The entries are added to the current bind group, the first one being `descriptor_set = 0` in the kernel, and [PipelineBuilder::bind_group] starts the next one. [PipelineBuilder::build] validates the entries before creating the pipeline:
+/// ```ignore
+/// let pipeline = PipelineBuilder::new(device, shader_module, "entry")
+///     .uniform(0, &ctx)
+///     .storage(1, &vals, Access::ReadWrite)
+///     .dynamic_storage(2, &big, Access::ReadOnly, chunk)
+///     .build()?;
+/// ```
+pub struct PipelineBuilder<'a> {
+    device: &'a wgpu::Device,
+    shader_module: &'a wgpu::ShaderModule,
+    name: &'a str,
+    /// Entry points of `shader_module`, the ones of the embedded kernels if `None`.
+    entry_points: Option<&'a [String]>,
+    groups: Vec<Vec<PipelineEntry<'a>>>,
+    constants: Vec<(String, f64)>,
+    push_constant_size: u32,
+}
+
+impl<'a> PipelineBuilder<'a> {
+    /// Start the pipeline of the entry point `name` of `shader_module`, without entries.
+    pub fn new(
+        device: &'a wgpu::Device,
+        shader_module: &'a wgpu::ShaderModule,
+        name: &'a str,
+    ) -> Self {
+        PipelineBuilder {
+            device,
+            shader_module,
+            name,
+            entry_points: None,
+            groups: vec![vec![]],
+            constants: vec![],
+            push_constant_size: 0,
+        }
+    }
+    /// Check the name against the `entry_points` of a module loaded at run time, see [KernelModule](super::kernel_module::KernelModule), instead of the ones of the embedded kernels.
+    pub fn entry_points(mut self, entry_points: &'a [String]) -> Self {
+        self.entry_points = Some(entry_points);
+        self
+    }
+    /// Add `entry` to the current bind group, for instance the entry of a [UniformRing](super::uniform_ring::UniformRing).
+    pub fn entry(mut self, entry: PipelineEntry<'a>) -> Self {
+        self.groups.last_mut().unwrap().push(entry);
+        self
+    }
+    /// Bind the whole `buffer` as a uniform at `binding`.
+    pub fn uniform(self, binding: u32, buffer: &'a wgpu::Buffer) -> Self {
+        self.entry((binding, buffer, None, None))
+    }
+    /// Bind chunks of `chunk` bytes of `buffer` as a uniform at `binding`, the chunk being selected by the dynamic offset given to [Pipeline::dispatch_with_offsets].
+    pub fn dynamic_uniform(self, binding: u32, buffer: &'a wgpu::Buffer, chunk: u64) -> Self {
+        self.entry((binding, buffer, None, Some(chunk)))
+    }
+    /// Bind the whole `buffer` as a storage buffer at `binding` with the `access` of the kernel.
+    pub fn storage(self, binding: u32, buffer: &'a wgpu::Buffer, access: Access) -> Self {
+        self.entry((binding, buffer, Some(access == Access::ReadOnly), None))
+    }
+    /// Bind chunks of `chunk` bytes of `buffer` as a storage buffer at `binding` with the `access` of the kernel, the chunk being selected by the dynamic offset given to [Pipeline::dispatch_with_offsets].
+    pub fn dynamic_storage(
+        self,
+        binding: u32,
+        buffer: &'a wgpu::Buffer,
+        access: Access,
+        chunk: u64,
+    ) -> Self {
+        self.entry((
+            binding,
+            buffer,
+            Some(access == Access::ReadOnly),
+            Some(chunk),
+        ))
+    }
+    /// Start the next bind group, the following entries being in `descriptor_set = i + 1` in the kernel if the current one is `i`.
+    pub fn bind_group(mut self) -> Self {
+        self.groups.push(vec![]);
+        self
+    }
+    /// Set the specialization constant with the id `id` to `value`.
+    pub fn constant(mut self, id: u32, value: f64) -> Self {
+        self.constants.push((id.to_string(), value));
+        self
+    }
+    /// Declare `size` bytes of push constants, see [Pipeline::with_push_constants].
+    pub fn push_constants(mut self, size: u32) -> Self {
+        self.push_constant_size = size;
+        self
+    }
+    /// Validate the entry point with [check_entry_point] and the entries with [check_entries], and create the pipeline.
+    pub fn build(self) -> Result<Pipeline, WGPUError> {
+        check_entry_point(
+            self.entry_points.unwrap_or(kernel_entry_points()),
+            self.name,
+        )?;
+        check_entries(
+            &self
+                .groups
+                .iter()
+                .map(|entries| {
+                    entries
+                        .iter()
+                        .map(|&(binding, buffer, _, chunk)| (binding, chunk, buffer.size()))
+                        .collect()
+                })
+                .collect::<Vec<_>>(),
+        )?;
+        let groups = self.groups.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let constants = self
+            .constants
+            .iter()
+            .map(|(id, value)| (id.as_str(), *value))
+            .collect::<Vec<_>>();
+        Ok(Pipeline::create(
+            self.device,
+            self.shader_module,
+            self.name,
+            &groups,
+            &constants,
+            self.push_constant_size,
+        ))
+    }
+}
+
+/// Check the entries of each bind group given as `(binding, dynamic chunk, size of the buffer)`: a binding must appear once per bind group, and a dynamic chunk must be non zero and fit in its buffer, which wgpu would otherwise only report as a validation error or a panic.
+pub fn check_entries(groups: &[Vec<(u32, Option<u64>, u64)>]) -> Result<(), WGPUError> {
+    for (group, entries) in groups.iter().enumerate() {
+        for (k, &(binding, chunk, buffer)) in entries.iter().enumerate() {
+            if entries[..k].iter().any(|&(other, _, _)| other == binding) {
+                return Err(WGPUError::DuplicateBinding { group, binding });
+            }
+            if let Some(size) = chunk.filter(|&size| size == 0 || size > buffer) {
+                return Err(WGPUError::InvalidChunkSize {
+                    group,
+                    binding,
+                    size,
+                    buffer,
+                });
+            }
+        }
+    }
+    Ok(())
+}
 
 /// Convenient wrapper for ComputePipeline with default parameters.
 pub struct Pipeline {
     pub pipeline: wgpu::ComputePipeline,
-    pub bind_group: wgpu::BindGroup,
+    /// Bind groups, set at the index of their position.
+    pub bind_groups: Vec<wgpu::BindGroup>,
+    /// Size in bytes of the push constants of the entry point, 0 if it has none.
+    pub push_constant_size: u32,
     pub name: String,
 }
 
@@ -13,49 +176,85 @@ impl Pipeline {
         device: &wgpu::Device,
         shader_module: &wgpu::ShaderModule,
         name: &str,
-        entries: [(u32, &wgpu::Buffer, Option<bool>, Option<u64>); N],
+        entries: [PipelineEntry; N],
     ) -> Self {
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some(&format!("{name} Bind Group Layout")),
-            entries: &entries.map(|(binding, _, read_only, has_dynamic_offset)| {
-                wgpu::BindGroupLayoutEntry {
-                    binding,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: if let Some(read_only) = read_only {
-                            wgpu::BufferBindingType::Storage { read_only }
-                        } else {
-                            wgpu::BufferBindingType::Uniform
-                        },
-                        has_dynamic_offset: has_dynamic_offset.is_some(),
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }
-            }),
-        });
-
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some(&format!("{name} Bind Group")),
-            layout: &bind_group_layout,
-            entries: &entries.map(|(binding, buffer, _, size)| wgpu::BindGroupEntry {
-                binding,
-                resource: if let Some(size) = size {
-                    wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                        buffer,
-                        offset: 0,
-                        size: Some(NonZero::new(size as u64).unwrap()),
-                    })
-                } else {
-                    buffer.as_entire_binding()
-                },
-            }),
-        });
+        Self::with_constants(device, shader_module, name, entries, &[])
+    }
+    /// Same as [Pipeline::new] with the values of the specialization constants of the entry point given as `(id, value)`, the id being the decimal number of the constant.
+    pub fn with_constants<const N: usize>(
+        device: &wgpu::Device,
+        shader_module: &wgpu::ShaderModule,
+        name: &str,
+        entries: [PipelineEntry; N],
+        constants: &[(&str, f64)],
+    ) -> Self {
+        Self::with_bind_groups(device, shader_module, name, &[&entries[..]], constants)
+    }
+    /// Same as [Pipeline::with_constants] with several bind groups, the entries of the bind group `i` being `groups[i]` (`descriptor_set = i` in the kernel). This allows to separate the uniforms from the storage buffers, or to stay below the limit of bindings per group.
+    pub fn with_bind_groups(
+        device: &wgpu::Device,
+        shader_module: &wgpu::ShaderModule,
+        name: &str,
+        groups: &[&[PipelineEntry]],
+        constants: &[(&str, f64)],
+    ) -> Self {
+        Self::with_push_constants(device, shader_module, name, groups, constants, 0)
+    }
+    /// Same as [Pipeline::with_bind_groups] for an entry point taking `push_constant_size` bytes of push constants, which are given to [Pipeline::dispatch_with_push_constants]. The device must have been created with the `PUSH_CONSTANTS` feature and a large enough limit, see [Pipeline::push_constants_supported].
+    ///
+    /// This is a wrapper of [PipelineBuilder] for a number of entries known at compile time, which panics if the entries are invalid.
+    pub fn with_push_constants(
+        device: &wgpu::Device,
+        shader_module: &wgpu::ShaderModule,
+        name: &str,
+        groups: &[&[PipelineEntry]],
+        constants: &[(&str, f64)],
+        push_constant_size: u32,
+    ) -> Self {
+        let mut builder = PipelineBuilder::new(device, shader_module, name);
+        for (i, entries) in groups.iter().enumerate() {
+            if i > 0 {
+                builder = builder.bind_group();
+            }
+            for &entry in entries.iter() {
+                builder = builder.entry(entry);
+            }
+        }
+        for &(id, value) in constants {
+            builder.constants.push((id.to_string(), value));
+        }
+        builder
+            .push_constants(push_constant_size)
+            .build()
+            .unwrap_or_else(|err| panic!("Invalid entries of the pipeline {name}: {err}"))
+    }
+    /// Create the pipeline of the entry point `name` with the bind groups `groups`, the specialization `constants` and `push_constant_size` bytes of push constants, the entries having been validated.
+    fn create(
+        device: &wgpu::Device,
+        shader_module: &wgpu::ShaderModule,
+        name: &str,
+        groups: &[&[PipelineEntry]],
+        constants: &[(&str, f64)],
+        push_constant_size: u32,
+    ) -> Self {
+        let (bind_group_layouts, bind_groups): (Vec<_>, Vec<_>) = groups
+            .iter()
+            .enumerate()
+            .map(|(i, entries)| Self::bind_group(device, &format!("{name} {i}"), entries))
+            .unzip();
 
+        let push_constant_range = wgpu::PushConstantRange {
+            stages: wgpu::ShaderStages::COMPUTE,
+            range: 0..push_constant_size,
+        };
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some(&format!("{name} Pipeline Layout")),
-            bind_group_layouts: &[&bind_group_layout],
-            push_constant_ranges: &[],
+            bind_group_layouts: &bind_group_layouts.iter().collect::<Vec<_>>(),
+            push_constant_ranges: if push_constant_size > 0 {
+                std::slice::from_ref(&push_constant_range)
+            } else {
+                &[]
+            },
         });
 
         let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
@@ -63,13 +262,154 @@ impl Pipeline {
             layout: Some(&pipeline_layout),
             module: shader_module,
             entry_point: Some(name),
-            compilation_options: Default::default(),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &constants
+                    .iter()
+                    .map(|&(id, value)| (id.to_string(), value))
+                    .collect::<HashMap<_, _>>(),
+                ..Default::default()
+            },
             cache: None,
         });
         Pipeline {
             pipeline,
-            bind_group,
+            bind_groups,
+            push_constant_size,
             name: name.to_string(),
         }
     }
+    /// Whether `device` supports push constants of `size` bytes. Otherwise the values must be given through a uniform buffer.
+    pub fn push_constants_supported(device: &wgpu::Device, size: u32) -> bool {
+        device.features().contains(wgpu::Features::PUSH_CONSTANTS)
+            && device.limits().max_push_constant_size >= size
+    }
+    /// Create the layout and the bind group of `entries`.
+    fn bind_group(
+        device: &wgpu::Device,
+        name: &str,
+        entries: &[PipelineEntry],
+    ) -> (wgpu::BindGroupLayout, wgpu::BindGroup) {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&format!("{name} Bind Group Layout")),
+            entries: &entries
+                .iter()
+                .map(
+                    |&(binding, _, read_only, has_dynamic_offset)| wgpu::BindGroupLayoutEntry {
+                        binding,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: if let Some(read_only) = read_only {
+                                wgpu::BufferBindingType::Storage { read_only }
+                            } else {
+                                wgpu::BufferBindingType::Uniform
+                            },
+                            has_dynamic_offset: has_dynamic_offset.is_some(),
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                )
+                .collect::<Vec<_>>(),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{name} Bind Group")),
+            layout: &bind_group_layout,
+            entries: &entries
+                .iter()
+                .map(|&(binding, buffer, _, size)| wgpu::BindGroupEntry {
+                    binding,
+                    resource: if let Some(size) = size {
+                        wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer,
+                            offset: 0,
+                            size: Some(NonZero::new(size).unwrap()),
+                        })
+                    } else {
+                        buffer.as_entire_binding()
+                    },
+                })
+                .collect::<Vec<_>>(),
+        });
+        (bind_group_layout, bind_group)
+    }
+    /// Record in `encoder` a compute pass running the pipeline on `(x, y, z)` workgroups.
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder, x: u32, y: u32, z: u32) {
+        self.dispatch_with_push_constants(encoder, x, y, z, &[]);
+    }
+    /// Same as [Pipeline::dispatch] with the `push_constants` set for this pass, which must be [Pipeline::push_constant_size] bytes long if the entry point has push constants.
+    pub fn dispatch_with_push_constants(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        x: u32,
+        y: u32,
+        z: u32,
+        push_constants: &[u8],
+    ) {
+        self.dispatch_timed(encoder, x, y, z, push_constants, None);
+    }
+    /// Same as [Pipeline::dispatch_with_push_constants] with the `timestamp_writes` of the pass, see [GpuTimer](super::timing::GpuTimer).
+    pub fn dispatch_timed(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        x: u32,
+        y: u32,
+        z: u32,
+        push_constants: &[u8],
+        timestamp_writes: Option<wgpu::ComputePassTimestampWrites>,
+    ) {
+        self.dispatch_with_offsets(encoder, x, y, z, push_constants, timestamp_writes, &[]);
+    }
+    /// Same as [Pipeline::dispatch_timed] with the `dynamic_offsets` of the entries with a dynamic offset of each bind group, in the order of their bindings. A bind group without entry in `dynamic_offsets` has no dynamic offset.
+    #[allow(clippy::too_many_arguments)]
+    pub fn dispatch_with_offsets(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        x: u32,
+        y: u32,
+        z: u32,
+        push_constants: &[u8],
+        timestamp_writes: Option<wgpu::ComputePassTimestampWrites>,
+        dynamic_offsets: &[&[u32]],
+    ) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some(&format!("{} Pass", self.name)),
+            timestamp_writes,
+        });
+        compute_pass.set_pipeline(&self.pipeline);
+        for (i, bind_group) in self.bind_groups.iter().enumerate() {
+            let offsets = dynamic_offsets.get(i).copied().unwrap_or_default();
+            compute_pass.set_bind_group(i as u32, bind_group, offsets);
+        }
+        if self.push_constant_size > 0 {
+            compute_pass.set_push_constants(0, push_constants);
+        }
+        compute_pass.dispatch_workgroups(x, y, z);
+    }
+}
+
+/// Verify that a binding repeated in a bind group and the dynamic chunks which are empty or larger than their buffer are rejected, and that the same binding is accepted in different bind groups.
+#[test]
+fn test_check_entries() {
+    assert!(check_entries(&[vec![(0, Some(16), 64), (1, None, 8)], vec![(0, None, 8)]]).is_ok());
+    assert!(matches!(
+        check_entries(&[vec![(0, None, 8), (2, None, 8), (0, None, 8)]]),
+        Err(WGPUError::DuplicateBinding {
+            group: 0,
+            binding: 0
+        })
+    ));
+    assert!(matches!(
+        check_entries(&[vec![], vec![(3, Some(0), 8)]]),
+        Err(WGPUError::InvalidChunkSize {
+            group: 1,
+            binding: 3,
+            size: 0,
+            buffer: 8
+        })
+    ));
+    assert!(matches!(
+        check_entries(&[vec![(1, Some(16), 8)]]),
+        Err(WGPUError::InvalidChunkSize { size: 16, .. })
+    ));
 }
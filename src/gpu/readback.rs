@@ -0,0 +1,252 @@
+use std::{
+    ops::Range,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+};
+
+use bytemuck::Pod;
+use wgpu::{Buffer, CommandEncoder};
+
+/// Identifier of a request made with [Readback::request], returned with the data by [Readback::poll].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ReadbackHandle(u64);
+
+/// Data read back from the GPU for the request `handle`.
+pub struct ReadbackData {
+    pub handle: ReadbackHandle,
+    pub bytes: Vec<u8>,
+}
+
+impl ReadbackData {
+    /// The data as values of type `T`. The bytes are copied as they might not be aligned for `T`.
+    pub fn to_vec<T: Pod>(&self) -> Vec<T> {
+        bytemuck::pod_collect_to_vec(&self.bytes)
+    }
+}
+
+/// A request whose copy has been recorded, waiting for its staging buffer to be mapped.
+struct Pending {
+    handle: ReadbackHandle,
+    staging_buffer: Buffer,
+    size: u64,
+    /// Whether the mapping has been requested, which must only be done once the copy has been submitted.
+    map_requested: bool,
+    mapped: Arc<AtomicBool>,
+}
+
+/// Non blocking read back of GPU buffers into the CPU memory, reusing a pool of staging buffers.
+///
+/// [Readback::request] records in an encoder the copy of a range of a buffer into a staging buffer, [Readback::submitted] must be called once this encoder has been submitted, and [Readback::poll] returns the data of the requests whose staging buffer has been mapped. The completion is driven by the callbacks of `map_async`: on native they are triggered by the non blocking poll of the device in [Readback::poll], whereas on the web, where the device cannot be polled in a blocking way, they are triggered asynchronously by the browser some frames later. Once read, the staging buffers return to the pool to be reused by the next requests.
+pub struct Readback {
+    /// Staging buffers which are not used by any request.
+    pool: Mutex<Vec<Buffer>>,
+    pending: Mutex<Vec<Pending>>,
+    next_handle: AtomicU64,
+}
+
+impl Readback {
+    pub fn new() -> Self {
+        Readback {
+            pool: Mutex::new(vec![]),
+            pending: Mutex::new(vec![]),
+            next_handle: AtomicU64::new(0),
+        }
+    }
+    /// Record in `encoder` the copy of the bytes `range` of `src` into a staging buffer. The buffer `src` must have the usage `COPY_SRC`, and the bounds of `range` must be multiples of [wgpu::COPY_BUFFER_ALIGNMENT].
+    pub fn request(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut CommandEncoder,
+        src: &Buffer,
+        range: Range<u64>,
+    ) -> ReadbackHandle {
+        let size = range.end - range.start;
+        let staging_buffer = self.staging_buffer(device, size);
+        encoder.copy_buffer_to_buffer(src, range.start, &staging_buffer, 0, size);
+        let handle = ReadbackHandle(self.next_handle.fetch_add(1, Ordering::Relaxed));
+        self.pending.lock().unwrap().push(Pending {
+            handle,
+            staging_buffer,
+            size,
+            map_requested: false,
+            mapped: Arc::new(AtomicBool::new(false)),
+        });
+        handle
+    }
+    /// Request the mapping of the staging buffers of the previous requests. Must be called after the submission of the encoders given to [Readback::request].
+    pub fn submitted(&self) {
+        for pending in self.pending.lock().unwrap().iter_mut() {
+            if pending.map_requested {
+                continue;
+            }
+            pending.map_requested = true;
+            let mapped = Arc::clone(&pending.mapped);
+            pending.staging_buffer.slice(..pending.size).map_async(
+                wgpu::MapMode::Read,
+                move |result| {
+                    if result.is_ok() {
+                        mapped.store(true, Ordering::Release);
+                    }
+                },
+            );
+        }
+    }
+    /// Return the data of the requests which are completed, without blocking.
+    pub fn poll(&self, device: &wgpu::Device) -> Vec<ReadbackData> {
+        let _ = device.poll(wgpu::MaintainBase::Poll);
+        let mut pending = self.pending.lock().unwrap();
+        let (completed, waiting) = pending
+            .drain(..)
+            .partition::<Vec<_>, _>(|p| p.mapped.load(Ordering::Acquire));
+        *pending = waiting;
+        drop(pending);
+        completed
+            .into_iter()
+            .map(|p| {
+                let bytes = p.staging_buffer.slice(..p.size).get_mapped_range().to_vec();
+                p.staging_buffer.unmap();
+                self.pool.lock().unwrap().push(p.staging_buffer);
+                ReadbackData {
+                    handle: p.handle,
+                    bytes,
+                }
+            })
+            .collect()
+    }
+    /// Number of requests which have not been returned by [Readback::poll] yet.
+    pub fn pending(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+    /// Take from the pool the smallest staging buffer of at least `size` bytes, or create one if there is none.
+    fn staging_buffer(&self, device: &wgpu::Device, size: u64) -> Buffer {
+        let mut pool = self.pool.lock().unwrap();
+        let best = pool
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.size() >= size)
+            .min_by_key(|(_, b)| b.size())
+            .map(|(i, _)| i);
+        if let Some(i) = best {
+            pool.swap_remove(i)
+        } else {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Readback staging buffer"),
+                // Rounded up so that slightly larger requests can reuse the buffer.
+                size: size.next_power_of_two().max(wgpu::COPY_BUFFER_ALIGNMENT),
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        }
+    }
+}
+
+impl Default for Readback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Wait for the completion of every pending request of `readback`.
+#[cfg(feature = "gpu_test")]
+fn wait_all(readback: &Readback, device: &wgpu::Device) -> Vec<ReadbackData> {
+    let mut data = vec![];
+    while readback.pending() > 0 {
+        let _ = device.poll(wgpu::MaintainBase::Wait);
+        data.extend(readback.poll(device));
+    }
+    data
+}
+
+/// Verify that the values computed by a kernel are read back. With chunks of a single value, the `reduce_sum` kernel copies its input into its output.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_readback_kernel() {
+    use wgpu::util::DeviceExt;
+
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    let values = (0..1000).map(|i| i as f32 * 0.5).collect::<Vec<_>>();
+    let input = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Readback test input"),
+        contents: bytemuck::cast_slice(&values),
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+    let ctx = kernel::ReduceCtx {
+        len: values.len() as u32,
+        chunk: 1,
+        half: 0,
+    };
+    let ctx_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Readback test ctx"),
+        contents: bytemuck::bytes_of(&ctx),
+        usage: wgpu::BufferUsages::UNIFORM,
+    });
+    let output = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback test output"),
+        size: (values.len() * size_of::<f32>()) as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let pipeline = super::pipeline::Pipeline::new(
+        &device,
+        &shader_module,
+        "reduce_sum",
+        [
+            (0, &ctx_buffer, None, None),
+            (1, &input, Some(true), None),
+            (2, &output, Some(false), None),
+        ],
+    );
+    let readback = Readback::new();
+    let mut encoder = device.create_command_encoder(&Default::default());
+    pipeline.dispatch(&mut encoder, values.len() as u32, 1, 1);
+    let handle = readback.request(&device, &mut encoder, &output, 0..output.size());
+    queue.submit(Some(encoder.finish()));
+    readback.submitted();
+    let data = wait_all(&readback, &device);
+    assert_eq!(data.len(), 1);
+    assert_eq!(data[0].handle, handle);
+    assert_eq!(data[0].to_vec::<f32>(), values);
+}
+
+/// Verify that many overlapping requests spread over several submissions each return their own range, and that the staging buffers are reused.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_readback_stress() {
+    use std::collections::HashMap;
+    use wgpu::util::DeviceExt;
+
+    let (_instance, device, queue, _) = crate::gpu::adapter::test_device();
+    let values = (0..4096u32).collect::<Vec<_>>();
+    let src = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Readback test source"),
+        contents: bytemuck::cast_slice(&values),
+        usage: wgpu::BufferUsages::COPY_SRC,
+    });
+    let readback = Readback::new();
+    let mut expected = HashMap::new();
+    for round in 0..10 {
+        let mut encoder = device.create_command_encoder(&Default::default());
+        for k in 0..50 {
+            let start = (round * 37 + k * 13) % 2048;
+            let end = start + 1 + (k * 29) % 2048;
+            let handle = readback.request(&device, &mut encoder, &src, start * 4..end * 4);
+            expected.insert(handle, start as usize..end as usize);
+        }
+        queue.submit(Some(encoder.finish()));
+        readback.submitted();
+        for data in readback.poll(&device) {
+            let range = expected.remove(&data.handle).unwrap();
+            assert_eq!(data.to_vec::<u32>(), values[range]);
+        }
+    }
+    for data in wait_all(&readback, &device) {
+        let range = expected.remove(&data.handle).unwrap();
+        assert_eq!(data.to_vec::<u32>(), values[range]);
+    }
+    assert!(expected.is_empty());
+    // The buffers of the completed requests are reused instead of allocating new ones.
+    let pooled = readback.pool.lock().unwrap().len();
+    assert!(pooled > 0 && pooled <= 500, "{pooled}");
+}
@@ -0,0 +1,457 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use bytemuck::bytes_of;
+use kernel::{ReduceCtx, max_nan, min_nan};
+use wgpu::{Buffer, CommandEncoder, util::DeviceExt};
+
+use crate::error::WGPUError;
+
+use super::{
+    physics::Precision,
+    pipeline::{Access, Pipeline, PipelineBuilder},
+};
+
+/// Number of values reduced by each invocation of the `reduce_sum` and `reduce_stats` kernels.
+const CHUNK: u32 = 1024;
+
+/// Sums of the groups of consecutive values of a storage buffer of f32 values, or of f16 values packed by pairs (see [Precision]), such as the replicas of a lattice. The GPU computes partial sums over chunks of [CHUNK] values which are read back and combined on the CPU with [combine].
+///
+/// The partial sums are accumulated in f32 on the GPU, so each of them has a relative error of at most about `CHUNK·ε` with `ε ≈ 6e-8` (and typically `√CHUNK·ε`). Values which are small integers, such as Ising spins or bond counts, are summed exactly as long as the partial sums stay below `2^24`. The combination of the partial sums is performed in f64 so that the error does not grow with the size of the lattice.
+///
+/// With [Reduction::new_stats], the minimum and the maximum of each group are computed in the same pass as the sum by the `reduce_stats` kernel and read with [Reduction::try_read_stats].
+///
+/// The read back is non blocking: [Reduction::encode] records the reduction and the copy into a staging buffer, [Reduction::map] must be called once the corresponding command buffer has been submitted, and [Reduction::try_read] returns the result once the staging buffer is mapped. On native the mapping is done as soon as the device is polled with `Wait`, whereas on the web it is done asynchronously some frames later.
+pub struct Reduction {
+    pipeline: Pipeline,
+    /// Number of partial sums of each group.
+    partials: u32,
+    /// Number of f32 of each partial result: 1 for a sum and 3 for the `(min, max, sum)` triples of [Reduction::new_stats].
+    stride: u32,
+    groups: u32,
+    partials_buffer: Buffer,
+    staging_buffer: Buffer,
+    pending: bool,
+    encoded: bool,
+    mapped: Arc<AtomicBool>,
+}
+
+impl Reduction {
+    /// Prepare the reduction of the `groups` groups of `len` consecutive words at the beginning of `input`, each word holding one value or two depending on `precision`.
+    pub fn new(
+        device: &wgpu::Device,
+        shader_module: &wgpu::ShaderModule,
+        input: &Buffer,
+        len: u32,
+        groups: u32,
+        precision: Precision,
+    ) -> Self {
+        Self::with_kernel(
+            device,
+            shader_module,
+            "reduce_sum",
+            1,
+            input,
+            len,
+            groups,
+            precision,
+        )
+    }
+    /// Prepare the reduction of the `groups` groups of `len` consecutive words at the beginning of `input` to their [Stats], see [Reduction::new].
+    pub fn new_stats(
+        device: &wgpu::Device,
+        shader_module: &wgpu::ShaderModule,
+        input: &Buffer,
+        len: u32,
+        groups: u32,
+        precision: Precision,
+    ) -> Self {
+        Self::with_kernel(
+            device,
+            shader_module,
+            "reduce_stats",
+            3,
+            input,
+            len,
+            groups,
+            precision,
+        )
+    }
+    /// Prepare the reduction by the kernel `entry_point` writing `stride` f32 per partial result.
+    #[allow(clippy::too_many_arguments)]
+    fn with_kernel(
+        device: &wgpu::Device,
+        shader_module: &wgpu::ShaderModule,
+        entry_point: &str,
+        stride: u32,
+        input: &Buffer,
+        len: u32,
+        groups: u32,
+        precision: Precision,
+    ) -> Self {
+        let partials = len.div_ceil(CHUNK).max(1);
+        let ctx = ReduceCtx {
+            len,
+            chunk: CHUNK,
+            half: (precision == Precision::F16) as u32,
+        };
+        let ctx_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Reduction ctx buffer"),
+            contents: bytes_of(&ctx),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let size = (stride * partials * groups) as u64 * size_of::<f32>() as u64;
+        let partials_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Reduction partials buffer"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Reduction staging buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let pipeline = Pipeline::new(
+            device,
+            shader_module,
+            entry_point,
+            [
+                (0, &ctx_buffer, None, None),
+                (1, input, Some(true), None),
+                (2, &partials_buffer, Some(false), None),
+            ],
+        );
+        Reduction {
+            pipeline,
+            partials,
+            stride,
+            groups,
+            partials_buffer,
+            staging_buffer,
+            pending: false,
+            encoded: false,
+            mapped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+    /// Record the reduction in `encoder`. Nothing is recorded if a previous result has not been read yet.
+    pub fn encode(&mut self, encoder: &mut CommandEncoder) {
+        if self.pending {
+            return;
+        }
+        self.dispatch(encoder);
+        encoder.copy_buffer_to_buffer(
+            &self.partials_buffer,
+            0,
+            &self.staging_buffer,
+            0,
+            self.partials_buffer.size(),
+        );
+        self.encoded = true;
+    }
+    /// Record in `encoder` the computation of the partial sums into [Reduction::partials_buffer] only, for a kernel using them on the GPU.
+    pub fn dispatch(&self, encoder: &mut CommandEncoder) {
+        self.pipeline
+            .dispatch(encoder, self.partials, self.groups, 1);
+    }
+    /// Buffer of the partial results, the [Reduction::partials] partial results of each group being stored one after the other.
+    pub fn partials_buffer(&self) -> &Buffer {
+        &self.partials_buffer
+    }
+    /// Number of partial sums of each group.
+    pub fn partials(&self) -> u32 {
+        self.partials
+    }
+    /// Request the mapping of the staging buffer. Must be called after the submission of the encoder given to [Reduction::encode].
+    pub fn map(&mut self) {
+        if !self.encoded {
+            return;
+        }
+        self.encoded = false;
+        self.pending = true;
+        let mapped = Arc::clone(&self.mapped);
+        self.staging_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                if result.is_ok() {
+                    mapped.store(true, Ordering::Release);
+                }
+            });
+    }
+    /// Return the sum of each group if the staging buffer has been mapped. Must only be used with a reduction created by [Reduction::new].
+    pub fn try_read(&mut self) -> Option<Vec<f64>> {
+        debug_assert_eq!(self.stride, 1);
+        self.read_groups(combine)
+    }
+    /// Return the [Stats] of each group if the staging buffer has been mapped. Must only be used with a reduction created by [Reduction::new_stats].
+    pub fn try_read_stats(&mut self) -> Option<Vec<Stats>> {
+        debug_assert_eq!(self.stride, 3);
+        self.read_groups(combine_stats)
+    }
+    /// Combine the partial results of each group with `combine` if the staging buffer has been mapped.
+    fn read_groups<T>(&mut self, combine: impl Fn(&[f32]) -> T) -> Option<Vec<T>> {
+        if !self.pending || !self.mapped.load(Ordering::Acquire) {
+            return None;
+        }
+        let results = {
+            let view = self.staging_buffer.slice(..).get_mapped_range();
+            bytemuck::cast_slice::<u8, f32>(&view)
+                .chunks_exact((self.stride * self.partials) as usize)
+                .map(combine)
+                .collect()
+        };
+        self.staging_buffer.unmap();
+        self.mapped.store(false, Ordering::Release);
+        self.pending = false;
+        Some(results)
+    }
+}
+
+/// Range of the f32 values of a storage buffer, such as a continuous field, computed on the GPU by the `reduce_min_max` and `reduce_range` kernels. The range is stored as a `(min, max)` pair in [RangeReduction::range_buffer], from which it is copied into the uniform of a fragment shader without being read back, for instance to normalize the field before applying a colormap. A NaN among the values makes both bounds NaN, so that a blow-up of the field is not shown with a finite range.
+pub struct RangeReduction {
+    min_max_pipeline: Pipeline,
+    range_pipeline: Pipeline,
+    /// Number of partial ranges.
+    partials: u32,
+    range_buffer: Buffer,
+}
+
+impl RangeReduction {
+    /// Prepare the reduction of the `count` first f32 values of `field`.
+    pub fn new(
+        device: &wgpu::Device,
+        shader_module: &wgpu::ShaderModule,
+        field: &Buffer,
+        count: u32,
+    ) -> Result<Self, WGPUError> {
+        let partials = count.div_ceil(CHUNK).max(1);
+        let ctx_buffer = |label, len, chunk| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: bytes_of(&ReduceCtx {
+                    len,
+                    chunk,
+                    half: 0,
+                }),
+                usage: wgpu::BufferUsages::UNIFORM,
+            })
+        };
+        let min_max_ctx = ctx_buffer("Range reduction min max ctx buffer", count, CHUNK);
+        let range_ctx = ctx_buffer("Range reduction range ctx buffer", partials, partials);
+        let partials_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Range reduction partials buffer"),
+            size: 2 * partials as u64 * size_of::<f32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let range_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Range reduction range buffer"),
+            size: 2 * size_of::<f32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let min_max_pipeline = PipelineBuilder::new(device, shader_module, "reduce_min_max")
+            .uniform(0, &min_max_ctx)
+            .storage(1, field, Access::ReadOnly)
+            .storage(2, &partials_buffer, Access::ReadWrite)
+            .build()?;
+        let range_pipeline = PipelineBuilder::new(device, shader_module, "reduce_range")
+            .uniform(0, &range_ctx)
+            .storage(1, &partials_buffer, Access::ReadOnly)
+            .storage(2, &range_buffer, Access::ReadWrite)
+            .build()?;
+        Ok(RangeReduction {
+            min_max_pipeline,
+            range_pipeline,
+            partials,
+            range_buffer,
+        })
+    }
+    /// Record in `encoder` the computation of the range into [RangeReduction::range_buffer].
+    pub fn dispatch(&self, encoder: &mut CommandEncoder) {
+        self.min_max_pipeline.dispatch(encoder, self.partials, 1, 1);
+        self.range_pipeline.dispatch(encoder, 1, 1, 1);
+    }
+    /// Record in `encoder` the computation of the range and its copy as two consecutive f32 (the minimum then the maximum) at `offset` in `buffer`, which must have the usage `COPY_DST`.
+    pub fn copy_to(&self, encoder: &mut CommandEncoder, buffer: &Buffer, offset: u64) {
+        self.dispatch(encoder);
+        encoder.copy_buffer_to_buffer(
+            &self.range_buffer,
+            0,
+            buffer,
+            offset,
+            self.range_buffer.size(),
+        );
+    }
+    /// Buffer of the `(min, max)` pair computed by [RangeReduction::dispatch].
+    pub fn range_buffer(&self) -> &Buffer {
+        &self.range_buffer
+    }
+}
+
+/// Sum of f32 values accumulated in f64 with Kahan compensated summation, so that the result is accurate even for a large number of values.
+pub fn combine(values: &[f32]) -> f64 {
+    let mut sum = 0.0f64;
+    let mut compensation = 0.0f64;
+    for &v in values {
+        let y = v as f64 - compensation;
+        let t = sum + y;
+        compensation = (t - sum) - y;
+        sum = t;
+    }
+    sum
+}
+
+/// Minimum, maximum and sum of a group of values computed by [Reduction::new_stats].
+///
+/// A NaN among the values makes the minimum, the maximum and the sum NaN, and an infinite value makes the corresponding bound infinite, so that [Stats::is_finite] detects a numerical blow-up. An empty group has `min = +∞`, `max = -∞` and `sum = 0`, see [Stats::is_empty].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    pub min: f32,
+    pub max: f32,
+    pub sum: f64,
+}
+
+impl Stats {
+    /// Whether the group had no values.
+    pub fn is_empty(&self) -> bool {
+        self.min > self.max
+    }
+    /// Whether all the values were finite, which is the case of an empty group.
+    pub fn is_finite(&self) -> bool {
+        self.is_empty() || (self.min.is_finite() && self.max.is_finite() && self.sum.is_finite())
+    }
+}
+
+/// Combine the `(min, max, sum)` triples of the partial results of a group computed by the `reduce_stats` kernel, the sums being combined like [combine].
+pub fn combine_stats(partials: &[f32]) -> Stats {
+    let (min, max) = partials
+        .chunks_exact(3)
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), p| {
+            (min_nan(min, p[0]), max_nan(max, p[1]))
+        });
+    let sums = partials.chunks_exact(3).map(|p| p[2]).collect::<Vec<_>>();
+    Stats {
+        min,
+        max,
+        sum: combine(&sums),
+    }
+}
+
+/// Verify that [combine] stays accurate on a large buffer where a naive f32 summation visibly drifts.
+#[test]
+fn test_combine_precision() {
+    let count = 10_000_000;
+    let values = vec![0.1f32; count];
+    let exact = 0.1f32 as f64 * count as f64;
+    let naive = values.iter().sum::<f32>() as f64;
+    assert!((naive - exact).abs() / exact > 1e-2, "{naive}");
+    let sum = combine(&values);
+    assert!((sum - exact).abs() / exact < 1e-12, "{sum}");
+}
+
+/// Verify that [combine_stats] propagates a NaN and an infinity and reports an empty group.
+#[test]
+fn test_combine_stats() {
+    let stats = combine_stats(&[-1.0, 2.0, 0.5, 0.0, 3.0, 1.5]);
+    assert_eq!(
+        stats,
+        Stats {
+            min: -1.0,
+            max: 3.0,
+            sum: 2.0
+        }
+    );
+    assert!(stats.is_finite() && !stats.is_empty());
+    let blow_up = combine_stats(&[-1.0, 2.0, 0.5, f32::NAN, f32::NAN, f32::NAN]);
+    assert!(blow_up.min.is_nan() && blow_up.max.is_nan() && blow_up.sum.is_nan());
+    assert!(!blow_up.is_finite());
+    let infinite = combine_stats(&[-1.0, f32::INFINITY, f32::INFINITY]);
+    assert_eq!(infinite.max, f32::INFINITY);
+    assert!(!infinite.is_finite());
+    let empty = combine_stats(&[f32::INFINITY, f32::NEG_INFINITY, 0.0]);
+    assert!(empty.is_empty() && empty.is_finite());
+}
+
+/// Verify that the minimum, maximum and sum of the groups of a buffer spanning several chunks match the ones computed on the CPU, and that a NaN is propagated.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_stats_reduction() {
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    let len = 2 * CHUNK + 100;
+    let mut values = (0..2 * len)
+        .map(|i| (i as f32 * 0.61).cos() * (1 + i % 7) as f32)
+        .collect::<Vec<_>>();
+    values[len as usize + 3] = f32::NAN;
+    let input = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: bytemuck::cast_slice(&values),
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+    let mut reduction =
+        Reduction::new_stats(&device, &shader_module, &input, len, 2, Precision::F32);
+    let mut encoder = device.create_command_encoder(&Default::default());
+    reduction.encode(&mut encoder);
+    queue.submit(Some(encoder.finish()));
+    reduction.map();
+    let stats = loop {
+        let _ = device.poll(wgpu::MaintainBase::Wait);
+        if let Some(stats) = reduction.try_read_stats() {
+            break stats;
+        }
+    };
+    let first = &values[..len as usize];
+    let min = first.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = first.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let sum = first.iter().map(|&v| v as f64).sum::<f64>();
+    assert_eq!((stats[0].min, stats[0].max), (min, max));
+    assert!((stats[0].sum - sum).abs() < 1e-3, "{} {sum}", stats[0].sum);
+    assert!(stats[1].min.is_nan() && stats[1].max.is_nan() && stats[1].sum.is_nan());
+}
+
+/// Verify that the range of a buffer spanning several chunks is found wherever its extrema are, including in the last partial chunk, and that a NaN in any chunk makes the range NaN.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_range_reduction() {
+    use crate::gpu::readback::Readback;
+
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    let count = 3 * CHUNK + 17;
+    let mut values = (0..count)
+        .map(|i| (i as f32 * 0.37).sin())
+        .collect::<Vec<_>>();
+    values[CHUNK as usize + 5] = -3.5;
+    values[count as usize - 1] = 7.25;
+    let field = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: bytemuck::cast_slice(&values),
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+    });
+    let reduction = RangeReduction::new(&device, &shader_module, &field, count).unwrap();
+    let read_range = || {
+        let readback = Readback::new();
+        let mut encoder = device.create_command_encoder(&Default::default());
+        reduction.dispatch(&mut encoder);
+        let range = reduction.range_buffer();
+        readback.request(&device, &mut encoder, range, 0..range.size());
+        queue.submit(Some(encoder.finish()));
+        readback.submitted();
+        loop {
+            let _ = device.poll(wgpu::MaintainBase::Wait);
+            if let Some(data) = readback.poll(&device).pop() {
+                break data.to_vec::<f32>();
+            }
+        }
+    };
+    assert_eq!(read_range(), [-3.5, 7.25]);
+    queue.write_buffer(
+        &field,
+        (2 * CHUNK as u64 + 1) * size_of::<f32>() as u64,
+        bytemuck::bytes_of(&f32::NAN),
+    );
+    let range = read_range();
+    assert!(range[0].is_nan() && range[1].is_nan(), "{range:?}");
+}
@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+/// Default target framerate of the [StepTuner], slightly below 60 fps so that frames limited by the vertical synchronization of a 60 Hz display are still considered fast enough.
+pub const DEFAULT_TARGET_FPS: f32 = 1.0 / 0.017;
+
+/// Fraction of the duration of a frame at the target framerate given to the steps by [StepTuner::budget], the rest being left to the rendering and the UI.
+const STEP_BUDGET: f32 = 0.75;
+
+/// Automatically handle performance by looking at the time taken by an entire frame, given to [StepTuner::frame], aiming for a target framerate. Increase the number of steps per frames if the average time of the last frames (the history window) is bellow `limit = 1/target_fps`, and decrease if the time exceeds `limit*1.05`. The gap between `limit` and `limit*1.05` is to avoible oscillations of the number of steps per frames. The number of steps per frame is kept between a minimum and a maximum, by default 1 and 10.
+pub struct StepTuner {
+    step_per_frames: usize,
+    min_steps: usize,
+    max_steps: usize,
+    target_fps: f32,
+    /// Number of frames over which the time of the frames is averaged.
+    window: usize,
+    /// Number of frames and total time in seconds of the frames recorded in the current window.
+    frames: usize,
+    elapsed: f32,
+    /// Steps requested by the previous frames which did not fit in their [StepTuner::budget], see [StepTuner::performed].
+    carried: usize,
+    /// Steps performed in the current frame, given to [StepTuner::performed] and consumed by [StepTuner::frame].
+    performed: usize,
+    /// Estimated time in seconds of a step, from which [StepTuner::within_budget] bounds the steps of a frame. `None` until a frame exceeds the target framerate.
+    step_time: Option<f32>,
+}
+
+impl StepTuner {
+    pub fn new() -> Self {
+        Self::with_target(DEFAULT_TARGET_FPS, 10)
+    }
+    /// Create a tuner aiming for `target_fps` frames per second, averaging the time of the frames over a history `window` of frames.
+    pub fn with_target(target_fps: f32, window: usize) -> Self {
+        StepTuner {
+            step_per_frames: 1,
+            min_steps: 1,
+            max_steps: 10,
+            target_fps,
+            window: window.max(1),
+            frames: 0,
+            elapsed: 0.0,
+            carried: 0,
+            performed: 0,
+            step_time: None,
+        }
+    }
+    /// Current number of steps to perform per frame.
+    pub fn step_per_frames(&self) -> usize {
+        self.step_per_frames
+    }
+    /// Number of steps to perform in the next frame when they are bounded by the [StepTuner::budget]: the steps per frame and the ones carried over from the previous frames.
+    pub fn requested(&self) -> usize {
+        self.step_per_frames + self.carried
+    }
+    /// Number of the [StepTuner::requested] steps which fit in the [StepTuner::budget] of the next frame at the time per step estimated from the previous frames, at least 1. All the steps are requested until a frame exceeds the target framerate.
+    pub fn within_budget(&self) -> usize {
+        let requested = self.requested();
+        match self.step_time {
+            Some(step_time) => {
+                let fit = (self.budget().as_secs_f32() / step_time) as usize;
+                requested.min(fit.max(1))
+            }
+            None => requested,
+        }
+    }
+    /// Record that `performed` of the [StepTuner::requested] steps were performed within the budget of the frame. The others are carried over to the next frame, up to the number of steps of one frame so that they do not pile up while the GPU cannot keep up.
+    pub fn performed(&mut self, performed: usize) {
+        self.carried = self
+            .requested()
+            .saturating_sub(performed)
+            .min(self.step_per_frames);
+        self.performed = performed;
+    }
+    /// Time which the steps of a frame may take at the target framerate.
+    pub fn budget(&self) -> Duration {
+        Duration::from_secs_f32(STEP_BUDGET / self.target_fps)
+    }
+    /// Keep the number of steps per frame between `min_steps` and `max_steps`. The minimum is at least 1 and the maximum at least the minimum.
+    pub fn with_bounds(mut self, min_steps: usize, max_steps: usize) -> Self {
+        self.set_bounds(min_steps, max_steps);
+        self
+    }
+    /// Change the bounds of the number of steps per frame, see [StepTuner::with_bounds].
+    pub fn set_bounds(&mut self, min_steps: usize, max_steps: usize) {
+        self.min_steps = min_steps.max(1);
+        self.max_steps = max_steps.max(self.min_steps);
+        self.step_per_frames = self.step_per_frames.clamp(self.min_steps, self.max_steps);
+    }
+    /// Change the target framerate.
+    pub fn set_target_fps(&mut self, target_fps: f32) {
+        self.target_fps = target_fps;
+    }
+    /// Record the end of a frame which lasted `elapsed` seconds and adapt the number of steps per frame. The duration is usually the [dt](crate::gpu::physics::FrameInfo::dt) of the frame, or the GPU time of the steps alone to exclude the rendering and the UI.
+    ///
+    /// If steps were [performed](StepTuner::performed) in the frame, the time per step used by [StepTuner::within_budget] is also updated, assuming that consecutive frames perform about as many steps. A frame slower than the target framerate gives the time per step, while a frame fast enough lowers it by 10% so that the steps bounded after a slow frame grow back. As the duration includes the waits for the vertical synchronization, it only measures the steps once the frames are too slow.
+    pub fn frame(&mut self, elapsed: f32) {
+        let limit = 1.0 / self.target_fps;
+        let performed = std::mem::take(&mut self.performed);
+        if performed > 0 {
+            if elapsed > limit * 1.05 {
+                self.step_time = Some(elapsed / performed as f32);
+            } else if let Some(step_time) = &mut self.step_time {
+                *step_time *= 0.9;
+            }
+        }
+        self.frames += 1;
+        self.elapsed += elapsed;
+        if self.frames == self.window {
+            let elapsed = self.elapsed / self.window as f32;
+            self.frames = 0;
+            self.elapsed = 0.0;
+            if elapsed < limit {
+                self.step_per_frames = (self.step_per_frames + 1).min(self.max_steps);
+            } else if elapsed > limit * 1.05 {
+                self.step_per_frames = self.step_per_frames.saturating_sub(1).max(self.min_steps);
+            }
+        }
+    }
+}
+
+impl Default for StepTuner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Verify that the steps which do not fit in the budget of a frame are carried over to the next one, at most the steps of one frame.
+#[test]
+fn test_carried_steps() {
+    let mut tuner = StepTuner::with_target(50.0, 1).with_bounds(100, 100);
+    assert_eq!(tuner.budget(), Duration::from_millis(15));
+    assert_eq!(tuner.requested(), 100);
+    tuner.performed(70);
+    assert_eq!(tuner.requested(), 130);
+    tuner.performed(0);
+    assert_eq!(tuner.requested(), 200);
+    tuner.performed(200);
+    assert_eq!(tuner.requested(), 100);
+}
+
+/// Verify that the steps are not bounded while the frames are fast enough, that a slow frame bounds the steps of the next ones to the budget at the measured time per step, and that the bound grows back once the frames are fast enough.
+#[test]
+fn test_within_budget() {
+    let mut tuner = StepTuner::with_target(50.0, 1000).with_bounds(100, 100);
+    assert_eq!(tuner.within_budget(), 100);
+    tuner.performed(100);
+    tuner.frame(0.015);
+    assert_eq!(tuner.within_budget(), 100);
+    // 100 steps in 40 ms: 37 steps of 0.4 ms fit in the budget of 15 ms.
+    tuner.performed(100);
+    tuner.frame(0.04);
+    assert_eq!(tuner.within_budget(), 37);
+    tuner.performed(37);
+    assert_eq!(tuner.requested(), 163);
+    tuner.frame(0.015);
+    assert_eq!(tuner.within_budget(), 41);
+}
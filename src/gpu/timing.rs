@@ -0,0 +1,137 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use wgpu::{Buffer, CommandEncoder};
+
+use super::readback::{Readback, ReadbackHandle};
+
+/// GPU duration of passes measured with timestamp queries. Each pass, identified by its index in the labels given to [GpuTimer::new], has a timestamp written at its beginning and one at its end.
+///
+/// The timestamps written since the last resolution are resolved by [GpuTimer::resolve] and read back asynchronously with a [Readback], so the durations returned by [GpuTimer::timings] are the ones of a few frames ago. Only the passes which were actually recorded are updated, so a pass which is not run every frame keeps its last duration.
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: Buffer,
+    labels: Vec<&'static str>,
+    /// Whether the timestamps of each pass were written since the last resolution.
+    written: Vec<AtomicBool>,
+    /// Passes written in each resolution being read back.
+    in_flight: Vec<(ReadbackHandle, Vec<bool>)>,
+    readback: Readback,
+    /// Nanoseconds per timestamp tick.
+    period: f64,
+    /// Last duration of each pass in milliseconds.
+    timings: Vec<Option<f64>>,
+}
+
+impl GpuTimer {
+    /// Timer of the passes named `labels`, or `None` if the device does not support `features` which must contain `TIMESTAMP_QUERY`, along with `TIMESTAMP_QUERY_INSIDE_PASSES` to write timestamps inside a render pass.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        labels: &[&'static str],
+        features: wgpu::Features,
+    ) -> Option<Self> {
+        if !device.features().contains(features) {
+            return None;
+        }
+        let count = 2 * labels.len() as u32;
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Timer query set"),
+            ty: wgpu::QueryType::Timestamp,
+            count,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timer resolve buffer"),
+            size: count as u64 * wgpu::QUERY_SIZE as u64,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        Some(GpuTimer {
+            query_set,
+            resolve_buffer,
+            labels: labels.to_vec(),
+            written: labels.iter().map(|_| AtomicBool::new(false)).collect(),
+            in_flight: vec![],
+            readback: Readback::new(),
+            period: queue.get_timestamp_period() as f64,
+            timings: vec![None; labels.len()],
+        })
+    }
+    /// Timestamp writes of the compute pass `repetition` among `repetitions` consecutive passes measured together as the pass `pass`: the first writes the beginning and the last writes the end.
+    pub fn compute_pass_writes(
+        &self,
+        pass: usize,
+        repetition: usize,
+        repetitions: usize,
+    ) -> wgpu::ComputePassTimestampWrites<'_> {
+        self.written[pass].store(true, Ordering::Relaxed);
+        wgpu::ComputePassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: (repetition == 0).then_some(2 * pass as u32),
+            end_of_pass_write_index: (repetition + 1 == repetitions).then_some(2 * pass as u32 + 1),
+        }
+    }
+    /// Write the beginning (`end = false`) or the end of the pass `pass` inside `render_pass`.
+    pub fn write_render_timestamp(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'_>,
+        pass: usize,
+        end: bool,
+    ) {
+        self.written[pass].store(true, Ordering::Relaxed);
+        render_pass.write_timestamp(&self.query_set, 2 * pass as u32 + end as u32);
+    }
+    /// Record in `encoder` the resolution and the read back of the timestamps written since the last call. [GpuTimer::submitted] must be called once `encoder` has been submitted.
+    pub fn resolve(&mut self, device: &wgpu::Device, encoder: &mut CommandEncoder) {
+        let written = self
+            .written
+            .iter()
+            .map(|w| w.swap(false, Ordering::Relaxed))
+            .collect::<Vec<_>>();
+        if !written.contains(&true) {
+            return;
+        }
+        encoder.resolve_query_set(
+            &self.query_set,
+            0..2 * self.labels.len() as u32,
+            &self.resolve_buffer,
+            0,
+        );
+        let handle = self.readback.request(
+            device,
+            encoder,
+            &self.resolve_buffer,
+            0..self.resolve_buffer.size(),
+        );
+        self.in_flight.push((handle, written));
+    }
+    /// Request the mapping of the read back, see [Readback::submitted].
+    pub fn submitted(&self) {
+        self.readback.submitted();
+    }
+    /// Update the durations with the timestamps which have been read back, without blocking.
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        for data in self.readback.poll(device) {
+            let Some(i) = self.in_flight.iter().position(|(h, _)| *h == data.handle) else {
+                continue;
+            };
+            let (_, written) = self.in_flight.swap_remove(i);
+            let timestamps = data.to_vec::<u64>();
+            for (pass, _) in written.iter().enumerate().filter(|(_, w)| **w) {
+                let ticks = timestamps[2 * pass + 1].wrapping_sub(timestamps[2 * pass]);
+                self.timings[pass] = Some(ticks as f64 * self.period * 1e-6);
+            }
+        }
+    }
+    /// Last duration in milliseconds of the pass `pass`, if it has been measured.
+    pub fn timing(&self, pass: usize) -> Option<f64> {
+        self.timings[pass]
+    }
+    /// Label and last duration in milliseconds of the passes which have been measured.
+    pub fn timings(&self) -> Vec<(&'static str, f64)> {
+        self.labels
+            .iter()
+            .zip(&self.timings)
+            .filter_map(|(&label, t)| t.map(|t| (label, t)))
+            .collect()
+    }
+}
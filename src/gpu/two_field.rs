@@ -0,0 +1,117 @@
+use bytemuck::bytes_of;
+use kernel::TwoFieldCtx;
+use wgpu::{Buffer, CommandEncoder, util::DeviceExt};
+
+use crate::error::WGPUError;
+
+use super::{
+    physics::{FragmentEntry, FragmentInfo},
+    reduction::RangeReduction,
+};
+
+/// Rendering shared by the models of two fields A and B of f32 values with the `two_field_fragment` of the embedded kernels, see [TwoFieldCtx].
+///
+/// The fields are normalized either by fixed ranges, or by their current range when the auto-scaling is enabled. The ranges are then computed on the GPU at each frame and copied into the uniform of the fragment, so the auto-scaling does not need any read back.
+pub struct TwoFieldRender {
+    ctx: TwoFieldCtx,
+    ctx_buffer: Buffer,
+    fields: [Buffer; 2],
+    reductions: [RangeReduction; 2],
+    /// Whether the ranges of the uniform are the ones computed by the reductions, so that the fixed ones are written back when the auto-scaling is disabled.
+    auto_scaled: bool,
+}
+
+impl TwoFieldRender {
+    /// Render the `fields` A and B of `width`×`height` f32 values, normalized by the fixed `ranges` as `[[min_a, max_a], [min_b, max_b]]` until the auto-scaling is enabled. The `shader_module` must be the one of the embedded kernels.
+    pub fn new(
+        device: &wgpu::Device,
+        shader_module: &wgpu::ShaderModule,
+        width: u32,
+        height: u32,
+        fields: [&Buffer; 2],
+        ranges: [[f32; 2]; 2],
+    ) -> Result<Self, WGPUError> {
+        let [[min_a, max_a], [min_b, max_b]] = ranges;
+        let ctx = TwoFieldCtx {
+            width,
+            height,
+            min_a,
+            max_a,
+            min_b,
+            max_b,
+        };
+        let ctx_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Two field ctx buffer"),
+            contents: bytes_of(&ctx),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let reduction = |field| RangeReduction::new(device, shader_module, field, width * height);
+        Ok(TwoFieldRender {
+            ctx,
+            ctx_buffer,
+            reductions: [reduction(fields[0])?, reduction(fields[1])?],
+            fields: fields.map(Buffer::clone),
+            auto_scaled: false,
+        })
+    }
+    /// Record in `encoder` the computation of the ranges of the fields and their copy into the uniform if `auto_scale`, otherwise restore the fixed ranges if the previous frame was auto-scaled.
+    pub fn update(&mut self, queue: &wgpu::Queue, encoder: &mut CommandEncoder, auto_scale: bool) {
+        if auto_scale {
+            // The ranges of A and B are copied over `min_a, max_a` and `min_b, max_b` of the uniform.
+            for (i, reduction) in self.reductions.iter().enumerate() {
+                let offset = (2 + 2 * i as u64) * size_of::<f32>() as u64;
+                reduction.copy_to(encoder, &self.ctx_buffer, offset);
+            }
+        } else if self.auto_scaled {
+            queue.write_buffer(&self.ctx_buffer, 0, bytes_of(&self.ctx));
+        }
+        self.auto_scaled = auto_scale;
+    }
+    /// Entries of `two_field_fragment`: the uniform at `binding = 0` and the fields A and B at the bindings 1 and 2.
+    pub fn fragment_info(&self) -> FragmentInfo {
+        let field = |binding, buffer| FragmentEntry {
+            binding,
+            buffer,
+            uniform: false,
+            dynamic_size: None,
+        };
+        FragmentInfo {
+            fragment_entry_point: "two_field_fragment",
+            embedded: true,
+            entries: vec![
+                FragmentEntry {
+                    binding: 0,
+                    buffer: &self.ctx_buffer,
+                    uniform: true,
+                    dynamic_size: None,
+                },
+                field(1, &self.fields[0]),
+                field(2, &self.fields[1]),
+            ],
+        }
+    }
+}
+
+/// Verify that the colormap puts the field A at one end, the field B at the other and balanced fields in the middle, whatever their normalization, and that a constant field does not divide by zero.
+#[test]
+fn test_two_field_mix() {
+    let ctx = TwoFieldCtx {
+        width: 1,
+        height: 1,
+        min_a: 0.0,
+        max_a: 1.0,
+        min_b: 0.0,
+        max_b: 0.5,
+    };
+    assert_eq!(ctx.mix(1.0, 0.0), 0.0);
+    assert_eq!(ctx.mix(0.0, 0.5), 1.0);
+    assert_eq!(ctx.mix(0.5, 0.25), 0.5);
+    assert_eq!(ctx.mix(2.0, -1.0), 0.0);
+    let flat = TwoFieldCtx {
+        max_a: 0.0,
+        max_b: 0.0,
+        ..ctx
+    };
+    let mix = flat.mix(0.0, 1.0);
+    assert!(mix.is_finite() && (0.0..=1.0).contains(&mix), "{mix}");
+}
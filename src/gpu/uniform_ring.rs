@@ -0,0 +1,123 @@
+use std::marker::PhantomData;
+
+use bytemuck::{Pod, bytes_of};
+use wgpu::{Buffer, CommandEncoder, util::DeviceExt};
+
+use super::{physics::write_staged, pipeline::PipelineEntry};
+
+/// Number of copies of the context in a [UniformRing].
+pub const SLOTS: u64 = 2;
+
+/// Uniform buffer holding [SLOTS] copies of a context of type `T`, bound with a dynamic offset selecting the slot of the current frame.
+///
+/// Each frame writes its context into the next slot with [UniformRing::advance], so the writes of a frame never target the slot which is still read by the rendering of the previous frame: the update of the parameters does not have to wait for the rendering. The compute passes and the fragment shader of a frame read the same slot, given by [UniformRing::offset].
+pub struct UniformRing<T> {
+    buffer: Buffer,
+    /// Distance in bytes between two slots, a multiple of the alignment of the dynamic offsets.
+    stride: u64,
+    /// Size in bytes of the binding of a slot.
+    binding_size: u64,
+    slot: u64,
+    _context: PhantomData<T>,
+}
+
+impl<T: Pod> UniformRing<T> {
+    /// Create the ring with all its slots holding `ctx`, the current slot being the first one.
+    pub fn new(device: &wgpu::Device, label: &str, ctx: &T) -> Self {
+        // The size of a uniform struct is rounded up to 16 bytes by the shader translation.
+        let binding_size = (size_of::<T>() as u64).next_multiple_of(16);
+        let stride = binding_size
+            .next_multiple_of(device.limits().min_uniform_buffer_offset_alignment as u64);
+        let mut contents = vec![0; (stride * SLOTS) as usize];
+        for slot in contents.chunks_exact_mut(stride as usize) {
+            slot[..size_of::<T>()].copy_from_slice(bytes_of(ctx));
+        }
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: &contents,
+            usage: wgpu::BufferUsages::UNIFORM
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+        });
+        UniformRing {
+            buffer,
+            stride,
+            binding_size,
+            slot: 0,
+            _context: PhantomData,
+        }
+    }
+    /// Entry of the ring at `binding` in a bind group with a dynamic offset, see [Pipeline::new](super::pipeline::Pipeline::new).
+    pub fn entry(&self, binding: u32) -> PipelineEntry<'_> {
+        (binding, &self.buffer, None, Some(self.binding_size))
+    }
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+    /// Size in bytes of the binding of a slot.
+    pub fn binding_size(&self) -> u64 {
+        self.binding_size
+    }
+    /// Dynamic offset of the current slot.
+    pub fn offset(&self) -> u32 {
+        (self.slot * self.stride) as u32
+    }
+    /// Move to the next slot, which is then written by [UniformRing::write].
+    pub fn advance(&mut self) {
+        self.slot = (self.slot + 1) % SLOTS;
+    }
+    /// Record in `encoder` the write of `ctx` into the current slot through the staging `belt`, see [write_staged].
+    pub fn write(
+        &self,
+        belt: &mut wgpu::util::StagingBelt,
+        device: &wgpu::Device,
+        encoder: &mut CommandEncoder,
+        ctx: &T,
+    ) {
+        write_staged(
+            belt,
+            device,
+            encoder,
+            &self.buffer,
+            self.offset() as u64,
+            bytes_of(ctx),
+        );
+    }
+}
+
+/// Verify that a write in the next slot is visible at its offset within the submission, while the previous slot keeps the context it was read with.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_uniform_ring_slots() {
+    use super::readback::Readback;
+
+    let (_instance, device, queue, _) = super::adapter::test_device();
+    let mut ring = UniformRing::new(&device, "Ring test buffer", &[1u32, 2, 3, 4]);
+    let mut belt = wgpu::util::StagingBelt::new(1024);
+    let mut encoder = device.create_command_encoder(&Default::default());
+    ring.advance();
+    ring.write(&mut belt, &device, &mut encoder, &[5u32, 6, 7, 8]);
+    let readback = Readback::new();
+    readback.request(
+        &device,
+        &mut encoder,
+        ring.buffer(),
+        0..ring.buffer().size(),
+    );
+    belt.finish();
+    queue.submit(Some(encoder.finish()));
+    readback.submitted();
+    belt.recall();
+    let data = loop {
+        let _ = device.poll(wgpu::MaintainBase::Wait);
+        if let Some(data) = readback.poll(&device).pop() {
+            break data.to_vec::<u32>();
+        }
+    };
+    let offset = ring.offset() as usize / size_of::<u32>();
+    assert_eq!(ring.offset() as u64, ring.stride);
+    assert_eq!(data[..4], [1, 2, 3, 4]);
+    assert_eq!(data[offset..offset + 4], [5, 6, 7, 8]);
+    ring.advance();
+    assert_eq!(ring.offset(), 0);
+}
@@ -0,0 +1,315 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, atomic::Ordering},
+};
+
+use kernel::{CellRng, INIT_RANDOM};
+use wgpu::{ErrorFilter, PowerPreference};
+
+use crate::{
+    config::{Config, Value},
+    error::{ConfigError, WGPUError},
+    export::{write_csv, write_lattice, write_npy},
+    gpu::{
+        adapter::{create_shader_module, request_adapter, request_device},
+        capabilities::capabilities,
+        physics::{
+            Precision,
+            ising::{INITS, IsingObservables, IsingParameters, IsingPipeline},
+        },
+    },
+    seed::Seed,
+};
+
+/// Parameters of a headless run of the Ising model, see [run].
+pub struct HeadlessConfig {
+    pub width: u32,
+    pub height: u32,
+    pub seed: Seed,
+    pub temperature: f32,
+    pub field: f32,
+    /// Number of sweeps discarded before the `sweeps` of the run, to let the transient of the initial state decay.
+    pub thermalize: usize,
+    pub sweeps: usize,
+    pub precision: Precision,
+    pub replicas: u32,
+    /// Initialization of the spins, one of the `INIT_*` constants of the kernel.
+    pub init: u32,
+    /// File or directory where the results are written by [HeadlessResult::write].
+    pub output: PathBuf,
+}
+
+impl Default for HeadlessConfig {
+    /// 1000 sweeps without thermalization of a 256×256 lattice at the critical temperature without field, with a random seed, written in the current directory.
+    fn default() -> Self {
+        HeadlessConfig {
+            width: 256,
+            height: 256,
+            seed: Seed::from_entropy(),
+            temperature: IsingParameters::default().temperature.load(),
+            field: 0.0,
+            thermalize: 0,
+            sweeps: 1000,
+            precision: Precision::F32,
+            replicas: 1,
+            init: INIT_RANDOM,
+            output: PathBuf::from("."),
+        }
+    }
+}
+
+impl HeadlessConfig {
+    /// Headless run configured by `config`, which must be one of the Ising model, the defaults being used for the settings it does not give. Only the parameters `T`, `h`, `init`, `replicas` and `half precision` of the Ising model are supported.
+    pub fn from_config(config: &Config) -> Result<Self, ConfigError> {
+        let field = |field: &str, message: String| ConfigError::Field {
+            field: field.to_string(),
+            message,
+        };
+        if config.simulation != "ising" {
+            return Err(field(
+                "simulation",
+                format!(
+                    "the headless mode runs the ising model, not {}",
+                    config.simulation
+                ),
+            ));
+        }
+        let default = HeadlessConfig::default();
+        let mut headless = HeadlessConfig {
+            width: config.size.map_or(default.width, |size| size.width),
+            height: config.size.map_or(default.height, |size| size.height),
+            seed: config.seed.unwrap_or(default.seed),
+            thermalize: config.schedule.thermalize.unwrap_or(default.thermalize),
+            sweeps: config.schedule.sweeps.unwrap_or(default.sweeps),
+            output: config.output.clone().unwrap_or(default.output.clone()),
+            ..default
+        };
+        for (tag, value) in &config.parameters {
+            let invalid = |expected: &str| {
+                field(
+                    &format!("parameters.{tag}"),
+                    format!("expected {expected}, found {value}"),
+                )
+            };
+            let number = || match value {
+                Value::Float(number) => Ok(*number as f32),
+                Value::Integer(number) => Ok(*number as f32),
+                _ => Err(invalid("a number")),
+            };
+            match (tag.as_str(), value) {
+                ("T", _) => headless.temperature = number()?,
+                ("h", _) => headless.field = number()?,
+                ("init", Value::String(name)) => {
+                    let names = INITS.map(|(name, _)| name);
+                    let (_, init) = INITS
+                        .iter()
+                        .find(|(n, _)| n == name)
+                        .ok_or_else(|| invalid(&format!("one of {}", names.join(", "))))?;
+                    headless.init = *init;
+                }
+                ("replicas", Value::Integer(n)) => {
+                    headless.replicas =
+                        u32::try_from(*n).map_err(|_| invalid("a positive number of replicas"))?;
+                }
+                ("replicas", Value::String(n)) => {
+                    headless.replicas = n
+                        .parse()
+                        .map_err(|_| invalid("a positive number of replicas"))?;
+                }
+                ("half precision", Value::Boolean(half)) => {
+                    headless.precision = if *half {
+                        Precision::F16
+                    } else {
+                        Precision::F32
+                    };
+                }
+                ("init", _) => return Err(invalid("the name of an initialization")),
+                ("replicas", _) => return Err(invalid("a number of replicas")),
+                ("half precision", _) => return Err(invalid("true or false")),
+                _ => {
+                    return Err(field(
+                        &format!("parameters.{tag}"),
+                        "not supported by the headless mode, expected one of `T`, `h`, `init`, `replicas`, `half precision`".to_string(),
+                    ));
+                }
+            }
+        }
+        Ok(headless)
+    }
+}
+
+/// Final state of a headless run.
+pub struct HeadlessResult {
+    /// Size of the lattice, whose width might have been rounded down to an even number in [Precision::F16].
+    pub width: u32,
+    pub height: u32,
+    pub seed: Seed,
+    pub temperature: f32,
+    pub field: f32,
+    pub sweeps: usize,
+    /// Values of the cells of the first replica, row by row.
+    pub vals: Vec<f32>,
+    /// Observables averaged over the replicas.
+    pub observables: IsingObservables,
+}
+
+impl HeadlessResult {
+    /// Write the final configuration and the observables. If `output` is a `.npy` file, the configuration is written in it as a NumPy array with one row per row of the lattice, and the observables in the CSV file with the same name. Conversely, if `output` is a `.csv` file, the observables are written in it and the configuration in the `.npy` file with the same name. Otherwise `output` is a directory, created if needed, where the configuration is written in `configuration.csv`, one line per row of the lattice, and the observables in `observables.csv`.
+    pub fn write(&self, output: impl AsRef<Path>) -> Result<(), WGPUError> {
+        let output = output.as_ref();
+        let extension = output.extension().and_then(|e| e.to_str());
+        let observables = if matches!(extension, Some("npy" | "csv")) {
+            if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)?;
+            }
+            write_npy(output.with_extension("npy"), self.width, &self.vals)?;
+            output.with_extension("csv")
+        } else {
+            std::fs::create_dir_all(output)?;
+            write_lattice(output.join("configuration.csv"), self.width, &self.vals)?;
+            output.join("observables.csv")
+        };
+        write_csv(
+            observables,
+            &[
+                "sweeps",
+                "temperature",
+                "field",
+                "magnetization",
+                "magnetization_error",
+                "interface_density",
+                "energy",
+                "binder_cumulant",
+            ],
+            [[
+                self.sweeps as f64,
+                self.temperature as f64,
+                self.field as f64,
+                self.observables.magnetization,
+                self.observables.magnetization_error,
+                self.observables.interface_density,
+                self.observables.energy,
+                self.observables.binder_cumulant(),
+            ]],
+        )?;
+        Ok(())
+    }
+}
+
+/// Run the Ising model described by `config` without any window: a device is created directly from the first available adapter (see [request_adapter]), and the run blocks until the final state is read back. The errors of wgpu are returned instead of panicking.
+pub fn run(config: &HeadlessConfig) -> Result<HeadlessResult, WGPUError> {
+    if config.width == 0 || config.height == 0 {
+        return Err(WGPUError::Other(format!(
+            "Empty lattice of size {}×{}",
+            config.width, config.height
+        )));
+    }
+    let instance = wgpu::Instance::default();
+    let adapter = pollster::block_on(request_adapter(&instance, PowerPreference::HighPerformance))?;
+    let (device, queue) = pollster::block_on(request_device(&adapter))?;
+    let capabilities = capabilities(&adapter, &device);
+    capabilities.log();
+    // The generators of the cells make the largest buffer, so a lattice too large for the device is reported before any allocation. The number of replicas is reduced by the pipeline instead if they do not fit.
+    let cells = config.width as usize * config.height as usize;
+    capabilities.check_buffer(cells, size_of::<CellRng>())?;
+    // Catch the errors of wgpu, which are otherwise reported by a panic of the default handler.
+    device.push_error_scope(ErrorFilter::OutOfMemory);
+    device.push_error_scope(ErrorFilter::Validation);
+    let shader_module = create_shader_module(&device);
+    let parameters = Arc::new(IsingParameters::default());
+    parameters.temperature.store(config.temperature);
+    parameters.external_field.store(config.field);
+    parameters.init.store(config.init, Ordering::Relaxed);
+    let mut pipeline = IsingPipeline::new(
+        &device,
+        &queue,
+        &shader_module,
+        config.seed.0,
+        config.width,
+        config.height,
+        parameters,
+        config.precision,
+        config.replicas,
+    )?;
+    pipeline.thermalize(&device, &queue, config.thermalize);
+    let (vals, observables) = pipeline.run(&device, &queue, config.sweeps);
+    for _ in 0..2 {
+        if let Some(err) = pollster::block_on(device.pop_error_scope()) {
+            return Err(err.into());
+        }
+    }
+    let (width, height) = pipeline.size();
+    Ok(HeadlessResult {
+        width,
+        height,
+        seed: config.seed,
+        temperature: config.temperature,
+        field: config.field,
+        sweeps: config.sweeps,
+        vals,
+        observables,
+    })
+}
+
+/// Verify that 10 sweeps of a 64² lattice run without a window and that their results are written, skipped if there is no adapter.
+#[test]
+fn test_headless_smoke() {
+    let output = std::env::temp_dir().join("phase_headless_smoke");
+    let config = HeadlessConfig {
+        width: 64,
+        height: 64,
+        seed: Seed::from_u64(1),
+        sweeps: 10,
+        output: output.clone(),
+        ..HeadlessConfig::default()
+    };
+    let result = match run(&config) {
+        Err(WGPUError::NoAdapter) => {
+            eprintln!("No adapter available, skipping the headless smoke test.");
+            return;
+        }
+        result => result.unwrap(),
+    };
+    assert_eq!(result.vals.len(), 64 * 64);
+    assert!(result.vals.iter().all(|&v| v == 1.0 || v == -1.0));
+    assert!(result.observables.magnetization.abs() <= 1.0);
+    result.write(&output).unwrap();
+    let configuration = std::fs::read_to_string(output.join("configuration.csv")).unwrap();
+    assert_eq!(configuration.lines().count(), 64);
+    let observables = std::fs::read_to_string(output.join("observables.csv")).unwrap();
+    assert_eq!(observables.lines().count(), 2);
+    result.write(output.join("run.npy")).unwrap();
+    assert!(output.join("run.npy").exists());
+    assert!(output.join("run.csv").exists());
+    result.write(output.join("results.csv")).unwrap();
+    assert!(output.join("results.npy").exists());
+    let observables = std::fs::read_to_string(output.join("results.csv")).unwrap();
+    assert_eq!(observables.lines().count(), 2);
+}
+
+/// Verify that a configuration file sets up a headless run with the defaults for the settings it does not give, and that the parameters the headless mode does not support are refused.
+#[test]
+fn test_headless_from_config() {
+    let text = "simulation = \"ising\"\nsize = 64\n[parameters]\nT = 1.5\ninit = \"all up\"\nreplicas = \"4\"\n[schedule]\nsweeps = 20\n";
+    let config = HeadlessConfig::from_config(&Config::parse(text).unwrap()).unwrap();
+    assert_eq!((config.width, config.height), (64, 64));
+    assert_eq!(config.temperature, 1.5);
+    assert_eq!(config.init, kernel::INIT_UP);
+    assert_eq!(config.replicas, 4);
+    assert_eq!((config.thermalize, config.sweeps), (0, 20));
+    let error = |text: &str| {
+        HeadlessConfig::from_config(&Config::parse(text).unwrap())
+            .err()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(
+        error("simulation = \"ising\"\n[parameters]\ninti = \"random\""),
+        "parameters.inti: not supported by the headless mode, expected one of `T`, `h`, `init`, `replicas`, `half precision`"
+    );
+    assert_eq!(
+        error("simulation = \"ising\"\n[parameters]\ninit = \"hot\""),
+        "parameters.init: expected one of random, all up, all down, stripe, quasi-random, found \"hot\""
+    );
+    assert!(error("simulation = \"langevin\"").starts_with("simulation: "));
+}
@@ -0,0 +1,187 @@
+use std::path::PathBuf;
+
+use image::{DynamicImage, imageops::FilterType};
+
+/// How an image whose aspect ratio differs from the one of the lattice is fitted into it by [image_to_values].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageFit {
+    /// The image is scaled to fit in the lattice with its aspect ratio, and centered with margins of [ImageMapping::background].
+    Letterbox,
+    /// The image is scaled to the size of the lattice, distorting it.
+    Stretch,
+}
+
+/// Resampling of an image to the size of the lattice by [image_to_values].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageFilter {
+    /// Each cell takes the pixel nearest to it, which keeps the edges sharp.
+    Nearest,
+    /// Each cell interpolates the pixels around it linearly, which smooths the edges.
+    Bilinear,
+}
+
+/// Image loaded as the state of a lattice, decoded by [ImageSource::decode].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImageSource {
+    /// Image file at the path, on native.
+    Path(PathBuf),
+    /// Content of an image file, such as one chosen in the browser.
+    Bytes(Vec<u8>),
+}
+
+impl ImageSource {
+    /// Decode the image, in any of the formats supported, PNG or JPEG.
+    pub fn decode(&self) -> image::ImageResult<DynamicImage> {
+        match self {
+            ImageSource::Path(path) => image::open(path),
+            ImageSource::Bytes(bytes) => image::load_from_memory(bytes),
+        }
+    }
+}
+
+impl std::fmt::Display for ImageSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageSource::Path(path) => write!(f, "{}", path.display()),
+            ImageSource::Bytes(bytes) => write!(f, "an image of {} bytes", bytes.len()),
+        }
+    }
+}
+
+/// How the luminance of the pixels of an image is mapped to the values of the cells by [image_to_values].
+///
+/// The darkness of a pixel, 1 minus its luminance in [0,1], or the luminance itself if `invert` is set, gives the value `high` when it is 1 and `low` when it is 0. With a `threshold`, the values are only `low` or `high`, as for spins, depending on whether the darkness is above the threshold, and otherwise they are interpolated linearly, as for a continuous field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageMapping {
+    pub fit: ImageFit,
+    pub filter: ImageFilter,
+    pub threshold: Option<f32>,
+    pub invert: bool,
+    pub low: f32,
+    pub high: f32,
+    /// Value of the cells of the margins of a [ImageFit::Letterbox].
+    pub background: f32,
+}
+
+impl ImageMapping {
+    /// Mapping of the dark pixels, of luminance below one half, to spins up and of the light ones to spins down, like the default colors of the rendering of the Ising model, with margins of spins down.
+    pub fn spins(fit: ImageFit) -> Self {
+        ImageMapping {
+            fit,
+            filter: ImageFilter::Bilinear,
+            threshold: Some(0.5),
+            invert: false,
+            low: -1.0,
+            high: 1.0,
+            background: -1.0,
+        }
+    }
+    /// Value of a cell for the `luminance` of its pixel, between 0 and 255.
+    fn value(&self, luminance: u8) -> f32 {
+        let luminance = luminance as f32 / 255.0;
+        let darkness = if self.invert {
+            luminance
+        } else {
+            1.0 - luminance
+        };
+        match self.threshold {
+            Some(threshold) if darkness > threshold => self.high,
+            Some(_) => self.low,
+            None => self.low + (self.high - self.low) * darkness,
+        }
+    }
+}
+
+/// Values of the cells of a lattice of `width`×`height` cells given row by row from the bottom one, drawn from `image` fitted and mapped according to `mapping`. The top of the image is the top of the lattice.
+pub fn image_to_values(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    mapping: &ImageMapping,
+) -> Vec<f32> {
+    let luma = image.to_luma8();
+    let (image_width, image_height) = luma.dimensions();
+    let (scaled_width, scaled_height) = match mapping.fit {
+        ImageFit::Stretch => (width, height),
+        // The side of the image which is the largest relative to the lattice fills it, the ratios being compared without rounding.
+        ImageFit::Letterbox => {
+            if image_width as u64 * height as u64 >= image_height as u64 * width as u64 {
+                let scaled = image_height as u64 * width as u64 / image_width.max(1) as u64;
+                (width, (scaled as u32).clamp(1, height))
+            } else {
+                let scaled = image_width as u64 * height as u64 / image_height.max(1) as u64;
+                ((scaled as u32).clamp(1, width), height)
+            }
+        }
+    };
+    let filter = match mapping.filter {
+        ImageFilter::Nearest => FilterType::Nearest,
+        ImageFilter::Bilinear => FilterType::Triangle,
+    };
+    let scaled = image::imageops::resize(&luma, scaled_width, scaled_height, filter);
+    let (x0, y0) = ((width - scaled_width) / 2, (height - scaled_height) / 2);
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            // The rows of the image go from the top down whereas the rows of the lattice go from the bottom up.
+            let row = height - 1 - y;
+            let inside =
+                (x0..x0 + scaled_width).contains(&x) && (y0..y0 + scaled_height).contains(&row);
+            if inside {
+                mapping.value(scaled.get_pixel(x - x0, row - y0).0[0])
+            } else {
+                mapping.background
+            }
+        })
+        .collect()
+}
+
+/// Verify the fit of an image with another aspect ratio than the lattice, and the mapping of its luminance with a threshold, inverted, or to a continuous range.
+#[test]
+fn test_image_to_values() {
+    let image =
+        DynamicImage::ImageLuma8(image::GrayImage::from_raw(2, 2, vec![0, 0, 255, 255]).unwrap());
+    let spins = ImageMapping::spins(ImageFit::Letterbox);
+    assert_eq!(
+        image_to_values(&image, 2, 4, &spins),
+        vec![-1.0, -1.0, -1.0, -1.0, 1.0, 1.0, -1.0, -1.0]
+    );
+    let stretched = ImageMapping::spins(ImageFit::Stretch);
+    assert_eq!(
+        image_to_values(&image, 2, 4, &stretched),
+        vec![-1.0, -1.0, -1.0, -1.0, 1.0, 1.0, 1.0, 1.0]
+    );
+    assert_eq!(image_to_values(&image, 6, 2, &spins).len(), 12);
+    let inverted = ImageMapping {
+        invert: true,
+        background: 0.0,
+        ..spins
+    };
+    assert_eq!(
+        image_to_values(&image, 2, 4, &inverted),
+        vec![0.0, 0.0, 1.0, 1.0, -1.0, -1.0, 0.0, 0.0]
+    );
+
+    let gray = DynamicImage::ImageLuma8(image::GrayImage::from_raw(2, 1, vec![51, 204]).unwrap());
+    let field = ImageMapping {
+        fit: ImageFit::Stretch,
+        filter: ImageFilter::Nearest,
+        threshold: None,
+        invert: false,
+        low: -2.0,
+        high: 3.0,
+        background: 0.0,
+    };
+    let values = image_to_values(&gray, 4, 1, &field);
+    for (value, expected) in values.iter().zip([2.0, 2.0, -1.0, -1.0]) {
+        assert!((value - expected).abs() < 1e-5, "{values:?}");
+    }
+    let threshold = ImageMapping {
+        threshold: Some(0.5),
+        ..field
+    };
+    assert_eq!(
+        image_to_values(&gray, 4, 1, &threshold),
+        vec![3.0, 3.0, -2.0, -2.0]
+    );
+}
@@ -1,5 +1,18 @@
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cli;
+pub mod config;
 pub mod error;
+pub mod export;
 pub mod gpu;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod headless;
+pub mod import;
+pub mod quasi_random;
+pub mod reference;
+pub mod seed;
 pub mod simulation;
+pub mod time;
 
 pub const SPIRV: &[u8] = include_bytes!(env!("KERNEL_SPV_PATH"));
+
+pub use gpu::entry_points::kernel_entry_points;
@@ -1,6 +1,150 @@
-use phase::simulation::ising::Ising;
+#[cfg(not(target_arch = "wasm32"))]
+use clap::Parser;
+#[cfg(feature = "gui")]
 use phase::simulation::with_egui;
+#[cfg(all(feature = "gui", not(target_arch = "wasm32")))]
+use phase::simulation::{
+    Simulation, SimulationSetup,
+    custom::CustomKernel,
+    settings::{apply_query, parse_query, setup_from_config},
+};
+#[cfg(not(target_arch = "wasm32"))]
+use phase::{
+    cli::{Cli, Selection, names},
+    config::{Config, Schedule},
+    headless::{self, HeadlessConfig},
+};
 
+/// Print the error of the command line and exit.
+#[cfg(not(target_arch = "wasm32"))]
+fn fail(err: impl std::fmt::Display) -> ! {
+    eprintln!("error: {err}");
+    std::process::exit(1);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    let cli = Cli::parse();
+    let names = names();
+    if cli.list {
+        for name in names {
+            println!("{name}");
+        }
+        return;
+    }
+    if cli.config.is_some() && !cli.simulations.is_empty() {
+        fail("--config gives the simulation, which cannot also be named on the command line");
+    }
+    let config = cli.config.as_deref().map(|path| {
+        let config = std::fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| Config::parse(&text).map_err(|err| err.to_string()));
+        config.unwrap_or_else(|err| fail(format!("{}: {err}", path.display())))
+    });
+    let selection = cli.select(&names).unwrap_or_else(|err| err.exit());
+    if selection.run.headless {
+        env_logger::init();
+        let run = match &config {
+            Some(config) => HeadlessConfig::from_config(config)
+                .map(|run| selection.run.override_config(run))
+                .map_err(|err| err.to_string()),
+            None => selection.headless_config(),
+        };
+        let run = run.unwrap_or_else(|err| fail(err));
+        let result = headless::run(&run).and_then(|result| {
+            result.write(&run.output)?;
+            Ok(result)
+        });
+        match result {
+            Ok(result) => println!(
+                "{} sweeps of {}×{} at T = {}, h = {} (seed {}): m = {:+.4} ± {:.4}, E = {:+.4}",
+                result.sweeps,
+                result.width,
+                result.height,
+                result.temperature,
+                result.field,
+                result.seed,
+                result.observables.magnetization,
+                result.observables.magnetization_error,
+                result.observables.energy
+            ),
+            Err(err) => fail(format!("Headless run failed: {err}")),
+        }
+        return;
+    }
+    selection.check_gui().unwrap_or_else(|err| fail(err));
+    let headless_only = config
+        .as_ref()
+        .is_some_and(|config| config.output.is_some() || config.schedule != Schedule::default());
+    if headless_only {
+        eprintln!(
+            "warning: the schedule and the output of the configuration are only used with --headless"
+        );
+    }
+    gui(selection, config);
+}
+
+/// Start the selected simulations side by side, with the parameters of the command line applied before the first frame, or the simulation of the `config` file if any.
+#[cfg(all(feature = "gui", not(target_arch = "wasm32")))]
+fn gui(selection: Selection, config: Option<Config>) {
+    let constructors = phase::simulation::simulations();
+    // The simulations named on the command line are replaced by the one of the configuration file.
+    let selected: Vec<SimulationSetup> = match config {
+        Some(config) => {
+            vec![setup_from_config(&config, &constructors).unwrap_or_else(|err| fail(err))]
+        }
+        None => selection
+            .simulations
+            .into_iter()
+            .map(|(name, args)| {
+                let (_, new) = constructors.iter().find(|(n, _)| *n == name).unwrap();
+                let mut simulation = new();
+                if let Some(seed) = args.seed {
+                    simulation.set_seed(seed);
+                }
+                simulation
+                    .apply_options(&args.options())
+                    .unwrap_or_else(|err| fail(format!("{name}: {err}")));
+                let mut size = args.size;
+                if let Some(params) = &args.params {
+                    let pairs = parse_query(params);
+                    for warning in apply_query(&mut *simulation, &mut size, &pairs) {
+                        eprintln!("warning: {name}: {warning}");
+                    }
+                }
+                SimulationSetup { simulation, size }
+            })
+            .collect(),
+    };
+    let simulations = selected
+        .into_iter()
+        .chain(selection.run.spv.iter().map(|path| {
+            let simulation: Box<dyn Simulation> = Box::new(
+                CustomKernel::load(path)
+                    .unwrap_or_else(|err| fail(format!("{}: {err}", path.display()))),
+            );
+            SimulationSetup::from(simulation)
+        }))
+        .collect();
+    with_egui(simulations);
+}
+
+#[cfg(all(not(feature = "gui"), not(target_arch = "wasm32")))]
+fn gui(_selection: Selection, _config: Option<Config>) {
+    fail("built without the \"gui\" feature: only the --headless mode is available");
+}
+
+/// Start the simulation configured by the query of the URL of the page, such as `?sim=ising&size=512&T=2.269&seed=42`, the Ising model with its defaults if there is none.
+#[cfg(target_arch = "wasm32")]
 fn main() {
-    with_egui(Box::new(Ising::new()));
+    let query = web_sys::window()
+        .and_then(|window| window.location().search().ok())
+        .unwrap_or_default();
+    let (setup, warnings) =
+        phase::simulation::settings::setup_from_query(&query, &phase::simulation::simulations());
+    with_egui(vec![setup]);
+    // The logger is only initialized by `with_egui`.
+    for warning in warnings {
+        log::warn!("{warning}");
+    }
 }
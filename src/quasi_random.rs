@@ -0,0 +1,96 @@
+/// Radical inverse of `index` in `base`: its digits in `base` mirrored around the point, in [0,1).
+fn radical_inverse(mut index: u64, base: u64) -> f64 {
+    let mut inverse = 0.0;
+    let mut scale = 1.0;
+    while index > 0 {
+        scale /= base as f64;
+        inverse += scale * (index % base) as f64;
+        index /= base;
+    }
+    inverse
+}
+
+/// Two dimensional Halton sequence, with the bases 2 and 3, shifted modulo 1 by a random `rotation` (Cranley–Patterson rotation) so that different rotations give different sequences with the same low discrepancy.
+///
+/// The points of a low-discrepancy sequence fill the unit square more evenly than independent random points, which cluster and leave holes. This only suits the initialization of a lattice: the Monte Carlo updates need independent random numbers, which are drawn from the pseudo-random generators of the cells.
+pub struct Halton2 {
+    index: u64,
+    rotation: [f64; 2],
+}
+
+impl Halton2 {
+    /// Sequence rotated by the fractions of 2^32 `rotation`, such as a random key.
+    pub fn new(rotation: [u32; 2]) -> Self {
+        Halton2 {
+            // The first point of the sequence is the origin, which is skipped.
+            index: 1,
+            rotation: rotation.map(|r| r as f64 / (1u64 << 32) as f64),
+        }
+    }
+}
+
+impl Iterator for Halton2 {
+    type Item = [f64; 2];
+    fn next(&mut self) -> Option<[f64; 2]> {
+        let point = [
+            radical_inverse(self.index, 2) + self.rotation[0],
+            radical_inverse(self.index, 3) + self.rotation[1],
+        ]
+        .map(f64::fract);
+        self.index += 1;
+        Some(point)
+    }
+}
+
+/// Spins of a `width`×`height` lattice given row by row, half of them up and the others down, the up spins being placed at the cells of the successive points of the [Halton2] sequence rotated by `rotation`. The points falling into a cell already up are skipped.
+///
+/// Unlike independent random signs, whose local magnetization fluctuates like the inverse of the square root of the size of the region, the spins up are spread evenly, without clusters nor holes, while not following a regular pattern like a checkerboard.
+pub fn quasi_random_spins(width: u32, height: u32, rotation: [u32; 2]) -> Vec<f32> {
+    let (width, height) = (width as usize, height as usize);
+    let mut spins = vec![-1.0; width * height];
+    let mut up = 0;
+    for [x, y] in Halton2::new(rotation) {
+        if up >= spins.len() / 2 {
+            break;
+        }
+        let cell = (x * width as f64) as usize + width * (y * height as f64) as usize;
+        if spins[cell] < 0.0 {
+            spins[cell] = 1.0;
+            up += 1;
+        }
+    }
+    spins
+}
+
+/// Verify the first points of the Halton sequence, and that the quasi-random spins have a zero magnetization spread evenly: every 4×4 block holds between 5 and 11 spins up, when independent random signs would give less or more in about one block out of 13.
+#[test]
+fn test_quasi_random_spins() {
+    let points: Vec<_> = Halton2::new([0, 0]).take(3).collect();
+    let expected = [[0.5, 1.0 / 3.0], [0.25, 2.0 / 3.0], [0.75, 1.0 / 9.0]];
+    for (point, expected) in points.iter().zip(expected) {
+        assert!((point[0] - expected[0]).abs() < 1e-12 && (point[1] - expected[1]).abs() < 1e-12);
+    }
+    let (width, height) = (64, 48);
+    for rotation in [[0, 0], [0x9E37_79B9, 0x7F4A_7C15]] {
+        let spins = quasi_random_spins(width, height, rotation);
+        assert_eq!(spins.iter().sum::<f32>(), 0.0);
+        for by in 0..height / 4 {
+            for bx in 0..width / 4 {
+                let up = (0..16)
+                    .filter(|k| {
+                        let (x, y) = (bx * 4 + k % 4, by * 4 + k / 4);
+                        spins[(x + width * y) as usize] > 0.0
+                    })
+                    .count();
+                assert!(
+                    (5..=11).contains(&up),
+                    "{up} spins up in the block ({bx}, {by})"
+                );
+            }
+        }
+    }
+    assert_ne!(
+        quasi_random_spins(width, height, [0, 0]),
+        quasi_random_spins(width, height, [1 << 31, 1 << 30])
+    );
+}
@@ -0,0 +1,295 @@
+use std::f64::consts::PI;
+
+use gpu_random::{philox::Philox4x32, sign_from_u32, stream_key, uniform_from_u32};
+use kernel::PROPOSAL_FLIP;
+
+/// Order in which [ReferenceIsing::step] updates the spins during a sweep.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UpdateOrder {
+    /// Every spin is updated from the previous state of its neighbors, like in the `ising_step` kernel, so that the configurations can be compared with the ones of the GPU. Neighboring spins flipping together, this dynamics does not sample the Boltzmann distribution exactly.
+    Simultaneous,
+    /// The cells whose coordinates have an even sum are updated first, then the other ones from the new state of their neighbors. Each half being updated at once without any of its cells being neighbors, this is a valid single spin dynamics which samples the Boltzmann distribution, provided that the width and the height are even.
+    Checkerboard,
+}
+
+/// Single-threaded Metropolis dynamics of the Ising model on the CPU, with periodic boundaries and without pinned rows nor drive.
+///
+/// The random numbers are drawn from the same [Philox4x32] streams as the cells of the [IsingPipeline](crate::gpu::physics::ising::IsingPipeline) with the default generator, at the same counters, so that a lattice of the same size and seed goes through the same configurations as on the GPU with [UpdateOrder::Simultaneous] and [RULE_METROPOLIS](kernel::RULE_METROPOLIS). It is meant to validate the kernels on small lattices, not to be fast.
+pub struct ReferenceIsing {
+    width: u32,
+    height: u32,
+    pub temperature: f32,
+    pub field: f32,
+    pub coupling: f32,
+    /// Candidate of the updates, one of the `PROPOSAL_*` constants of the kernel.
+    pub proposal: u32,
+    pub order: UpdateOrder,
+    /// Values of the spins, row by row.
+    spins: Vec<f32>,
+    rngs: Vec<Philox4x32>,
+    sweep: u32,
+}
+
+impl ReferenceIsing {
+    /// Lattice of `width`×`height` random spins drawn like the [INIT_RANDOM](kernel::INIT_RANDOM) initialization of a new pipeline with the same `seed`, with the default parameters of the [IsingParameters](crate::gpu::physics::ising::IsingParameters) and the [UpdateOrder::Simultaneous] order of the GPU.
+    pub fn new(seed: u128, width: u32, height: u32) -> Self {
+        let rngs: Vec<_> = (0..width as u64 * height as u64)
+            .map(|i| {
+                let mut rng = Philox4x32::new(seed, 0);
+                rng.set_key(stream_key(seed, i));
+                rng
+            })
+            .collect();
+        // The initialization is the kernel 1 of the random counters, at the sweep 0.
+        let spins = rngs
+            .iter()
+            .map(|rng| sign_from_u32(rng.block_at([0, 0, 0, 1])[0]))
+            .collect();
+        ReferenceIsing {
+            width,
+            height,
+            temperature: 2.2691853142,
+            field: 0.0,
+            coupling: 1.0,
+            proposal: kernel::PROPOSAL_RESAMPLE,
+            order: UpdateOrder::Simultaneous,
+            spins,
+            rngs,
+            sweep: 0,
+        }
+    }
+    /// Set every spin to `value`, such as 1 for a cold start.
+    pub fn fill(&mut self, value: f32) {
+        self.spins.fill(value);
+    }
+    /// Values of the spins, row by row.
+    pub fn spins(&self) -> &[f32] {
+        &self.spins
+    }
+    /// Number of sweeps performed since the creation of the lattice.
+    pub fn sweep(&self) -> u32 {
+        self.sweep
+    }
+    /// Sum of the 4 neighbors of the cell at (`x`, `y`).
+    fn neighbors(&self, x: usize, y: usize) -> f32 {
+        let (w, h) = (self.width as usize, self.height as usize);
+        self.spins[(x + w - 1) % w + w * y]
+            + self.spins[(x + 1) % w + w * y]
+            + self.spins[x + w * ((y + 1) % h)]
+            + self.spins[x + w * ((y + h - 1) % h)]
+    }
+    /// New value of the spin `v` of the cell `i` whose neighbors sum to `neighbors`, with the same random numbers and the same arithmetic as `update_spin` in the kernel.
+    fn update(&self, i: usize, v: f32, neighbors: f32) -> f32 {
+        let rng = &self.rngs[i];
+        let candidate = if self.proposal == PROPOSAL_FLIP {
+            -v
+        } else {
+            sign_from_u32(rng.block_at([0, 0, self.sweep, 0])[0])
+        };
+        let s = -self.coupling * neighbors;
+        let energy = |v: f32| v * s - self.field * v;
+        let p = ((energy(v) - energy(candidate)) / self.temperature)
+            .exp()
+            .min(1.0);
+        if uniform_from_u32(rng.block_at([1, 0, self.sweep, 0])[0]) < p {
+            candidate
+        } else {
+            v
+        }
+    }
+    /// Perform one sweep, which proposes a new value to every spin once in the [UpdateOrder] of the lattice.
+    pub fn step(&mut self) {
+        let (w, h) = (self.width as usize, self.height as usize);
+        match self.order {
+            UpdateOrder::Simultaneous => {
+                self.spins = (0..w * h)
+                    .map(|i| self.update(i, self.spins[i], self.neighbors(i % w, i / w)))
+                    .collect();
+            }
+            UpdateOrder::Checkerboard => {
+                for parity in 0..2 {
+                    for i in (0..w * h).filter(|i| (i % w + i / w) % 2 == parity) {
+                        let neighbors = self.neighbors(i % w, i / w);
+                        self.spins[i] = self.update(i, self.spins[i], neighbors);
+                    }
+                }
+            }
+        }
+        self.sweep = self.sweep.wrapping_add(1);
+    }
+    /// Perform `sweeps` sweeps, see [ReferenceIsing::step].
+    pub fn run(&mut self, sweeps: usize) {
+        for _ in 0..sweeps {
+            self.step();
+        }
+    }
+    /// Mean value of the spins.
+    pub fn magnetization(&self) -> f64 {
+        self.spins.iter().map(|&s| s as f64).sum::<f64>() / self.spins.len() as f64
+    }
+    /// Energy per spin `-(J Σ s_i s_j + h Σ s_i)/N`, the first sum running over the pairs of neighbors.
+    pub fn energy(&self) -> f64 {
+        let w = self.width as usize;
+        let bonds: f64 = (0..self.spins.len())
+            .map(|i| {
+                let (x, y) = (i % w, i / w);
+                let right = self.spins[(x + 1) % w + w * y];
+                let up = self.spins[x + w * ((y + 1) % self.height as usize)];
+                (self.spins[i] * (right + up)) as f64
+            })
+            .sum();
+        -(self.coupling as f64 * bonds
+            + self.field as f64 * self.magnetization() * self.spins.len() as f64)
+            / self.spins.len() as f64
+    }
+}
+
+/// Complete elliptic integral of the first kind `K(k)`, computed with the arithmetic-geometric mean of 1 and `√(1 - k²)`.
+fn elliptic_k(k: f64) -> f64 {
+    let (mut a, mut b) = (1.0, (1.0 - k * k).sqrt());
+    while (a - b).abs() > 1e-15 * a {
+        (a, b) = ((a + b) / 2.0, (a * b).sqrt());
+    }
+    PI / (2.0 * a)
+}
+
+/// Exact energy per spin of the infinite square lattice without field at `temperature`, in units of `J > 0` (Onsager).
+pub fn onsager_energy(temperature: f64) -> f64 {
+    let twice_beta = 2.0 / temperature;
+    let k = 2.0 * twice_beta.sinh() / twice_beta.cosh().powi(2);
+    let tanh = twice_beta.tanh();
+    -(1.0 + 2.0 / PI * (2.0 * tanh * tanh - 1.0) * elliptic_k(k)) / tanh
+}
+
+/// Exact spontaneous magnetization of the infinite square lattice without field at `temperature`, in units of `J > 0` (Onsager and Yang), which vanishes above the critical temperature `2/ln(1 + √2)`.
+pub fn onsager_magnetization(temperature: f64) -> f64 {
+    let sinh = (2.0 / temperature).sinh();
+    (1.0 - sinh.powi(-4)).max(0.0).powf(0.125)
+}
+
+/// Spins of a `width`×`height` lattice of the [IsingPipeline](crate::gpu::physics::ising::IsingPipeline) after `sweeps` sweeps at T = 2 with the Metropolis rule, with the tiled step if `tiled`, or `None` if there is no adapter.
+#[cfg(all(
+    test,
+    not(any(feature = "small_rng", feature = "xoshiro_rng", feature = "pcg_rng"))
+))]
+fn gpu_spins(seed: u128, width: u32, height: u32, sweeps: usize, tiled: bool) -> Option<Vec<f32>> {
+    use std::sync::{Arc, atomic::Ordering};
+
+    use crate::{
+        error::WGPUError,
+        gpu::{
+            adapter::{create_shader_module, request_adapter, request_device},
+            physics::{
+                Precision,
+                ising::{IsingParameters, IsingPipeline},
+            },
+        },
+    };
+
+    let instance = wgpu::Instance::default();
+    let adapter = match pollster::block_on(request_adapter(
+        &instance,
+        wgpu::PowerPreference::HighPerformance,
+    )) {
+        Err(WGPUError::NoAdapter) => return None,
+        adapter => adapter.unwrap(),
+    };
+    let (device, queue) = pollster::block_on(request_device(&adapter)).unwrap();
+    let shader_module = create_shader_module(&device);
+    let parameters = Arc::new(IsingParameters::default());
+    parameters.temperature.store(2.0);
+    parameters
+        .rule
+        .store(kernel::RULE_METROPOLIS, Ordering::Relaxed);
+    parameters.tiled.store(tiled, Ordering::Relaxed);
+    let mut pipeline = IsingPipeline::new(
+        &device,
+        &queue,
+        &shader_module,
+        seed,
+        width,
+        height,
+        parameters,
+        Precision::F32,
+        1,
+    )
+    .unwrap();
+    Some(pipeline.run(&device, &queue, sweeps).0)
+}
+
+/// Verify that the [IsingPipeline](crate::gpu::physics::ising::IsingPipeline) goes through exactly the same configuration as the reference after 100 sweeps of a 16×16 lattice with the Metropolis rule, skipped if there is no adapter.
+#[cfg(not(any(feature = "small_rng", feature = "xoshiro_rng", feature = "pcg_rng")))]
+#[test]
+fn test_reference_matches_gpu() {
+    let (width, height, sweeps) = (16, 16, 100);
+    let seed = crate::seed::Seed::from_u64(7).0;
+    let Some(vals) = gpu_spins(seed, width, height, sweeps, false) else {
+        eprintln!("No adapter available, skipping the comparison with the reference.");
+        return;
+    };
+
+    let mut reference = ReferenceIsing::new(seed, width, height);
+    reference.temperature = 2.0;
+    reference.run(sweeps);
+    let mismatch = vals.iter().zip(reference.spins()).position(|(a, b)| a != b);
+    assert_eq!(
+        mismatch, None,
+        "first difference at the cell {mismatch:?} after {sweeps} sweeps"
+    );
+}
+
+/// Verify that a 256×128 lattice and its transpose go through the same configurations on the GPU, with the naive and the tiled steps, as the reference, whose neighbors wrap around each axis by its own length, skipped if there is no adapter.
+#[cfg(not(any(feature = "small_rng", feature = "xoshiro_rng", feature = "pcg_rng")))]
+#[test]
+fn test_rectangular_lattice() {
+    let sweeps = 50;
+    let seed = crate::seed::Seed::from_u64(11).0;
+    for (width, height) in [(256, 128), (128, 256)] {
+        let mut reference = ReferenceIsing::new(seed, width, height);
+        reference.temperature = 2.0;
+        reference.run(sweeps);
+        for tiled in [false, true] {
+            let Some(vals) = gpu_spins(seed, width, height, sweeps, tiled) else {
+                eprintln!("No adapter available, skipping the rectangular lattices.");
+                return;
+            };
+            assert_eq!(vals.len(), (width * height) as usize);
+            let mismatch = vals.iter().zip(reference.spins()).position(|(a, b)| a != b);
+            assert_eq!(
+                mismatch, None,
+                "first difference of the {width}×{height} lattice (tiled: {tiled}) at the cell {mismatch:?}"
+            );
+        }
+    }
+}
+
+/// Verify that the energy and the magnetization of a 32×32 lattice updated in the checkerboard order agree with the exact values of Onsager in the ordered phase at T = 1.5 and T = 2, and in the disordered phase at T = 3.5 where only a small magnetization of finite size remains.
+#[test]
+fn test_reference_onsager() {
+    let (thermalize, sweeps) = (200, 2000);
+    for temperature in [1.5, 2.0, 3.5] {
+        let mut reference = ReferenceIsing::new(1, 32, 32);
+        reference.temperature = temperature as f32;
+        reference.proposal = PROPOSAL_FLIP;
+        reference.order = UpdateOrder::Checkerboard;
+        reference.fill(1.0);
+        reference.run(thermalize);
+        let (mut energy, mut magnetization) = (0.0, 0.0);
+        for _ in 0..sweeps {
+            reference.step();
+            energy += reference.energy() / sweeps as f64;
+            magnetization += reference.magnetization().abs() / sweeps as f64;
+        }
+        let expected = onsager_energy(temperature);
+        assert!(
+            (energy - expected).abs() < 0.01,
+            "E({temperature}) = {energy} instead of {expected}"
+        );
+        let expected = onsager_magnetization(temperature);
+        let tolerance = if expected > 0.0 { 0.01 } else { 0.1 };
+        assert!(
+            (magnetization - expected).abs() < tolerance,
+            "|m|({temperature}) = {magnetization} instead of {expected}"
+        );
+    }
+}
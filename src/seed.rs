@@ -0,0 +1,77 @@
+use std::{fmt, num::ParseIntError, str::FromStr};
+
+use gpu_random::splitmix64;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+
+/// Seed of the random number generators of a simulation. It is displayed and parsed as 32 hexadecimal digits, so that a run can be reproduced exactly by entering the seed it was started with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Seed(pub u128);
+
+impl Seed {
+    /// Draw a seed from the entropy source of the operating system (or of the browser on the web).
+    pub fn from_entropy() -> Self {
+        let mut bytes = [0; 16];
+        getrandom::fill(&mut bytes).expect("No entropy source available.");
+        Seed(u128::from_le_bytes(bytes))
+    }
+    /// Expand a 64 bits `seed` into a full seed with two steps of SplitMix64, so that small seeds such as 1 or 2 give unrelated seeds.
+    pub fn from_u64(seed: u64) -> Self {
+        let mut x = seed;
+        let lo = splitmix64(&mut x) as u128;
+        let hi = splitmix64(&mut x) as u128;
+        Seed(lo | (hi << 64))
+    }
+}
+
+impl fmt::Display for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl FromStr for Seed {
+    type Err = ParseIntError;
+    /// Parse a seed written in hexadecimal, with an optional `0x` prefix.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let digits = s
+            .strip_prefix("0x")
+            .or_else(|| s.strip_prefix("0X"))
+            .unwrap_or(s);
+        u128::from_str_radix(digits, 16).map(Seed)
+    }
+}
+
+impl Serialize for Seed {
+    /// Written as its display, so that a seed does not depend on the support of 128 bits integers by the format.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Seed {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse()
+            .map_err(|err| de::Error::custom(format!("invalid seed \"{text}\": {err}")))
+    }
+}
+
+/// Verify that a seed is parsed back from its display, and that invalid texts are rejected.
+#[test]
+fn test_seed_hex() {
+    for seed in [
+        Seed(0),
+        Seed(u128::MAX),
+        Seed::from_u64(1),
+        Seed::from_entropy(),
+    ] {
+        assert_eq!(seed.to_string().len(), 32);
+        assert_eq!(seed.to_string().parse(), Ok(seed));
+    }
+    assert_eq!(" 0x2a ".parse(), Ok(Seed(42)));
+    assert_ne!(Seed::from_u64(1), Seed::from_u64(2));
+    assert!("".parse::<Seed>().is_err());
+    assert!("xyz".parse::<Seed>().is_err());
+    assert!("1".repeat(33).parse::<Seed>().is_err());
+}
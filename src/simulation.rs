@@ -1,30 +1,165 @@
 use std::ops::RangeInclusive;
+#[cfg(feature = "gui")]
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 
+#[cfg(feature = "gui")]
+use custom::CustomKernel;
+#[cfg(feature = "gui")]
+use dropped::{DroppedKind, Toasts, dropped_bytes, dropped_name};
+#[cfg(feature = "gui")]
 use egui::Frame;
+#[cfg(feature = "gui")]
 use egui_wgpu::RenderState;
-use instant::SystemTime;
+#[cfg(feature = "gui")]
+use kernel::{FILTER_LINEAR, FILTER_NEAREST, RenderCtx};
+#[cfg(feature = "gui")]
+use plots::ObservablePlots;
+#[cfg(feature = "gui")]
+use recorder::Recorder;
+#[cfg(feature = "gui")]
 use render_square::RenderSquare;
-use wgpu::ShaderModule;
+#[cfg(feature = "gui")]
+use replay::{Replay, ReplayAction, ReplayLog};
+#[cfg(feature = "gui")]
+use settings::{Change, Settings};
+
+#[cfg(feature = "gui")]
+use crate::{
+    config::{LatticeSize, Value},
+    error::{ParameterError, WGPUError},
+    gpu::{
+        capabilities::{Capabilities, capabilities, clamp_lattice},
+        diagnostics::{RngCheck, RngSelfTest},
+        kernel_module::{KernelModule, SpirvFile},
+    },
+    import::ImageSource,
+    seed::Seed,
+};
 
 pub mod atomic_f32;
+#[cfg(feature = "gui")]
+pub mod custom;
+#[cfg(feature = "gui")]
+pub mod dropped;
+#[cfg(feature = "gui")]
+pub mod image_loader;
+#[cfg(feature = "gui")]
 pub mod ising;
+#[cfg(feature = "gui")]
+pub mod langevin;
+#[cfg(feature = "gui")]
+pub mod plots;
+pub mod protocol;
+#[cfg(feature = "gui")]
+pub mod recorder;
+#[cfg(feature = "gui")]
 pub mod render_square;
+#[cfg(feature = "gui")]
+pub mod replay;
+#[cfg(feature = "gui")]
+pub mod settings;
 
 /// Enumeration of the possible parameters that a simulation needs to display inside the egui UI.
+///
+/// Most parameters are live: a change of their value, such as the temperature, is applied in place by the next steps of the physics. The structural parameters instead describe the state itself, such as the initialization or the boundary conditions, which a change leaves stale: the toggles and choices with `reset_on_change` make the GUI reset the state of the physics when they change, see [Physics::reset](crate::gpu::physics::Physics::reset). The parameters which the physics cannot change at all, such as the storage precision, recreate it through [Simulation::take_restart] instead.
 pub enum Parameter {
     Slider {
         tag: &'static str,
         value: f32,
         logarithmic: bool,
         range: RangeInclusive<f32>,
+        /// Help shown when the slider is hovered.
+        tooltip: Option<&'static str>,
+        /// Unit of the value, appended to the tag in the label of the slider.
+        unit: Option<&'static str>,
     },
     Toggle {
         tag: &'static str,
         enable: bool,
+        /// Whether the state of the physics is reset when the toggle changes.
+        reset_on_change: bool,
     },
     Button {
         tag: &'static str,
     },
+    Choice {
+        tag: &'static str,
+        options: Vec<&'static str>,
+        selected: usize,
+        /// Whether the state of the physics is reset when another option is selected.
+        reset_on_change: bool,
+    },
+    /// Text box to enter an exact value. The value is sent once the text box loses the focus (or enter is pressed), and the parameters are then displayed again from [Simulation::egui_parameters], so that a text that the simulation failed to parse is replaced by the last valid value.
+    Text {
+        tag: &'static str,
+        value: String,
+    },
+    /// Color picker of an opaque color in sRGB.
+    Color {
+        tag: &'static str,
+        rgb: [u8; 3],
+    },
+}
+
+impl Parameter {
+    /// Tag identifying the parameter in its [UpadeParameter].
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Parameter::Slider { tag, .. }
+            | Parameter::Toggle { tag, .. }
+            | Parameter::Button { tag }
+            | Parameter::Choice { tag, .. }
+            | Parameter::Text { tag, .. }
+            | Parameter::Color { tag, .. } => tag,
+        }
+    }
+    /// Whether a change of the parameter resets the state of the physics, see [Parameter].
+    pub fn reset_on_change(&self) -> bool {
+        match self {
+            Parameter::Toggle {
+                reset_on_change, ..
+            }
+            | Parameter::Choice {
+                reset_on_change, ..
+            } => *reset_on_change,
+            _ => false,
+        }
+    }
+}
+
+/// Parameters of a [Simulation] given on the command line, `None` (or `false`) meaning that the option was not given, see [Simulation::apply_options].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SimulationOptions {
+    pub temperature: Option<f32>,
+    pub field: Option<f32>,
+    pub replicas: Option<u32>,
+    pub half: bool,
+}
+
+impl SimulationOptions {
+    /// Error naming the options which were given but are not among the `supported` ones, such as `"--temperature"`.
+    pub fn unsupported(&self, supported: &[&str]) -> Result<(), String> {
+        let given = [
+            ("--temperature", self.temperature.is_some()),
+            ("--field", self.field.is_some()),
+            ("--replicas", self.replicas.is_some()),
+            ("--half", self.half),
+        ];
+        let unsupported = given
+            .iter()
+            .filter(|(option, given)| *given && !supported.contains(option))
+            .map(|(option, _)| *option)
+            .collect::<Vec<_>>();
+        if unsupported.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("unsupported option(s) {}", unsupported.join(", ")))
+        }
+    }
 }
 
 /// Enumeration for updating the value of the parameters from [Parameter] once they have been changed in the egui UI. This enum is provided to the [Simulation] through its [Simulation::update_parameter] method.
@@ -32,216 +167,1760 @@ pub enum UpadeParameter {
     Slider { tag: &'static str, value: f32 },
     Toggle { tag: &'static str, enable: bool },
     Button { tag: &'static str },
+    Choice { tag: &'static str, selected: usize },
+    Text { tag: &'static str, value: String },
+    Color { tag: &'static str, rgb: [u8; 3] },
 }
 
 /// Trait to define the behavior of a simulation with respect to the egui event loop.
+#[cfg(feature = "gui")]
 pub trait Simulation: Send + 'static {
+    /// Name of the simulation in the registry of [simulations].
+    fn name(&self) -> &'static str;
     /// Provides a list of parameter to be desplayed by egui.
     fn egui_parameters(&self) -> Vec<Parameter>;
-    /// Update a parameter which was changed in the egui UI.
-    fn update_parameter(&mut self, update: UpadeParameter);
+    /// Update a parameter which was changed in the egui UI. An unknown tag or an invalid value is reported as an error, which the GUI logs.
+    fn update_parameter(&mut self, update: UpadeParameter) -> Result<(), ParameterError>;
+    /// Short description of the model, such as its Hamiltonian and update rule, displayed in a collapsible panel above the parameters. Nothing is displayed if empty.
+    fn description(&self) -> &'static str {
+        ""
+    }
+    /// Display additional content such as plots in the egui UI, below the parameters.
+    fn egui_panel(&mut self, _ui: &mut egui::Ui) {}
+    /// Seed of the random number generators used by [Simulation::physics]. The physics only depends on the seed and the size of the lattice, so that a run can be reproduced exactly.
+    fn seed(&self) -> Seed;
+    /// Change the seed used by the next calls to [Simulation::physics].
+    fn set_seed(&mut self, seed: Seed);
+    /// Whether the simulation is paused, given to the physics with the [FrameInfo](crate::gpu::physics::FrameInfo) of each frame. False by default.
+    fn paused(&self) -> bool {
+        false
+    }
+    /// Whether a parameter which cannot be changed on the fly, such as the storage precision, was modified since the last call, in which case the physics is recreated.
+    fn take_restart(&mut self) -> bool {
+        false
+    }
+    /// Apply the `options` given on the command line, before the first frame. The options which the simulation does not support are rejected, which is the case of all of them by default.
+    fn apply_options(&mut self, options: &SimulationOptions) -> Result<(), String> {
+        options.unsupported(&[])
+    }
+    /// Load the image of `source` as the state of the physics, like the `load image` button, for instance when it is dropped on the window. The simulations which cannot load images return an error, which is the default.
+    fn load_image(&mut self, _source: ImageSource) -> Result<(), String> {
+        Err(format!(
+            "The {} simulation cannot load images.",
+            self.name()
+        ))
+    }
+    /// SPIR-V module read at run time whose kernels are given to [Simulation::physics] and to the [RenderSquare] instead of the embedded ones, `None` by default. The module is created again when the file is read again, see [SpirvFile::id].
+    fn spirv_file(&self) -> Option<&SpirvFile> {
+        None
+    }
     /// Contrust the physics pipeline in the GPU and return a [Physics](crate::gpu::physics::Physics) needed to update the physics (run the compute pipeline) and setup the rendering inside egui with [RenderSquare].
     fn physics(
         &self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         shader_module: &wgpu::ShaderModule,
-        seed: u128,
         width: u32,
         height: u32,
-    ) -> Box<dyn crate::gpu::physics::Physics>;
+    ) -> Result<Box<dyn crate::gpu::physics::Physics>, WGPUError>;
+}
+/// Name and constructor of a [Simulation].
+#[cfg(feature = "gui")]
+pub type SimulationEntry = (&'static str, fn() -> Box<dyn Simulation>);
+
+/// Name and constructor of each available [Simulation], in the order in which they are listed on the command line and in the selector of the GUI. Each simulation module declares its own `ENTRY`, so that adding a model only takes adding its entry here.
+#[cfg(feature = "gui")]
+pub fn simulations() -> [SimulationEntry; 2] {
+    [ising::ENTRY, langevin::ENTRY]
+}
+
+/// A [Simulation] to start, with the size of its lattice if it is fixed instead of following the size of the canvas.
+#[cfg(feature = "gui")]
+pub struct SimulationSetup {
+    pub simulation: Box<dyn Simulation>,
+    pub size: Option<LatticeSize>,
 }
-/// Strut that handles the setup of egui and wgpu, and then starts the [Simulation] and handles the update of the different parameters (see [Parameter]). The rendering of the simulation is performed with the [CallbackTrait](egui_wgpu::CallbackTrait) from [egui_wgpu] used by the [RenderSquare] helper.
+
+#[cfg(feature = "gui")]
+impl From<Box<dyn Simulation>> for SimulationSetup {
+    fn from(simulation: Box<dyn Simulation>) -> Self {
+        SimulationSetup {
+            simulation,
+            size: None,
+        }
+    }
+}
+
+/// Strut that handles the setup of egui and wgpu, and then starts the [Simulation]s side by side and handles the update of their different parameters (see [Parameter]). The rendering of each simulation is performed with the [CallbackTrait](egui_wgpu::CallbackTrait) from [egui_wgpu] used by the [RenderSquare] helper.
+#[cfg(feature = "gui")]
 pub struct SimulationGUI {
+    views: Vec<SimulationView>,
+    /// Simulations which can be selected in each view, see [simulations].
+    registry: Vec<SimulationEntry>,
+    modules: KernelModules,
+    /// Content of the text box of the SPIR-V module to load as a [CustomKernel].
+    spirv_path: String,
+    /// Error of the last load of a [CustomKernel], shown next to the text box.
+    load_error: Option<String>,
+    /// Check of the random numbers of the GPU, shown in the diagnostics panel.
+    rng_self_test: RngSelfTest,
+    /// Limits and features of the GPU, shown in the "About / GPU info" window.
+    capabilities: Capabilities,
+    /// Whether the "About / GPU info" window is open.
+    show_about: bool,
+    /// Errors and losses of the device reported by the callbacks of wgpu, which would otherwise panic.
+    events: Arc<Mutex<GpuEvents>>,
+    /// Number of recoveries so far, limited to [MAX_RECOVERIES].
+    recoveries: usize,
+    /// Reason of the last recovery and time until which it is shown in a banner.
+    banner: Option<(String, f64)>,
+    /// Notifications of the loads of the dropped files.
+    toasts: Toasts,
+}
+
+/// Events reported by the callbacks of wgpu, which are handled at the next frame.
+#[cfg(feature = "gui")]
+#[derive(Default)]
+struct GpuEvents {
+    /// Error which stopped the simulations, shown by [error_screen] instead of the simulations.
+    error: Option<String>,
+    /// Reason why the physics of the simulations must be recreated, after the loss of the device or a lack of memory.
+    recover: Option<String>,
+}
+
+/// Shader modules of the simulations: the embedded kernels, and the modules read at run time by the simulations which provide one, see [Simulation::spirv_file].
+#[cfg(feature = "gui")]
+struct KernelModules {
+    embedded: KernelModule,
+    /// Modules read at run time indexed by their path, with the [id](SpirvFile::id) of the read they were created from.
+    external: HashMap<PathBuf, (u64, KernelModule)>,
+}
+
+#[cfg(feature = "gui")]
+impl KernelModules {
+    /// Embedded module, which provides the vertex shader of the [RenderSquare], and module of the kernels of `simulation`, created if its file was not loaded yet or was read again since.
+    fn get(
+        &mut self,
+        device: &wgpu::Device,
+        simulation: &dyn Simulation,
+    ) -> Result<[&KernelModule; 2], WGPUError> {
+        let Some(file) = simulation.spirv_file() else {
+            return Ok([&self.embedded, &self.embedded]);
+        };
+        let stale = self
+            .external
+            .get(&file.path)
+            .is_none_or(|(id, _)| *id != file.id);
+        if stale {
+            let module = KernelModule::external(device, file)?;
+            self.external.insert(file.path.clone(), (file.id, module));
+        }
+        Ok([&self.embedded, &self.external[&file.path].1])
+    }
+}
+
+/// Number of times the physics of the simulations is recreated before the error is considered fatal, so that a device which keeps failing does not loop forever.
+#[cfg(feature = "gui")]
+const MAX_RECOVERIES: usize = 3;
+
+/// Duration in seconds of the banner shown after a recovery.
+#[cfg(feature = "gui")]
+const BANNER_DURATION: f64 = 5.0;
+
+/// A [Simulation] with its parameters and the [RenderSquare] displaying it.
+#[cfg(feature = "gui")]
+struct SimulationView {
     parameters: Vec<Parameter>,
+    /// Content of the seed text box.
+    seed_text: String,
     simulation: Box<dyn Simulation>,
     render_square: RenderSquare,
+    /// Index in [FILTERS] of the sampling of the rendering.
+    filter: usize,
+    /// Whether the boundaries of the cells are drawn, for small lattices.
+    show_grid: bool,
+    grid_color: egui::Color32,
+    /// Whether the cell under the pointer is shown in a tooltip, with its neighbors read back by [Physics::probe](crate::gpu::physics::Physics::probe).
+    probe: bool,
+    /// Cell last given to [Physics::probe](crate::gpu::physics::Physics::probe), to only change it when the pointer moves to another cell.
+    probed_cell: Option<[u32; 2]>,
+    /// Size of the lattice, which otherwise follows the size of the canvas.
+    size: Option<LatticeSize>,
+    /// Size of the lattice requested, which might have been clamped to the limits of the device.
     width: u32,
     height: u32,
-    shader_module: ShaderModule,
+    /// Warning shown when the lattice requested was clamped to the limits of the device.
+    warning: Option<String>,
+    /// Recording of a clip of the canvas.
+    recorder: Recorder,
+    /// Content of the text box of the file of [Physics::save_state](crate::gpu::physics::Physics::save_state) and [Physics::load_state](crate::gpu::physics::Physics::load_state).
+    state_path: String,
+    /// Whether the physics is updated continuously on a thread instead of once per frame, see [RenderSquare::set_background].
+    background: bool,
+    /// Number of steps per second of the physics.
+    step_rate: StepRate,
+    /// Plots of the observables of the physics, replaced with the physics.
+    plots: ObservablePlots,
+    /// Content of the text box of the file of the [ReplayLog]s.
+    replay_path: String,
+    /// Log of the run being recorded, written to [SimulationView::replay_path] when the recording stops.
+    recording: Option<ReplayLog>,
+    /// Replay in progress, during which the parameters cannot be edited.
+    replay: Option<Replay>,
+    /// Files dropped on the view, loaded at its next frame.
+    dropped: Vec<egui::DroppedFile>,
+    /// Area of the view in the last frame, to find the view on which the files are dropped.
+    rect: egui::Rect,
+    /// Content of the text box of the [Settings] being pasted, shown with the preview of their changes until they are applied.
+    paste: Option<String>,
+    /// Name of the simulation and default values of its parameters, left out of the queries of the links.
+    query_defaults: Option<(&'static str, Vec<(String, Value)>)>,
+    /// Whether the query of the URL of the page follows the settings of the simulation.
+    #[cfg(target_arch = "wasm32")]
+    sync_url: bool,
+    /// Query last written to the URL of the page.
+    #[cfg(target_arch = "wasm32")]
+    synced_query: String,
 }
 
-impl SimulationGUI {
-    pub fn new<'a>(cc: &'a eframe::CreationContext<'a>, simulation: Box<dyn Simulation>) -> Self {
-        let parameters = simulation.egui_parameters();
-        let width = 1024;
-        let height = 1024;
+/// Number of steps per second of a physics, estimated from its number of [steps](crate::gpu::physics::Physics::steps) over intervals of about [StepRate::INTERVAL] seconds.
+#[cfg(feature = "gui")]
+#[derive(Default)]
+struct StepRate {
+    /// Time in seconds and number of steps at the start of the current interval.
+    start: Option<(f64, u64)>,
+    rate: Option<f64>,
+}
+
+#[cfg(feature = "gui")]
+impl StepRate {
+    const INTERVAL: f64 = 1.0;
+    /// Record the number of `steps` of the physics at `time`, in seconds. The rate is forgotten if the physics does not count its steps, and an interval during which the counter went back, such as after a reset, is skipped.
+    fn update(&mut self, time: f64, steps: Option<u64>) {
+        let Some(steps) = steps else {
+            *self = StepRate::default();
+            return;
+        };
+        match self.start {
+            Some((start, _)) if time - start < Self::INTERVAL => {}
+            Some((start, start_steps)) => {
+                self.rate = (steps >= start_steps)
+                    .then(|| (steps - start_steps) as f64 / (time - start))
+                    .or(self.rate);
+                self.start = Some((time, steps));
+            }
+            None => self.start = Some((time, steps)),
+        }
+    }
+}
 
+/// Possible choices for the sampling of the fields by the fragment shaders.
+#[cfg(feature = "gui")]
+const FILTERS: [(&str, u32); 2] = [("nearest", FILTER_NEAREST), ("bilinear", FILTER_LINEAR)];
+
+#[cfg(feature = "gui")]
+impl SimulationGUI {
+    /// Start the `simulations`, which are displayed in columns with independent parameters.
+    pub fn new<'a>(
+        cc: &'a eframe::CreationContext<'a>,
+        simulations: Vec<SimulationSetup>,
+    ) -> Result<Self, WGPUError> {
         let wgpu_render_state = cc
             .wgpu_render_state
             .as_ref()
-            .expect("No wgpu render state available.");
-
-        let shader_module = unsafe {
-            wgpu_render_state.device.create_shader_module_trusted(
-                wgpu::ShaderModuleDescriptor {
-                    label: Some("Shader module"),
-                    source: wgpu::util::make_spirv(crate::SPIRV),
-                },
-                wgpu::ShaderRuntimeChecks::unchecked(),
+            .ok_or(WGPUError::NoRenderState)?;
+        let events = Arc::new(Mutex::new(GpuEvents::default()));
+        install_handlers(&wgpu_render_state.device, &events);
+
+        let mut modules = KernelModules {
+            embedded: KernelModule::embedded(&wgpu_render_state.device),
+            external: HashMap::new(),
+        };
+        let views = simulations
+            .into_iter()
+            .enumerate()
+            .map(|(id, setup)| SimulationView::new(wgpu_render_state, &mut modules, setup, id))
+            .collect::<Result<_, WGPUError>>()?;
+        let rng_self_test = RngSelfTest::new(
+            &wgpu_render_state.device,
+            &wgpu_render_state.queue,
+            &modules.embedded.module,
+        );
+        let capabilities = capabilities(&wgpu_render_state.adapter, &wgpu_render_state.device);
+        capabilities.log();
+        Ok(SimulationGUI {
+            views,
+            registry: simulations().to_vec(),
+            modules,
+            spirv_path: "custom.spv".to_string(),
+            load_error: None,
+            rng_self_test,
+            capabilities,
+            show_about: false,
+            events,
+            recoveries: 0,
+            banner: None,
+            toasts: Toasts::default(),
+        })
+    }
+    /// Start the `simulations` with [SimulationGUI::new], or show the error which prevented them from starting.
+    pub fn start<'a>(
+        cc: &'a eframe::CreationContext<'a>,
+        simulations: Vec<SimulationSetup>,
+    ) -> Box<dyn eframe::App> {
+        match SimulationGUI::new(cc, simulations) {
+            Ok(gui) => Box::new(gui),
+            Err(err) => {
+                let events = Arc::new(Mutex::new(GpuEvents::default()));
+                report_error(&events, err);
+                Box::new(ErrorScreen(events))
+            }
+        }
+    }
+    /// Create the [RenderSquare] of the physics of `simulation` on a `width`×`height` lattice, with the kernels of its module in `modules`, and the plots of the observables of the physics. A lattice too large for the limits of the device is clamped to the largest one with the same aspect ratio, which is returned with a warning instead of failing.
+    fn new_render_square(
+        wgpu_render_state: &RenderState,
+        modules: &mut KernelModules,
+        simulation: &dyn Simulation,
+        id: usize,
+        width: u32,
+        height: u32,
+    ) -> Result<(RenderSquare, Option<String>, ObservablePlots), WGPUError> {
+        let [embedded, module] = modules.get(&wgpu_render_state.device, simulation)?;
+        let physics = |width, height| {
+            simulation.physics(
+                &wgpu_render_state.device,
+                &wgpu_render_state.queue,
+                &module.module,
+                width,
+                height,
             )
         };
-        let render_square = Self::new_render_square(
+        let (physics, warning) = match physics(width, height) {
+            Err(WGPUError::BufferSizeOverflow(_, bytes_per_cell)) => {
+                let limits = wgpu_render_state.device.limits();
+                let (w, h) = clamp_lattice(&limits, width, height, bytes_per_cell);
+                let warning = format!(
+                    "The {width}×{height} lattice exceeds the limits of the GPU, clamped to {w}×{h}."
+                );
+                log::warn!("{warning}");
+                (physics(w, h)?, Some(warning))
+            }
+            physics => (physics?, None),
+        };
+        let plots = ObservablePlots::new(physics.observables());
+        Ok((
+            RenderSquare::new(wgpu_render_state, embedded, module, physics, id)?,
+            warning,
+            plots,
+        ))
+    }
+    /// Add a column with a [CustomKernel] of the SPIR-V module at [SimulationGUI::spirv_path]. An error while loading it is shown next to the text box instead of stopping the simulations.
+    fn load_custom(&mut self, wgpu_render_state: &RenderState) {
+        let id = self.views.len();
+        let view = CustomKernel::load(&self.spirv_path).and_then(|custom| {
+            let simulation: Box<dyn Simulation> = Box::new(custom);
+            SimulationView::new(wgpu_render_state, &mut self.modules, simulation.into(), id)
+        });
+        match view {
+            Ok(view) => {
+                self.views.push(view);
+                self.load_error = None;
+            }
+            Err(err) => {
+                log::error!("Failed to load {}: {err}", self.spirv_path);
+                self.load_error = Some(err.to_string());
+            }
+        }
+    }
+    /// Recreate the [RenderSquare] and the physics of every view with the current render state, after the loss of the device or a lack of memory. The parameters of the simulations are kept, but their state restarts from their seed since it was lost with the buffers.
+    fn recover(&mut self, frame: &eframe::Frame, reason: String) -> Result<(), WGPUError> {
+        self.recoveries += 1;
+        if self.recoveries > MAX_RECOVERIES {
+            return Err(WGPUError::DeviceLost(format!(
+                "{reason} (gave up after {MAX_RECOVERIES} recoveries)"
+            )));
+        }
+        log::warn!("Recovering from: {reason}");
+        let wgpu_render_state = frame.wgpu_render_state().ok_or(WGPUError::NoRenderState)?;
+        install_handlers(&wgpu_render_state.device, &self.events);
+        for (id, view) in self.views.iter_mut().enumerate() {
+            RenderSquare::release(wgpu_render_state, id);
+            (view.render_square, view.warning, view.plots) = Self::new_render_square(
+                wgpu_render_state,
+                &mut self.modules,
+                &*view.simulation,
+                id,
+                view.width,
+                view.height,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Install the callbacks of wgpu on `device`, which record its errors and its loss in `events` instead of panicking. A lack of memory and the loss of the device are recovered from by recreating the simulations, whereas any other error stops them.
+#[cfg(feature = "gui")]
+fn install_handlers(device: &wgpu::Device, events: &Arc<Mutex<GpuEvents>>) {
+    let handler_events = Arc::clone(events);
+    device.on_uncaptured_error(Box::new(move |err: wgpu::Error| match err {
+        wgpu::Error::OutOfMemory { .. } => {
+            log::error!("{err}");
+            handler_events.lock().unwrap().recover = Some(err.to_string());
+        }
+        err => report_error(&handler_events, WGPUError::from(err)),
+    }));
+    let lost_events = Arc::clone(events);
+    device.set_device_lost_callback(move |reason, message| {
+        // The device is destroyed on purpose when the app closes.
+        if reason != wgpu::DeviceLostReason::Destroyed {
+            let err = WGPUError::DeviceLost(message);
+            log::error!("{err}");
+            lost_events.lock().unwrap().recover = Some(err.to_string());
+        }
+    });
+}
+
+#[cfg(feature = "gui")]
+impl SimulationView {
+    /// Start the simulation of `setup` with the [RenderSquare] `id`, on a 1024×1024 lattice until the size of the canvas is known if its size is not fixed.
+    fn new(
+        wgpu_render_state: &RenderState,
+        modules: &mut KernelModules,
+        SimulationSetup { simulation, size }: SimulationSetup,
+        id: usize,
+    ) -> Result<Self, WGPUError> {
+        let width = size.map_or(1024, |size| size.width);
+        let height = size.map_or(1024, |size| size.height);
+        let (render_square, warning, plots) = SimulationGUI::new_render_square(
             wgpu_render_state,
-            &shader_module,
+            modules,
             &*simulation,
+            id,
             width,
             height,
-        );
-        SimulationGUI {
-            parameters,
-            simulation,
+        )?;
+        Ok(SimulationView {
+            parameters: simulation.egui_parameters(),
+            seed_text: simulation.seed().to_string(),
             render_square,
+            simulation,
+            filter: 0,
+            show_grid: false,
+            grid_color: egui::Color32::from_black_alpha(128),
+            probe: false,
+            probed_cell: None,
+            size,
             width,
             height,
-            shader_module,
+            warning,
+            recorder: Recorder::new(),
+            state_path: "state.bin".to_string(),
+            background: false,
+            step_rate: StepRate::default(),
+            plots,
+            replay_path: "replay.toml".to_string(),
+            recording: None,
+            replay: None,
+            dropped: vec![],
+            rect: egui::Rect::NOTHING,
+            paste: None,
+            query_defaults: None,
+            #[cfg(target_arch = "wasm32")]
+            sync_url: false,
+            #[cfg(target_arch = "wasm32")]
+            synced_query: String::new(),
+        })
+    }
+    /// Probe the cell of the lattice under the pointer in the canvas drawn in `rect`, and show the last values read back in a tooltip.
+    fn probe_ui(&mut self, ui: &mut egui::Ui, frame: &eframe::Frame, id: usize, rect: egui::Rect) {
+        let Some(wgpu_render_state) = frame.wgpu_render_state() else {
+            return;
+        };
+        let response = ui.interact(rect, ui.id().with(("probe", id)), egui::Sense::hover());
+        let cell = response
+            .hover_pos()
+            .filter(|_| self.probe)
+            .and_then(|pos| cell_under(rect, pos, self.width, self.height));
+        let probed = RenderSquare::with_physics(wgpu_render_state, id, |physics| {
+            if cell != self.probed_cell {
+                physics.probe(cell);
+            }
+            physics.probed()
+        })
+        .flatten();
+        self.probed_cell = cell;
+        let Some([x, y]) = cell else {
+            return;
+        };
+        response.on_hover_ui_at_pointer(|ui| {
+            ui.label(format!("cell ({x}, {y})"));
+            // The values of the previous cell are not shown while the new one is read back.
+            match probed.filter(|probed| probed.cell == [x, y]) {
+                Some(probed) => {
+                    let [left, right, bottom, top] = probed.neighbors;
+                    egui::Grid::new("probe").show(ui, |ui| {
+                        ui.label("step");
+                        ui.label(probed.step.to_string());
+                        ui.end_row();
+                        ui.label("value");
+                        ui.label(format!("{:+}", probed.value));
+                        ui.end_row();
+                        if let Some(energy) = probed.energy {
+                            ui.label("local energy");
+                            ui.label(format!("{energy:+}"));
+                            ui.end_row();
+                        }
+                        ui.label("neighbors");
+                        ui.label(format!(
+                            "left {left:+}, right {right:+}, bottom {bottom:+}, top {top:+}"
+                        ));
+                        ui.end_row();
+                    });
+                }
+                None => {
+                    ui.label("reading…");
+                }
+            }
+        });
+    }
+    /// Write the state of the physics of the [RenderSquare] `id` to [SimulationView::state_path]. The errors are logged.
+    fn save_state(&self, wgpu_render_state: &RenderState, id: usize) {
+        let state = RenderSquare::with_physics(wgpu_render_state, id, |physics| {
+            physics.save_state(&wgpu_render_state.device, &wgpu_render_state.queue)
+        })
+        .flatten();
+        let Some(state) = state else {
+            log::error!("The state of {} cannot be saved.", self.simulation.name());
+            return;
+        };
+        match std::fs::write(&self.state_path, state) {
+            Ok(()) => log::info!("Saved the state to {}.", self.state_path),
+            Err(err) => log::error!("Failed to save the state to {}: {err}", self.state_path),
         }
     }
-    fn new_render_square(
+    /// Restore the state of the physics of the [RenderSquare] `id` from [SimulationView::state_path]. The errors are logged.
+    fn load_state(&mut self, wgpu_render_state: &RenderState, id: usize) {
+        let loaded = std::fs::read(&self.state_path)
+            .map_err(WGPUError::from)
+            .and_then(|bytes| self.load_state_bytes(wgpu_render_state, id, &bytes));
+        match loaded {
+            Ok(()) => log::info!("Loaded the state from {}.", self.state_path),
+            Err(err) => log::error!("Failed to load the state from {}: {err}", self.state_path),
+        }
+    }
+    /// Restore the state of the physics of the [RenderSquare] `id` from `bytes`, and display again the parameters it changed.
+    fn load_state_bytes(
+        &mut self,
         wgpu_render_state: &RenderState,
-        shader_module: &ShaderModule,
-        simulation: &dyn Simulation,
-        width: u32,
-        height: u32,
-    ) -> RenderSquare {
-        let seed =
-            unsafe { std::mem::transmute(SystemTime::UNIX_EPOCH.elapsed().unwrap().as_millis()) };
-        let physics = simulation.physics(
-            &wgpu_render_state.device,
-            &wgpu_render_state.queue,
-            &shader_module,
-            seed,
-            width,
-            height,
+        id: usize,
+        bytes: &[u8],
+    ) -> Result<(), WGPUError> {
+        RenderSquare::with_physics(wgpu_render_state, id, |physics| {
+            physics.load_state(&wgpu_render_state.queue, bytes)
+        })
+        .unwrap_or(Err(WGPUError::LoadStateUnsupported))?;
+        self.parameters = self.simulation.egui_parameters();
+        Ok(())
+    }
+    /// Load the dropped `file` into the simulation according to its [DroppedKind], and return the message of the notification. A replay requests a restart with `restart`.
+    ///
+    /// The file is loaded between two updates of the physics: the state is written while holding the lock of the physics, which pauses its background thread if any, and the image is applied by the next update, the simulation resuming afterwards.
+    fn load_dropped(
+        &mut self,
+        frame: &eframe::Frame,
+        registry: &[SimulationEntry],
+        id: usize,
+        file: egui::DroppedFile,
+        restart: &mut bool,
+    ) -> Result<String, String> {
+        let name = dropped_name(&file);
+        let failed = |err: &dyn std::fmt::Display| format!("Failed to load {name}: {err}");
+        // The events of a recording or a replay would not match the state any more.
+        if self.recording.is_some() || self.replay.is_some() {
+            return Err(failed(
+                &"files cannot be loaded while recording or replaying",
+            ));
+        }
+        let bytes = dropped_bytes(&file).map_err(|err| failed(&err))?;
+        match DroppedKind::detect(&name, &bytes) {
+            Some(DroppedKind::State) => {
+                let wgpu_render_state = frame
+                    .wgpu_render_state()
+                    .ok_or_else(|| failed(&"no physics"))?;
+                self.load_state_bytes(wgpu_render_state, id, &bytes)
+                    .map_err(|err| failed(&err))?;
+                Ok(format!("Loaded the state from {name}."))
+            }
+            Some(DroppedKind::Image) => {
+                let source = match file.path {
+                    Some(path) => ImageSource::Path(path),
+                    None => ImageSource::Bytes(bytes),
+                };
+                self.simulation
+                    .load_image(source)
+                    .map_err(|err| failed(&err))?;
+                Ok(format!("Loading the image {name}."))
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            Some(DroppedKind::Replay) => {
+                self.start_replay(registry, &String::from_utf8_lossy(&bytes))
+                    .map_err(|err| failed(&err))?;
+                if let Some(path) = file.path {
+                    self.replay_path = path.display().to_string();
+                }
+                *restart = true;
+                Ok(format!("Replaying {name}."))
+            }
+            #[cfg(target_arch = "wasm32")]
+            Some(DroppedKind::Replay) => {
+                let _ = (registry, restart);
+                Err(failed(&"the replays are only supported natively"))
+            }
+            None => Err(failed(
+                &"unrecognized file, expected a state, a PNG or JPEG image or a replay log",
+            )),
+        }
+    }
+    /// Apply the events of the replay due at the step `sweep` of the physics of the [RenderSquare] `id`, and return the number of steps of the next update, up to the next event. A restart of the physics is requested with `restart`, the following events waiting for the new physics. The replay stops at the end of its log, and the hash of the final state is logged to be compared with the one of the recording.
+    fn replay_events(
+        &mut self,
+        frame: &eframe::Frame,
+        id: usize,
+        sweep: Option<u64>,
+        restart: &mut bool,
+    ) -> Option<u32> {
+        let (Some(replay), Some(sweep)) = (&mut self.replay, sweep) else {
+            return None;
+        };
+        while let Some(action) = replay.next_due(sweep) {
+            match action {
+                ReplayAction::Reset => {
+                    if let Some(state) = frame.wgpu_render_state() {
+                        RenderSquare::with_physics(state, id, |physics| {
+                            physics.reset(&state.device, &state.queue)
+                        });
+                    }
+                }
+                ReplayAction::Seed(seed) => {
+                    self.simulation.set_seed(seed);
+                    self.seed_text = seed.to_string();
+                    *restart = true;
+                }
+                action => {
+                    if let Err(err) = action.apply(&mut *self.simulation) {
+                        log::error!("{err}");
+                    }
+                }
+            }
+            *restart |= self.simulation.take_restart();
+            if *restart {
+                break;
+            }
+        }
+        self.parameters = self.simulation.egui_parameters();
+        if *restart {
+            return Some(0);
+        }
+        let steps = replay.steps(sweep);
+        if steps.is_none() {
+            self.replay = None;
+            let hash = self.state_hash(frame, id);
+            log::info!("Replay finished at step {sweep}, state hash {hash}.");
+        }
+        steps
+    }
+    /// Hash of the state of the physics of the [RenderSquare] `id` as hexadecimal, see [state_hash](crate::gpu::physics::state_hash), or a placeholder if the physics cannot save its state.
+    fn state_hash(&self, frame: &eframe::Frame, id: usize) -> String {
+        frame
+            .wgpu_render_state()
+            .and_then(|state| {
+                RenderSquare::with_physics(state, id, |physics| {
+                    physics.save_state(&state.device, &state.queue)
+                })
+            })
+            .flatten()
+            .map_or("unavailable".to_string(), |bytes| {
+                format!("{:016x}", crate::gpu::physics::state_hash(&bytes))
+            })
+    }
+    /// Display the buttons recording the run in a [ReplayLog] and replaying one, with the text box of the file of the log. A recording starts the physics again on a lattice of the current size, which is then fixed, and a replay starts the simulation of the log from its defaults and seed, both requesting a restart with `restart`.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn replay_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        frame: &eframe::Frame,
+        registry: &[SimulationEntry],
+        id: usize,
+        sweep: Option<u64>,
+        restart: &mut bool,
+    ) {
+        ui.horizontal(|ui| {
+            let mut recording = self.recording.is_some();
+            let toggle = egui::SelectableLabel::new(recording, "record");
+            if ui.add_enabled(self.replay.is_none(), toggle).clicked() {
+                recording = !recording;
+                match self.recording.take() {
+                    None if recording => {
+                        self.size = Some(LatticeSize {
+                            width: self.width,
+                            height: self.height,
+                        });
+                        self.recording =
+                            Some(ReplayLog::new(&*self.simulation, self.width, self.height));
+                        *restart = true;
+                    }
+                    Some(mut log) => {
+                        log.end = sweep.unwrap_or(0);
+                        let hash = self.state_hash(frame, id);
+                        match std::fs::write(&self.replay_path, log.to_string()) {
+                            Ok(()) => log::info!(
+                                "Recorded {} events until step {} in {}, state hash {hash}.",
+                                log.events.len(),
+                                log.end,
+                                self.replay_path
+                            ),
+                            Err(err) => {
+                                log::error!("Failed to write {}: {err}", self.replay_path)
+                            }
+                        }
+                    }
+                    None => {}
+                }
+            }
+            if let Some(replay) = &self.replay {
+                if ui.button("stop replay").clicked() {
+                    self.replay = None;
+                } else {
+                    ui.label(format!("replaying to step {}", replay.log().end));
+                }
+            } else if ui
+                .add_enabled(self.recording.is_none(), egui::Button::new("replay"))
+                .clicked()
+            {
+                let started = std::fs::read_to_string(&self.replay_path)
+                    .map_err(WGPUError::from)
+                    .and_then(|text| self.start_replay(registry, &text));
+                match started {
+                    Ok(()) => *restart = true,
+                    Err(err) => log::error!("Failed to replay {}: {err}", self.replay_path),
+                }
+            }
+            ui.add(egui::TextEdit::singleline(&mut self.replay_path).desired_width(160.0));
+        });
+    }
+    /// Start the replay of the log `text` with a new simulation of `registry` with the seed of the log, on a lattice of the size of the log.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start_replay(&mut self, registry: &[SimulationEntry], text: &str) -> Result<(), WGPUError> {
+        let log = ReplayLog::parse(text)?;
+        let (_, new) = registry
+            .iter()
+            .find(|(name, _)| *name == log.simulation)
+            .ok_or_else(|| {
+                WGPUError::InvalidReplay(format!("unknown simulation \"{}\"", log.simulation))
+            })?;
+        self.simulation = new();
+        self.simulation.set_seed(log.seed);
+        self.seed_text = log.seed.to_string();
+        self.parameters = self.simulation.egui_parameters();
+        self.size = Some(LatticeSize {
+            width: log.width,
+            height: log.height,
+        });
+        self.replay = Some(Replay::new(log));
+        Ok(())
+    }
+    /// Display the buttons copying the [Settings] of the simulation to the clipboard and pasting some, which shows a text box for them with the preview of their changes. They are applied only once the preview is accepted and if all their values are valid, requesting a restart with `restart` if the simulation, the seed or the size changes, and a reset of the state with `reset` if a structural parameter changes. The settings cannot be pasted during a recording or a replay.
+    fn settings_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        registry: &[SimulationEntry],
+        toasts: &mut Toasts,
+        restart: &mut bool,
+        reset: &mut bool,
+    ) {
+        let editable = self.recording.is_none() && self.replay.is_none();
+        ui.horizontal(|ui| {
+            if ui.button("copy settings").clicked() {
+                let settings = Settings::capture(&*self.simulation, self.size);
+                ui.ctx().copy_text(settings.to_string());
+                toasts.info(format!(
+                    "Copied the settings of {} to the clipboard.",
+                    settings.simulation
+                ));
+            }
+            let toggle = egui::SelectableLabel::new(self.paste.is_some(), "paste settings");
+            if ui.add_enabled(editable, toggle).clicked() {
+                self.paste = match self.paste {
+                    Some(_) => None,
+                    None => Some(String::new()),
+                };
+            }
+            #[cfg(target_arch = "wasm32")]
+            let (label, copied) = ("copy link", "link");
+            #[cfg(not(target_arch = "wasm32"))]
+            let (label, copied) = ("copy command", "command line");
+            if ui.button(label).clicked() {
+                ui.ctx().copy_text(self.link(registry));
+                toasts.info(format!("Copied the {copied} to the clipboard."));
+            }
+            #[cfg(target_arch = "wasm32")]
+            ui.toggle_value(&mut self.sync_url, "sync URL");
+        });
+        #[cfg(target_arch = "wasm32")]
+        if self.sync_url {
+            let query = self.query(registry);
+            if query != self.synced_query {
+                replace_url_query(&query);
+                self.synced_query = query;
+            }
+        }
+        if !editable {
+            self.paste = None;
+        }
+        let Some(text) = &mut self.paste else {
+            return;
+        };
+        ui.add(
+            egui::TextEdit::multiline(text)
+                .hint_text("paste the settings here")
+                .desired_rows(4)
+                .desired_width(f32::INFINITY),
         );
-        RenderSquare::new(wgpu_render_state, &shader_module, physics)
+        if text.trim().is_empty() {
+            return;
+        }
+        let error_color = ui.visuals().error_fg_color;
+        let warn_color = ui.visuals().warn_fg_color;
+        let settings = match Settings::parse(text) {
+            Ok(settings) => settings,
+            Err(err) => {
+                ui.colored_label(error_color, err);
+                return;
+            }
+        };
+        // The changes of the parameters are previewed against the simulation of the settings, a new one with its defaults if it is not the current one.
+        let other = match registry
+            .iter()
+            .find(|(name, _)| *name == settings.simulation)
+        {
+            Some((name, _)) if *name == self.simulation.name() => None,
+            Some((_, new)) => Some(new()),
+            None => {
+                ui.colored_label(
+                    error_color,
+                    format!("unknown simulation \"{}\"", settings.simulation),
+                );
+                return;
+            }
+        };
+        let target = other.as_deref().unwrap_or(&*self.simulation);
+        if let Some(other) = &other {
+            ui.label(format!(
+                "simulation: {} → {} (with its defaults)",
+                self.simulation.name(),
+                other.name()
+            ));
+        }
+        if settings.seed != self.simulation.seed() {
+            ui.label(format!(
+                "seed: {} → {}",
+                self.simulation.seed(),
+                settings.seed
+            ));
+        }
+        if settings.size != self.size {
+            let show = |size: Option<LatticeSize>| {
+                size.map_or("canvas".to_string(), |size| size.to_string())
+            };
+            ui.label(format!(
+                "size: {} → {}",
+                show(self.size),
+                show(settings.size)
+            ));
+        }
+        let changes = settings.changes(target);
+        let mut unchanged = vec![];
+        let mut valid = true;
+        for change in &changes {
+            match change {
+                Change::Changed { tag, old, new, .. } => {
+                    ui.label(format!("{tag}: {old} → {new}"));
+                }
+                Change::Unchanged(tag) => unchanged.push(*tag),
+                Change::Unknown(tag) => {
+                    ui.colored_label(warn_color, format!("unknown parameter \"{tag}\" ignored"));
+                }
+                Change::Invalid { tag, reason } => {
+                    valid = false;
+                    ui.colored_label(error_color, format!("{tag}: {reason}"));
+                }
+            }
+        }
+        for key in &settings.ignored {
+            ui.colored_label(warn_color, format!("unknown setting \"{key}\" ignored"));
+        }
+        if !unchanged.is_empty() {
+            ui.label(format!("unchanged: {}", unchanged.join(", ")));
+        }
+        if ui.add_enabled(valid, egui::Button::new("apply")).clicked() {
+            if let Some(other) = other {
+                self.simulation = other;
+                *restart = true;
+            }
+            if settings.seed != self.simulation.seed() {
+                self.simulation.set_seed(settings.seed);
+                *restart = true;
+            }
+            self.seed_text = self.simulation.seed().to_string();
+            self.size = settings.size;
+            for change in changes {
+                let Change::Changed { tag, update, .. } = change else {
+                    continue;
+                };
+                match self.simulation.update_parameter(update) {
+                    Ok(()) => {
+                        *reset |= self
+                            .parameters
+                            .iter()
+                            .any(|p| p.tag() == tag && p.reset_on_change());
+                    }
+                    Err(err) => toasts.error(err.to_string()),
+                }
+            }
+            self.parameters = self.simulation.egui_parameters();
+            self.paste = None;
+            toasts.info(format!("Applied the settings of {}.", settings.simulation));
+        }
     }
-}
-impl eframe::App for SimulationGUI {
-    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
+    /// Settings of the simulation as the query of a link, see [Settings::to_query], with the parameters left to the defaults of the simulation in `registry` left out.
+    fn query(&mut self, registry: &[SimulationEntry]) -> String {
+        let name = self.simulation.name();
+        if self.query_defaults.as_ref().is_none_or(|(n, _)| *n != name) {
+            // The defaults of a simulation which is not in the registry, such as custom kernels, are unknown, and all its parameters are given.
+            let defaults = registry
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, new)| Settings::capture(&*new(), None).parameters)
+                .unwrap_or_default();
+            self.query_defaults = Some((name, defaults));
+        }
+        let defaults = self.query_defaults.as_ref().map_or(&[][..], |(_, d)| d);
+        Settings::capture(&*self.simulation, self.size).to_query(defaults)
+    }
+    /// Link to the page of the web app with the settings of the simulation in its query.
+    #[cfg(target_arch = "wasm32")]
+    fn link(&mut self, registry: &[SimulationEntry]) -> String {
+        let query = self.query(registry);
+        let page = web_sys::window()
+            .and_then(|window| {
+                let location = window.location();
+                Some(location.origin().ok()? + &location.pathname().ok()?)
+            })
+            .unwrap_or_default();
+        format!("{page}?{query}")
+    }
+    /// Command line starting the simulation with its settings, given with `--params` in the format of the query of the links of the web app.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn link(&mut self, registry: &[SimulationEntry]) -> String {
+        let query = self.query(registry);
+        format!("phase {} --params \"{query}\"", self.simulation.name())
+    }
+    /// Display the selector of the simulation, the description, the parameters, the additional panel and the rendering of the simulation. `id` is the one of its [RenderSquare].
+    fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        frame: &eframe::Frame,
+        modules: &mut KernelModules,
+        registry: &[SimulationEntry],
+        id: usize,
+        toasts: &mut Toasts,
+    ) -> Result<(), WGPUError> {
+        self.rect = ui.max_rect();
+        // Selecting another simulation replaces this one with the same seed, and its physics is recreated below with the same size, fixed or following the canvas, the resources of the previous one being released. A recording, a replay or settings being pasted are abandoned, as they are tied to the previous simulation.
+        let mut restart = false;
+        let mut selected = self.simulation.name();
+        egui::ComboBox::from_label("simulation")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                for (name, _) in registry {
+                    ui.selectable_value(&mut selected, *name, *name);
+                }
+            });
+        let switch = registry
+            .iter()
+            .find(|(name, _)| *name == selected && *name != self.simulation.name());
+        if let Some((_, new)) = switch {
+            self.recording = None;
+            self.replay = None;
+            self.paste = None;
+            let seed = self.simulation.seed();
+            self.simulation = new();
+            self.simulation.set_seed(seed);
+            self.parameters = self.simulation.egui_parameters();
+            self.seed_text = self.simulation.seed().to_string();
+            restart = true;
+        }
+        for file in std::mem::take(&mut self.dropped) {
+            match self.load_dropped(frame, registry, id, file, &mut restart) {
+                Ok(text) => toasts.info(text),
+                Err(text) => toasts.error(text),
+            }
+        }
+
+        let description = self.simulation.description();
+        if !description.is_empty() {
+            egui::CollapsingHeader::new("About this model").show(ui, |ui| {
+                ui.label(description);
+            });
+        }
+
+        // Step of the physics at which the edits of this frame are applied, by the update following the UI.
+        let sweep = frame
+            .wgpu_render_state()
+            .and_then(|state| RenderSquare::with_physics(state, id, |physics| physics.steps()))
+            .flatten();
+        let replay_steps = self.replay_events(frame, id, sweep, &mut restart);
+
+        // The parameters and the seed are edited by the replay, if any, instead of the UI.
+        let seed = self.simulation.seed();
+        // A structural parameter changed in the UI resets the state of the physics once the parameters are applied, see [Parameter].
+        let mut reset = false;
+        ui.add_enabled_ui(self.replay.is_none(), |ui| {
+            let mut refresh = false;
             for p in self.parameters.iter_mut() {
-                match p {
+                let reset_on_change = p.reset_on_change();
+                let update = match p {
                     Parameter::Slider {
                         tag,
                         value,
                         logarithmic,
                         range,
+                        tooltip,
+                        unit,
                     } => {
-                        if ui
-                            .add(
-                                egui::Slider::new(value, range.clone())
-                                    .logarithmic(*logarithmic)
-                                    .text(*tag),
-                            )
-                            .changed()
-                        {
-                            self.simulation
-                                .update_parameter(UpadeParameter::Slider { tag, value: *value });
+                        let label = match unit {
+                            Some(unit) => format!("{tag} ({unit})"),
+                            None => tag.to_string(),
+                        };
+                        let mut response = ui.add(
+                            egui::Slider::new(value, range.clone())
+                                .logarithmic(*logarithmic)
+                                .text(label),
+                        );
+                        if let Some(tooltip) = tooltip {
+                            response = response.on_hover_text(*tooltip);
+                        }
+                        if response.changed() {
+                            Some(UpadeParameter::Slider { tag, value: *value })
+                        } else {
+                            None
                         }
                     }
-                    Parameter::Toggle { tag, enable } => {
-                        if ui.toggle_value(enable, *tag).changed() {
-                            self.simulation.update_parameter(UpadeParameter::Toggle {
-                                tag,
-                                enable: *enable,
+                    Parameter::Toggle { tag, enable, .. } => ui
+                        .toggle_value(enable, *tag)
+                        .changed()
+                        .then_some(UpadeParameter::Toggle {
+                            tag,
+                            enable: *enable,
+                        }),
+                    Parameter::Button { tag } => ui
+                        .button(*tag)
+                        .clicked()
+                        .then_some(UpadeParameter::Button { tag }),
+                    Parameter::Choice {
+                        tag,
+                        options,
+                        selected,
+                        ..
+                    } => {
+                        let mut changed = false;
+                        egui::ComboBox::from_label(*tag)
+                            .selected_text(options[*selected])
+                            .show_ui(ui, |ui| {
+                                for (i, option) in options.iter().enumerate() {
+                                    changed |= ui.selectable_value(selected, i, *option).changed();
+                                }
                             });
+                        changed.then_some(UpadeParameter::Choice {
+                            tag,
+                            selected: *selected,
+                        })
+                    }
+                    Parameter::Text { tag, value } => {
+                        let response = ui
+                            .horizontal(|ui| {
+                                let response =
+                                    ui.add(egui::TextEdit::singleline(value).desired_width(100.0));
+                                ui.label(*tag);
+                                response
+                            })
+                            .inner;
+                        // The parameters are displayed again so that a text which the simulation failed to parse is replaced by the last valid value.
+                        refresh |= response.lost_focus();
+                        response.lost_focus().then(|| UpadeParameter::Text {
+                            tag,
+                            value: value.clone(),
+                        })
+                    }
+                    Parameter::Color { tag, rgb } => {
+                        let changed = ui
+                            .horizontal(|ui| {
+                                let changed = ui.color_edit_button_srgb(rgb).changed();
+                                ui.label(*tag);
+                                changed
+                            })
+                            .inner;
+                        changed.then_some(UpadeParameter::Color { tag, rgb: *rgb })
+                    }
+                };
+                if let Some(update) = update {
+                    let action = ReplayAction::from_update(&update);
+                    match self.simulation.update_parameter(update) {
+                        Ok(()) => {
+                            if let (Some(log), Some(sweep)) = (&mut self.recording, sweep) {
+                                log.push(sweep, action);
+                            }
+                            reset |= reset_on_change;
                         }
+                        Err(err) => log::error!("{err}"),
                     }
-                    Parameter::Button { tag } => {
-                        if ui.button(*tag).clicked() {
-                            self.simulation
-                                .update_parameter(UpadeParameter::Button { tag });
+                }
+            }
+            if refresh {
+                self.parameters = self.simulation.egui_parameters();
+            }
+
+            // The physics is restarted when the seed changes, either entered in the text box to reproduce a run or drawn by the reseed button.
+            ui.horizontal(|ui| {
+                let response =
+                    ui.add(egui::TextEdit::singleline(&mut self.seed_text).desired_width(260.0));
+                ui.label("seed");
+                if response.lost_focus() {
+                    match self.seed_text.parse::<Seed>() {
+                        Ok(seed) if seed != self.simulation.seed() => {
+                            self.simulation.set_seed(seed);
+                            restart = true;
                         }
+                        Ok(_) => {}
+                        Err(err) => log::warn!("Invalid seed \"{}\": {err}", self.seed_text),
                     }
+                    self.seed_text = self.simulation.seed().to_string();
+                }
+                if ui.button("reseed").clicked() {
+                    self.simulation.set_seed(Seed::from_entropy());
+                    self.seed_text = self.simulation.seed().to_string();
+                    restart = true;
                 }
+            });
+        });
+        // A change of the seed restarts the physics, with steps counted from zero.
+        match (&mut self.recording, sweep) {
+            (Some(log), Some(sweep)) if self.simulation.seed() != seed => {
+                log.push(sweep, ReplayAction::Seed(self.simulation.seed()))
+            }
+            _ => {}
+        }
+
+        // A restart might change the parameters, such as the ones of a reloaded manifest.
+        if self.simulation.take_restart() {
+            restart = true;
+            self.parameters = self.simulation.egui_parameters();
+        }
+
+        // Generic controls of the physics, shown when it supports them.
+        if let Some(wgpu_render_state) = frame.wgpu_render_state() {
+            let (reset, state) = RenderSquare::with_physics(wgpu_render_state, id, |physics| {
+                (physics.supports_reset(), physics.supports_state())
+            })
+            .unwrap_or_default();
+            if reset || state {
+                ui.horizontal(|ui| {
+                    let button = egui::Button::new("reset state");
+                    if reset && ui.add_enabled(self.replay.is_none(), button).clicked() {
+                        RenderSquare::with_physics(wgpu_render_state, id, |physics| {
+                            physics.reset(&wgpu_render_state.device, &wgpu_render_state.queue)
+                        });
+                        if let (Some(log), Some(sweep)) = (&mut self.recording, sweep) {
+                            log.push(sweep, ReplayAction::Reset);
+                        }
+                    }
+                    if state {
+                        if ui.button("save state").clicked() {
+                            self.save_state(wgpu_render_state, id);
+                        }
+                        if ui.button("load state").clicked() {
+                            self.load_state(wgpu_render_state, id);
+                        }
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.state_path).desired_width(160.0),
+                        );
+                    }
+                });
             }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        self.replay_ui(ui, frame, registry, id, sweep, &mut restart);
+        self.settings_ui(ui, registry, toasts, &mut restart, &mut reset);
+
+        // The state is reset like with the `reset state` button, unless the physics is recreated anyway.
+        let reset_state = frame.wgpu_render_state().filter(|_| reset && !restart);
+        if let Some(wgpu_render_state) = reset_state {
+            RenderSquare::with_physics(wgpu_render_state, id, |physics| {
+                if physics.supports_reset() {
+                    physics.reset(&wgpu_render_state.device, &wgpu_render_state.queue)
+                }
+            });
+            if let (Some(log), Some(sweep)) = (&mut self.recording, sweep) {
+                log.push(sweep, ReplayAction::Reset);
+            }
+        }
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("filter")
+                .selected_text(FILTERS[self.filter].0)
+                .show_ui(ui, |ui| {
+                    for (i, (name, _)) in FILTERS.iter().enumerate() {
+                        ui.selectable_value(&mut self.filter, i, *name);
+                    }
+                });
+            ui.toggle_value(&mut self.show_grid, "grid");
+            ui.color_edit_button_srgba(&mut self.grid_color);
+            let probe = frame.wgpu_render_state().is_some_and(|wgpu_render_state| {
+                RenderSquare::with_physics(wgpu_render_state, id, |physics| {
+                    physics.supports_probe()
+                })
+                .unwrap_or_default()
+            });
+            if probe {
+                ui.toggle_value(&mut self.probe, "probe").on_hover_text(
+                    "Show the value of the cell under the pointer, its local energy and its neighbors.",
+                );
+            }
+        });
+
+        if let Some(wgpu_render_state) = frame.wgpu_render_state() {
+            #[cfg(not(target_arch = "wasm32"))]
+            RenderSquare::set_background(
+                wgpu_render_state,
+                id,
+                // The steps at which the events are applied are only exact when the physics is updated with the frames.
+                self.background && self.recording.is_none() && self.replay.is_none(),
+            );
+            let steps =
+                RenderSquare::with_physics(wgpu_render_state, id, |physics| physics.steps())
+                    .flatten();
+            let time = ui.input(|input| input.time);
+            self.step_rate.update(time, steps);
+            self.plots.record(steps.map_or(time, |steps| steps as f64));
+        }
+        ui.horizontal(|ui| {
+            #[cfg(not(target_arch = "wasm32"))]
+            ui.toggle_value(&mut self.background, "background compute")
+                .on_hover_text(
+                    "Update the physics continuously on a thread instead of once per frame, so that the number of steps per second is not limited by the framerate.",
+                );
+            if let Some(rate) = self.step_rate.rate {
+                ui.label(format!("{rate:.0} steps/s"));
+            }
+        });
+
+        // The GPU time of the passes is only known if the device supports timestamp queries.
+        if let Some(wgpu_render_state) = frame.wgpu_render_state() {
+            let timings = RenderSquare::gpu_timings(wgpu_render_state, id);
+            if !timings.is_empty() {
+                let timings = timings
+                    .iter()
+                    .map(|(label, ms)| format!("{label} {ms:.2} ms"))
+                    .collect::<Vec<_>>();
+                ui.label(format!("GPU: {}", timings.join(", ")));
+            }
+        }
+
+        self.plots.ui(ui, id);
 
-            Frame::canvas(ui.style()).show(ui, |ui| {
+        self.simulation.egui_panel(ui);
+
+        self.recorder.ui(ui);
+
+        if let Some(warning) = &self.warning {
+            ui.colored_label(ui.visuals().warn_fg_color, warning)
+                .on_hover_text(
+                    "See \"About / GPU info\" in the diagnostics for the limits of the device.",
+                );
+        }
+
+        Frame::canvas(ui.style())
+            .show(ui, |ui| {
                 let desired_size = ui.available_size();
                 let (_id, rect) = ui.allocate_space(desired_size);
-                // If the rendering size changed, create a new [RenderSquare] with the new size.
-                if self.width != rect.width() as u32 || self.height != rect.height() as u32 {
-                    self.width = rect.width() as u32;
-                    self.height = rect.height() as u32;
-                    let wgpu_render_state = frame
-                        .wgpu_render_state()
-                        .expect("No wgpu render state available.");
-                    self.render_square = Self::new_render_square(
-                        wgpu_render_state,
-                        &self.shader_module,
-                        &*self.simulation,
-                        self.width,
-                        self.height,
-                    );
+                self.recorder.frame(ui.ctx(), id, rect);
+                // If the size of the lattice, the seed or a parameter requiring a restart changed, create a new [RenderSquare] with a new physics. Without a fixed size, the lattice has one cell per pixel of the canvas.
+                let (width, height) = match self.size {
+                    Some(size) => (size.width, size.height),
+                    None => (rect.width() as u32, rect.height() as u32),
+                };
+                // A lattice of fixed size is shown with square cells, centered in the canvas.
+                let rect = match self.size {
+                    Some(_) => fit_aspect(rect, width, height),
+                    None => rect,
+                };
+                if restart || self.width != width || self.height != height {
+                    self.width = width;
+                    self.height = height;
+                    // The new physics probes no cell.
+                    self.probed_cell = None;
+                    let wgpu_render_state =
+                        frame.wgpu_render_state().ok_or(WGPUError::NoRenderState)?;
+                    RenderSquare::release(wgpu_render_state, id);
+                    (self.render_square, self.warning, self.plots) =
+                        SimulationGUI::new_render_square(
+                            wgpu_render_state,
+                            modules,
+                            &*self.simulation,
+                            id,
+                            self.width,
+                            self.height,
+                        )?;
                 }
                 ui.painter().add(egui_wgpu::Callback::new_paint_callback(
                     rect,
-                    self.render_square,
+                    self.render_square
+                        .with_render_ctx(RenderCtx {
+                            filter: FILTERS[self.filter].1,
+                            show_grid: self.show_grid as u32,
+                            grid_color: u32::from_le_bytes(self.grid_color.to_array()),
+                            viewport_width: rect.width() * ui.ctx().pixels_per_point(),
+                            viewport_height: rect.height() * ui.ctx().pixels_per_point(),
+                        })
+                        .with_timing(
+                            ui.ctx().input(|input| input.stable_dt),
+                            self.simulation.paused() && self.replay.is_none(),
+                        )
+                        .with_steps(self.replay.as_ref().map(|_| replay_steps.unwrap_or(0))),
+                ));
+                self.probe_ui(ui, frame, id, rect);
+                Ok(())
+            })
+            .inner
+    }
+}
+
+/// Largest rectangle centered in `rect` with the aspect ratio of a `width`×`height` lattice, so that its cells are square.
+#[cfg(feature = "gui")]
+fn fit_aspect(rect: egui::Rect, width: u32, height: u32) -> egui::Rect {
+    let aspect = width.max(1) as f32 / height.max(1) as f32;
+    let size = if rect.width() > rect.height() * aspect {
+        egui::vec2(rect.height() * aspect, rect.height())
+    } else {
+        egui::vec2(rect.width(), rect.width() / aspect)
+    };
+    egui::Rect::from_center_size(rect.center(), size)
+}
+
+/// Cell of a `width`×`height` lattice drawn in `rect` under `pos`, counted from the bottom left corner like the cells sampled by the fragments, see [kernel::cell_at]. `None` outside of `rect`.
+#[cfg(feature = "gui")]
+fn cell_under(rect: egui::Rect, pos: egui::Pos2, width: u32, height: u32) -> Option<[u32; 2]> {
+    if !rect.contains(pos) || width == 0 || height == 0 {
+        return None;
+    }
+    let u = (pos.x - rect.min.x) / rect.width();
+    let v = (rect.max.y - pos.y) / rect.height();
+    Some([
+        ((u * width as f32) as u32).min(width - 1),
+        ((v * height as f32) as u32).min(height - 1),
+    ])
+}
+
+/// Show the result of the [RngSelfTest].
+#[cfg(feature = "gui")]
+fn diagnostics_ui(ui: &mut egui::Ui, check: RngCheck) {
+    ui.horizontal(|ui| {
+        ui.label("RNG self-test:");
+        match check {
+            RngCheck::Running => {
+                ui.label("running");
+            }
+            RngCheck::Passed { checked } => {
+                ui.colored_label(egui::Color32::GREEN, "passed")
+                    .on_hover_text(format!("{checked} values equal to the CPU ones"));
+            }
+            RngCheck::Failed {
+                mismatches,
+                index,
+                gpu,
+                cpu,
+            } => {
+                ui.colored_label(egui::Color32::RED, "FAILED");
+                ui.label(format!(
+                    "{mismatches} mismatches, first at {index}: GPU {gpu:#010x}, CPU {cpu:#010x}"
+                ));
+            }
+        }
+    });
+}
+
+/// Show the adapter with the [Capabilities] of the GPU.
+#[cfg(feature = "gui")]
+fn capabilities_ui(ui: &mut egui::Ui, capabilities: &Capabilities) {
+    let info = &capabilities.info;
+    ui.label(format!(
+        "Adapter: {} ({:?}, {:?} backend, driver {} {})",
+        info.name, info.device_type, info.backend, info.driver, info.driver_info
+    ));
+    ui.label(format!(
+        "Largest square lattice: {0}×{0} cells",
+        capabilities.max_square_lattice(size_of::<kernel::CellRng>() as u64)
+    ))
+    .on_hover_text("Limited by the size of the buffer of the random number generators of a replica, the largest buffer per cell, and by the number of workgroups of a dispatch.");
+    if let Err(reason) = capabilities.timestamps() {
+        ui.label(format!("GPU time of the passes not measured: {reason}"));
+    }
+    egui::CollapsingHeader::new("Limits and features")
+        .default_open(true)
+        .show(ui, |ui| {
+            egui::Grid::new("capabilities")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.label("device");
+                    ui.label("adapter");
+                    ui.end_row();
+                    for (name, device, adapter) in capabilities.limits() {
+                        ui.label(name);
+                        ui.label(device.to_string());
+                        ui.label(adapter.to_string());
+                        ui.end_row();
+                    }
+                    for (name, supported, enabled) in capabilities.optional_features() {
+                        ui.label(name);
+                        ui.label(if enabled { "enabled" } else { "disabled" });
+                        ui.label(if supported {
+                            "supported"
+                        } else {
+                            "unsupported"
+                        });
+                        ui.end_row();
+                    }
+                });
+        });
+}
+
+/// Record the `err` which stopped the simulations in `events`, unless an earlier error was already recorded. The error is logged, and on the web it is also shown in the `loading_text` element of the page in case the canvas cannot display it.
+#[cfg(feature = "gui")]
+fn report_error(events: &Mutex<GpuEvents>, err: WGPUError) {
+    log::error!("{err}");
+    let error = &mut events.lock().unwrap().error;
+    if error.is_none() {
+        #[cfg(target_arch = "wasm32")]
+        show_loading_text(&format!("The app has stopped: {err}"));
+        *error = Some(err.to_string());
+    }
+}
+
+/// Show the `error` which stopped the simulations, with a button to copy it for a bug report.
+#[cfg(feature = "gui")]
+pub fn error_screen(ctx: &egui::Context, error: &str) {
+    egui::CentralPanel::default().show(ctx, |ui| {
+        ui.heading("The simulation stopped because of an error");
+        ui.add_space(8.0);
+        ui.label(egui::RichText::new(error).monospace());
+        ui.add_space(8.0);
+        if ui.button("copy to clipboard").clicked() {
+            ui.ctx().copy_text(error.to_string());
+        }
+    });
+}
+
+/// App showing the error which prevented the [SimulationGUI] from starting, see [SimulationGUI::start].
+#[cfg(feature = "gui")]
+struct ErrorScreen(Arc<Mutex<GpuEvents>>);
+
+#[cfg(feature = "gui")]
+impl eframe::App for ErrorScreen {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let error = self.0.lock().unwrap().error.clone().unwrap_or_default();
+        error_screen(ctx, &error);
+    }
+}
+
+#[cfg(feature = "gui")]
+impl eframe::App for SimulationGUI {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        // After the loss of the device or a lack of memory, the simulations are recreated before this frame.
+        let recover = self.events.lock().unwrap().recover.take();
+        if let Some(reason) = recover {
+            match self.recover(frame, reason.clone()) {
+                Ok(()) => self.banner = Some((reason, ctx.input(|i| i.time) + BANNER_DURATION)),
+                Err(err) => report_error(&self.events, err),
+            }
+        }
+        // Once an error stopped the simulations, only the error is shown.
+        let error = self.events.lock().unwrap().error.clone();
+        if let Some(error) = error {
+            error_screen(ctx, &error);
+            return;
+        }
+        if let Some((reason, until)) = &self.banner {
+            if ctx.input(|i| i.time) < *until {
+                egui::TopBottomPanel::top("recovery").show(ctx, |ui| {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        format!("device lost, recovering… ({reason})"),
+                    );
+                });
+            } else {
+                self.banner = None;
+            }
+        }
+        if let Some(wgpu_render_state) = frame.wgpu_render_state() {
+            let check = self.rng_self_test.poll(&wgpu_render_state.device);
+            let mut load = false;
+            egui::TopBottomPanel::bottom("diagnostics").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut self.spirv_path).desired_width(260.0));
+                    load = ui
+                        .button("load SPIR-V kernel")
+                        .on_hover_text("Add a column running the kernels of this module, described by the file with the same name and the extension \"manifest\".")
+                        .clicked();
+                    if let Some(err) = &self.load_error {
+                        ui.colored_label(ui.visuals().error_fg_color, err);
+                    }
+                });
+                egui::CollapsingHeader::new("Diagnostics").show(ui, |ui| {
+                    diagnostics_ui(ui, check);
+                    ui.toggle_value(&mut self.show_about, "About / GPU info");
+                });
+            });
+            if load {
+                self.load_custom(wgpu_render_state);
+            }
+        }
+        egui::Window::new("About / GPU info")
+            .open(&mut self.show_about)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Phase {}: GPU simulations of phase transitions",
+                    env!("CARGO_PKG_VERSION")
                 ));
+                ui.separator();
+                capabilities_ui(ui, &self.capabilities);
+            });
+        // The dropped files go to the view under the cursor, or to the first one.
+        let files = ctx.input(|input| input.raw.dropped_files.clone());
+        if !files.is_empty() {
+            let pointer = ctx.input(|input| input.pointer.latest_pos());
+            let view = self
+                .views
+                .iter()
+                .position(|view| pointer.is_some_and(|pointer| view.rect.contains(pointer)))
+                .unwrap_or(0);
+            if let Some(view) = self.views.get_mut(view) {
+                view.dropped.extend(files);
+            }
+        }
+        dropped::hover_overlay(ctx);
+        self.toasts.show(ctx);
+        let modules = &mut self.modules;
+        let registry = &self.registry;
+        let views = &mut self.views;
+        let events = &self.events;
+        let toasts = &mut self.toasts;
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.columns(views.len(), |columns| {
+                for (id, (ui, view)) in columns.iter_mut().zip(views.iter_mut()).enumerate() {
+                    // Distinct ids so that the widgets with the same label in different columns do not clash.
+                    let result = ui
+                        .push_id(id, |ui| view.ui(ui, frame, modules, registry, id, toasts))
+                        .inner;
+                    if let Err(err) = result {
+                        report_error(events, err);
+                    }
+                }
             });
         });
         ctx.request_repaint();
     }
 }
 
-#[cfg(not(target_arch = "wasm32"))]
-pub fn with_egui(simulation: Box<dyn Simulation>) {
+#[cfg(all(feature = "gui", not(target_arch = "wasm32")))]
+pub fn with_egui(simulations: Vec<SimulationSetup>) {
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
 
-    let native_options = eframe::NativeOptions::default();
+    let mut native_options = eframe::NativeOptions::default();
+    crate::gpu::adapter::request_adapter_capabilities(&mut native_options.wgpu_options);
     if let Err(err) = eframe::run_native(
         "Phase",
         native_options,
-        Box::new(|cc| Ok(Box::new(SimulationGUI::new(cc, simulation)))),
+        Box::new(|cc| Ok(SimulationGUI::start(cc, simulations))),
     ) {
         log::log!(log::Level::Error, "{err}");
     }
 }
 
 // When compiling to web using trunk:
-#[cfg(target_arch = "wasm32")]
-pub fn with_egui(simulation: Box<dyn Simulation>) {
+#[cfg(all(feature = "gui", target_arch = "wasm32"))]
+pub fn with_egui(simulations: Vec<SimulationSetup>) {
     use eframe::wasm_bindgen::JsCast as _;
 
     // Redirect `log` message to `console.log` and friends:
     eframe::WebLogger::init(log::LevelFilter::Debug).ok();
 
-    let web_options = eframe::WebOptions::default();
+    let mut web_options = eframe::WebOptions::default();
+    crate::gpu::adapter::request_adapter_capabilities(&mut web_options.wgpu_options);
 
     wasm_bindgen_futures::spawn_local(async {
-        let document = web_sys::window()
-            .expect("No window")
-            .document()
-            .expect("No document");
-
-        let canvas = document
-            .get_element_by_id("the_canvas_id")
-            .expect("Failed to find the_canvas_id")
-            .dyn_into::<web_sys::HtmlCanvasElement>()
-            .expect("the_canvas_id was not a HtmlCanvasElement");
-
-        let start_result = eframe::WebRunner::new()
+        let Some(canvas) = web_sys::window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.get_element_by_id("the_canvas_id"))
+            .and_then(|canvas| canvas.dyn_into::<web_sys::HtmlCanvasElement>().ok())
+        else {
+            log::error!("Failed to find the canvas the_canvas_id");
+            show_loading_text("The app could not start: no canvas found in the page.");
+            return;
+        };
+
+        let runner = eframe::WebRunner::new();
+        let start_result = runner
             .start(
                 canvas,
                 web_options,
-                Box::new(|cc| Ok(Box::new(SimulationGUI::new(cc, simulation)))),
+                Box::new(|cc| Ok(SimulationGUI::start(cc, simulations))),
             )
             .await;
 
-        // Remove the loading text and spinner:
-        if let Some(loading_text) = document.get_element_by_id("loading_text") {
-            match start_result {
-                Ok(_) => {
-                    loading_text.remove();
-                }
-                Err(e) => {
-                    loading_text.set_inner_html(
-                        "<p> The app has crashed. See the developer console for details. </p>",
-                    );
-                    panic!("Failed to start eframe: {e:?}");
-                }
+        // Remove the loading text and spinner, or replace them by the error.
+        match start_result {
+            Ok(_) => show_loading_text(""),
+            Err(err) => {
+                log::error!("Failed to start eframe: {err:?}");
+                show_loading_text(&format!("The app could not start: {err:?}"));
+                return;
             }
         }
+        // A panic stops the app without any message in the canvas, so it is shown in the loading text.
+        loop {
+            gloo_timers::future::TimeoutFuture::new(500).await;
+            if let Some(summary) = runner.panic_summary() {
+                show_loading_text(&format!("The app has crashed: {}", summary.message()));
+                break;
+            }
+        }
+    });
+}
+
+/// Replace the query of the URL of the page by `query`, without reloading the page nor adding an entry to its history.
+#[cfg(all(feature = "gui", target_arch = "wasm32"))]
+fn replace_url_query(query: &str) {
+    let Some(history) = web_sys::window().and_then(|window| window.history().ok()) else {
+        return;
+    };
+    let url = format!("?{query}");
+    if let Err(err) =
+        history.replace_state_with_url(&eframe::wasm_bindgen::JsValue::NULL, "", Some(&url))
+    {
+        log::error!("Failed to update the URL: {err:?}");
+    }
+}
+
+/// Replace the content of the `loading_text` element of the page by `text`, the element being emptied if `text` is empty.
+#[cfg(all(feature = "gui", target_arch = "wasm32"))]
+fn show_loading_text(text: &str) {
+    let Some(loading_text) = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.get_element_by_id("loading_text"))
+    else {
+        return;
+    };
+    if text.is_empty() {
+        loading_text.set_inner_html("");
+    } else {
+        let text = text
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        loading_text.set_inner_html(&format!(
+            "<p> {text} </p><p> See the developer console for details. </p>"
+        ));
+    }
+}
+
+/// Verify that the error screen shows the error.
+#[cfg(feature = "gui")]
+#[test]
+fn test_error_screen() {
+    fn collect_texts(shape: &egui::Shape, texts: &mut Vec<String>) {
+        match shape {
+            egui::Shape::Text(text) => texts.push(text.galley.text().to_string()),
+            egui::Shape::Vec(shapes) => shapes.iter().for_each(|shape| collect_texts(shape, texts)),
+            _ => {}
+        }
+    }
+    let ctx = egui::Context::default();
+    let output = ctx.run(egui::RawInput::default(), |ctx| {
+        error_screen(ctx, "Buffer size overflow")
     });
+    let mut shown = vec![];
+    for clipped in &output.shapes {
+        collect_texts(&clipped.shape, &mut shown);
+    }
+    assert!(shown.iter().any(|text| text == "Buffer size overflow"));
+    assert!(shown.iter().any(|text| text == "copy to clipboard"));
+}
+
+/// Verify that the number of steps per second is measured over intervals of about one second, skipping an interval during which the steps were reset.
+#[cfg(feature = "gui")]
+#[test]
+fn test_step_rate() {
+    let mut rate = StepRate::default();
+    rate.update(0.0, Some(100));
+    rate.update(0.5, Some(600));
+    assert_eq!(rate.rate, None);
+    rate.update(1.0, Some(1100));
+    assert_eq!(rate.rate, Some(1000.0));
+    rate.update(2.0, Some(50));
+    assert_eq!(rate.rate, Some(1000.0));
+    rate.update(4.0, Some(4050));
+    assert_eq!(rate.rate, Some(2000.0));
+    rate.update(5.0, None);
+    assert_eq!(rate.rate, None);
+}
+
+/// Verify that a lattice of fixed size is drawn with square cells in the middle of the canvas, whether it is wider or taller than the canvas.
+#[cfg(feature = "gui")]
+#[test]
+fn test_fit_aspect() {
+    let canvas = egui::Rect::from_min_size(egui::pos2(10.0, 20.0), egui::vec2(400.0, 300.0));
+    let wide = fit_aspect(canvas, 256, 128);
+    assert_eq!(wide.size(), egui::vec2(400.0, 200.0));
+    assert_eq!(wide.center(), canvas.center());
+    let tall = fit_aspect(canvas, 128, 256);
+    assert_eq!(tall.size(), egui::vec2(150.0, 300.0));
+    assert_eq!(tall.center(), canvas.center());
+    assert_eq!(fit_aspect(canvas, 4, 3), canvas);
+}
+
+/// Verify that the cell under the pointer counts the rows from the bottom of the canvas, and that the edges of the canvas fall in the last cells.
+#[cfg(feature = "gui")]
+#[test]
+fn test_cell_under() {
+    let rect = egui::Rect::from_min_size(egui::pos2(10.0, 20.0), egui::vec2(256.0, 128.0));
+    assert_eq!(
+        cell_under(rect, egui::pos2(10.0, 147.5), 256, 128),
+        Some([0, 0])
+    );
+    assert_eq!(
+        cell_under(rect, egui::pos2(10.0, 20.0), 256, 128),
+        Some([0, 127])
+    );
+    assert_eq!(
+        cell_under(rect, egui::pos2(266.0, 148.0), 256, 128),
+        Some([255, 0])
+    );
+    assert_eq!(
+        cell_under(rect, egui::pos2(138.5, 83.5), 64, 32),
+        Some([32, 16])
+    );
+    assert_eq!(cell_under(rect, egui::pos2(9.0, 30.0), 256, 128), None);
 }
@@ -0,0 +1,214 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, atomic::Ordering},
+};
+
+use crate::{
+    error::{ParameterError, WGPUError},
+    gpu::{
+        kernel_module::SpirvFile,
+        physics::custom::{CustomManifest, CustomParameters, CustomPipeline},
+    },
+    seed::Seed,
+};
+
+use super::{Parameter, Simulation, UpadeParameter};
+
+/// Bridge between the egui rendering/events and the [CustomPipeline] of the kernels of a SPIR-V module loaded at run time, described by a [CustomManifest].
+pub struct CustomKernel {
+    file: SpirvFile,
+    manifest: CustomManifest,
+    parameters: Arc<CustomParameters>,
+    seed: Seed,
+    restart: bool,
+}
+
+impl CustomKernel {
+    /// Load the SPIR-V module at `path` with its manifest, the file with the same name and the extension `manifest`, and check that the entry points of the manifest are in the module.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, WGPUError> {
+        let (file, manifest) = Self::read(path.as_ref())?;
+        Ok(CustomKernel {
+            parameters: Arc::new(CustomParameters::new(&manifest)),
+            file,
+            manifest,
+            seed: Seed::from_entropy(),
+            restart: false,
+        })
+    }
+    /// Path of the manifest of the module at `path`.
+    pub fn manifest_path(path: &Path) -> PathBuf {
+        path.with_extension("manifest")
+    }
+    fn read(path: &Path) -> Result<(SpirvFile, CustomManifest), WGPUError> {
+        let file = SpirvFile::read(path)?;
+        let manifest = CustomManifest::read(Self::manifest_path(path))?;
+        manifest.check(&file)?;
+        Ok((file, manifest))
+    }
+    /// Read the module and its manifest again, for instance after rebuilding the kernels, keeping the values of the parameters which are still in the manifest. The previous module is kept if they cannot be loaded.
+    fn reload(&mut self) -> Result<(), WGPUError> {
+        let (file, manifest) = Self::read(&self.file.path)?;
+        let parameters = CustomParameters::new(&manifest);
+        for (parameter, value) in manifest.parameters.iter().zip(&parameters.values) {
+            let previous = self
+                .manifest
+                .parameters
+                .iter()
+                .position(|p| p.name == parameter.name);
+            if let Some(i) = previous {
+                value.store(self.parameters.values[i].load());
+            }
+        }
+        let steps = self.parameters.steps_per_frame.load(Ordering::Relaxed);
+        parameters.steps_per_frame.store(steps, Ordering::Relaxed);
+        let normalize_every = self.parameters.normalize_every.load(Ordering::Relaxed);
+        parameters
+            .normalize_every
+            .store(normalize_every, Ordering::Relaxed);
+        let auto_scale = self.parameters.auto_scale.load(Ordering::Relaxed);
+        parameters.auto_scale.store(auto_scale, Ordering::Relaxed);
+        self.file = file;
+        self.manifest = manifest;
+        self.parameters = Arc::new(parameters);
+        self.restart = true;
+        Ok(())
+    }
+}
+
+impl Simulation for CustomKernel {
+    fn name(&self) -> &'static str {
+        "custom"
+    }
+    fn egui_parameters(&self) -> Vec<Parameter> {
+        let mut parameters = self
+            .manifest
+            .parameters
+            .iter()
+            .zip(&self.parameters.values)
+            .map(|(parameter, value)| Parameter::Slider {
+                tag: parameter.name,
+                value: value.load(),
+                logarithmic: false,
+                range: parameter.range.clone(),
+                tooltip: None,
+                unit: None,
+            })
+            .collect::<Vec<_>>();
+        parameters.push(Parameter::Slider {
+            tag: "steps/frame",
+            value: self.parameters.steps_per_frame.load(Ordering::Relaxed) as f32,
+            logarithmic: false,
+            range: 0.0..=64.0,
+            tooltip: None,
+            unit: None,
+        });
+        // The vector spins of the first buffer can be renormalized to unit length every few steps.
+        if self.manifest.element.components().is_some() {
+            parameters.push(Parameter::Slider {
+                tag: "normalize every",
+                value: self.parameters.normalize_every.load(Ordering::Relaxed) as f32,
+                logarithmic: false,
+                range: 0.0..=100.0,
+                tooltip: None,
+                unit: None,
+            });
+        }
+        parameters.extend([
+            Parameter::Button { tag: "reset" },
+            Parameter::Button { tag: "reload" },
+        ]);
+        // The two fields rendered without fragment entry point can be normalized by their current range.
+        if self.manifest.fragment.is_none() {
+            parameters.push(Parameter::Toggle {
+                tag: "auto-scale",
+                enable: self.parameters.auto_scale.load(Ordering::Relaxed),
+                reset_on_change: false,
+            });
+        }
+        parameters
+    }
+    fn update_parameter(&mut self, update: UpadeParameter) -> Result<(), ParameterError> {
+        match update {
+            UpadeParameter::Slider {
+                tag: "steps/frame",
+                value,
+            } => self
+                .parameters
+                .steps_per_frame
+                .store(value as u32, Ordering::Relaxed),
+            UpadeParameter::Slider {
+                tag: "normalize every",
+                value,
+            } => self
+                .parameters
+                .normalize_every
+                .store(value as u32, Ordering::Relaxed),
+            UpadeParameter::Slider { tag, value } => {
+                let i = self
+                    .manifest
+                    .parameters
+                    .iter()
+                    .position(|p| p.name == tag)
+                    .ok_or(ParameterError::UnknownTag(tag))?;
+                self.parameters.values[i].store(value);
+            }
+            UpadeParameter::Button { tag: "reset" } => self
+                .parameters
+                .reset_requested
+                .store(true, Ordering::Relaxed),
+            UpadeParameter::Toggle {
+                tag: "auto-scale",
+                enable,
+            } => self.parameters.auto_scale.store(enable, Ordering::Relaxed),
+            UpadeParameter::Button { tag: "reload" } => match self.reload() {
+                Ok(()) => log::info!("Reloaded {}.", self.file.path.display()),
+                Err(err) => log::error!("Failed to reload {}: {err}", self.file.path.display()),
+            },
+            UpadeParameter::Button { tag }
+            | UpadeParameter::Toggle { tag, .. }
+            | UpadeParameter::Choice { tag, .. }
+            | UpadeParameter::Text { tag, .. }
+            | UpadeParameter::Color { tag, .. } => return Err(ParameterError::UnknownTag(tag)),
+        }
+        Ok(())
+    }
+    fn egui_panel(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!("Kernels of {}", self.file.path.display()))
+            .on_hover_text(format!(
+                "Entry points: {}",
+                self.file.entry_points.join(", ")
+            ));
+    }
+    fn seed(&self) -> Seed {
+        self.seed
+    }
+    fn set_seed(&mut self, seed: Seed) {
+        self.seed = seed;
+    }
+    fn take_restart(&mut self) -> bool {
+        std::mem::take(&mut self.restart)
+    }
+    fn spirv_file(&self) -> Option<&SpirvFile> {
+        Some(&self.file)
+    }
+    fn physics(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        shader_module: &wgpu::ShaderModule,
+        width: u32,
+        height: u32,
+    ) -> Result<Box<dyn crate::gpu::physics::Physics>, WGPUError> {
+        Ok(Box::new(CustomPipeline::new(
+            device,
+            queue,
+            shader_module,
+            &self.file,
+            &self.manifest,
+            self.seed.0,
+            width,
+            height,
+            Arc::clone(&self.parameters),
+        )?))
+    }
+}
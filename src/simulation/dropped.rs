@@ -0,0 +1,157 @@
+use std::path::Path;
+
+use egui::{Align2, Color32, Id, LayerId, Order, TextStyle};
+
+use crate::gpu::physics::ising::STATE_MAGIC;
+
+use super::replay;
+
+/// Kind of a file dropped on the window, which decides how the [SimulationView](super::SimulationView) it is dropped on loads it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DroppedKind {
+    /// State saved by [Physics::save_state](crate::gpu::physics::Physics::save_state), loaded like with the `load state` button.
+    State,
+    /// PNG or JPEG image, loaded like with the `load image` button.
+    Image,
+    /// [ReplayLog](replay::ReplayLog), replayed like with the `replay` button.
+    Replay,
+}
+
+impl DroppedKind {
+    /// Kind of the file named `name` with the content `bytes`, recognized by its first bytes, by its content for the replay logs, or by its extension for the states whose format depends on the physics. `None` if it is not recognized.
+    pub fn detect(name: &str, bytes: &[u8]) -> Option<Self> {
+        const PNG: &[u8] = b"\x89PNG\r\n\x1a\n";
+        const JPEG: &[u8] = &[0xff, 0xd8, 0xff];
+        if bytes.starts_with(PNG) || bytes.starts_with(JPEG) {
+            return Some(DroppedKind::Image);
+        }
+        if bytes.starts_with(&STATE_MAGIC.to_le_bytes()) {
+            return Some(DroppedKind::State);
+        }
+        let text = std::str::from_utf8(bytes).ok();
+        if text.is_some_and(|text| replay::ReplayLog::parse(text).is_ok()) {
+            return Some(DroppedKind::Replay);
+        }
+        let extension = Path::new(name).extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "bin" | "state" => Some(DroppedKind::State),
+            _ => None,
+        }
+    }
+}
+
+/// Name of a dropped file, which egui only fills on the web, the file name of its path being used on native.
+pub fn dropped_name(file: &egui::DroppedFile) -> String {
+    match (&file.path, file.name.is_empty()) {
+        (Some(path), true) => path.file_name().map_or_else(
+            || path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        ),
+        _ => file.name.clone(),
+    }
+}
+
+/// Content of a dropped file, which is already in memory on the web, and read from its path on native.
+pub fn dropped_bytes(file: &egui::DroppedFile) -> std::io::Result<Vec<u8>> {
+    match (&file.bytes, &file.path) {
+        (Some(bytes), _) => Ok(bytes.to_vec()),
+        (None, Some(path)) => std::fs::read(path),
+        (None, None) => Err(std::io::Error::other("the file has no content")),
+    }
+}
+
+/// Cover the window with the files which can be dropped while some are dragged over it.
+pub fn hover_overlay(ctx: &egui::Context) {
+    if ctx.input(|input| input.raw.hovered_files.is_empty()) {
+        return;
+    }
+    let painter = ctx.layer_painter(LayerId::new(Order::Foreground, Id::new("dropped files")));
+    let screen = ctx.screen_rect();
+    painter.rect_filled(screen, 0.0, Color32::from_black_alpha(192));
+    painter.text(
+        screen.center(),
+        Align2::CENTER_CENTER,
+        "drop to load a state, an image or a replay on the simulation under the cursor…",
+        TextStyle::Heading.resolve(&ctx.style()),
+        Color32::WHITE,
+    );
+}
+
+/// Duration in seconds during which a notification of the [Toasts] is shown.
+const TOAST_DURATION: f64 = 5.0;
+
+/// Short notifications stacked in the bottom right corner of the window, such as the results of the loads of the dropped files, each one disappearing after [TOAST_DURATION] seconds. They are also logged.
+#[derive(Default)]
+pub struct Toasts {
+    /// Text of each notification, whether it is an error, and the time until which it is shown, set when it is first shown.
+    toasts: Vec<(String, bool, Option<f64>)>,
+}
+
+impl Toasts {
+    pub fn info(&mut self, text: String) {
+        log::info!("{text}");
+        self.toasts.push((text, false, None));
+    }
+    pub fn error(&mut self, text: String) {
+        log::error!("{text}");
+        self.toasts.push((text, true, None));
+    }
+    /// Display the notifications which have not expired.
+    pub fn show(&mut self, ctx: &egui::Context) {
+        let time = ctx.input(|input| input.time);
+        self.toasts
+            .retain(|(_, _, until)| until.is_none_or(|until| time < until));
+        if self.toasts.is_empty() {
+            return;
+        }
+        egui::Area::new(Id::new("toasts"))
+            .anchor(Align2::RIGHT_BOTTOM, [-8.0, -48.0])
+            .order(Order::Foreground)
+            .show(ctx, |ui| {
+                for (text, error, until) in &mut self.toasts {
+                    until.get_or_insert(time + TOAST_DURATION);
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        let color = if *error {
+                            ui.visuals().error_fg_color
+                        } else {
+                            ui.visuals().text_color()
+                        };
+                        ui.colored_label(color, text.as_str());
+                    });
+                }
+            });
+    }
+}
+
+/// Verify that the dropped files are recognized by their first bytes whatever their name, and by their extension for the states of other physics.
+#[test]
+fn test_dropped_kind() {
+    let detect = DroppedKind::detect;
+    assert_eq!(
+        detect("a.txt", b"\x89PNG\r\n\x1a\n..."),
+        Some(DroppedKind::Image)
+    );
+    assert_eq!(
+        detect("photo.jpg", &[0xff, 0xd8, 0xff, 0xe0]),
+        Some(DroppedKind::Image)
+    );
+    let log = replay::ReplayLog {
+        simulation: "ising".to_string(),
+        seed: crate::seed::Seed(42),
+        width: 8,
+        height: 8,
+        end: 100,
+        events: vec![],
+    };
+    assert_eq!(
+        detect("run.toml", log.to_string().as_bytes()),
+        Some(DroppedKind::Replay)
+    );
+    assert_eq!(
+        detect("state", &STATE_MAGIC.to_le_bytes()),
+        Some(DroppedKind::State)
+    );
+    assert_eq!(detect("langevin.BIN", &[1, 2, 3]), Some(DroppedKind::State));
+    assert_eq!(detect("notes.txt", b"hello"), None);
+    assert_eq!(detect("no extension", b""), None);
+}
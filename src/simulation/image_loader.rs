@@ -0,0 +1,177 @@
+#[cfg(target_arch = "wasm32")]
+use std::sync::{Arc, Mutex};
+
+use crate::import::{ImageFilter, ImageFit, ImageMapping, ImageSource};
+
+use super::{Parameter, UpadeParameter};
+
+/// Options of the images loaded as the state of a simulation, shared by the simulations as a group of [Parameter]s.
+///
+/// On native, the image is the file at the path entered in the `image` text box. On the web, where the files cannot be opened by path, the `load image` button opens the file picker of the browser instead, and the file chosen is read asynchronously until [ImageLoader::take_picked] returns it.
+pub struct ImageLoader {
+    #[cfg(not(target_arch = "wasm32"))]
+    path: String,
+    /// Whether the image is stretched to the lattice instead of letterboxed, see [ImageFit].
+    pub stretch: bool,
+    /// Whether the image is resampled with [ImageFilter::Nearest] instead of [ImageFilter::Bilinear].
+    pub nearest: bool,
+    /// Whether the light pixels give high values instead of the dark ones.
+    pub invert: bool,
+    /// Content of the file chosen in the browser, once it has been read.
+    #[cfg(target_arch = "wasm32")]
+    picked: Arc<Mutex<Option<Vec<u8>>>>,
+}
+
+impl ImageLoader {
+    pub fn new() -> Self {
+        ImageLoader {
+            #[cfg(not(target_arch = "wasm32"))]
+            path: "initial.png".to_string(),
+            stretch: false,
+            nearest: false,
+            invert: false,
+            #[cfg(target_arch = "wasm32")]
+            picked: Arc::new(Mutex::new(None)),
+        }
+    }
+    /// Parameters of the image: its path on native, the toggles of the fit, the filter and the inversion, and the `load image` button.
+    pub fn parameters(&self) -> Vec<Parameter> {
+        let mut parameters = vec![];
+        #[cfg(not(target_arch = "wasm32"))]
+        parameters.push(Parameter::Text {
+            tag: "image",
+            value: self.path.clone(),
+        });
+        parameters.extend([
+            Parameter::Toggle {
+                tag: "stretch image",
+                enable: self.stretch,
+                reset_on_change: false,
+            },
+            Parameter::Toggle {
+                tag: "nearest pixels",
+                enable: self.nearest,
+                reset_on_change: false,
+            },
+            Parameter::Toggle {
+                tag: "invert image",
+                enable: self.invert,
+                reset_on_change: false,
+            },
+            Parameter::Button { tag: "load image" },
+        ]);
+        parameters
+    }
+    /// Apply the `update` of one of the [ImageLoader::parameters] except the `load image` button, returning whether it was one of them.
+    pub fn update_parameter(&mut self, update: &UpadeParameter) -> bool {
+        match *update {
+            #[cfg(not(target_arch = "wasm32"))]
+            UpadeParameter::Text {
+                tag: "image",
+                ref value,
+            } => self.path = value.trim().to_string(),
+            UpadeParameter::Toggle {
+                tag: "stretch image",
+                enable,
+            } => self.stretch = enable,
+            UpadeParameter::Toggle {
+                tag: "nearest pixels",
+                enable,
+            } => self.nearest = enable,
+            UpadeParameter::Toggle {
+                tag: "invert image",
+                enable,
+            } => self.invert = enable,
+            _ => return false,
+        }
+        true
+    }
+    /// Image to load when the `load image` button is pressed: the file at the path.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn request(&self) -> Option<ImageSource> {
+        Some(ImageSource::Path(self.path.clone().into()))
+    }
+    /// Image to load when the `load image` button is pressed: the file picker is opened and `None` is returned, the file chosen being given later by [ImageLoader::take_picked].
+    #[cfg(target_arch = "wasm32")]
+    pub fn request(&self) -> Option<ImageSource> {
+        if let Err(err) = pick_file(Arc::clone(&self.picked)) {
+            log::error!("Failed to open the file picker: {err:?}");
+        }
+        None
+    }
+    /// Image chosen in the file picker of the browser since the last call, only on the web.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn take_picked(&self) -> Option<ImageSource> {
+        None
+    }
+    /// Image chosen in the file picker of the browser since the last call.
+    #[cfg(target_arch = "wasm32")]
+    pub fn take_picked(&self) -> Option<ImageSource> {
+        self.picked.lock().unwrap().take().map(ImageSource::Bytes)
+    }
+    /// Mapping of the image to the values from `low`, for the light pixels, to `high`, for the dark ones, thresholded at `threshold` if any, with margins of `background`, see [ImageMapping].
+    pub fn mapping(
+        &self,
+        low: f32,
+        high: f32,
+        threshold: Option<f32>,
+        background: f32,
+    ) -> ImageMapping {
+        ImageMapping {
+            fit: if self.stretch {
+                ImageFit::Stretch
+            } else {
+                ImageFit::Letterbox
+            },
+            filter: if self.nearest {
+                ImageFilter::Nearest
+            } else {
+                ImageFilter::Bilinear
+            },
+            threshold,
+            invert: self.invert,
+            low,
+            high,
+            background,
+        }
+    }
+}
+
+impl Default for ImageLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Open the file picker of the browser for an image, whose content is stored in `picked` once it has been read.
+#[cfg(target_arch = "wasm32")]
+fn pick_file(picked: Arc<Mutex<Option<Vec<u8>>>>) -> Result<(), eframe::wasm_bindgen::JsValue> {
+    use eframe::wasm_bindgen::{JsCast as _, closure::Closure};
+    use web_sys::js_sys;
+
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or("no document")?;
+    let input = document
+        .create_element("input")?
+        .dyn_into::<web_sys::HtmlInputElement>()?;
+    input.set_type("file");
+    input.set_accept("image/png,image/jpeg");
+    let target = input.clone();
+    let on_change = Closure::once_into_js(move || {
+        let Some(file) = target.files().and_then(|files| files.get(0)) else {
+            return;
+        };
+        wasm_bindgen_futures::spawn_local(async move {
+            match wasm_bindgen_futures::JsFuture::from(file.array_buffer()).await {
+                Ok(buffer) => {
+                    *picked.lock().unwrap() = Some(js_sys::Uint8Array::new(&buffer).to_vec())
+                }
+                Err(err) => log::error!("Failed to read the image: {err:?}"),
+            }
+        });
+    });
+    input.set_onchange(Some(on_change.unchecked_ref()));
+    input.click();
+    Ok(())
+}
@@ -1,71 +1,989 @@
-use std::sync::Arc;
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicU32, Ordering},
+};
 
-use crate::gpu::physics::ising::IsingPipeline;
+use egui_plot::{Line, Plot, PlotPoints};
 
-use super::{Parameter, Simulation, UpadeParameter, atomic_f32::AtomicF32};
+use kernel::{
+    DYNAMICS_KAWASAKI, DYNAMICS_SPIN_FLIP, NOT_PINNED, PROPOSAL_FLIP, PROPOSAL_RESAMPLE,
+    RULE_GLAUBER, RULE_HEAT_BATH, RULE_METROPOLIS,
+};
+
+use crate::{
+    error::{ParameterError, WGPUError},
+    gpu::{
+        command::{CommandReceiver, CommandSender, command_channel},
+        physics::{
+            Precision,
+            ising::{INITS, IsingCommand, IsingParameters, IsingPipeline, Snapshot},
+        },
+    },
+    import::ImageSource,
+    seed::Seed,
+};
+use quench::Quench;
+
+use super::{
+    Parameter, Simulation, SimulationEntry, SimulationOptions, UpadeParameter,
+    image_loader::ImageLoader,
+};
+
+pub mod quench;
+
+/// Possible choices for pinning the top and bottom rows, with the corresponding pinned value.
+const PINS: [(&str, f32); 3] = [("free", NOT_PINNED), ("+1", 1.0), ("-1", -1.0)];
+
+/// Index in [PINS] of the pinned value `pin`.
+fn pin_index(pin: f32) -> usize {
+    PINS.iter().position(|&(_, p)| p == pin).unwrap_or(0)
+}
+
+/// Red, green and blue channels of a color stored as RGBA with 8 bits per channel, red in the low bits.
+fn rgb(color: &AtomicU32) -> [u8; 3] {
+    let [r, g, b, _] = color.load(Ordering::Relaxed).to_le_bytes();
+    [r, g, b]
+}
+
+/// Possible choices for the size of the coarse-graining blocks, 0 meaning no coarse-graining.
+const BLOCKS: [(&str, u32); 4] = [("off", 0), ("2×2", 2), ("4×4", 4), ("8×8", 8)];
+
+/// Possible choices for the acceptance rule of the steps.
+const RULES: [(&str, u32); 3] = [
+    ("Glauber", RULE_GLAUBER),
+    ("Metropolis", RULE_METROPOLIS),
+    ("heat bath", RULE_HEAT_BATH),
+];
+
+/// Possible choices for the candidate of the steps.
+const PROPOSALS: [(&str, u32); 2] = [("resample", PROPOSAL_RESAMPLE), ("flip", PROPOSAL_FLIP)];
+/// Possible choices for the dynamics of the steps.
+const DYNAMICS: [(&str, u32); 2] = [
+    ("spin flip", DYNAMICS_SPIN_FLIP),
+    ("Kawasaki", DYNAMICS_KAWASAKI),
+];
+/// Largest number of frames kept to scrub through the recent history, which bounds its memory with the size of the lattice.
+const MAX_HISTORY: u32 = 500;
+/// Possible numbers of replicas simulated together.
+const REPLICAS: [(&str, u32); 5] = [("1", 1), ("2", 2), ("4", 4), ("8", 8), ("16", 16)];
+/// Largest memory in bytes of the frames of a time series held until it is exported, the series being exported as soon as it is reached.
+const MAX_SERIES_BYTES: usize = 256 << 20;
+
+/// Save the `vals` as an array of the given `shape` in the `.npy` file `name` in the working directory, or download it on the web, and describe the result, the errors being logged.
+fn save_npy(name: &str, shape: &[usize], vals: &[f32]) -> String {
+    let bytes = crate::export::encode_npy(shape, vals);
+    #[cfg(not(target_arch = "wasm32"))]
+    let saved = std::fs::write(name, bytes).map_err(|err| err.to_string());
+    #[cfg(target_arch = "wasm32")]
+    let saved = super::recorder::download(name, "application/octet-stream", &bytes)
+        .map_err(|err| format!("{err:?}"));
+    match saved {
+        Ok(()) => format!("{shape:?} saved in {name}"),
+        Err(err) => {
+            log::error!("Failed to export {name}: {err}");
+            format!("failed to export {name}: {err}")
+        }
+    }
+}
+
+/// Entry of the Ising model in the registry of [simulations](super::simulations).
+pub const ENTRY: SimulationEntry = ("ising", || Box::new(Ising::new()));
 
 /// Bridge between the egui rendering/events and the compute pipeline [IsingPipeline].
 pub struct Ising {
-    temperature: Arc<AtomicF32>,
-    external_field: Arc<AtomicF32>,
+    parameters: Arc<IsingParameters>,
+    /// Commands to the physics, see [IsingCommand].
+    commands: CommandSender<IsingCommand>,
+    /// Receiving end of `commands`, given to each new physics.
+    receiver: CommandReceiver<IsingCommand>,
+    /// Temperature and field requested from the UI and shown by the parameters. They are replaced by the ones of the physics, which the protocols also change, once it has applied the commands.
+    temperature: f32,
+    field: f32,
+    /// Whether a command changing the temperature or the field was sent during this frame, and might not be applied by the physics yet.
+    sent: bool,
+    seed: Seed,
+    /// Storage precision of the spins, changing it restarts the physics.
+    precision: Precision,
+    /// Number of independent replicas averaged by the measurements, changing it restarts the physics.
+    replicas: u32,
+    restart: bool,
+    /// Image loaded as the state by the "load image" button.
+    image: ImageLoader,
+    /// Darkness of the pixels above which they become spins up, see [ImageMapping](crate::import::ImageMapping).
+    image_threshold: f32,
+    /// Whether the margins of a letterboxed image are spins up instead of down.
+    image_margin_up: bool,
+    /// Number of sweeps performed by the "thermalize" button.
+    thermalize_sweeps: f32,
+    quench_high: f32,
+    quench_low: f32,
+    quench_equilibration: f32,
+    quench_duration: f32,
+    /// Domain size `L(t)` measured by the last [Quench].
+    quench_curve: Arc<Mutex<Vec<[f64; 2]>>>,
+    /// Number of sweeps between two frames of the time series exported as `.npy`.
+    series_every: u32,
+    /// Frames of the time series being recorded, if any.
+    series: Option<Vec<Snapshot>>,
+    /// Result of the last export, shown next to its buttons.
+    export_status: Option<String>,
 }
 
 impl Ising {
     pub fn new() -> Self {
+        let parameters = Arc::new(IsingParameters::default());
+        let (commands, receiver) = command_channel();
         Ising {
-            temperature: Arc::new(AtomicF32::new(2.2691853142)),
-            external_field: Arc::new(AtomicF32::new(0.0)),
+            temperature: parameters.temperature.load(),
+            field: parameters.external_field.load(),
+            sent: false,
+            parameters,
+            commands,
+            receiver,
+            seed: Seed::from_entropy(),
+            precision: Precision::F32,
+            replicas: 1,
+            restart: false,
+            image: ImageLoader::new(),
+            image_threshold: 0.5,
+            image_margin_up: false,
+            thermalize_sweeps: 1e4,
+            quench_high: 10.0,
+            quench_low: 1.0,
+            quench_equilibration: 100.0,
+            quench_duration: 10000.0,
+            quench_curve: Arc::new(Mutex::new(vec![])),
+            series_every: 100,
+            series: None,
+            export_status: None,
         }
     }
+    /// Send the image of `source` to the physics, mapped to spins with the threshold and the margins chosen.
+    fn send_image(&mut self, source: ImageSource) {
+        let margin = if self.image_margin_up { 1.0 } else { -1.0 };
+        let mapping = self
+            .image
+            .mapping(-1.0, 1.0, Some(self.image_threshold), margin);
+        // Like a reset, the image replaces the live state.
+        self.parameters.history.shown.store(0, Ordering::Relaxed);
+        self.commands.send(IsingCommand::LoadImage(source, mapping));
+    }
+    /// Send the temperature `temperature` to the physics.
+    fn set_temperature(&mut self, temperature: f32) {
+        self.temperature = temperature;
+        self.commands.send(IsingCommand::Temperature(temperature));
+        self.sent = true;
+    }
+    /// Display the buttons exporting the state shown and recording a time series of the states as `.npy` files, and save the [Snapshot]s read back since the previous frame. The state is exported in `lattice.npy` with the shape `(height, width)`, and the series in `series.npy` with the shape `(frames, height, width)` once it is stopped or reaches [MAX_SERIES_BYTES].
+    fn export_ui(&mut self, ui: &mut egui::Ui) {
+        let snapshots = std::mem::take(&mut *self.parameters.export.snapshots.lock().unwrap());
+        for snapshot in snapshots {
+            if !snapshot.series {
+                let shape = [snapshot.height as usize, snapshot.width as usize];
+                self.export_status = Some(save_npy("lattice.npy", &shape, &snapshot.vals));
+            } else if let Some(series) = &mut self.series {
+                // The frames of a lattice recreated at another size, for instance when the window is resized, are skipped.
+                let size = series.first().map(|first| (first.width, first.height));
+                if size.is_none_or(|size| size == (snapshot.width, snapshot.height)) {
+                    series.push(snapshot);
+                }
+            }
+        }
+        let bytes = self.series.as_ref().map_or(0, |series| {
+            series.iter().map(|frame| frame.vals.len() * 4).sum()
+        });
+        if bytes >= MAX_SERIES_BYTES {
+            log::warn!("The time series reached {MAX_SERIES_BYTES} bytes and is exported.");
+            self.stop_series();
+        }
+        ui.horizontal(|ui| {
+            if ui.button("export .npy").clicked() {
+                self.commands.send(IsingCommand::Export);
+            }
+            match &self.series {
+                Some(series) => {
+                    if ui.button("stop series").clicked() {
+                        self.stop_series();
+                    } else {
+                        ui.label(format!("{} frames", series.len()));
+                    }
+                }
+                None => {
+                    if ui.button("record series").clicked() {
+                        self.series = Some(vec![]);
+                        self.parameters
+                            .export
+                            .every
+                            .store(self.series_every, Ordering::Relaxed);
+                    }
+                    ui.add(
+                        egui::DragValue::new(&mut self.series_every)
+                            .range(1..=1_000_000)
+                            .prefix("every ")
+                            .suffix(" sweeps"),
+                    );
+                }
+            }
+            if let Some(status) = &self.export_status {
+                ui.label(status);
+            }
+        });
+    }
+    /// Stop the recording of the time series and export its frames stacked in `series.npy`.
+    fn stop_series(&mut self) {
+        self.parameters.export.every.store(0, Ordering::Relaxed);
+        let Some(series) = self.series.take() else {
+            return;
+        };
+        let Some(first) = series.first() else {
+            self.export_status = Some("no frame recorded".to_string());
+            return;
+        };
+        let shape = [series.len(), first.height as usize, first.width as usize];
+        let vals = series
+            .iter()
+            .flat_map(|frame| frame.vals.iter().copied())
+            .collect::<Vec<_>>();
+        self.export_status = Some(save_npy("series.npy", &shape, &vals));
+    }
+    /// Send the external field `field` to the physics.
+    fn set_field(&mut self, field: f32) {
+        self.field = field;
+        self.commands.send(IsingCommand::Field(field));
+        self.sent = true;
+    }
 }
 
 impl Simulation for Ising {
+    fn name(&self) -> &'static str {
+        ENTRY.0
+    }
     fn egui_parameters(&self) -> Vec<Parameter> {
-        vec![
+        let mut parameters = vec![
             Parameter::Slider {
                 tag: "T",
-                value: self.temperature.load(),
+                value: self.temperature,
                 logarithmic: true,
                 range: 1e-1..=1e1,
+                tooltip: Some(
+                    "Temperature of the heat bath, in units of the coupling over the Boltzmann constant. The critical temperature of the square lattice without field is T_c = 2/ln(1+√2) ≈ 2.269. Below it the spins order into large domains, above it they are disordered.",
+                ),
+                unit: Some("J/k_B"),
+            },
+            Parameter::Text {
+                tag: "exact T",
+                value: self.temperature.to_string(),
             },
             Parameter::Slider {
                 tag: "h",
-                value: self.external_field.load(),
+                value: self.field,
                 logarithmic: false,
                 range: -2.0..=2.0,
+                tooltip: Some(
+                    "External magnetic field, which favors the spins up when positive and the spins down when negative.",
+                ),
+                unit: Some("J"),
+            },
+            Parameter::Slider {
+                tag: "J",
+                value: self.parameters.coupling.load(),
+                logarithmic: false,
+                range: -2.0..=2.0,
+                tooltip: Some(
+                    "Coupling between neighboring spins: positive (ferromagnetic) couplings align them, negative (antiferromagnetic) ones anti-align them. The temperature and the field are in units of a coupling of 1.",
+                ),
+                unit: None,
+            },
+            Parameter::Choice {
+                tag: "dynamics",
+                options: DYNAMICS.map(|(name, _)| name).to_vec(),
+                selected: DYNAMICS
+                    .iter()
+                    .position(|&(_, d)| d == self.parameters.dynamics.load(Ordering::Relaxed))
+                    .unwrap_or(0),
+                reset_on_change: false,
+            },
+            Parameter::Choice {
+                tag: "acceptance",
+                options: RULES.map(|(name, _)| name).to_vec(),
+                selected: RULES
+                    .iter()
+                    .position(|&(_, r)| r == self.parameters.rule.load(Ordering::Relaxed))
+                    .unwrap_or(0),
+                reset_on_change: false,
+            },
+            Parameter::Choice {
+                tag: "proposal",
+                options: PROPOSALS.map(|(name, _)| name).to_vec(),
+                selected: PROPOSALS
+                    .iter()
+                    .position(|&(_, p)| p == self.parameters.proposal.load(Ordering::Relaxed))
+                    .unwrap_or(0),
+                reset_on_change: false,
+            },
+            Parameter::Toggle {
+                tag: "drive",
+                enable: self.parameters.drive.enable.load(Ordering::Relaxed),
+                reset_on_change: false,
+            },
+            Parameter::Slider {
+                tag: "A",
+                value: self.parameters.drive.amplitude.load(),
+                logarithmic: false,
+                range: 0.0..=2.0,
+                tooltip: Some(
+                    "Amplitude of the oscillation of the field when the drive is enabled, h(t) = h + A·sin(2π t/P).",
+                ),
+                unit: Some("J"),
+            },
+            Parameter::Slider {
+                tag: "P",
+                value: self.parameters.drive.period.load(),
+                logarithmic: true,
+                range: 1e1..=1e5,
+                tooltip: Some("Period of the oscillation of the field when the drive is enabled."),
+                unit: Some("sweeps"),
+            },
+            Parameter::Choice {
+                tag: "init",
+                options: INITS.map(|(name, _)| name).to_vec(),
+                selected: INITS
+                    .iter()
+                    .position(|&(_, i)| i == self.parameters.init.load(Ordering::Relaxed))
+                    .unwrap_or(0),
+                reset_on_change: true,
+            },
+            Parameter::Button { tag: "reset" },
+            Parameter::Slider {
+                tag: "thermalize sweeps",
+                value: self.thermalize_sweeps,
+                logarithmic: true,
+                range: 1e1..=1e6,
+                tooltip: Some(
+                    "Number of sweeps performed by the thermalize button over the next frames, without measuring.",
+                ),
+                unit: None,
+            },
+            Parameter::Button { tag: "thermalize" },
+        ];
+        parameters.extend(self.image.parameters());
+        parameters.extend([
+            Parameter::Slider {
+                tag: "image threshold",
+                value: self.image_threshold,
+                logarithmic: false,
+                range: 0.0..=1.0,
+                tooltip: None,
+                unit: None,
+            },
+            Parameter::Choice {
+                tag: "image margin",
+                options: vec!["spin down", "spin up"],
+                selected: self.image_margin_up as usize,
+                reset_on_change: false,
+            },
+            Parameter::Choice {
+                tag: "top row",
+                options: PINS.map(|(name, _)| name).to_vec(),
+                selected: pin_index(self.parameters.top_pin.load()),
+                reset_on_change: true,
+            },
+            Parameter::Choice {
+                tag: "bottom row",
+                options: PINS.map(|(name, _)| name).to_vec(),
+                selected: pin_index(self.parameters.bottom_pin.load()),
+                reset_on_change: true,
+            },
+            Parameter::Choice {
+                tag: "coarse-graining",
+                options: BLOCKS.map(|(name, _)| name).to_vec(),
+                selected: BLOCKS
+                    .iter()
+                    .position(|&(_, b)| b == self.parameters.block_size.load(Ordering::Relaxed))
+                    .unwrap_or(0),
+                reset_on_change: false,
+            },
+            Parameter::Toggle {
+                tag: "iterate blocking",
+                enable: self.parameters.block_iterate.load(Ordering::Relaxed),
+                reset_on_change: false,
+            },
+            Parameter::Color {
+                tag: "spin up",
+                rgb: rgb(&self.parameters.up_color),
+            },
+            Parameter::Color {
+                tag: "spin down",
+                rgb: rgb(&self.parameters.down_color),
+            },
+            Parameter::Toggle {
+                tag: "pause",
+                enable: self.parameters.history.paused.load(Ordering::Relaxed),
+                reset_on_change: false,
+            },
+            Parameter::Slider {
+                tag: "history frames",
+                value: self.parameters.history.capacity.load(Ordering::Relaxed) as f32,
+                logarithmic: false,
+                range: 0.0..=MAX_HISTORY as f32,
+                tooltip: None,
+                unit: None,
+            },
+            Parameter::Slider {
+                tag: "history every",
+                value: self.parameters.history.every.load(Ordering::Relaxed) as f32,
+                logarithmic: true,
+                range: 1e0..=1e4,
+                tooltip: Some("Interval between the frames of the history kept to scrub through."),
+                unit: Some("sweeps"),
+            },
+            Parameter::Toggle {
+                tag: "tune on GPU time",
+                enable: self.parameters.tuner.gpu_time.load(Ordering::Relaxed),
+                reset_on_change: false,
+            },
+            Parameter::Toggle {
+                tag: "frame budget",
+                enable: self.parameters.tuner.frame_budget.load(Ordering::Relaxed),
+                reset_on_change: false,
+            },
+            Parameter::Toggle {
+                tag: "tiled step",
+                enable: self.parameters.tiled.load(Ordering::Relaxed),
+                reset_on_change: false,
+            },
+            Parameter::Toggle {
+                tag: "half precision",
+                enable: self.precision == Precision::F16,
+                reset_on_change: false,
+            },
+            Parameter::Choice {
+                tag: "replicas",
+                options: REPLICAS.map(|(name, _)| name).to_vec(),
+                selected: REPLICAS
+                    .iter()
+                    .position(|&(_, r)| r == self.replicas)
+                    .unwrap_or(0),
+                reset_on_change: false,
+            },
+            Parameter::Toggle {
+                tag: "parallel tempering",
+                enable: self.parameters.tempering.enable.load(Ordering::Relaxed),
+                reset_on_change: false,
+            },
+            Parameter::Slider {
+                tag: "tempering T low",
+                value: self.parameters.tempering.low.load(),
+                logarithmic: true,
+                range: 1e-1..=1e1,
+                tooltip: Some("Lowest temperature of the ladder of the parallel tempering."),
+                unit: Some("J/k_B"),
             },
-        ]
+            Parameter::Slider {
+                tag: "tempering T high",
+                value: self.parameters.tempering.high.load(),
+                logarithmic: true,
+                range: 1e-1..=1e1,
+                tooltip: Some("Highest temperature of the ladder of the parallel tempering."),
+                unit: Some("J/k_B"),
+            },
+            Parameter::Slider {
+                tag: "swap every",
+                value: self.parameters.tempering.swap_every.load(Ordering::Relaxed) as f32,
+                logarithmic: true,
+                range: 1e0..=1e3,
+                tooltip: Some("Interval between the attempted swaps of the replicas at neighboring temperatures."),
+                unit: Some("sweeps"),
+            },
+            Parameter::Slider {
+                tag: "target FPS",
+                value: self.parameters.tuner.target_fps.load(),
+                logarithmic: false,
+                range: 10.0..=240.0,
+                tooltip: None,
+                unit: None,
+            },
+            Parameter::Slider {
+                tag: "min steps/frame",
+                value: self.parameters.tuner.min_steps.load(Ordering::Relaxed) as f32,
+                logarithmic: true,
+                range: 1e0..=1e4,
+                tooltip: None,
+                unit: None,
+            },
+            Parameter::Slider {
+                tag: "max steps/frame",
+                value: self.parameters.tuner.max_steps.load(Ordering::Relaxed) as f32,
+                logarithmic: true,
+                range: 1e0..=1e4,
+                tooltip: None,
+                unit: None,
+            },
+            Parameter::Slider {
+                tag: "measure every",
+                value: self.parameters.measurements.every.load(Ordering::Relaxed) as f32,
+                logarithmic: true,
+                range: 1e0..=1e3,
+                tooltip: Some("Interval between the measurements of the observables."),
+                unit: Some("sweeps"),
+            },
+            Parameter::Slider {
+                tag: "quench T high",
+                value: self.quench_high,
+                logarithmic: true,
+                range: 1e-1..=1e1,
+                tooltip: Some("Temperature at which the lattice is equilibrated before the quench."),
+                unit: Some("J/k_B"),
+            },
+            Parameter::Slider {
+                tag: "quench T low",
+                value: self.quench_low,
+                logarithmic: true,
+                range: 1e-1..=1e1,
+                tooltip: Some("Temperature to which the lattice is suddenly cooled, below T_c ≈ 2.269 for domains to grow."),
+                unit: Some("J/k_B"),
+            },
+            Parameter::Slider {
+                tag: "quench equilibration",
+                value: self.quench_equilibration,
+                logarithmic: true,
+                range: 1e0..=1e4,
+                tooltip: Some("Sweeps at the high temperature before the quench."),
+                unit: Some("sweeps"),
+            },
+            Parameter::Slider {
+                tag: "quench sweeps",
+                value: self.quench_duration,
+                logarithmic: true,
+                range: 1e2..=1e6,
+                tooltip: Some("Duration of the measurement of the growth of the domains after the quench."),
+                unit: Some("sweeps"),
+            },
+            Parameter::Button { tag: "quench" },
+            Parameter::Button {
+                tag: "abort quench",
+            },
+        ]);
+        parameters
     }
-    fn update_parameter(&mut self, update: UpadeParameter) {
+    fn update_parameter(&mut self, update: UpadeParameter) -> Result<(), ParameterError> {
+        if self.image.update_parameter(&update) {
+            return Ok(());
+        }
         match update {
             UpadeParameter::Slider { tag, value } => match tag {
-                "T" => self.temperature.store(value),
-                "h" => self.external_field.store(value),
-                _ => {
-                    panic!("Unexpected tag in update_parameter: \"{tag}\"")
+                "T" => self.set_temperature(value),
+                "h" => self.set_field(value),
+                "J" => self.parameters.coupling.store(value),
+                "A" => self.parameters.drive.amplitude.store(value),
+                "P" => self.parameters.drive.period.store(value),
+                "target FPS" => self.parameters.tuner.target_fps.store(value),
+                "min steps/frame" => self
+                    .parameters
+                    .tuner
+                    .min_steps
+                    .store(value as u32, Ordering::Relaxed),
+                "max steps/frame" => self
+                    .parameters
+                    .tuner
+                    .max_steps
+                    .store(value as u32, Ordering::Relaxed),
+                "measure every" => self
+                    .parameters
+                    .measurements
+                    .every
+                    .store(value as u32, Ordering::Relaxed),
+                "tempering T low" => self.parameters.tempering.low.store(value),
+                "tempering T high" => self.parameters.tempering.high.store(value),
+                "swap every" => self
+                    .parameters
+                    .tempering
+                    .swap_every
+                    .store(value as u32, Ordering::Relaxed),
+                "history frames" => self
+                    .parameters
+                    .history
+                    .capacity
+                    .store(value as u32, Ordering::Relaxed),
+                "history every" => self
+                    .parameters
+                    .history
+                    .every
+                    .store(value as u32, Ordering::Relaxed),
+                "thermalize sweeps" => self.thermalize_sweeps = value,
+                "quench T high" => self.quench_high = value,
+                "quench T low" => self.quench_low = value,
+                "quench equilibration" => self.quench_equilibration = value,
+                "quench sweeps" => self.quench_duration = value,
+                "image threshold" => self.image_threshold = value,
+                _ => return Err(ParameterError::UnknownTag(tag)),
+            },
+            UpadeParameter::Toggle { tag, enable } => match tag {
+                "drive" => self
+                    .parameters
+                    .drive
+                    .enable
+                    .store(enable, Ordering::Relaxed),
+                "pause" => self
+                    .parameters
+                    .history
+                    .paused
+                    .store(enable, Ordering::Relaxed),
+                "parallel tempering" => self
+                    .parameters
+                    .tempering
+                    .enable
+                    .store(enable, Ordering::Relaxed),
+                "iterate blocking" => self
+                    .parameters
+                    .block_iterate
+                    .store(enable, Ordering::Relaxed),
+                "tune on GPU time" => self
+                    .parameters
+                    .tuner
+                    .gpu_time
+                    .store(enable, Ordering::Relaxed),
+                "frame budget" => self
+                    .parameters
+                    .tuner
+                    .frame_budget
+                    .store(enable, Ordering::Relaxed),
+                "tiled step" => self.parameters.tiled.store(enable, Ordering::Relaxed),
+                "half precision" => {
+                    self.precision = if enable {
+                        Precision::F16
+                    } else {
+                        Precision::F32
+                    };
+                    self.restart = true;
                 }
+                _ => return Err(ParameterError::UnknownTag(tag)),
             },
-            _ => {}
+            UpadeParameter::Choice { tag, selected } => match tag {
+                "dynamics" => self
+                    .parameters
+                    .dynamics
+                    .store(DYNAMICS[selected].1, Ordering::Relaxed),
+                "acceptance" => self
+                    .parameters
+                    .rule
+                    .store(RULES[selected].1, Ordering::Relaxed),
+                "proposal" => self
+                    .parameters
+                    .proposal
+                    .store(PROPOSALS[selected].1, Ordering::Relaxed),
+                "replicas" => {
+                    self.replicas = REPLICAS[selected].1;
+                    self.restart = true;
+                }
+                "init" => self
+                    .parameters
+                    .init
+                    .store(INITS[selected].1, Ordering::Relaxed),
+                "top row" => self.parameters.top_pin.store(PINS[selected].1),
+                "bottom row" => self.parameters.bottom_pin.store(PINS[selected].1),
+                "coarse-graining" => self
+                    .parameters
+                    .block_size
+                    .store(BLOCKS[selected].1, Ordering::Relaxed),
+                "image margin" => self.image_margin_up = selected == 1,
+                _ => return Err(ParameterError::UnknownTag(tag)),
+            },
+            UpadeParameter::Text { tag, value } => match tag {
+                "exact T" => match value.trim().parse::<f32>() {
+                    Ok(t) if t.is_finite() && t > 0.0 => self.set_temperature(t),
+                    _ => return Err(ParameterError::InvalidValue { tag, value }),
+                },
+                _ => return Err(ParameterError::UnknownTag(tag)),
+            },
+            UpadeParameter::Color {
+                tag,
+                rgb: [r, g, b],
+            } => {
+                let color = u32::from_le_bytes([r, g, b, 255]);
+                match tag {
+                    "spin up" => self.parameters.up_color.store(color, Ordering::Relaxed),
+                    "spin down" => self.parameters.down_color.store(color, Ordering::Relaxed),
+                    _ => return Err(ParameterError::UnknownTag(tag)),
+                }
+            }
+            UpadeParameter::Button { tag } => match tag {
+                "quench" => {
+                    let quench = Quench::new(
+                        self.quench_high,
+                        self.quench_low,
+                        self.quench_equilibration as u32,
+                        self.quench_duration as u32,
+                        Arc::clone(&self.quench_curve),
+                    );
+                    self.parameters
+                        .protocol
+                        .lock()
+                        .unwrap()
+                        .start(Box::new(quench));
+                }
+                "abort quench" => self.parameters.protocol.lock().unwrap().abort(),
+                "load image" => {
+                    if let Some(source) = self.image.request() {
+                        self.send_image(source);
+                    }
+                }
+                "thermalize" => {
+                    // Like a reset, the thermalization waits for the live state.
+                    self.parameters.history.shown.store(0, Ordering::Relaxed);
+                    self.commands
+                        .send(IsingCommand::Thermalize(self.thermalize_sweeps as u32));
+                }
+                "reset" => {
+                    // The reset replaces the live state, so the live state is shown again if a past frame was shown.
+                    self.parameters.history.shown.store(0, Ordering::Relaxed);
+                    self.commands.send(IsingCommand::Reset);
+                }
+                _ => return Err(ParameterError::UnknownTag(tag)),
+            },
+        }
+        Ok(())
+    }
+    fn description(&self) -> &'static str {
+        "Ising model: spins s = ±1 on a square lattice with periodic boundaries and energy H = -J Σ s_i s_j - h Σ s_i, the first sum running over the pairs of neighbors.\n\
+        At each sweep, every spin draws a random candidate value, which is accepted with the probability 1/(1 + exp(ΔE/T)) where ΔE is the change of energy (Glauber rule, a smooth variant of the Metropolis rule which accepts with probability min(1, exp(-ΔE/T))). The Metropolis rule, or the heat bath rule which draws the spin from its equilibrium distribution in the field of its neighbors, can be selected instead. The candidate is a random value by default, which is the current value half of the time, or the flipped spin with the \"flip\" proposal, which changes the spins twice as often for the same equilibrium. All the spins are updated simultaneously from the previous state of their neighbors.\n\
+        Without external field and for J > 0, the model orders spontaneously below the critical temperature T_c = 2J/ln(1+√2) ≈ 2.269 J (Onsager), where domains of all sizes appear."
+    }
+    fn egui_panel(&mut self, ui: &mut egui::Ui) {
+        // The commands sent during the previous frame have been applied by the physics since.
+        if !std::mem::take(&mut self.sent) {
+            self.temperature = self.parameters.temperature.load();
+            self.field = self.parameters.external_field.load();
+        }
+        if let Some(source) = self.image.take_picked() {
+            self.send_image(source);
+        }
+        if let Some(observables) = *self.parameters.measurements.observables.lock().unwrap() {
+            ui.label(format!(
+                "m = {:+.4} ± {:.4}    E = {:+.4}",
+                observables.magnetization, observables.magnetization_error, observables.energy
+            ));
+        }
+        let thermalizing = self.parameters.thermalizing.load(Ordering::Relaxed);
+        if thermalizing > 0 {
+            ui.label(format!("thermalizing, {thermalizing} sweeps left"));
+        }
+        let mut moments = self.parameters.measurements.moments.lock().unwrap();
+        if let Some(cumulant) = moments.binder_cumulant() {
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "U = {cumulant:.4} over {} measurements",
+                    moments.measurements()
+                ));
+                if ui.small_button("clear").clicked() {
+                    moments.clear();
+                }
+            });
+        }
+        drop(moments);
+        self.export_ui(ui);
+        // While paused, a slider selects the recorded frame shown, the live state being on the right.
+        let sweeps = self.parameters.history.sweeps.lock().unwrap().clone();
+        if self.parameters.history.paused.load(Ordering::Relaxed) && !sweeps.is_empty() {
+            let mut shown = self.parameters.history.shown.load(Ordering::Relaxed) as i32;
+            shown = shown.min(sweeps.len() as i32);
+            let mut position = -shown;
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::Slider::new(&mut position, -(sweeps.len() as i32)..=0).text("history"),
+                );
+                match position {
+                    0 => ui.label("live"),
+                    _ => ui.label(format!(
+                        "sweep {}",
+                        sweeps[(sweeps.len() as i32 + position) as usize]
+                    )),
+                };
+            });
+            self.parameters
+                .history
+                .shown
+                .store((-position) as u32, Ordering::Relaxed);
+        }
+        if self.parameters.tempering.enable.load(Ordering::Relaxed) {
+            let rates = self.parameters.tempering.exchange_rates.lock().unwrap();
+            if !rates.is_empty() {
+                ui.label("Exchange acceptance rates:");
+                for ([low, high], rate) in rates.iter() {
+                    ui.label(format!("T {low:.3} ↔ {high:.3}: {rate:.2}"));
+                }
+            }
+        }
+        // With the drive, the magnetization lags behind the field h(t) and traces a hysteresis loop.
+        if self.parameters.drive.enable.load(Ordering::Relaxed) {
+            let hysteresis = self.parameters.drive.hysteresis.lock().unwrap();
+            if !hysteresis.is_empty() {
+                ui.label("Hysteresis loop m(h)");
+                Plot::new("hysteresis")
+                    .height(150.0)
+                    .x_axis_label("h(t)")
+                    .y_axis_label("m")
+                    .show(ui, |plot_ui| {
+                        plot_ui.line(Line::new(
+                            "m(h)",
+                            PlotPoints::from_iter(hysteresis.iter().copied()),
+                        ));
+                    });
+            }
+        }
+        let curve = self.quench_curve.lock().unwrap();
+        if curve.is_empty() {
+            return;
+        }
+        ui.horizontal(|ui| {
+            ui.label("Quench domain size L(t), log-log");
+            if ui.button("export CSV").clicked() {
+                if let Err(err) = crate::export::write_csv("quench.csv", &["t", "L"], curve.iter())
+                {
+                    log::error!("Failed to export the quench curve: {err}");
+                }
+            }
+        });
+        let log_curve = curve
+            .iter()
+            .map(|&[t, l]| [t.log10(), l.log10()])
+            .collect::<Vec<_>>();
+        // Reference line with slope 1/2 going through the first point.
+        let [t0, l0] = log_curve[0];
+        let t1 = log_curve[log_curve.len() - 1][0];
+        let reference = vec![[t0, l0], [t1, l0 + 0.5 * (t1 - t0)]];
+        Plot::new("quench")
+            .height(150.0)
+            .x_axis_label("log10 t")
+            .y_axis_label("log10 L")
+            .show(ui, |plot_ui| {
+                plot_ui.line(Line::new("L(t)", PlotPoints::from(log_curve)));
+                plot_ui.line(Line::new("t^(1/2)", PlotPoints::from(reference)));
+            });
+    }
+    fn load_image(&mut self, source: ImageSource) -> Result<(), String> {
+        self.send_image(source);
+        Ok(())
+    }
+    fn seed(&self) -> Seed {
+        self.seed
+    }
+    fn set_seed(&mut self, seed: Seed) {
+        self.seed = seed;
+    }
+    fn take_restart(&mut self) -> bool {
+        std::mem::take(&mut self.restart)
+    }
+    fn paused(&self) -> bool {
+        self.parameters.history.paused.load(Ordering::Relaxed)
+    }
+    fn apply_options(&mut self, options: &SimulationOptions) -> Result<(), String> {
+        if let Some(temperature) = options.temperature {
+            self.set_temperature(temperature);
+        }
+        if let Some(field) = options.field {
+            self.set_field(field);
+        }
+        if let Some(replicas) = options.replicas {
+            if !REPLICAS.iter().any(|&(_, r)| r == replicas) {
+                let valid = REPLICAS.map(|(name, _)| name);
+                return Err(format!(
+                    "invalid number of replicas {replicas}, expected one of {}",
+                    valid.join(", ")
+                ));
+            }
+            self.replicas = replicas;
+        }
+        if options.half {
+            self.precision = Precision::F16;
         }
+        Ok(())
     }
     fn physics(
         &self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         shader_module: &wgpu::ShaderModule,
-        seed: u128,
         width: u32,
         height: u32,
-    ) -> Box<dyn crate::gpu::physics::Physics> {
-        Box::new(IsingPipeline::new(
-            device,
-            queue,
-            shader_module,
-            seed,
-            width,
-            height,
-            Arc::clone(&self.temperature),
-            Arc::clone(&self.external_field),
+    ) -> Result<Box<dyn crate::gpu::physics::Physics>, WGPUError> {
+        Ok(Box::new(
+            IsingPipeline::new(
+                device,
+                queue,
+                shader_module,
+                self.seed.0,
+                width,
+                height,
+                Arc::clone(&self.parameters),
+                self.precision,
+                self.replicas,
+            )?
+            .with_commands(self.receiver.clone()),
         ))
     }
 }
+
+/// Verify that an unknown tag and an invalid exact temperature are reported as errors instead of panicking, without sending any command, and that a valid one is sent to the physics.
+#[test]
+fn test_update_parameter_errors() {
+    let mut ising = Ising::new();
+    let temperature = ising.temperature;
+    assert_eq!(
+        ising.update_parameter(UpadeParameter::Slider {
+            tag: "unknown",
+            value: 1.0
+        }),
+        Err(ParameterError::UnknownTag("unknown"))
+    );
+    assert_eq!(
+        ising.update_parameter(UpadeParameter::Text {
+            tag: "exact T",
+            value: "-1".to_string()
+        }),
+        Err(ParameterError::InvalidValue {
+            tag: "exact T",
+            value: "-1".to_string()
+        })
+    );
+    assert_eq!(ising.temperature, temperature);
+    assert!(ising.receiver.drain().is_empty());
+    assert_eq!(
+        ising.update_parameter(UpadeParameter::Text {
+            tag: "exact T",
+            value: "2.5".to_string()
+        }),
+        Ok(())
+    );
+    assert_eq!(ising.temperature, 2.5);
+    assert_eq!(ising.receiver.drain(), [IsingCommand::Temperature(2.5)]);
+}
+
+/// Verify that only the initialization and the pinned rows reset the state when they change, the temperature being live and the precision and the replicas restarting the physics instead.
+#[test]
+fn test_structural_parameters() {
+    let mut ising = Ising::new();
+    let structural: Vec<_> = ising
+        .egui_parameters()
+        .iter()
+        .filter(|p| p.reset_on_change())
+        .map(Parameter::tag)
+        .collect();
+    assert_eq!(structural, ["init", "top row", "bottom row"]);
+    assert_eq!(
+        ising.update_parameter(UpadeParameter::Choice {
+            tag: "top row",
+            selected: 1
+        }),
+        Ok(())
+    );
+    assert!(!ising.take_restart());
+    assert_eq!(
+        ising.update_parameter(UpadeParameter::Toggle {
+            tag: "half precision",
+            enable: true
+        }),
+        Ok(())
+    );
+    assert!(ising.take_restart());
+}
@@ -0,0 +1,74 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    gpu::physics::ising::{IsingObservables, IsingParameters},
+    simulation::protocol::Protocol,
+};
+
+/// Ratio between two consecutive measurement times, so that there are 10 measurements per decade.
+const LOG_STEP: f32 = 1.258_925_4; // 10^(1/10)
+
+/// Quench experiment: equilibrate at the temperature `high` for `equilibration` sweeps, then drop instantly to the temperature `low` and record the characteristic domain size `L(t)` at logarithmically spaced times for `duration` sweeps.
+///
+/// The domain size is measured as the inverse of the density of domain wall bonds, and is expected to grow as `t^(1/2)` at low temperature.
+pub struct Quench {
+    high: f32,
+    low: f32,
+    equilibration: u32,
+    duration: u32,
+    next_measurement: u32,
+    curve: Arc<Mutex<Vec<[f64; 2]>>>,
+    done: bool,
+}
+
+impl Quench {
+    /// Create a new quench which stores the measured `(t, L)` points in `curve`, replacing its previous content.
+    pub fn new(
+        high: f32,
+        low: f32,
+        equilibration: u32,
+        duration: u32,
+        curve: Arc<Mutex<Vec<[f64; 2]>>>,
+    ) -> Self {
+        curve.lock().unwrap().clear();
+        Quench {
+            high,
+            low,
+            equilibration,
+            duration,
+            next_measurement: 1,
+            curve,
+            done: false,
+        }
+    }
+}
+
+impl Protocol<IsingParameters, IsingObservables> for Quench {
+    fn wants_measurement(&self, sweep: u32) -> bool {
+        sweep >= self.equilibration + self.next_measurement
+    }
+    fn update(
+        &mut self,
+        sweep: u32,
+        parameters: &IsingParameters,
+        measurement: Option<(u32, &IsingObservables)>,
+    ) {
+        if sweep < self.equilibration {
+            parameters.temperature.store(self.high);
+            return;
+        }
+        parameters.temperature.store(self.low);
+        if let Some((measured, observables)) = measurement {
+            let t = measured.saturating_sub(self.equilibration);
+            if t > 0 && observables.interface_density > 0.0 {
+                let size = 1.0 / observables.interface_density;
+                self.curve.lock().unwrap().push([t as f64, size]);
+            }
+            self.next_measurement = ((t as f32 * LOG_STEP).ceil() as u32).max(t + 1);
+        }
+        self.done = sweep - self.equilibration >= self.duration;
+    }
+    fn finished(&self) -> bool {
+        self.done
+    }
+}
@@ -0,0 +1,318 @@
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, AtomicU32, Ordering},
+};
+
+use crate::{
+    error::{ParameterError, WGPUError},
+    gpu::{
+        command::{CommandReceiver, CommandSender, command_channel},
+        physics::langevin::{LangevinCommand, LangevinParameters, LangevinPipeline},
+        step_tuner::DEFAULT_TARGET_FPS,
+    },
+    import::ImageSource,
+    seed::Seed,
+};
+
+use super::{
+    Parameter, Simulation, SimulationEntry, UpadeParameter, atomic_f32::AtomicF32,
+    image_loader::ImageLoader,
+};
+
+/// Entry of the Langevin model in the registry of [simulations](super::simulations).
+pub const ENTRY: SimulationEntry = ("langevin", || Box::new(Langevin::new()));
+
+/// Bridge between the egui rendering/events and the compute pipeline [LangevinPipeline].
+pub struct Langevin {
+    parameters: Arc<LangevinParameters>,
+    /// Commands to the physics, see [LangevinCommand].
+    commands: CommandSender<LangevinCommand>,
+    /// Receiving end of `commands`, given to each new physics.
+    receiver: CommandReceiver<LangevinCommand>,
+    seed: Seed,
+    /// Image loaded as the field by the "load image" button, mapped from `range min` for the light pixels to `range max` for the dark ones.
+    image: ImageLoader,
+    /// Value of the field in the margins of a letterboxed image.
+    image_margin: f32,
+}
+
+impl Langevin {
+    pub fn new() -> Self {
+        let (commands, receiver) = command_channel();
+        Langevin {
+            parameters: Arc::new(LangevinParameters {
+                r: AtomicF32::new(0.0),
+                u: AtomicF32::new(1.0),
+                gamma: AtomicF32::new(1.0),
+                dt: AtomicF32::new(0.05),
+                noise: AtomicF32::new(0.5),
+                target_fps: AtomicF32::new(DEFAULT_TARGET_FPS),
+                min_steps: AtomicU32::new(1),
+                max_steps: AtomicU32::new(10),
+                auto_scale: AtomicBool::new(false),
+                range_min: AtomicF32::new(-2.0),
+                range_max: AtomicF32::new(2.0),
+                paused: AtomicBool::new(false),
+                check_every: AtomicU32::new(30),
+                unstable: Mutex::new(None),
+                stable_saved: AtomicBool::new(false),
+            }),
+            commands,
+            receiver,
+            seed: Seed::from_entropy(),
+            image: ImageLoader::new(),
+            image_margin: 0.0,
+        }
+    }
+    /// Send the image of `source` to the physics, mapped to the range of the colors.
+    fn send_image(&self, source: ImageSource) {
+        let mapping = self.image.mapping(
+            self.parameters.range_min.load(),
+            self.parameters.range_max.load(),
+            None,
+            self.image_margin,
+        );
+        self.commands
+            .send(LangevinCommand::LoadImage(source, mapping));
+    }
+}
+
+impl Simulation for Langevin {
+    fn name(&self) -> &'static str {
+        ENTRY.0
+    }
+    fn egui_parameters(&self) -> Vec<Parameter> {
+        let mut parameters = vec![
+            Parameter::Slider {
+                tag: "r",
+                value: self.parameters.r.load(),
+                logarithmic: false,
+                range: -2.0..=2.0,
+                tooltip: Some(
+                    "Coefficient of the quadratic term of the potential: the field orders around ±√(-r/u) for r < 0 and fluctuates around 0 for r > 0, r = 0 being the mean field transition.",
+                ),
+                unit: None,
+            },
+            Parameter::Slider {
+                tag: "u",
+                value: self.parameters.u.load(),
+                logarithmic: true,
+                range: 1e-2..=1e1,
+                tooltip: Some(
+                    "Coefficient of the quartic term of the potential, which bounds the field.",
+                ),
+                unit: None,
+            },
+            Parameter::Slider {
+                tag: "Γ",
+                value: self.parameters.gamma.load(),
+                logarithmic: true,
+                range: 1e-2..=1e1,
+                tooltip: Some(
+                    "Mobility, the rate at which the field relaxes towards the minimum of the potential.",
+                ),
+                unit: None,
+            },
+            Parameter::Slider {
+                tag: "dt",
+                value: self.parameters.dt.load(),
+                logarithmic: true,
+                range: 1e-3..=1e0,
+                tooltip: Some(
+                    "Time step of the explicit integration, clamped to 1/(Γ(8+|r|)) for stability.",
+                ),
+                unit: None,
+            },
+            Parameter::Slider {
+                tag: "noise",
+                value: self.parameters.noise.load(),
+                logarithmic: false,
+                range: 0.0..=2.0,
+                tooltip: Some("Amplitude of the thermal noise."),
+                unit: None,
+            },
+            Parameter::Slider {
+                tag: "target FPS",
+                value: self.parameters.target_fps.load(),
+                logarithmic: false,
+                range: 10.0..=240.0,
+                tooltip: None,
+                unit: None,
+            },
+            Parameter::Slider {
+                tag: "min steps/frame",
+                value: self.parameters.min_steps.load(Ordering::Relaxed) as f32,
+                logarithmic: true,
+                range: 1e0..=1e4,
+                tooltip: None,
+                unit: None,
+            },
+            Parameter::Slider {
+                tag: "max steps/frame",
+                value: self.parameters.max_steps.load(Ordering::Relaxed) as f32,
+                logarithmic: true,
+                range: 1e0..=1e4,
+                tooltip: None,
+                unit: None,
+            },
+            Parameter::Toggle {
+                tag: "auto-scale",
+                enable: self.parameters.auto_scale.load(Ordering::Relaxed),
+                reset_on_change: false,
+            },
+            Parameter::Slider {
+                tag: "range min",
+                value: self.parameters.range_min.load(),
+                logarithmic: false,
+                range: -10.0..=10.0,
+                tooltip: None,
+                unit: None,
+            },
+            Parameter::Slider {
+                tag: "range max",
+                value: self.parameters.range_max.load(),
+                logarithmic: false,
+                range: -10.0..=10.0,
+                tooltip: None,
+                unit: None,
+            },
+            Parameter::Toggle {
+                tag: "pause",
+                enable: self.parameters.paused.load(Ordering::Relaxed),
+                reset_on_change: false,
+            },
+            Parameter::Button { tag: "reset" },
+            Parameter::Slider {
+                tag: "check every",
+                value: self.parameters.check_every.load(Ordering::Relaxed) as f32,
+                logarithmic: false,
+                range: 0.0..=120.0,
+                tooltip: Some(
+                    "Interval between the checks that the field is finite, 0 disabling them.",
+                ),
+                unit: Some("frames"),
+            },
+        ];
+        parameters.extend(self.image.parameters());
+        parameters.push(Parameter::Slider {
+            tag: "image margin",
+            value: self.image_margin,
+            logarithmic: false,
+            range: -10.0..=10.0,
+            tooltip: None,
+            unit: None,
+        });
+        parameters
+    }
+    fn update_parameter(&mut self, update: UpadeParameter) -> Result<(), ParameterError> {
+        if self.image.update_parameter(&update) {
+            return Ok(());
+        }
+        match update {
+            UpadeParameter::Slider { tag, value } => match tag {
+                "r" => self.parameters.r.store(value),
+                "u" => self.parameters.u.store(value),
+                "Γ" => self.parameters.gamma.store(value),
+                "dt" => self.parameters.dt.store(value),
+                "noise" => self.parameters.noise.store(value),
+                "target FPS" => self.parameters.target_fps.store(value),
+                "min steps/frame" => self
+                    .parameters
+                    .min_steps
+                    .store(value as u32, Ordering::Relaxed),
+                "max steps/frame" => self
+                    .parameters
+                    .max_steps
+                    .store(value as u32, Ordering::Relaxed),
+                "range min" => self.parameters.range_min.store(value),
+                "range max" => self.parameters.range_max.store(value),
+                "check every" => self
+                    .parameters
+                    .check_every
+                    .store(value as u32, Ordering::Relaxed),
+                "image margin" => self.image_margin = value,
+                _ => return Err(ParameterError::UnknownTag(tag)),
+            },
+            UpadeParameter::Toggle { tag, enable } => match tag {
+                "auto-scale" => self.parameters.auto_scale.store(enable, Ordering::Relaxed),
+                "pause" => self.parameters.paused.store(enable, Ordering::Relaxed),
+                _ => return Err(ParameterError::UnknownTag(tag)),
+            },
+            UpadeParameter::Button { tag: "reset" } => self.commands.send(LangevinCommand::Reset),
+            UpadeParameter::Button { tag: "load image" } => {
+                if let Some(source) = self.image.request() {
+                    self.send_image(source);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+    fn description(&self) -> &'static str {
+        "Langevin dynamics of a continuous scalar field φ with the Ginzburg–Landau free energy F = Σ (½|∇φ|² + ½ r φ² + ¼ u φ⁴).\n\
+        The field relaxes as ∂φ/∂t = -Γ δF/δφ + noise, with a Gaussian white noise of variance 2Γ (scaled by the noise parameter), integrated with an explicit Euler scheme.\n\
+        In mean field, the transition happens at r = 0: for r < 0 the field orders around ±√(-r/u)."
+    }
+    fn egui_panel(&mut self, ui: &mut egui::Ui) {
+        if let Some(source) = self.image.take_picked() {
+            self.send_image(source);
+        }
+        let Some(stats) = *self.parameters.unstable.lock().unwrap() else {
+            return;
+        };
+        ui.colored_label(
+            egui::Color32::RED,
+            format!(
+                "The field diverged (min {}, max {}) and the simulation is paused: reduce dt or Γ.",
+                stats.min, stats.max
+            ),
+        );
+        ui.horizontal(|ui| {
+            let restore = self.parameters.stable_saved.load(Ordering::Relaxed)
+                && ui.button("restore last stable state").clicked();
+            let reset = ui.button("reset").clicked();
+            if restore || reset {
+                self.commands.send(if restore {
+                    LangevinCommand::RestoreStable
+                } else {
+                    LangevinCommand::Reset
+                });
+                self.parameters.paused.store(false, Ordering::Relaxed);
+            }
+        });
+    }
+    fn load_image(&mut self, source: ImageSource) -> Result<(), String> {
+        self.send_image(source);
+        Ok(())
+    }
+    fn seed(&self) -> Seed {
+        self.seed
+    }
+    fn set_seed(&mut self, seed: Seed) {
+        self.seed = seed;
+    }
+    fn paused(&self) -> bool {
+        self.parameters.paused.load(Ordering::Relaxed)
+    }
+    fn physics(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        shader_module: &wgpu::ShaderModule,
+        width: u32,
+        height: u32,
+    ) -> Result<Box<dyn crate::gpu::physics::Physics>, WGPUError> {
+        Ok(Box::new(
+            LangevinPipeline::new(
+                device,
+                queue,
+                shader_module,
+                self.seed.0,
+                width,
+                height,
+                Arc::clone(&self.parameters),
+            )?
+            .with_commands(self.receiver.clone()),
+        ))
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::VecDeque;
+
+use egui_plot::{Line, Plot, PlotPoints};
+
+use crate::gpu::observable::Observable;
+
+/// Number of points kept in the plot of each observable, the oldest ones being dropped.
+const MAX_POINTS: usize = 1000;
+
+/// Plots of the [Observable]s of a physics, filled at each frame with their last measurement.
+///
+/// The abscissa is the number of steps of the physics when it counts them, and the time otherwise. The measurements being held between two readbacks, a point whose value repeats the two previous ones only moves the last point forward, so that the points follow the measurements rather than the frames. The points are cleared when the abscissa goes back, after a reset of the physics.
+pub struct ObservablePlots {
+    observables: Vec<Box<dyn Observable>>,
+    /// Points of each observable, as the abscissa and the value.
+    points: Vec<VecDeque<[f64; 2]>>,
+}
+
+impl ObservablePlots {
+    pub fn new(observables: Vec<Box<dyn Observable>>) -> Self {
+        ObservablePlots {
+            points: observables.iter().map(|_| VecDeque::new()).collect(),
+            observables,
+        }
+    }
+    /// Add the last measurement of each observable at the abscissa `x`.
+    pub fn record(&mut self, x: f64) {
+        for (observable, points) in self.observables.iter().zip(&mut self.points) {
+            let Some(value) = observable.measure() else {
+                continue;
+            };
+            if points.back().is_some_and(|[last, _]| x < *last) {
+                points.clear();
+            }
+            let len = points.len();
+            if len >= 2 && points[len - 2][1] == value && points[len - 1][1] == value {
+                points[len - 1][0] = x;
+            } else {
+                points.push_back([x, value]);
+                if points.len() > MAX_POINTS {
+                    points.pop_front();
+                }
+            }
+        }
+    }
+    /// Display a plot per observable in a collapsible panel, nothing if the physics has no observable.
+    pub fn ui(&self, ui: &mut egui::Ui, id: usize) {
+        if self.observables.is_empty() {
+            return;
+        }
+        egui::CollapsingHeader::new("Observables")
+            .id_salt(("observables", id))
+            .show(ui, |ui| {
+                for (observable, points) in self.observables.iter().zip(&self.points) {
+                    let name = observable.name();
+                    match points.back() {
+                        Some([_, value]) => ui.label(format!("{name}: {value:.4}")),
+                        None => ui.label(format!("{name}: not measured")),
+                    };
+                    Plot::new((name, id))
+                        .height(80.0)
+                        .allow_scroll(false)
+                        .show(ui, |plot_ui| {
+                            plot_ui.line(Line::new(
+                                name,
+                                PlotPoints::from_iter(points.iter().copied()),
+                            ));
+                        });
+                }
+            });
+    }
+}
+
+/// Verify that a held measurement only moves the last point forward, that a new value adds a point, and that the points are cleared when the abscissa goes back.
+#[test]
+fn test_observable_plots() {
+    use std::sync::{Arc, Mutex};
+
+    use crate::gpu::observable::MeasuredObservable;
+
+    let measurement = Arc::new(Mutex::new(None));
+    let mut plots = ObservablePlots::new(vec![Box::new(MeasuredObservable::new(
+        "m",
+        Arc::clone(&measurement),
+        |m: &Mutex<Option<f64>>| *m.lock().unwrap(),
+    ))]);
+    plots.record(0.0);
+    assert!(plots.points[0].is_empty());
+    *measurement.lock().unwrap() = Some(0.5);
+    for x in [1.0, 2.0, 3.0, 4.0] {
+        plots.record(x);
+    }
+    assert_eq!(plots.points[0], [[1.0, 0.5], [4.0, 0.5]]);
+    *measurement.lock().unwrap() = Some(0.25);
+    plots.record(5.0);
+    assert_eq!(plots.points[0], [[1.0, 0.5], [4.0, 0.5], [5.0, 0.25]]);
+    plots.record(2.0);
+    assert_eq!(plots.points[0], [[2.0, 0.25]]);
+}
@@ -0,0 +1,64 @@
+/// Scripted experiment which drives the shared parameters `P` of a simulation over the sweeps and records measurements of the observables `O`. The sweeps are counted from the start of the protocol.
+pub trait Protocol<P, O>: Send {
+    /// Whether the observables must be measured at `sweep`.
+    fn wants_measurement(&self, sweep: u32) -> bool;
+    /// Advance the protocol at `sweep` by modifying the `parameters`. The `measurement` contains the observables together with the sweep at which they were measured, when they were requested by [Protocol::wants_measurement] and are available.
+    fn update(&mut self, sweep: u32, parameters: &P, measurement: Option<(u32, &O)>);
+    /// Whether the protocol has finished.
+    fn finished(&self) -> bool;
+}
+
+/// Holds the protocol currently running, if any, and converts the sweeps of the physics into sweeps counted from the start of the protocol.
+pub struct ProtocolRunner<P, O> {
+    protocol: Option<Box<dyn Protocol<P, O>>>,
+    start: Option<u32>,
+}
+
+impl<P, O> ProtocolRunner<P, O> {
+    pub fn new() -> Self {
+        ProtocolRunner {
+            protocol: None,
+            start: None,
+        }
+    }
+    /// Start a new protocol, aborting the current one if any.
+    pub fn start(&mut self, protocol: Box<dyn Protocol<P, O>>) {
+        self.protocol = Some(protocol);
+        self.start = None;
+    }
+    /// Abort the current protocol.
+    pub fn abort(&mut self) {
+        self.protocol = None;
+    }
+    /// Whether a protocol is running.
+    pub fn running(&self) -> bool {
+        self.protocol.is_some()
+    }
+    /// Whether the running protocol needs the observables at the sweep `sweep` of the physics.
+    pub fn wants_measurement(&self, sweep: u32) -> bool {
+        let start = self.start.unwrap_or(sweep);
+        self.protocol
+            .as_ref()
+            .is_some_and(|p| p.wants_measurement(sweep.wrapping_sub(start)))
+    }
+    /// Advance the running protocol at the sweep `sweep` of the physics, see [Protocol::update].
+    pub fn update(&mut self, sweep: u32, parameters: &P, measurement: Option<(u32, &O)>) {
+        let start = *self.start.get_or_insert(sweep);
+        if let Some(protocol) = self.protocol.as_mut() {
+            protocol.update(
+                sweep.wrapping_sub(start),
+                parameters,
+                measurement.map(|(s, o)| (s.wrapping_sub(start), o)),
+            );
+            if protocol.finished() {
+                self.protocol = None;
+            }
+        }
+    }
+}
+
+impl<P, O> Default for ProtocolRunner<P, O> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
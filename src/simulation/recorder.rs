@@ -0,0 +1,284 @@
+use egui::{ColorImage, Event, Pos2, Rect, UserData, ViewportCommand};
+
+/// Formats in which a recording is saved.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecordFormat {
+    /// Animated GIF looping forever.
+    Gif,
+    /// One PPM image per frame, to be encoded for instance into an MP4 with `ffmpeg -framerate 15 -i frame_%05d.ppm recording.mp4`.
+    Frames,
+}
+
+/// Possible choices for the format of the recordings. The frames cannot be written on the web, where the recording is downloaded as a single file.
+#[cfg(not(target_arch = "wasm32"))]
+const FORMATS: [(&str, RecordFormat); 2] =
+    [("GIF", RecordFormat::Gif), ("frames", RecordFormat::Frames)];
+#[cfg(target_arch = "wasm32")]
+const FORMATS: [(&str, RecordFormat); 1] = [("GIF", RecordFormat::Gif)];
+
+/// Largest duration of a recording in seconds, which bounds the memory of the frames held until the recording is saved.
+const MAX_DURATION: f32 = 60.0;
+
+/// Records a clip of the canvas of a view from screenshots of the window, cropped to the canvas.
+///
+/// The screenshots are requested with [ViewportCommand::Screenshot] at the framerate of the recording rather than at every frame, and a new one is only requested once the previous one has been received, so that the frames of the window which come in between are skipped instead of slowing the simulation down. The frames are held in memory until the recording stops, after its duration or with the stop button, and are then saved in the working directory, or downloaded on the web.
+pub struct Recorder {
+    format: RecordFormat,
+    /// Framerate of the recording.
+    fps: f32,
+    /// Duration of the recording in seconds.
+    duration: f32,
+    recording: Option<Recording>,
+    /// Result of the last save, shown next to the buttons.
+    status: Option<String>,
+}
+
+/// Frames of a recording in progress.
+struct Recording {
+    frames: Vec<ColorImage>,
+    /// Time at which the recording stops.
+    end: f64,
+    /// Time of the next frame.
+    next: f64,
+    /// Whether a screenshot has been requested and not received yet.
+    pending: bool,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Recorder {
+            format: RecordFormat::Gif,
+            fps: 15.0,
+            duration: 10.0,
+            recording: None,
+            status: None,
+        }
+    }
+    /// Display the buttons starting and stopping the recording, and its options.
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if let Some(recording) = &self.recording {
+                if ui.button("stop recording").clicked() {
+                    self.stop();
+                } else {
+                    ui.label(format!("recording… {} frames", recording.frames.len()));
+                }
+                return;
+            }
+            if ui.button("record").clicked() {
+                let time = ui.input(|i| i.time);
+                self.recording = Some(Recording {
+                    frames: vec![],
+                    end: time + self.duration as f64,
+                    next: time,
+                    pending: false,
+                });
+                self.status = None;
+            }
+            egui::ComboBox::from_id_salt("record format")
+                .selected_text(FORMATS.iter().find(|&&(_, f)| f == self.format).unwrap().0)
+                .show_ui(ui, |ui| {
+                    for (name, format) in FORMATS {
+                        ui.selectable_value(&mut self.format, format, name);
+                    }
+                });
+            ui.add(
+                egui::DragValue::new(&mut self.fps)
+                    .range(1.0..=60.0)
+                    .suffix(" fps"),
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.duration)
+                    .range(1.0..=MAX_DURATION)
+                    .suffix(" s"),
+            );
+            if let Some(status) = &self.status {
+                ui.label(status);
+            }
+        });
+    }
+    /// Receive the screenshots addressed to the view `id`, cropped to its canvas `rect`, and request the next one when it is due. Once the duration is over, the recording is saved.
+    pub fn frame(&mut self, ctx: &egui::Context, id: usize, rect: Rect) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+        let screenshots = ctx.input(|i| {
+            i.raw
+                .events
+                .iter()
+                .filter_map(|event| match event {
+                    Event::Screenshot {
+                        user_data, image, ..
+                    } if user_data
+                        .data
+                        .as_ref()
+                        .and_then(|data| data.downcast_ref::<usize>())
+                        == Some(&id) =>
+                    {
+                        Some(image.clone())
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        });
+        let pixels_per_point = ctx.pixels_per_point();
+        for image in screenshots {
+            recording.pending = false;
+            let [width, height] = image.size;
+            let screen = Rect::from_min_size(
+                Pos2::ZERO,
+                egui::vec2(width as f32, height as f32) / pixels_per_point,
+            );
+            let frame = image.region(&rect.intersect(screen), Some(pixels_per_point));
+            // The frames of a clip have the size of the first one, so the ones taken while the canvas was resized are skipped.
+            if recording
+                .frames
+                .first()
+                .is_none_or(|first| first.size == frame.size)
+            {
+                recording.frames.push(frame);
+            }
+        }
+        let time = ctx.input(|i| i.time);
+        if time >= recording.end {
+            self.stop();
+        } else if !recording.pending && time >= recording.next {
+            recording.pending = true;
+            // The time of the next frame is not late by more than one frame, so that the frames skipped are not caught up.
+            recording.next = (recording.next + 1.0 / self.fps as f64).max(time);
+            ctx.send_viewport_cmd(ViewportCommand::Screenshot(UserData::new(id)));
+        }
+    }
+    /// Stop the recording and save its frames.
+    fn stop(&mut self) {
+        let Some(recording) = self.recording.take() else {
+            return;
+        };
+        let status = match save(&recording.frames, self.format, self.fps) {
+            Ok(status) => status,
+            Err(err) => {
+                log::error!("Failed to save the recording: {err}");
+                format!("failed to save the recording: {err}")
+            }
+        };
+        self.status = Some(status);
+    }
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Encode the `frames` into an animated GIF played at `fps` frames per second and looping forever.
+pub fn encode_gif(frames: &[ColorImage], fps: f32) -> Result<Vec<u8>, gif::EncodingError> {
+    let mut bytes = vec![];
+    let Some(first) = frames.first() else {
+        return Ok(bytes);
+    };
+    let [width, height] = first.size.map(|size| size.min(u16::MAX as usize) as u16);
+    {
+        let mut encoder = gif::Encoder::new(&mut bytes, width, height, &[])?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        for image in frames {
+            let mut rgba = image.as_raw().to_vec();
+            let mut frame = gif::Frame::from_rgba_speed(width, height, &mut rgba, 10);
+            // The delay is given in hundredths of a second.
+            frame.delay = (100.0 / fps).round() as u16;
+            encoder.write_frame(&frame)?;
+        }
+    }
+    Ok(bytes)
+}
+
+/// Encode `image` into a binary PPM image, which drops the alpha channel.
+pub fn encode_ppm(image: &ColorImage) -> Vec<u8> {
+    let [width, height] = image.size;
+    let mut bytes = format!("P6\n{width} {height}\n255\n").into_bytes();
+    bytes.extend(
+        image
+            .pixels
+            .iter()
+            .flat_map(|pixel| [pixel.r(), pixel.g(), pixel.b()]),
+    );
+    bytes
+}
+
+/// Save the `frames` recorded at `fps` frames per second in the `format`, and describe where they were saved.
+#[cfg(not(target_arch = "wasm32"))]
+fn save(frames: &[ColorImage], format: RecordFormat, fps: f32) -> Result<String, String> {
+    match format {
+        RecordFormat::Gif => {
+            let bytes = encode_gif(frames, fps).map_err(|err| err.to_string())?;
+            std::fs::write("recording.gif", bytes).map_err(|err| err.to_string())?;
+            Ok(format!("{} frames saved in recording.gif", frames.len()))
+        }
+        RecordFormat::Frames => {
+            let dir = std::path::Path::new("recording");
+            std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+            for (k, image) in frames.iter().enumerate() {
+                std::fs::write(dir.join(format!("frame_{k:05}.ppm")), encode_ppm(image))
+                    .map_err(|err| err.to_string())?;
+            }
+            Ok(format!("{} frames saved in recording/", frames.len()))
+        }
+    }
+}
+
+/// Encode the `frames` recorded at `fps` frames per second into a GIF and download it.
+#[cfg(target_arch = "wasm32")]
+fn save(frames: &[ColorImage], _format: RecordFormat, fps: f32) -> Result<String, String> {
+    let bytes = encode_gif(frames, fps).map_err(|err| err.to_string())?;
+    download("recording.gif", "image/gif", &bytes).map_err(|err| format!("{err:?}"))?;
+    Ok(format!("{} frames downloaded", frames.len()))
+}
+
+/// Offer the `bytes` as a download of the file `name` of the MIME type `mime`.
+#[cfg(target_arch = "wasm32")]
+pub fn download(name: &str, mime: &str, bytes: &[u8]) -> Result<(), eframe::wasm_bindgen::JsValue> {
+    use eframe::wasm_bindgen::JsCast as _;
+    use web_sys::js_sys;
+
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes));
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type(mime);
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or("no document")?;
+    let anchor = document
+        .create_element("a")?
+        .dyn_into::<web_sys::HtmlAnchorElement>()?;
+    anchor.set_href(&url);
+    anchor.set_download(name);
+    anchor.click();
+    web_sys::Url::revoke_object_url(&url)
+}
+
+/// Verify that the frames are encoded into an animated GIF with one frame each and the delay of the framerate, and into PPM images.
+#[test]
+fn test_encode() {
+    let frames = [
+        egui::Color32::RED,
+        egui::Color32::BLUE,
+        egui::Color32::WHITE,
+    ]
+    .map(|color| ColorImage::new([4, 3], color));
+    let bytes = encode_gif(&frames, 20.0).unwrap();
+    assert_eq!(&bytes[..6], b"GIF89a");
+    let mut decoder = gif::DecodeOptions::new().read_info(&bytes[..]).unwrap();
+    assert_eq!((decoder.width(), decoder.height()), (4, 3));
+    let mut delays = vec![];
+    while let Some(frame) = decoder.read_next_frame().unwrap() {
+        delays.push(frame.delay);
+    }
+    assert_eq!(delays, vec![5; 3]);
+    assert!(encode_gif(&[], 20.0).unwrap().is_empty());
+
+    let ppm = encode_ppm(&frames[0]);
+    assert!(ppm.starts_with(b"P6\n4 3\n255\n"));
+    assert_eq!(ppm.len(), 11 + 4 * 3 * 3);
+    assert_eq!(&ppm[11..14], &[255, 0, 0]);
+}
@@ -1,24 +1,259 @@
-use egui_wgpu::{CallbackTrait, RenderState};
-use wgpu::ShaderModule;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 
-use crate::gpu::physics::{FragmentEntry, FragmentInfo, Physics};
+use bytemuck::{Zeroable, bytes_of};
+use egui_wgpu::{CallbackResources, CallbackTrait, RenderState};
+use kernel::RenderCtx;
+use wgpu::{ShaderModule, util::DeviceExt};
+
+use crate::{
+    error::WGPUError,
+    gpu::{
+        kernel_module::KernelModule,
+        physics::{FragmentEntry, FragmentInfo, FrameInfo, Physics, SharedPhysics},
+        timing::GpuTimer,
+    },
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::gpu::compute_thread::ComputeThread;
 
 /// Handle wgpu rendering from inside egui by implementing the [CallbackTrait]. It creates a simple square from a strip of two triangles which provides `uv` coordinates to a fragment shader provided to [RenderSquare::new].
+///
+/// Each render square is identified by an `id`, so that several simulations can be rendered side by side.
 #[derive(Clone, Copy)]
-pub struct RenderSquare {}
+pub struct RenderSquare {
+    id: usize,
+    /// Options of the rendering given to the fragment shader.
+    render_ctx: RenderCtx,
+    /// Duration of the previous frame and whether the simulation is paused, given to the physics with the index of the frame counted by the [SquareRenderResources].
+    frame: FrameInfo,
+}
 
 impl RenderSquare {
-    /// Setup the rendering of the fragment shader informations provided by `physics` which egui's [CallbackTrait].
+    /// Setup the rendering of the fragment shader informations provided by `physics` which egui's [CallbackTrait]. A previous render square with the same `id` is replaced, along with its physics.
+    ///
+    /// The vertex shader `square_vertex` is taken from `vertex_module`, the embedded kernels, and the fragment shader from `fragment_module`, the module of the physics, unless it is one of the embedded kernels (see [FragmentInfo::embedded]). Fails with [WGPUError::KernelNotFound] if one of the entry points is missing from its module.
     pub fn new(
         wgpu_render_state: &RenderState,
-        shader_module: &ShaderModule,
+        vertex_module: &KernelModule,
+        fragment_module: &KernelModule,
         physics: Box<dyn Physics>,
-    ) -> Self {
-        let device = &wgpu_render_state.device;
+        id: usize,
+    ) -> Result<Self, WGPUError> {
+        vertex_module.check("square_vertex")?;
+        let info = physics.wgpu_fragment_info();
+        let fragment_module = if info.embedded {
+            vertex_module
+        } else {
+            fragment_module
+        };
+        fragment_module.check(info.fragment_entry_point)?;
+        let resources = SquareRenderResources::new(
+            &wgpu_render_state.device,
+            &wgpu_render_state.queue,
+            wgpu_render_state.target_format,
+            [&vertex_module.module, &fragment_module.module],
+            physics,
+        );
+        // Because the graphics pipeline must have the same lifetime as the egui render pass,
+        // instead of storing the pipeline in our `Custom3D` struct, we insert it into the
+        // `paint_callback_resources` type map, which is stored alongside the render pass.
+        insert_resources(
+            &mut wgpu_render_state.renderer.write().callback_resources,
+            id,
+            resources,
+        );
+        Ok(Self {
+            id,
+            render_ctx: RenderCtx::zeroed(),
+            frame: FrameInfo::default(),
+        })
+    }
+    /// Render with the options `render_ctx`, such as the sampling of the fields or the grid lines.
+    pub fn with_render_ctx(self, render_ctx: RenderCtx) -> Self {
+        Self { render_ctx, ..self }
+    }
+    /// Update the physics knowing that the previous frame lasted `dt` seconds, as given by egui, and whether the simulation is `paused`.
+    pub fn with_timing(self, dt: f32, paused: bool) -> Self {
+        Self {
+            frame: FrameInfo {
+                dt,
+                paused,
+                ..self.frame
+            },
+            ..self
+        }
+    }
+    /// Impose the number of `steps` of the next update of the physics, see [FrameInfo::steps].
+    pub fn with_steps(self, steps: Option<u32>) -> Self {
+        Self {
+            frame: FrameInfo {
+                steps,
+                ..self.frame
+            },
+            ..self
+        }
+    }
+    /// Call `f` with the physics of the render square `id`, if any, for instance to reset or save its state from the UI.
+    pub fn with_physics<R>(
+        wgpu_render_state: &RenderState,
+        id: usize,
+        f: impl FnOnce(&mut dyn Physics) -> R,
+    ) -> Option<R> {
+        let mut renderer = wgpu_render_state.renderer.write();
+        let resources = renderer
+            .callback_resources
+            .get_mut::<SquareRenderResourcesMap>()?
+            .0
+            .get_mut(&id)?;
+        let mut physics = resources.physics.lock().unwrap();
+        Some(f(physics.as_mut()))
+    }
+    /// Update the physics of the render square `id`, if any, continuously on a [ComputeThread] when `background` is set, or once per frame before its rendering otherwise. The thread is started or stopped only when `background` changes, and it is stopped along with the physics when the render square is released.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_background(wgpu_render_state: &RenderState, id: usize, background: bool) {
+        let mut renderer = wgpu_render_state.renderer.write();
+        let Some(resources) = renderer
+            .callback_resources
+            .get_mut::<SquareRenderResourcesMap>()
+            .and_then(|map| map.0.get_mut(&id))
+        else {
+            return;
+        };
+        if background == resources.compute.is_some() {
+            return;
+        }
+        resources.compute = background.then(|| {
+            ComputeThread::spawn(
+                wgpu_render_state.device.clone(),
+                wgpu_render_state.queue.clone(),
+                Arc::clone(&resources.physics),
+            )
+        });
+    }
+    /// Label and duration in milliseconds of the GPU passes of the render square `id`, the ones of its physics followed by the render pass, if they are measured.
+    pub fn gpu_timings(wgpu_render_state: &RenderState, id: usize) -> Vec<(&'static str, f64)> {
+        let renderer = wgpu_render_state.renderer.read();
+        let Some(resources) = renderer
+            .callback_resources
+            .get::<SquareRenderResourcesMap>()
+            .and_then(|map| map.0.get(&id))
+        else {
+            return vec![];
+        };
+        let mut timings = resources.physics.lock().unwrap().gpu_timings();
+        if let Some(timer) = &resources.timer {
+            timings.extend(timer.timings());
+        }
+        timings
+    }
+    /// Drop the physics and the render pipeline of the render square `id`, if any. This should be called before creating a new render square with the same `id` (for instance when the window is resized), so that the buffers of the previous physics are released before the new ones are allocated.
+    pub fn release(wgpu_render_state: &RenderState, id: usize) {
+        remove_resources(
+            &mut wgpu_render_state.renderer.write().callback_resources,
+            id,
+        );
+    }
+}
+
+/// Store the `resources` of the render square `id`, dropping the previous ones. The type map holds a single value per type, so the resources of all the render squares are stored together and looked up by id.
+fn insert_resources(
+    callback_resources: &mut CallbackResources,
+    id: usize,
+    resources: SquareRenderResources,
+) {
+    if callback_resources
+        .get::<SquareRenderResourcesMap>()
+        .is_none()
+    {
+        callback_resources.insert(SquareRenderResourcesMap::default());
+    }
+    callback_resources
+        .get_mut::<SquareRenderResourcesMap>()
+        .unwrap()
+        .0
+        .insert(id, resources);
+}
+
+/// Drop the resources of the render square `id`, if any.
+fn remove_resources(callback_resources: &mut CallbackResources, id: usize) {
+    if let Some(map) = callback_resources.get_mut::<SquareRenderResourcesMap>() {
+        map.0.remove(&id);
+    }
+}
+
+impl CallbackTrait for RenderSquare {
+    fn prepare(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        _screen_descriptor: &egui_wgpu::ScreenDescriptor,
+        egui_encoder: &mut wgpu::CommandEncoder,
+        resources: &mut egui_wgpu::CallbackResources,
+    ) -> Vec<wgpu::CommandBuffer> {
+        let resources: &mut SquareRenderResourcesMap = resources.get_mut().unwrap();
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render square frame Encoder"),
+        });
+        resources.0.get_mut(&self.id).unwrap().prepare(
+            device,
+            queue,
+            egui_encoder,
+            &mut encoder,
+            self.render_ctx,
+            self.frame,
+        );
+        // The command buffers returned here are submitted by egui in the same submission as its own encoder, before it, so the physics of the frame is computed before being rendered without a submission of its own.
+        vec![encoder.finish()]
+    }
+
+    fn paint(
+        &self,
+        _info: egui::PaintCallbackInfo,
+        render_pass: &mut wgpu::RenderPass<'static>,
+        resources: &egui_wgpu::CallbackResources,
+    ) {
+        let resources: &SquareRenderResourcesMap = resources.get().unwrap();
+        resources.0[&self.id].paint(render_pass);
+    }
+}
 
+/// Resources of all the [RenderSquare] indexed by their id.
+#[derive(Default)]
+struct SquareRenderResourcesMap(HashMap<usize, SquareRenderResources>);
+
+struct SquareRenderResources {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    /// Options of the rendering, bound at `descriptor_set = 1` and written when they change.
+    render_ctx: RenderCtx,
+    render_ctx_buffer: wgpu::Buffer,
+    render_bind_group: wgpu::BindGroup,
+    /// GPU duration of the render pass, if the timestamp queries inside passes are supported.
+    timer: Option<GpuTimer>,
+    physics: SharedPhysics,
+    /// Thread updating the physics in the background instead of [SquareRenderResources::prepare], see [RenderSquare::set_background].
+    #[cfg(not(target_arch = "wasm32"))]
+    compute: Option<ComputeThread>,
+    /// Number of frames prepared since the creation of the physics, see [FrameInfo::frame_index].
+    frame_index: u64,
+}
+
+impl SquareRenderResources {
+    fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target_format: wgpu::TextureFormat,
+        [vertex_module, fragment_module]: [&ShaderModule; 2],
+        physics: Box<dyn Physics>,
+    ) -> Self {
         let FragmentInfo {
             fragment_entry_point,
             entries,
+            ..
         } = physics.wgpu_fragment_info();
 
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -27,7 +262,10 @@ impl RenderSquare {
                 .iter()
                 .map(
                     |&FragmentEntry {
-                         binding, uniform, ..
+                         binding,
+                         uniform,
+                         dynamic_size,
+                         ..
                      }| wgpu::BindGroupLayoutEntry {
                         binding,
                         visibility: wgpu::ShaderStages::FRAGMENT,
@@ -37,7 +275,7 @@ impl RenderSquare {
                             } else {
                                 wgpu::BufferBindingType::Storage { read_only: true }
                             },
-                            has_dynamic_offset: false,
+                            has_dynamic_offset: dynamic_size.is_some(),
                             min_binding_size: None,
                         },
                         count: None,
@@ -46,9 +284,38 @@ impl RenderSquare {
                 .collect::<Vec<_>>(),
         });
 
+        let render_ctx = RenderCtx::zeroed();
+        let render_ctx_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Render square ctx buffer"),
+            contents: bytes_of(&render_ctx),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let render_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Render square ctx bind group layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Render square ctx bind group"),
+            layout: &render_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: render_ctx_buffer.as_entire_binding(),
+            }],
+        });
+
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Render square pipeline layout"),
-            bind_group_layouts: &[&bind_group_layout],
+            bind_group_layouts: &[&bind_group_layout, &render_bind_group_layout],
             push_constant_ranges: &[],
         });
 
@@ -56,15 +323,15 @@ impl RenderSquare {
             label: Some("Render square pipeline"),
             layout: Some(&pipeline_layout),
             vertex: wgpu::VertexState {
-                module: &shader_module,
+                module: vertex_module,
                 entry_point: Some("square_vertex"),
                 buffers: &[],
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader_module,
+                module: fragment_module,
                 entry_point: Some(fragment_entry_point),
-                targets: &[Some(wgpu_render_state.target_format.into())],
+                targets: &[Some(target_format.into())],
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
             }),
             primitive: wgpu::PrimitiveState {
@@ -84,71 +351,128 @@ impl RenderSquare {
                 .into_iter()
                 .map(
                     |FragmentEntry {
-                         binding, buffer, ..
+                         binding,
+                         buffer,
+                         dynamic_size,
+                         ..
                      }| wgpu::BindGroupEntry {
                         binding,
-                        resource: buffer.as_entire_binding(),
+                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer,
+                            offset: 0,
+                            size: dynamic_size.and_then(wgpu::BufferSize::new),
+                        }),
                     },
                 )
                 .collect::<Vec<_>>(),
         });
 
-        // Because the graphics pipeline must have the same lifetime as the egui render pass,
-        // instead of storing the pipeline in our `Custom3D` struct, we insert it into the
-        // `paint_callback_resources` type map, which is stored alongside the render pass.
-        wgpu_render_state
-            .renderer
-            .write()
-            .callback_resources
-            .insert(SquareRenderResources {
-                pipeline,
-                bind_group,
-                physics,
-            });
-
-        Self {}
+        SquareRenderResources {
+            pipeline,
+            bind_group,
+            render_ctx,
+            render_ctx_buffer,
+            render_bind_group,
+            timer: GpuTimer::new(
+                device,
+                queue,
+                &["render"],
+                wgpu::Features::TIMESTAMP_QUERY | wgpu::Features::TIMESTAMP_QUERY_INSIDE_PASSES,
+            ),
+            physics: Arc::new(Mutex::new(physics)),
+            #[cfg(not(target_arch = "wasm32"))]
+            compute: None,
+            frame_index: 0,
+        }
     }
-}
 
-impl CallbackTrait for RenderSquare {
+    /// Record the work of the frame: the resolution of the timestamps of the render pass in `egui_encoder`, and the update of the physics in `encoder` with the `frame` timing given by the [RenderSquare] and the index of the frame.
     fn prepare(
-        &self,
+        &mut self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        _screen_descriptor: &egui_wgpu::ScreenDescriptor,
-        _egui_encoder: &mut wgpu::CommandEncoder,
-        resources: &mut egui_wgpu::CallbackResources,
-    ) -> Vec<wgpu::CommandBuffer> {
-        let resources: &mut SquareRenderResources = resources.get_mut().unwrap();
-        resources.prepare(device, queue);
-        Vec::new()
-    }
-
-    fn paint(
-        &self,
-        _info: egui::PaintCallbackInfo,
-        render_pass: &mut wgpu::RenderPass<'static>,
-        resources: &egui_wgpu::CallbackResources,
+        egui_encoder: &mut wgpu::CommandEncoder,
+        encoder: &mut wgpu::CommandEncoder,
+        render_ctx: RenderCtx,
+        frame: FrameInfo,
     ) {
-        let resources: &SquareRenderResources = resources.get().unwrap();
-        resources.paint(render_pass);
-    }
-}
-
-struct SquareRenderResources {
-    pipeline: wgpu::RenderPipeline,
-    bind_group: wgpu::BindGroup,
-    physics: Box<dyn Physics>,
-}
-
-impl SquareRenderResources {
-    fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
-        self.physics.update(device, queue);
+        // The timestamps of the previous render pass are resolved in the encoder of this frame, whose submission is only known at the next frame, when the read back is mapped.
+        if let Some(timer) = &mut self.timer {
+            timer.submitted();
+            timer.poll(device);
+            timer.resolve(device, egui_encoder);
+        }
+        if bytes_of(&self.render_ctx) != bytes_of(&render_ctx) {
+            self.render_ctx = render_ctx;
+            queue.write_buffer(&self.render_ctx_buffer, 0, bytes_of(&self.render_ctx));
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(compute) = &self.compute {
+            compute.set_paused(frame.paused);
+            return;
+        }
+        let frame = FrameInfo {
+            frame_index: self.frame_index,
+            ..frame
+        };
+        self.frame_index += 1;
+        self.physics
+            .lock()
+            .unwrap()
+            .update(device, queue, encoder, &frame);
     }
 
     fn paint(&self, render_pass: &mut wgpu::RenderPass<'_>) {
         render_pass.set_pipeline(&self.pipeline);
-        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        let offsets = self.physics.lock().unwrap().fragment_offsets();
+        render_pass.set_bind_group(0, &self.bind_group, &offsets);
+        render_pass.set_bind_group(1, &self.render_bind_group, &[]);
+        if let Some(timer) = &self.timer {
+            timer.write_render_timestamp(render_pass, 0, false);
+        }
         render_pass.draw(0..4, 0..1);
+        if let Some(timer) = &self.timer {
+            timer.write_render_timestamp(render_pass, 0, true);
+        }
+    }
+}
+
+/// Verify that recreating a render square, as done on each resize, does not accumulate GPU buffers. Requires a GPU and the compiled kernel.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_render_square_recreation() {
+    use crate::simulation::{Simulation, ising::Ising};
+
+    let (instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    let ising = Ising::new();
+    let buffers = || {
+        instance
+            .generate_report()
+            .unwrap()
+            .hub
+            .buffers
+            .num_allocated
+    };
+    let mut callback_resources = CallbackResources::default();
+    let mut allocated = None;
+    for size in 64..74 {
+        remove_resources(&mut callback_resources, 0);
+        let physics = ising
+            .physics(&device, &queue, &shader_module, size, size)
+            .unwrap();
+        let resources = SquareRenderResources::new(
+            &device,
+            &queue,
+            wgpu::TextureFormat::Rgba8Unorm,
+            [&shader_module, &shader_module],
+            physics,
+        );
+        insert_resources(&mut callback_resources, 0, resources);
+        device.poll(wgpu::Maintain::Wait);
+        let count = buffers();
+        assert_eq!(*allocated.get_or_insert(count), count);
     }
+    remove_resources(&mut callback_resources, 0);
+    device.poll(wgpu::Maintain::Wait);
+    assert!(buffers() < allocated.unwrap());
 }
@@ -0,0 +1,496 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::WGPUError,
+    gpu::physics::{FrameInfo, Physics},
+    seed::Seed,
+};
+
+use super::{Parameter, Simulation, SimulationEntry, UpadeParameter};
+
+/// Largest number of steps performed in a single update while replaying, so that the window stays responsive.
+pub const REPLAY_CHUNK: u32 = 1000;
+
+/// Event of a recorded run, applied to the simulation or to its physics by a [Replay]. The parameters are the [UpadeParameter]s of the UI, with their tag owned so that they can be read back from a file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+pub enum ReplayAction {
+    Slider {
+        tag: String,
+        value: f32,
+    },
+    Toggle {
+        tag: String,
+        enable: bool,
+    },
+    Button {
+        tag: String,
+    },
+    Choice {
+        tag: String,
+        selected: usize,
+    },
+    Text {
+        tag: String,
+        value: String,
+    },
+    Color {
+        tag: String,
+        rgb: [u8; 3],
+    },
+    /// Reset of the state of the physics, see [Physics::reset].
+    Reset,
+    /// Change of the seed, which restarts the physics.
+    Seed(Seed),
+}
+
+impl ReplayAction {
+    /// Action recording `update`.
+    pub fn from_update(update: &UpadeParameter) -> Self {
+        match update {
+            UpadeParameter::Slider { tag, value } => ReplayAction::Slider {
+                tag: tag.to_string(),
+                value: *value,
+            },
+            UpadeParameter::Toggle { tag, enable } => ReplayAction::Toggle {
+                tag: tag.to_string(),
+                enable: *enable,
+            },
+            UpadeParameter::Button { tag } => ReplayAction::Button {
+                tag: tag.to_string(),
+            },
+            UpadeParameter::Choice { tag, selected } => ReplayAction::Choice {
+                tag: tag.to_string(),
+                selected: *selected,
+            },
+            UpadeParameter::Text { tag, value } => ReplayAction::Text {
+                tag: tag.to_string(),
+                value: value.clone(),
+            },
+            UpadeParameter::Color { tag, rgb } => ReplayAction::Color {
+                tag: tag.to_string(),
+                rgb: *rgb,
+            },
+        }
+    }
+    /// Action setting the current value of `parameter`, `None` for a button which has no value.
+    fn from_parameter(parameter: &Parameter) -> Option<Self> {
+        let update = match parameter {
+            Parameter::Slider { tag, value, .. } => UpadeParameter::Slider { tag, value: *value },
+            Parameter::Toggle { tag, enable } => UpadeParameter::Toggle {
+                tag,
+                enable: *enable,
+            },
+            Parameter::Button { .. } => return None,
+            Parameter::Choice { tag, selected, .. } => UpadeParameter::Choice {
+                tag,
+                selected: *selected,
+            },
+            Parameter::Text { tag, value } => UpadeParameter::Text {
+                tag,
+                value: value.clone(),
+            },
+            Parameter::Color { tag, rgb } => UpadeParameter::Color { tag, rgb: *rgb },
+        };
+        Some(Self::from_update(&update))
+    }
+    /// Tag of the parameter updated by the action, if it updates one.
+    fn tag(&self) -> Option<&str> {
+        match self {
+            ReplayAction::Slider { tag, .. }
+            | ReplayAction::Toggle { tag, .. }
+            | ReplayAction::Button { tag }
+            | ReplayAction::Choice { tag, .. }
+            | ReplayAction::Text { tag, .. }
+            | ReplayAction::Color { tag, .. } => Some(tag),
+            ReplayAction::Reset | ReplayAction::Seed(_) => None,
+        }
+    }
+    /// Update of the parameter of the action with the static `tag` of the simulation, `None` for the actions which are not parameters.
+    fn to_update(&self, tag: &'static str) -> Option<UpadeParameter> {
+        Some(match self {
+            ReplayAction::Slider { value, .. } => UpadeParameter::Slider { tag, value: *value },
+            ReplayAction::Toggle { enable, .. } => UpadeParameter::Toggle {
+                tag,
+                enable: *enable,
+            },
+            ReplayAction::Button { .. } => UpadeParameter::Button { tag },
+            ReplayAction::Choice { selected, .. } => UpadeParameter::Choice {
+                tag,
+                selected: *selected,
+            },
+            ReplayAction::Text { value, .. } => UpadeParameter::Text {
+                tag,
+                value: value.clone(),
+            },
+            ReplayAction::Color { rgb, .. } => UpadeParameter::Color { tag, rgb: *rgb },
+            ReplayAction::Reset | ReplayAction::Seed(_) => return None,
+        })
+    }
+    /// Update the parameter of the action in `simulation`, whose tag is looked up among its current [Simulation::egui_parameters]. Does nothing for the actions which are not parameters.
+    pub fn apply(&self, simulation: &mut dyn Simulation) -> Result<(), WGPUError> {
+        let Some(tag) = self.tag() else {
+            return Ok(());
+        };
+        let tag = simulation
+            .egui_parameters()
+            .iter()
+            .map(Parameter::tag)
+            .find(|t| *t == tag)
+            .ok_or_else(|| WGPUError::InvalidReplay(format!("unknown parameter \"{tag}\"")))?;
+        let update = self.to_update(tag).unwrap();
+        simulation
+            .update_parameter(update)
+            .map_err(|err| WGPUError::InvalidReplay(err.to_string()))
+    }
+}
+
+/// Event of a [ReplayLog], applied before the steps following the step `sweep`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReplayEvent {
+    pub sweep: u64,
+    pub action: ReplayAction,
+}
+
+/// Record of a run which can be reconstructed from its seed by a [Replay]: the simulation, its seed and the size of its lattice, the events in the order in which they happened with the step of the physics at which they were applied, and the step at which the recording stopped.
+///
+/// The log starts with the values of every parameter at the start of the recording, so that the replay starts from the defaults of a new simulation. It is saved as a TOML file, each action being a table named after its kind, or a string for the resets:
+/// ```toml
+/// simulation = "ising"
+/// seed = "0123456789abcdef0123456789abcdef"
+/// width = 256
+/// height = 256
+/// end = 4000
+///
+/// [[events]]
+/// sweep = 0
+/// action = { slider = { tag = "T", value = 2.269 } }
+///
+/// [[events]]
+/// sweep = 1500
+/// action = { button = { tag = "reset" } }
+///
+/// [[events]]
+/// sweep = 3000
+/// action = "reset"
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReplayLog {
+    pub simulation: String,
+    pub seed: Seed,
+    pub width: u32,
+    pub height: u32,
+    pub end: u64,
+    pub events: Vec<ReplayEvent>,
+}
+
+impl ReplayLog {
+    /// Start the log of `simulation` on a `width`×`height` lattice, whose physics is started again, with the current values of its parameters.
+    pub fn new(simulation: &dyn Simulation, width: u32, height: u32) -> Self {
+        ReplayLog {
+            simulation: simulation.name().to_string(),
+            seed: simulation.seed(),
+            width,
+            height,
+            events: simulation
+                .egui_parameters()
+                .iter()
+                .filter_map(ReplayAction::from_parameter)
+                .map(|action| ReplayEvent { sweep: 0, action })
+                .collect(),
+            end: 0,
+        }
+    }
+    /// Record `action`, applied at the step `sweep` of the physics.
+    pub fn push(&mut self, sweep: u64, action: ReplayAction) {
+        self.events.push(ReplayEvent { sweep, action });
+    }
+    /// Parse a log written by the [Display](fmt::Display) of [ReplayLog], whose errors give the line at fault.
+    pub fn parse(text: &str) -> Result<Self, WGPUError> {
+        toml::from_str(text).map_err(|err| {
+            WGPUError::InvalidReplay(match err.span() {
+                Some(span) => format!(
+                    "line {}: {}",
+                    text[..span.start].matches('\n').count() + 1,
+                    err.message()
+                ),
+                None => err.message().to_string(),
+            })
+        })
+    }
+}
+
+impl fmt::Display for ReplayLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = toml::to_string(self).map_err(|_| fmt::Error)?;
+        write!(f, "{text}")
+    }
+}
+
+/// Reconstruction of a run from a [ReplayLog], by stepping its physics from the seed with the numbers of steps which stop exactly at the steps of the events, see [FrameInfo::steps].
+///
+/// The replay gives the same state as the recorded run as long as the physics only depends on the seed, the parameters and the steps at which they changed. The changes which are not recorded, such as the protocols which drive the parameters at each frame, are not reproduced.
+pub struct Replay {
+    log: ReplayLog,
+    /// Index of the next event to apply.
+    next: usize,
+}
+
+impl Replay {
+    pub fn new(log: ReplayLog) -> Self {
+        Replay { log, next: 0 }
+    }
+    pub fn log(&self) -> &ReplayLog {
+        &self.log
+    }
+    /// Next event to apply when the physics is at the step `sweep`, if it is due. An event whose step was already passed, after the counter of the physics went back, is applied right away.
+    pub fn next_due(&mut self, sweep: u64) -> Option<ReplayAction> {
+        let event = self.log.events.get(self.next)?;
+        if event.sweep > sweep {
+            return None;
+        }
+        self.next += 1;
+        Some(event.action.clone())
+    }
+    /// Number of steps of the next update of the physics at the step `sweep`, up to the next event or to the end of the log, `None` once the replay is finished.
+    pub fn steps(&self, sweep: u64) -> Option<u32> {
+        let target = match self.log.events.get(self.next) {
+            Some(event) => event.sweep,
+            None => self.log.end,
+        };
+        (target > sweep).then(|| (target - sweep).min(REPLAY_CHUNK as u64) as u32)
+    }
+}
+
+/// Replay `log` without window with the simulations of `registry`, as fast as possible, and return the physics in its final state. Each update is submitted and waited for, like the frames of the GUI.
+pub fn replay_headless(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    shader_module: &wgpu::ShaderModule,
+    registry: &[SimulationEntry],
+    log: ReplayLog,
+) -> Result<Box<dyn Physics>, WGPUError> {
+    let (_, new) = registry
+        .iter()
+        .find(|(name, _)| *name == log.simulation)
+        .ok_or_else(|| {
+            WGPUError::InvalidReplay(format!("unknown simulation \"{}\"", log.simulation))
+        })?;
+    let mut simulation = new();
+    simulation.set_seed(log.seed);
+    let (width, height) = (log.width, log.height);
+    let physics_of = |simulation: &dyn Simulation| {
+        simulation.physics(device, queue, shader_module, width, height)
+    };
+    let mut physics = physics_of(&*simulation)?;
+    let mut replay = Replay::new(log);
+    let mut frame_index = 0;
+    loop {
+        let sweep = physics.steps().ok_or_else(|| {
+            WGPUError::InvalidReplay("the physics does not count its steps".to_string())
+        })?;
+        let mut restart = false;
+        while let Some(action) = replay.next_due(sweep) {
+            match action {
+                ReplayAction::Reset => physics.reset(device, queue),
+                ReplayAction::Seed(seed) => {
+                    simulation.set_seed(seed);
+                    restart = true;
+                }
+                action => action.apply(&mut *simulation)?,
+            }
+            // The following events were recorded with the steps of the new physics.
+            restart |= simulation.take_restart();
+            if restart {
+                break;
+            }
+        }
+        if restart {
+            physics = physics_of(&*simulation)?;
+            continue;
+        }
+        let Some(steps) = replay.steps(sweep) else {
+            return Ok(physics);
+        };
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Replay Encoder"),
+        });
+        let frame = FrameInfo {
+            dt: 0.0,
+            frame_index,
+            paused: false,
+            steps: Some(steps),
+        };
+        physics.update(device, queue, &mut encoder, &frame);
+        queue.submit(Some(encoder.finish()));
+        let _ = device.poll(wgpu::MaintainBase::Wait);
+        frame_index += 1;
+    }
+}
+
+/// Verify that every kind of event is written and parsed back exactly, including the tags with spaces and the floats, and that the invalid logs are rejected with the line at fault.
+#[test]
+fn test_replay_log() {
+    let log = ReplayLog {
+        simulation: "ising".to_string(),
+        seed: Seed::from_u64(5),
+        width: 64,
+        height: 48,
+        events: vec![
+            ReplayAction::Slider {
+                tag: "T".to_string(),
+                value: 2.2691853142,
+            },
+            ReplayAction::Toggle {
+                tag: "iterate blocking".to_string(),
+                enable: true,
+            },
+            ReplayAction::Button {
+                tag: "reset".to_string(),
+            },
+            ReplayAction::Choice {
+                tag: "acceptance".to_string(),
+                selected: 2,
+            },
+            ReplayAction::Text {
+                tag: "exact T".to_string(),
+                value: "2.5 ".to_string(),
+            },
+            ReplayAction::Color {
+                tag: "spin up".to_string(),
+                rgb: [255, 0, 12],
+            },
+            ReplayAction::Reset,
+            ReplayAction::Seed(Seed::from_u64(6)),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, action)| ReplayEvent {
+            sweep: 100 * i as u64,
+            action,
+        })
+        .collect(),
+        end: 1000,
+    };
+    assert_eq!(ReplayLog::parse(&log.to_string()).unwrap(), log);
+
+    let mut replay = Replay::new(log);
+    // No step is performed before the events due.
+    assert_eq!(replay.steps(0), None);
+    assert!(replay.next_due(0).is_some());
+    assert_eq!(replay.next_due(0), None);
+    assert_eq!(replay.steps(0), Some(100));
+    assert_eq!(replay.steps(60), Some(40));
+
+    let header = "simulation = \"ising\"\nseed = \"5\"\nwidth = 4\nheight = 4\nend = 0\n";
+    for (text, reason) in [
+        (String::new(), "missing field `simulation`".to_string()),
+        (
+            header.replace("\"5\"", "\"x\""),
+            "line 2: invalid seed \"x\"".to_string(),
+        ),
+        (
+            format!("{header}size = 4\n"),
+            "line 6: unknown field `size`".to_string(),
+        ),
+        (
+            format!("{header}[[events]]\nsweep = 12\naction = {{ slider = {{ tag = \"T\" }} }}\n"),
+            "line 8: missing field `value`".to_string(),
+        ),
+        (
+            format!("{header}[[events]]\nsweep = 12\naction = \"jump\"\n"),
+            "line 8: unknown variant `jump`".to_string(),
+        ),
+    ] {
+        let err = ReplayLog::parse(text).unwrap_err().to_string();
+        assert!(err.contains(reason), "{err} should contain {reason}");
+    }
+}
+
+/// Verify that the replay of a session recorded with the varying numbers of steps per frame of a live run, with changes of parameters, resets and a restart, ends in the same state as the session.
+#[cfg(feature = "gpu_test")]
+#[test]
+fn test_replay_matches_session() {
+    use super::simulations;
+    use crate::gpu::physics::state_hash;
+
+    let (_instance, device, queue, shader_module) = crate::gpu::adapter::test_device();
+    let registry = simulations();
+    let mut simulation = (registry[0].1)();
+    simulation.set_seed(Seed::from_u64(11));
+    let (width, height) = (64, 64);
+    let mut log = ReplayLog::new(&*simulation, width, height);
+    let mut physics = simulation
+        .physics(&device, &queue, &shader_module, width, height)
+        .unwrap();
+    // Events of the session by frame, applied at the top of the frame like the edits of the UI.
+    let slider = |tag: &str, value| ReplayAction::Slider {
+        tag: tag.to_string(),
+        value,
+    };
+    let session = [
+        (10, slider("T", 1.8)),
+        (25, slider("h", 0.1)),
+        (
+            40,
+            ReplayAction::Choice {
+                tag: "acceptance".to_string(),
+                selected: 1,
+            },
+        ),
+        (55, ReplayAction::Reset),
+        (
+            70,
+            ReplayAction::Button {
+                tag: "reset".to_string(),
+            },
+        ),
+        (
+            85,
+            ReplayAction::Toggle {
+                tag: "half precision".to_string(),
+                enable: true,
+            },
+        ),
+        (100, slider("T", 3.0)),
+    ];
+    for frame_index in 0..120 {
+        let sweep = physics.steps().unwrap();
+        for (_, action) in session.iter().filter(|(frame, _)| *frame == frame_index) {
+            match action {
+                ReplayAction::Reset => physics.reset(&device, &queue),
+                action => action.apply(&mut *simulation).unwrap(),
+            }
+            log.push(sweep, action.clone());
+        }
+        if simulation.take_restart() {
+            physics = simulation
+                .physics(&device, &queue, &shader_module, width, height)
+                .unwrap();
+        }
+        // The number of steps of the frames varies like the ones chosen by the tuner.
+        let frame = FrameInfo {
+            dt: 0.016,
+            frame_index,
+            paused: false,
+            steps: Some([7, 13, 1, 29, 3][frame_index as usize % 5]),
+        };
+        let mut encoder = device.create_command_encoder(&Default::default());
+        physics.update(&device, &queue, &mut encoder, &frame);
+        queue.submit(Some(encoder.finish()));
+        let _ = device.poll(wgpu::MaintainBase::Wait);
+    }
+    log.end = physics.steps().unwrap();
+    let recorded = state_hash(&physics.save_state(&device, &queue).unwrap());
+
+    let log = ReplayLog::parse(&log.to_string()).unwrap();
+    let replayed = replay_headless(&device, &queue, &shader_module, &registry, log).unwrap();
+    assert_eq!(
+        state_hash(&replayed.save_state(&device, &queue).unwrap()),
+        recorded
+    );
+}
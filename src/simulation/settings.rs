@@ -0,0 +1,569 @@
+use std::fmt;
+
+use crate::{
+    config::{Config, LatticeSize, Schedule, Value, number, show},
+    error::ConfigError,
+    seed::Seed,
+};
+
+use super::{Parameter, Simulation, SimulationEntry, SimulationSetup, UpadeParameter};
+
+/// Value of `parameter` in the [Settings], `None` for a button which has no value.
+fn parameter_value(parameter: &Parameter) -> Option<Value> {
+    Some(match parameter {
+        Parameter::Slider { value, .. } => number(*value),
+        Parameter::Toggle { enable, .. } => Value::Boolean(*enable),
+        Parameter::Button { .. } => return None,
+        Parameter::Choice {
+            options, selected, ..
+        } => Value::String(options[*selected].to_string()),
+        Parameter::Text { value, .. } => Value::String(value.clone()),
+        Parameter::Color { rgb: [r, g, b], .. } => Value::String(format!("#{r:02x}{g:02x}{b:02x}")),
+    })
+}
+
+/// Update setting `parameter` to `value`, with the value as it would be captured afterwards, or the reason why the parameter cannot take it.
+fn parameter_update(
+    parameter: &Parameter,
+    value: &Value,
+) -> Result<(UpadeParameter, Value), String> {
+    let expected = |kind: &str| Err(format!("expected {kind}, found {value}"));
+    match (parameter, value) {
+        (Parameter::Slider { .. }, Value::Integer(integer)) => {
+            parameter_update(parameter, &Value::Float(*integer as f64))
+        }
+        (Parameter::Slider { tag, range, .. }, Value::Float(value)) => {
+            let value = *value as f32;
+            if !range.contains(&value) {
+                return Err(format!(
+                    "{value} is outside of {}..{}",
+                    range.start(),
+                    range.end()
+                ));
+            }
+            Ok((UpadeParameter::Slider { tag, value }, number(value)))
+        }
+        (Parameter::Slider { .. }, _) => expected("a number"),
+        (Parameter::Toggle { tag, .. }, Value::Boolean(enable)) => Ok((
+            UpadeParameter::Toggle {
+                tag,
+                enable: *enable,
+            },
+            value.clone(),
+        )),
+        (Parameter::Toggle { .. }, _) => expected("true or false"),
+        (Parameter::Button { .. }, _) => Err("a button has no value".to_string()),
+        (Parameter::Choice { tag, options, .. }, Value::String(option)) => {
+            let selected = options.iter().position(|o| o == option).ok_or_else(|| {
+                format!("unknown option {value}, expected {}", options.join(", "))
+            })?;
+            Ok((UpadeParameter::Choice { tag, selected }, value.clone()))
+        }
+        (Parameter::Choice { .. }, _) => expected("the name of an option"),
+        (Parameter::Text { tag, .. }, Value::String(text)) => Ok((
+            UpadeParameter::Text {
+                tag,
+                value: text.clone(),
+            },
+            value.clone(),
+        )),
+        (Parameter::Text { .. }, _) => expected("a string"),
+        (Parameter::Color { tag, .. }, Value::String(color)) => {
+            let rgb = color
+                .strip_prefix('#')
+                .filter(|hex| hex.len() == 6)
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .map(|rgb| [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8])
+                .ok_or_else(|| format!("invalid color {value}, expected \"#rrggbb\""))?;
+            Ok((
+                UpadeParameter::Color { tag, rgb },
+                Value::String(color.to_ascii_lowercase()),
+            ))
+        }
+        (Parameter::Color { .. }, _) => expected("a color \"#rrggbb\""),
+    }
+}
+
+/// Effect of a parameter of the [Settings] on a simulation, shown before they are applied.
+pub enum Change {
+    /// The parameter changes from `old` to `new`, with `update`.
+    Changed {
+        tag: &'static str,
+        old: String,
+        new: String,
+        update: UpadeParameter,
+    },
+    Unchanged(&'static str),
+    /// Parameter which the simulation does not have, ignored.
+    Unknown(String),
+    /// Value which the parameter cannot take, which prevents the settings from being applied.
+    Invalid {
+        tag: &'static str,
+        reason: String,
+    },
+}
+
+/// Settings of a simulation shared as text, for instance through the clipboard: its name, its seed, the size of its lattice if it is fixed, and the values of all its parameters. They are written as a [Config] without schedule nor output.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub simulation: String,
+    pub seed: Seed,
+    /// Size of the lattice, `None` if it follows the size of the canvas.
+    pub size: Option<LatticeSize>,
+    /// Values of the parameters, by tag.
+    pub parameters: Vec<(String, Value)>,
+    /// Keys of a [Config] which are not settings, the schedule and the output of a headless run, ignored with a warning.
+    pub ignored: Vec<String>,
+}
+
+impl Settings {
+    /// Current settings of `simulation`, on a lattice of fixed `size` if any.
+    pub fn capture(simulation: &dyn Simulation, size: Option<LatticeSize>) -> Self {
+        Settings {
+            simulation: simulation.name().to_string(),
+            seed: simulation.seed(),
+            size,
+            parameters: simulation
+                .egui_parameters()
+                .iter()
+                .filter_map(|parameter| {
+                    Some((parameter.tag().to_string(), parameter_value(parameter)?))
+                })
+                .collect(),
+            ignored: vec![],
+        }
+    }
+    /// Parse settings written by the [Display](fmt::Display) of [Settings], which is a [Config] with the same errors. The schedule and the output of a [Config], which do not apply to the GUI, are listed in [Settings::ignored].
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let config = Config::parse(text).map_err(|err| err.to_string())?;
+        let mut ignored = vec![];
+        if config.output.is_some() {
+            ignored.push("output".to_string());
+        }
+        if config.schedule != Schedule::default() {
+            ignored.push("schedule".to_string());
+        }
+        Ok(Settings {
+            simulation: config.simulation,
+            seed: config.seed.ok_or("missing \"seed\"")?,
+            size: config.size,
+            parameters: config.parameters.into_iter().collect(),
+            ignored,
+        })
+    }
+    /// Effect of each parameter of the settings on `simulation`, which must be the simulation of the settings.
+    pub fn changes(&self, simulation: &dyn Simulation) -> Vec<Change> {
+        let current = simulation.egui_parameters();
+        self.parameters
+            .iter()
+            .map(|(tag, value)| {
+                let Some(parameter) = current.iter().find(|p| p.tag() == tag) else {
+                    return Change::Unknown(tag.clone());
+                };
+                let tag = parameter.tag();
+                match parameter_update(parameter, value) {
+                    Ok((update, new)) => match parameter_value(parameter) {
+                        Some(old) if old == new => Change::Unchanged(tag),
+                        old => Change::Changed {
+                            tag,
+                            old: old.as_ref().map_or(String::new(), show),
+                            new: show(&new),
+                            update,
+                        },
+                    },
+                    Err(reason) => Change::Invalid { tag, reason },
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let config = Config {
+            seed: Some(self.seed),
+            size: self.size,
+            parameters: self.parameters.iter().cloned().collect(),
+            ..Config::new(&self.simulation)
+        };
+        write!(f, "{config}")
+    }
+}
+
+impl Settings {
+    /// Settings as the query of a URL, without the `?`, such as `sim=ising&seed=42&size=512&T=2.269`, read back by [setup_from_query]. Only the parameters whose value differs from its value in `defaults` are given, to keep the links short.
+    pub fn to_query(&self, defaults: &[(String, Value)]) -> String {
+        let mut pairs = vec![
+            ("sim".to_string(), self.simulation.clone()),
+            ("seed".to_string(), self.seed.to_string()),
+        ];
+        pairs.extend(self.size.map(|size| ("size".to_string(), size.to_string())));
+        pairs.extend(
+            self.parameters
+                .iter()
+                .filter(|parameter| !defaults.contains(parameter))
+                .map(|(tag, value)| (tag.clone(), show(value))),
+        );
+        pairs
+            .iter()
+            .map(|(key, value)| format!("{}={}", percent_encode(key), percent_encode(value)))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+}
+
+/// Encode the characters of `text` other than the letters, the digits and `-_.~` as `%XX`, so that it can be a key or a value of the query of a URL.
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// Decode a key or a value of the query of a URL, whose `+` are spaces and `%XX` bytes. An invalid `%` is kept as is.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Decoded pairs of keys and values of the query of a URL, with or without its leading `?`. A key without `=` has an empty value.
+pub fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .strip_prefix('?')
+        .unwrap_or(query)
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+/// Value of the text `text` given to `parameter` in a query, whose values are not typed: it is a number for a slider, a boolean for a toggle (`true`, `false`, `1` or `0`), and a string otherwise or if it is not a valid number.
+fn query_value(parameter: &Parameter, text: &str) -> Value {
+    match (parameter, text) {
+        (Parameter::Slider { .. }, _) => text
+            .parse()
+            .map_or_else(|_| Value::String(text.to_string()), Value::Float),
+        (Parameter::Toggle { .. }, "true" | "1") => Value::Boolean(true),
+        (Parameter::Toggle { .. }, "false" | "0") => Value::Boolean(false),
+        _ => Value::String(text.to_string()),
+    }
+}
+
+/// Apply the `pairs` of a query, given by [parse_query], to `simulation`: its `seed`, the `size` of its lattice, and its parameters by tag. The settings which are unknown or invalid are ignored, and returned as warnings, as well as a `sim` which is not `simulation`.
+pub fn apply_query(
+    simulation: &mut dyn Simulation,
+    size: &mut Option<LatticeSize>,
+    pairs: &[(String, String)],
+) -> Vec<String> {
+    let mut warnings = vec![];
+    for (key, text) in pairs {
+        match key.as_str() {
+            "sim" if text == simulation.name() => {}
+            "sim" => warnings.push(format!(
+                "sim={text} ignored for the simulation {}",
+                simulation.name()
+            )),
+            "size" => match text.parse::<LatticeSize>() {
+                Ok(value) => *size = Some(value),
+                Err(_) => warnings.push(format!("invalid size \"{text}\" ignored")),
+            },
+            "seed" => match text.parse::<Seed>() {
+                Ok(seed) => simulation.set_seed(seed),
+                Err(err) => warnings.push(format!("invalid seed \"{text}\" ignored: {err}")),
+            },
+            tag => {
+                let parameters = simulation.egui_parameters();
+                let Some(parameter) = parameters.iter().find(|p| p.tag() == tag) else {
+                    warnings.push(format!("unknown parameter \"{tag}\" ignored"));
+                    continue;
+                };
+                let applied = parameter_update(parameter, &query_value(parameter, text)).and_then(
+                    |(update, _)| {
+                        simulation
+                            .update_parameter(update)
+                            .map_err(|err| err.to_string())
+                    },
+                );
+                if let Err(err) = applied {
+                    warnings.push(format!("{tag}={text} ignored: {err}"));
+                }
+            }
+        }
+    }
+    warnings
+}
+
+/// Simulation of `registry` configured by the query of a URL, such as `?sim=ising&size=512&T=2.269&seed=42`, with the warnings of the settings ignored, see [apply_query]. The first simulation of the registry is used if the query has no `sim`, or one which is not in the registry.
+pub fn setup_from_query(
+    query: &str,
+    registry: &[SimulationEntry],
+) -> (SimulationSetup, Vec<String>) {
+    let (sims, pairs): (Vec<_>, Vec<_>) = parse_query(query)
+        .into_iter()
+        .partition(|(key, _)| key == "sim");
+    let mut warnings = vec![];
+    let (_, new) = sims
+        .last()
+        .and_then(|(_, name)| {
+            let entry = registry.iter().find(|(n, _)| n == name);
+            if entry.is_none() {
+                warnings.push(format!("unknown simulation \"{name}\" ignored"));
+            }
+            entry
+        })
+        .unwrap_or(&registry[0]);
+    let mut simulation = new();
+    let mut size = None;
+    warnings.extend(apply_query(&mut *simulation, &mut size, &pairs));
+    (SimulationSetup { simulation, size }, warnings)
+}
+
+/// Keys listed in an error, in the style of the unknown keys of a [Config], such as `` `T`, `h` ``.
+pub fn expected<'a>(keys: impl IntoIterator<Item = &'a str>) -> String {
+    keys.into_iter()
+        .map(|key| format!("`{key}`"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Simulation of `registry` configured by `config`, its seed and its parameters being applied before its physics is created. The schedule and the output, which only apply to a headless run, are ignored. The errors name the field at fault, such as `parameters.T`, and list the simulations or the parameters expected in place of an unknown one.
+pub fn setup_from_config(
+    config: &Config,
+    registry: &[SimulationEntry],
+) -> Result<SimulationSetup, ConfigError> {
+    let (_, new) = registry
+        .iter()
+        .find(|(name, _)| *name == config.simulation)
+        .ok_or_else(|| ConfigError::Field {
+            field: "simulation".to_string(),
+            message: format!(
+                "unknown simulation \"{}\", expected one of {}",
+                config.simulation,
+                expected(registry.iter().map(|(name, _)| *name))
+            ),
+        })?;
+    let mut simulation = new();
+    if let Some(seed) = config.seed {
+        simulation.set_seed(seed);
+    }
+    let settings = Settings {
+        simulation: config.simulation.clone(),
+        seed: simulation.seed(),
+        size: config.size,
+        parameters: config.parameters.clone().into_iter().collect(),
+        ignored: vec![],
+    };
+    let parameters = simulation.egui_parameters();
+    for change in settings.changes(&*simulation) {
+        let field = |tag: &str| format!("parameters.{tag}");
+        match change {
+            Change::Changed { tag, update, .. } => {
+                simulation
+                    .update_parameter(update)
+                    .map_err(|err| ConfigError::Field {
+                        field: field(tag),
+                        message: err.to_string(),
+                    })?;
+            }
+            Change::Unchanged(_) => {}
+            Change::Unknown(tag) => {
+                return Err(ConfigError::Field {
+                    field: field(&tag),
+                    message: format!(
+                        "unknown parameter of {}, expected one of {}",
+                        config.simulation,
+                        expected(parameters.iter().map(Parameter::tag))
+                    ),
+                });
+            }
+            Change::Invalid { tag, reason } => {
+                return Err(ConfigError::Field {
+                    field: field(tag),
+                    message: reason,
+                });
+            }
+        }
+    }
+    Ok(SimulationSetup {
+        simulation,
+        size: config.size,
+    })
+}
+
+/// Verify that the settings of the Ising model are parsed back from their text, and that the changes of pasted settings are previewed with the unchanged, unknown and invalid parameters.
+#[test]
+fn test_settings() {
+    use super::ising::Ising;
+
+    let mut ising = Ising::new();
+    let settings = Settings::capture(&ising, Some(LatticeSize::square(512)));
+    let text = settings.to_string();
+    assert!(text.contains("\"spin up\" = \"#"));
+    assert_eq!(Settings::parse(&text), Ok(settings.clone()));
+    assert!(
+        Settings::capture(&ising, Some(LatticeSize::square(512)))
+            .changes(&ising)
+            .iter()
+            .all(|change| matches!(change, Change::Unchanged(_)))
+    );
+
+    // The pasted parameters replace the captured ones, as a key cannot be repeated.
+    let pasted = text
+        .lines()
+        .filter(|line| !line.starts_with("T =") && !line.starts_with("init ="))
+        .chain([
+            "T = 1.5",
+            "unknown = 1",
+            "init = \"sideways\"",
+            "extra = \"x\" # comment",
+        ])
+        .collect::<Vec<_>>()
+        .join("\n");
+    let pasted = Settings::parse(&pasted).unwrap();
+    let changes = pasted.changes(&ising);
+    assert!(changes.iter().any(|change| matches!(
+        change,
+        Change::Changed { tag: "T", new, .. } if new == "1.5"
+    )));
+    assert!(
+        changes
+            .iter()
+            .any(|change| matches!(change, Change::Unchanged("h")))
+    );
+    assert!(
+        changes
+            .iter()
+            .any(|change| matches!(change, Change::Unknown(tag) if tag == "unknown"))
+    );
+    assert!(
+        changes
+            .iter()
+            .any(|change| matches!(change, Change::Invalid { tag: "init", .. }))
+    );
+    for change in changes {
+        if let Change::Changed { update, .. } = change {
+            ising.update_parameter(update).unwrap();
+        }
+    }
+    assert_eq!(
+        Settings::capture(&ising, None).parameters[0],
+        ("T".to_string(), number(1.5))
+    );
+
+    let with_output =
+        Settings::parse("simulation = \"ising\"\nseed = \"2a\"\noutput = \"x\"\n").unwrap();
+    assert_eq!(with_output.ignored, vec!["output".to_string()]);
+    assert_eq!(with_output.size, None);
+    assert!(Settings::parse("simulation = \"ising\"\nseed = \"2a\"\ncolor = \"x\"\n").is_err());
+    assert!(Settings::parse("simulation = \"ising\"\n").is_err());
+    assert!(Settings::parse("simulation = \"ising\nseed = \"2a\"").is_err());
+    assert!(Settings::parse("[other]\n").is_err());
+}
+
+/// Verify that a query configures the simulation, ignoring the unknown and invalid settings with warnings, and that the query of the settings gives them back.
+#[test]
+fn test_query() {
+    let registry = super::simulations();
+    let (setup, warnings) = setup_from_query(
+        "?sim=ising&size=512&T=2.269&seed=42&bogus=1&thermalize+sweeps=20&init=quasi-random&h=x",
+        &registry,
+    );
+    assert_eq!(setup.simulation.name(), "ising");
+    assert_eq!(setup.size, Some(LatticeSize::square(512)));
+    assert_eq!(setup.simulation.seed(), Seed(0x42));
+    assert_eq!(warnings.len(), 2, "{warnings:?}");
+    let settings = Settings::capture(&*setup.simulation, setup.size);
+    let value = |tag: &str| {
+        let (_, value) = settings.parameters.iter().find(|(t, _)| t == tag).unwrap();
+        show(value)
+    };
+    assert_eq!(value("T"), "2.269");
+    assert_eq!(value("thermalize sweeps"), "20");
+    assert_eq!(value("init"), "quasi-random");
+
+    let (setup, warnings) = setup_from_query("sim=langevin&seed=1", &registry);
+    assert_eq!(setup.simulation.name(), "langevin");
+    assert!(warnings.is_empty());
+    let (setup, _) = setup_from_query("sim=ising&size=256x128", &registry);
+    let rectangle = LatticeSize {
+        width: 256,
+        height: 128,
+    };
+    assert_eq!(setup.size, Some(rectangle));
+    let query = Settings::capture(&*setup.simulation, setup.size).to_query(&[]);
+    assert!(query.contains("&size=256x128&"), "{query}");
+    let (setup, warnings) = setup_from_query("sim=potts", &registry);
+    assert_eq!(setup.simulation.name(), "ising");
+    assert_eq!(warnings.len(), 1);
+
+    let defaults = Settings::capture(&*registry[0].1(), None).parameters;
+    let query = settings.to_query(&defaults);
+    assert!(query.starts_with("sim=ising&seed=00000000000000000000000000000042&size=512&"));
+    assert!(query.contains("thermalize%20sweeps=20"));
+    let (setup, warnings) = setup_from_query(&query, &registry);
+    assert!(warnings.is_empty(), "{warnings:?}");
+    assert_eq!(Settings::capture(&*setup.simulation, setup.size), settings);
+    assert_eq!(
+        parse_query("a%2Bb=%23ff+0&flag&%zz"),
+        vec![
+            ("a+b".to_string(), "#ff 0".to_string()),
+            ("flag".to_string(), String::new()),
+            ("%zz".to_string(), String::new()),
+        ]
+    );
+}
+
+/// Verify that a configuration sets up its simulation, and that its errors name the field at fault.
+#[test]
+fn test_setup_from_config() {
+    let registry = super::simulations();
+    let text = "simulation = \"ising\"\nseed = \"2a\"\nsize = 64\n\n[parameters]\nT = 1.5\ninit = \"stripe\"\n\n[schedule]\nsweeps = 10\n";
+    let config = Config::parse(text).unwrap();
+    let setup = setup_from_config(&config, &registry).unwrap();
+    assert_eq!(setup.simulation.seed(), Seed(0x2a));
+    assert_eq!(setup.size, Some(LatticeSize::square(64)));
+    let settings = Settings::capture(&*setup.simulation, setup.size);
+    assert!(settings.to_string().contains("init = \"stripe\""));
+    assert_eq!(settings.parameters[0].1, number(1.5));
+
+    let error = |text: &str| {
+        setup_from_config(&Config::parse(text).unwrap(), &registry)
+            .err()
+            .unwrap()
+            .to_string()
+    };
+    assert!(
+        error("simulation = \"isng\"")
+            .starts_with("simulation: unknown simulation \"isng\", expected one of `ising`")
+    );
+    assert!(
+        error("simulation = \"ising\"\n[parameters]\nTT = 2")
+            .starts_with("parameters.TT: unknown parameter of ising, expected one of `T`")
+    );
+    assert!(error("simulation = \"ising\"\n[parameters]\nT = 100").starts_with("parameters.T: "));
+}
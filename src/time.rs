@@ -0,0 +1,17 @@
+pub use instant::Instant;
+
+/// Current time of the monotonic clock, to be used for every timing of the simulation instead of `SystemTime` which can jump backward and is not available in `std` on `wasm32-unknown-unknown`. On the web it is based on `performance.now()` through [instant].
+pub fn now() -> Instant {
+    Instant::now()
+}
+
+/// Verify that the clock is monotonic.
+#[test]
+fn test_now_monotonic() {
+    let mut last = now();
+    for _ in 0..1000 {
+        let t = now();
+        assert!(t >= last);
+        last = t;
+    }
+}